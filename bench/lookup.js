@@ -0,0 +1,33 @@
+const { Benchmark } = require('tiny-benchy');
+const { SourceMap } = require('./setup');
+const AngularSourceMap = require('./maps/angular');
+
+// Roughly what symbolicating a batch of stack traces looks like, many lookups into one map
+const LOOKUPS = new Array(1000).fill(0).map((_, index) => ({
+  line: 1 + ((index * 7919) % 50),
+  column: (index * 104729) % 20000,
+}));
+
+exports.lookup = function () {
+  const suite = new Benchmark({
+    iterations: 100,
+  });
+
+  suite.add(
+    'lookup#findClosestMapping x1000',
+    (sourcemapInstance) => {
+      for (let { line, column } of LOOKUPS) {
+        sourcemapInstance.findClosestMapping(line, column);
+      }
+    },
+    {
+      setup: () => {
+        let sourcemapInstance = new SourceMap();
+        sourcemapInstance.addVLQMap(AngularSourceMap);
+        return sourcemapInstance;
+      },
+    }
+  );
+
+  return suite.run();
+};
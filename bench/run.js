@@ -6,6 +6,7 @@ const { consume } = require('./consume');
 const { serialize } = require('./serialize');
 const { modify } = require('./modify');
 const { append } = require('./append');
+const { lookup } = require('./lookup');
 
 function formatSummary(results) {
   return results
@@ -23,7 +24,7 @@ async function run() {
   await init;
 
   console.log('Running benchmark...');
-  const results = [await consume(), await serialize(), await modify(), await append()];
+  const results = [await consume(), await serialize(), await modify(), await append(), await lookup()];
 
   console.log('Formatting benchmark results...');
   const output = results.map(formatSummary).join('\n');
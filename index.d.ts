@@ -27,6 +27,19 @@ export type VLQMap = Readonly<{
   version?: number;
   file?: string;
   sourceRoot?: string;
+  ignoreList?: ReadonlyArray<number>;
+  debugId?: string;
+}>;
+
+/**
+* A VLQMap that is already utf-8 encoded, e.g. sliced out of a file that was read from disk.
+* sources, sourcesContent and names are JSON arrays.
+*/
+export type VLQMapBuffers = Readonly<{
+  mappings: Buffer;
+  sources: Buffer;
+  sourcesContent?: Buffer;
+  names?: Buffer;
 }>;
 
 /**
@@ -63,11 +76,99 @@ export type GenerateEmptyMapOptions = {
   lineOffset?: number;
 };
 
+/**
+ * A batch of mappings as parallel arrays, -1 means there is no original position or name.
+ * A mapping without original position has -1 as source, original line, original column and name,
+ * addFlatArrays rejects mappings that only have some of them set to -1.
+ */
+export type FlatMappingArrays = {
+  generatedLines: Uint32Array;
+  generatedColumns: Uint32Array;
+  sources: Int32Array;
+  originalLines: Int32Array;
+  originalColumns: Int32Array;
+  names: Int32Array;
+};
+
+/**
+ * Optional capabilities the loaded binary was built with
+ */
+export type SourceMapFeatures = {
+  diagnostics: boolean;
+  native: boolean;
+  wasm: boolean;
+  simd: boolean;
+  compressedSourcesContent: boolean;
+  parallel: boolean;
+  mmap: boolean;
+  serde: boolean;
+  lspTypes: boolean;
+};
+
+/**
+ * 'json' errors are a JSON string: { code, message, reason, position }
+ */
+export type ErrorFormat = 'text' | 'json';
+
+/**
+ * Reserved up front, for callers that know roughly how big the map is going to get
+ */
+export type CapacityOptions = {
+  reserveSources?: number;
+  reserveNames?: number;
+  reserveLines?: number;
+  reserveMappings?: number;
+};
+
+export type AddSourceMode = 'dedupeByPath' | 'alwaysAppend' | 'dedupeByPathAndContent';
+
+export type OverlayFrame = {
+  file: string;
+  line: number;
+  column: number;
+  functionName: string | null;
+  original: boolean;
+  codeFrame: string | null;
+};
+
+export type MutationEvent = {
+  type:
+    | 'sourceAdded'
+    | 'nameAdded'
+    | 'mappingAdded'
+    | 'lineReplaced'
+    | 'lineCleared'
+    | 'linesTruncated'
+    | 'linesShifted'
+    | 'columnsShifted'
+    | 'reset';
+  index?: number;
+  line?: number;
+  column?: number;
+  offset?: number;
+  lineCount?: number;
+  originalLine?: number;
+  originalColumn?: number;
+  source?: number;
+  name?: number;
+};
+
+export type Progress = {
+  stage: 'parse' | 'serialize';
+  processed: number;
+  total: number | null;
+};
+
 /**
 * A source map to assist in debugging during development
 */
 export default class SourceMap {
+  constructor(projectRoot?: string, buffer?: Buffer, options?: CapacityOptions);
   static generateEmptyMap(opts: GenerateEmptyMapOptions): SourceMap;
+  static features(): SourceMapFeatures;
+  static setErrorFormat(format: ErrorFormat): void;
+  static setMemoryBudget(bytes: number | null): void;
+  static getMemoryUsage(): number;
   addEmptyMap(sourceName: string, sourceContent: string, lineOffset?: number): SourceMap;
   addVLQMap(map: VLQMap, lineOffset?: number, columnOffset?: number): SourceMap;
   addBuffer(buffer: Buffer, lineOffset?: number): SourceMap;
@@ -79,17 +180,54 @@ export default class SourceMap {
   addSources(sources: string[]): number[];
   getSourceIndex(source: string): number;
   getSource(index: number): string;
+  getResolvedSources(): string[];
+  getSourceProvenance(index: number): 'added' | 'declared' | 'synthesized';
+  addIgnoredSource(index: number): SourceMap;
+  getDebugId(): string | null;
+  getSourceRoot(): string | null;
+  setSourceRoot(sourceRoot: string | null): SourceMap;
+  setDebugId(debugId: string | null): SourceMap;
+  sortSources(): SourceMap;
+  setAddSourceMode(mode: AddSourceMode): SourceMap;
   setSourceContent(sourceName: string, sourceContent: string): void;
   getSourceContent(sourceName: string): string;
   getNameIndex(name: string): number;
   getName(index: number): string;
   extends(buffer: Buffer): SourceMap;
+  apply(buffer: Buffer | SourceMap): SourceMap;
   getMap(): ParsedMap;
-  findClosestMapping(line: number, column: number): IndexedMapping<string> | undefined;
+  remapStackTrace(stack: string): string;
+  toOverlayFrames(errorStack: string, code: string): OverlayFrame[];
+  findClosestMapping(
+    line: number,
+    column: number,
+    bias?: 'greatestLowerBound' | 'leastUpperBound'
+  ): IndexedMapping<string> | undefined;
   offsetLines(line: number, lineOffset: number): IndexedMapping<string> | undefined;
   offsetColumns(line: number, column: number, columnOffset: number): IndexedMapping<string> | undefined;
+  toDataURL(): string;
+  estimateJSONSize(): number;
+  estimateBufferSize(): number;
   toBuffer(): Buffer;
   toVLQ(): VLQMap;
   delete(): void;
+  /**
+   * Node only, same as addVLQMap without copying the buffers into strings first
+   */
+  addVLQMapBuffer(map: VLQMapBuffers, lineOffset?: number, columnOffset?: number): SourceMap;
+  /**
+   * Node only, iterates over the mappings in batches
+   */
+  iterate(options?: { batchSize?: number }): AsyncIterableIterator<FlatMappingArrays>;
+  /**
+   * Node only, reports progress while parsing or serializing large maps.
+   * Called synchronously, while the method that parses or serializes is running
+   */
+  setProgressCallback(callback: ((progress: Progress) => void) | null, options?: { every?: number }): SourceMap;
+  /**
+   * Node only, gets called for every change to the map (asynchronously, in order).
+   * Keeps the process alive until cleared with null or delete()
+   */
+  setMutationObserver(callback: ((event: MutationEvent) => void) | null): SourceMap;
   stringify(options: SourceMapStringifyOptions): Promise<string | VLQMap>;
 }
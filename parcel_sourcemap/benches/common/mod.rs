@@ -0,0 +1,69 @@
+// Maps shared by the benchmarks. "dev bundle" has the mappings per line of unminified compiler
+// output (the distribution was taken from the tsc output of a mid-sized library: a third of
+// the lines have 2 mappings, half of them at most 4), "angular.min" is bench/maps/angular.js,
+// a minified bundle with a few very long lines.
+use speedy_parcel_sourcemap::{OriginalLocation, SourceMap};
+
+// Lines with n mappings, for n = 1..=24, per 1000 lines
+const DEV_BUNDLE_LINES: [u32; 24] = [
+    55, 348, 70, 92, 30, 74, 61, 58, 41, 45, 41, 23, 25, 17, 14, 6, 13, 10, 9, 5, 8, 4, 5, 6,
+];
+
+pub struct Random(pub u64);
+
+impl Random {
+    pub fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as u32
+    }
+}
+
+pub fn dev_bundle(lines: u32) -> SourceMap {
+    let total: u32 = DEV_BUNDLE_LINES.iter().sum();
+    let mut random = Random(42);
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("src/app.js");
+    for line in 0..lines {
+        let mut pick = random.next() % total;
+        let mut count = 1;
+        for (i, lines) in DEV_BUNDLE_LINES.iter().enumerate() {
+            if pick < *lines {
+                count = i as u32 + 1;
+                break;
+            }
+            pick -= lines;
+        }
+        for i in 0..count {
+            map.add_mapping(
+                line,
+                i * 6,
+                Some(OriginalLocation::new(line, i * 6, source, None)),
+            );
+        }
+    }
+    map
+}
+
+pub fn angular() -> SourceMap {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../bench/maps/angular.js");
+    let module = std::fs::read_to_string(path).unwrap();
+    let start = module.find("mappings:").unwrap();
+    let start = start + module[start..].find('\'').unwrap() + 1;
+    let end = start + module[start..].find('\'').unwrap();
+    let names = module.matches("',").count();
+    let names: Vec<String> = (0..names).map(|i| format!("n{}", i)).collect();
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(
+        &module.as_bytes()[start..end],
+        vec!["angular.js"],
+        vec![],
+        names.iter().map(|n| n.as_str()).collect(),
+        0,
+        0,
+    )
+    .unwrap();
+    map
+}
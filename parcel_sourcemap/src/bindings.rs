@@ -0,0 +1,168 @@
+// Methods that the napi and wasm bindings both expose, defined once so the two bindings
+// can't drift apart. Every binding implements a callback macro that turns these
+// definitions into methods on its own SourceMap class, `map` is bound to the
+// wrapped core SourceMap.
+//
+// infallible methods return their value as is, fallible methods return
+// Result<T, SourceMapError> which each binding converts into its own error type.
+//
+// New core APIs should be added here, only methods that need binding specific
+// conversions (JS objects, typed arrays, other SourceMap instances, ...) belong in
+// the bindings themselves. Arguments and return values have to be types that both
+// napi and wasm-bindgen can convert (numbers, bool, String).
+// Exported for the binding crates only, it isn't part of the public API.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! shared_binding_methods {
+    ($callback:ident) => {
+        $callback! {
+            infallible {
+                "getProjectRoot" fn get_project_root() -> String |map| {
+                    map.project_root.clone()
+                }
+                "addSource" fn add_source(source: String) -> u32 |map| {
+                    map.add_source(&source)
+                }
+                "getSource" fn get_source(source_index: u32) -> String |map| {
+                    map.get_source(source_index).unwrap_or_default().to_owned()
+                }
+                "addName" fn add_name(name: String) -> u32 |map| {
+                    map.add_name(&name)
+                }
+                "getName" fn get_name(name_index: u32) -> String |map| {
+                    map.get_name(name_index).unwrap_or_default().to_owned()
+                }
+                "getNameIndex" fn get_name_index(name: String) -> i32 |map| {
+                    map.get_name_index(&name).map(|i| i as i32).unwrap_or(-1)
+                }
+                "setCaseInsensitiveSources" fn set_case_insensitive_sources(case_insensitive: bool) |map| {
+                    map.set_case_insensitive_sources(case_insensitive)
+                }
+                "setRepairMissingSources" fn set_repair_missing_sources(repair: bool) |map| {
+                    map.set_repair_missing_sources(repair)
+                }
+                "setMergeEquivalentSources" fn set_merge_equivalent_sources(merge: bool) |map| {
+                    map.set_merge_equivalent_sources(merge)
+                }
+                "setGeneratedOffset" fn set_generated_offset(lines: u32, columns: u32) |map| {
+                    map.set_generated_offset(lines, columns)
+                }
+                "enableVLQCache" fn enable_vlq_cache() |map| {
+                    map.enable_vlq_cache()
+                }
+                "disableVLQCache" fn disable_vlq_cache() |map| {
+                    map.disable_vlq_cache()
+                }
+                "buildReverseIndex" fn build_reverse_index() |map| {
+                    map.build_reverse_index();
+                }
+                "clearReverseIndex" fn clear_reverse_index() |map| {
+                    map.clear_reverse_index()
+                }
+                "buildLookupIndex" fn build_lookup_index() |map| {
+                    map.build_lookup_index();
+                }
+                "clearLookupIndex" fn clear_lookup_index() |map| {
+                    map.clear_lookup_index()
+                }
+                "shrinkToFit" fn shrink_to_fit() |map| {
+                    map.shrink_to_fit()
+                }
+                "remapStackTrace" fn remap_stack_trace(stack: String) -> String |map| {
+                    map.remap_stack_trace(&stack)
+                }
+                "estimateJsonSize" fn estimate_json_size() -> u32 |map| {
+                    map.estimate_json_size() as u32
+                }
+                "estimateBufferSize" fn estimate_buffer_size() -> u32 |map| {
+                    map.estimate_buffer_size() as u32
+                }
+                "getDebugId" fn get_debug_id() -> String |map| {
+                    map.get_debug_id().unwrap_or_default().to_owned()
+                }
+                "setDebugId" fn set_debug_id(debug_id: String) |map| {
+                    map.set_debug_id(Some(debug_id.as_str()).filter(|d| !d.is_empty()))
+                }
+                "getSourceRoot" fn get_source_root() -> String |map| {
+                    map.get_source_root().unwrap_or_default().to_owned()
+                }
+                "setSourceRoot" fn set_source_root(source_root: String) |map| {
+                    map.set_source_root(Some(source_root.as_str()))
+                }
+                "getSourceProvenance" fn get_source_provenance(index: u32) -> String |map| {
+                    String::from(map.get_source_provenance(index).as_str())
+                }
+            }
+            fallible {
+                "trimTrailingLines" fn trim_trailing_lines() -> () |map| {
+                    map.trim_trailing_lines()
+                }
+                "sortSources" fn sort_sources() -> () |map| {
+                    map.sort_sources()
+                }
+                "setNameWithoutSourcePolicy" fn set_name_without_source_policy(policy: String) -> () |map| {
+                    let policy = $crate::NameWithoutSourcePolicy::from_name(&policy).ok_or_else(|| {
+                        $crate::SourceMapError::new_with_reason(
+                            $crate::SourceMapErrorType::InvalidOptions,
+                            "policy must be one of error, dropName, syntheticSource",
+                        )
+                    })?;
+                    map.set_name_without_source_policy(policy);
+                    Ok(())
+                }
+                "setAddSourceMode" fn set_add_source_mode(mode: String) -> () |map| {
+                    let mode = $crate::AddSourceMode::from_name(&mode).ok_or_else(|| {
+                        $crate::SourceMapError::new_with_reason(
+                            $crate::SourceMapErrorType::InvalidOptions,
+                            "mode must be one of dedupeByPath, alwaysAppend, dedupeByPathAndContent",
+                        )
+                    })?;
+                    map.set_add_source_mode(mode);
+                    Ok(())
+                }
+                "toOverlayFrames" fn to_overlay_frames(error_stack: String, code: String) -> String |map| {
+                    map.to_overlay_frames(&error_stack, &code)
+                }
+                "addIgnoredSource" fn add_ignored_source(source_index: u32) -> () |map| {
+                    map.add_ignored_source(source_index)
+                }
+                "toDataURL" fn to_data_url() -> String |map| {
+                    map.to_data_url(&$crate::ToJsonOptions::default())
+                }
+                "getSourceIndex" fn get_source_index(source: String) -> i32 |map| {
+                    Ok(map.get_source_index(&source)?.map(|i| i as i32).unwrap_or(-1))
+                }
+                "setSourceContentBySource" fn set_source_content_by_source(
+                    source: String,
+                    source_content: String
+                ) -> () |map| {
+                    let source_index = map.add_source(&source) as usize;
+                    map.set_source_content(source_index, &source_content)
+                }
+                "getSourceContentBySource" fn get_source_content_by_source(source: String) -> String |map| {
+                    Ok(match map.get_source_index(&source)? {
+                        Some(i) => map.get_source_content(i)?.to_owned(),
+                        None => String::new(),
+                    })
+                }
+                "addEmptyMap" fn add_empty_map(source: String, source_content: String, line_offset: i32) -> () |map| {
+                    map.add_empty_map(&source, &source_content, line_offset.into())
+                }
+                "offsetLines" fn offset_lines(generated_line: u32, generated_line_offset: i32) -> () |map| {
+                    map.offset_lines(generated_line, generated_line_offset.into())
+                }
+                "offsetColumns" fn offset_columns(
+                    generated_line: u32,
+                    generated_column: u32,
+                    generated_column_offset: i32
+                ) -> () |map| {
+                    map.offset_columns(
+                        generated_line,
+                        generated_column,
+                        generated_column_offset.into(),
+                    )
+                }
+            }
+        }
+    };
+}
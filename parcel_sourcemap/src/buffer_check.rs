@@ -0,0 +1,412 @@
+use crate::mapping::{ArchivedOriginalLocation, DuplicateMappingPolicy};
+use crate::mapping_line::{ArchivedLineMapping, ArchivedMappingLine};
+use crate::mapping_lines::ArchivedMappingLines;
+use crate::reverse_index::{ArchivedReverseIndex, ArchivedReverseMapping};
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::{ArchivedSourceMapInner, SourceMapInner, SourceProvenance};
+use rkyv::{
+    de::deserializers::AllocDeserializer, std_impl::ArchivedString, Archived, Deserialize, RelPtr,
+};
+use std::mem::{align_of, offset_of, size_of};
+
+// Buffers can come from anywhere (a cache on disk, an upload), and rkyv reads them without
+// any checks. This walks the whole archive first and makes sure every relative pointer,
+// length, tag and index stays in bounds, so reading it afterwards can't panic or go out of
+// the buffer.
+pub(crate) fn check_archive(archive: &[u8]) -> Result<(), SourceMapError> {
+    check_archive_layout(archive, &CURRENT_LAYOUT)
+}
+
+// Where the fields of an archived SourceMapInner are, for the current format and the previous
+// one that from_buffer can still read (see buffer_legacy)
+pub(crate) struct InnerLayout {
+    pub size: usize,
+    pub align: usize,
+    pub sources: usize,
+    pub sources_content: usize,
+    pub names: usize,
+    pub mapping_lines: usize,
+    // The previous format has no room for the reverse index
+    pub reverse_index: Option<usize>,
+    pub duplicate_mapping_policy: usize,
+    pub source_provenance: usize,
+    pub source_hashes: usize,
+    pub ignore_list: usize,
+    pub debug_id: usize,
+    pub source_root: usize,
+}
+
+const CURRENT_LAYOUT: InnerLayout = InnerLayout {
+    size: size_of::<ArchivedSourceMapInner>(),
+    align: align_of::<ArchivedSourceMapInner>(),
+    sources: offset_of!(ArchivedSourceMapInner, sources),
+    sources_content: offset_of!(ArchivedSourceMapInner, sources_content),
+    names: offset_of!(ArchivedSourceMapInner, names),
+    mapping_lines: offset_of!(ArchivedSourceMapInner, mapping_lines),
+    reverse_index: Some(offset_of!(ArchivedSourceMapInner, reverse_index)),
+    duplicate_mapping_policy: offset_of!(ArchivedSourceMapInner, duplicate_mapping_policy),
+    source_provenance: offset_of!(ArchivedSourceMapInner, source_provenance),
+    source_hashes: offset_of!(ArchivedSourceMapInner, source_hashes),
+    ignore_list: offset_of!(ArchivedSourceMapInner, ignore_list),
+    debug_id: offset_of!(ArchivedSourceMapInner, debug_id),
+    source_root: offset_of!(ArchivedSourceMapInner, source_root),
+};
+
+pub(crate) fn check_archive_layout(
+    archive: &[u8],
+    layout: &InnerLayout,
+) -> Result<(), SourceMapError> {
+    if archive.len() < layout.size {
+        return Err(invalid("archive is too small"));
+    }
+    let mut checker = Checker {
+        bytes: archive,
+        claimed: 0,
+    };
+    checker.check_inner(archive.len() - layout.size, layout)
+}
+
+pub(crate) fn archived_strings(strings: &[ArchivedString]) -> Vec<String> {
+    strings.iter().map(|s| String::from(s.as_str())).collect()
+}
+
+// Same as deserializing the archive with rkyv, except for the strings: rkyv 0.6 turns an
+// empty ArchivedString into a Box from a null pointer
+pub(crate) fn deserialize_inner(
+    archived: &ArchivedSourceMapInner,
+) -> Result<SourceMapInner, SourceMapError> {
+    let mut deserializer = AllocDeserializer;
+    Ok(SourceMapInner {
+        sources: archived_strings(&archived.sources),
+        sources_content: archived_strings(&archived.sources_content),
+        names: archived_strings(&archived.names),
+        mapping_lines: archived.mapping_lines.deserialize(&mut deserializer)?,
+        duplicate_mapping_policy: archived
+            .duplicate_mapping_policy
+            .deserialize(&mut deserializer)?,
+        source_provenance: archived.source_provenance.deserialize(&mut deserializer)?,
+        source_hashes: archived.source_hashes.deserialize(&mut deserializer)?,
+        ignore_list: archived.ignore_list.deserialize(&mut deserializer)?,
+        debug_id: archived
+            .debug_id
+            .as_ref()
+            .map(|debug_id| String::from(debug_id.as_str())),
+        source_root: archived
+            .source_root
+            .as_ref()
+            .map(|source_root| String::from(source_root.as_str())),
+        reverse_index: archived.reverse_index.deserialize(&mut deserializer)?,
+    })
+}
+
+fn invalid(reason: &str) -> SourceMapError {
+    SourceMapError::new_with_reason(SourceMapErrorType::BufferError, reason)
+}
+
+struct Checker<'a> {
+    bytes: &'a [u8],
+    // Bytes pointed to so far. Every value in an archive is written once, so this can't get
+    // past the size of the archive unless pointers overlap, which would let a small buffer
+    // deserialize into something huge.
+    claimed: usize,
+}
+
+impl<'a> Checker<'a> {
+    fn check_range(&self, pos: usize, size: usize, align: usize) -> Result<(), SourceMapError> {
+        match pos.checked_add(size) {
+            Some(end) if end <= self.bytes.len() => {}
+            _ => return Err(invalid("pointer out of bounds")),
+        }
+        if !(self.bytes.as_ptr() as usize + pos).is_multiple_of(align) {
+            return Err(invalid("pointer is not aligned"));
+        }
+        Ok(())
+    }
+
+    fn byte(&self, pos: usize) -> u8 {
+        self.bytes[pos]
+    }
+
+    // The value at pos, only after everything in it has been checked
+    unsafe fn get<T>(&self, pos: usize) -> &'a T {
+        &*(self.bytes.as_ptr().add(pos) as *const T)
+    }
+
+    // Checks the slice a RelPtr at pos points to, returns its start and length
+    fn check_slice<T>(&mut self, pos: usize) -> Result<(usize, usize), SourceMapError> {
+        self.check_range(pos, size_of::<RelPtr<[T]>>(), align_of::<RelPtr<[T]>>())?;
+        // ArchivedVec<T> is a transparent RelPtr<[T]>
+        let ptr = unsafe { self.get::<RelPtr<[T]>>(pos) };
+        let len = *ptr.metadata() as usize;
+        if len == 0 {
+            // rkyv points these at the start of what it wrote (the header for our buffers),
+            // they only get read as empty slices so all they need is to be non-null and aligned
+            let target = (self.bytes.as_ptr() as usize)
+                .wrapping_add(pos)
+                .wrapping_add_signed(ptr.offset());
+            if target == 0 || !target.is_multiple_of(align_of::<T>()) {
+                return Err(invalid("pointer is not aligned"));
+            }
+            return Ok((pos, 0));
+        }
+
+        let start = (pos as isize)
+            .checked_add(ptr.offset())
+            .filter(|start| *start >= 0)
+            .ok_or_else(|| invalid("pointer out of bounds"))? as usize;
+        let size = len
+            .checked_mul(size_of::<T>())
+            .ok_or_else(|| invalid("length out of bounds"))?;
+        self.check_range(start, size, align_of::<T>())?;
+        self.claimed += size;
+        if self.claimed > self.bytes.len() {
+            return Err(invalid("overlapping pointers"));
+        }
+        Ok((start, len))
+    }
+
+    fn check_str(&mut self, pos: usize) -> Result<(), SourceMapError> {
+        // ArchivedString is a transparent RelPtr<str>, with the same layout as RelPtr<[u8]>
+        let (start, len) = self.check_slice::<u8>(pos)?;
+        if std::str::from_utf8(&self.bytes[start..start + len]).is_err() {
+            return Err(invalid("string is not valid utf-8"));
+        }
+        Ok(())
+    }
+
+    fn check_strings(&mut self, pos: usize) -> Result<usize, SourceMapError> {
+        let (start, len) = self.check_slice::<ArchivedString>(pos)?;
+        for i in 0..len {
+            self.check_str(start + i * size_of::<ArchivedString>())?;
+        }
+        Ok(len)
+    }
+
+    fn check_tag(&self, pos: usize, variants: u8) -> Result<u8, SourceMapError> {
+        let tag = self.byte(pos);
+        if tag >= variants {
+            return Err(invalid("invalid enum tag"));
+        }
+        Ok(tag)
+    }
+
+    fn check_inner(&mut self, pos: usize, layout: &InnerLayout) -> Result<(), SourceMapError> {
+        self.check_range(pos, layout.size, layout.align)?;
+        let sources_len = self.check_strings(pos + layout.sources)?;
+        self.check_strings(pos + layout.sources_content)?;
+        let names_len = self.check_strings(pos + layout.names)?;
+        self.check_mapping_lines(pos + layout.mapping_lines, sources_len, names_len)?;
+
+        if let Some(reverse_index) = layout.reverse_index {
+            let reverse_index = pos + reverse_index;
+            if self.check_tag(reverse_index, 2)? == 1 {
+                self.check_reverse_index(reverse_index + option_payload::<ArchivedReverseIndex>())?;
+            }
+        }
+
+        self.check_tag(
+            pos + layout.duplicate_mapping_policy,
+            DuplicateMappingPolicy::KeepLast as u8 + 1,
+        )?;
+
+        let (start, len) =
+            self.check_slice::<Archived<SourceProvenance>>(pos + layout.source_provenance)?;
+        for i in 0..len {
+            self.check_tag(
+                start + i * size_of::<Archived<SourceProvenance>>(),
+                SourceProvenance::Synthesized as u8 + 1,
+            )?;
+        }
+
+        let (start, len) = self.check_slice::<Archived<Option<u64>>>(pos + layout.source_hashes)?;
+        for i in 0..len {
+            self.check_tag(start + i * size_of::<Archived<Option<u64>>>(), 2)?;
+        }
+
+        // is_source_ignored binary searches it
+        let (start, len) = self.check_slice::<u32>(pos + layout.ignore_list)?;
+        let ignore_list: &[u32] =
+            unsafe { std::slice::from_raw_parts(self.get::<u32>(start), len) };
+        if ignore_list.windows(2).any(|w| w[0] >= w[1])
+            || ignore_list
+                .last()
+                .is_some_and(|last| *last as usize >= sources_len)
+        {
+            return Err(invalid("ignore list is not ascending or out of range"));
+        }
+
+        let debug_id = pos + layout.debug_id;
+        if self.check_tag(debug_id, 2)? == 1 {
+            self.check_str(debug_id + option_payload::<ArchivedString>())?;
+        }
+
+        let source_root = pos + layout.source_root;
+        if self.check_tag(source_root, 2)? == 1 {
+            self.check_str(source_root + option_payload::<ArchivedString>())?;
+        }
+        Ok(())
+    }
+
+    fn check_mapping_lines(
+        &mut self,
+        pos: usize,
+        sources_len: usize,
+        names_len: usize,
+    ) -> Result<(), SourceMapError> {
+        let (numbers_start, numbers_len) =
+            self.check_slice::<u32>(pos + ArchivedMappingLines::LINE_NUMBERS_OFFSET)?;
+        let (lines_start, lines_len) =
+            self.check_slice::<ArchivedMappingLine>(pos + ArchivedMappingLines::LINES_OFFSET)?;
+        if numbers_len != lines_len {
+            return Err(invalid("line numbers don't match the lines"));
+        }
+
+        // Lookups binary search the line numbers
+        let len = *unsafe { self.get::<u32>(pos + ArchivedMappingLines::LEN_OFFSET) };
+        let line_numbers: &[u32] =
+            unsafe { std::slice::from_raw_parts(self.get::<u32>(numbers_start), numbers_len) };
+        if line_numbers.windows(2).any(|w| w[0] >= w[1])
+            || line_numbers.last().is_some_and(|last| *last >= len)
+        {
+            return Err(invalid("line numbers are not ascending"));
+        }
+
+        for i in 0..lines_len {
+            self.check_mapping_line(
+                lines_start + i * size_of::<ArchivedMappingLine>(),
+                sources_len,
+                names_len,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check_mapping_line(
+        &mut self,
+        pos: usize,
+        sources_len: usize,
+        names_len: usize,
+    ) -> Result<(), SourceMapError> {
+        self.check_tag(pos + offset_of!(ArchivedMappingLine, is_sorted), 2)?;
+        self.check_tag(
+            pos + offset_of!(ArchivedMappingLine, may_have_duplicates),
+            2,
+        )?;
+        let (start, len) = self
+            .check_slice::<ArchivedLineMapping>(pos + offset_of!(ArchivedMappingLine, mappings))?;
+        for i in 0..len {
+            let mapping = start + i * size_of::<ArchivedLineMapping>();
+            let original = mapping + offset_of!(ArchivedLineMapping, original);
+            if self.check_tag(original, 2)? == 1 {
+                let location = original + option_payload::<ArchivedOriginalLocation>();
+                self.check_tag(location + offset_of!(ArchivedOriginalLocation, name), 2)?;
+            }
+        }
+
+        let line = unsafe { self.get::<ArchivedMappingLine>(pos) };
+        let mappings: &[ArchivedLineMapping] = &line.mappings;
+        for mapping in mappings {
+            if let Some(original) = mapping.original.as_ref() {
+                if original.source as usize >= sources_len {
+                    return Err(invalid("source index out of range"));
+                }
+                if original
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| *name as usize >= names_len)
+                {
+                    return Err(invalid("name index out of range"));
+                }
+            }
+        }
+        // Sorted lines get binary searched
+        if line.is_sorted
+            && mappings
+                .windows(2)
+                .any(|w| w[0].generated_column > w[1].generated_column)
+        {
+            return Err(invalid("line is marked sorted but isn't"));
+        }
+        Ok(())
+    }
+
+    fn check_reverse_index(&mut self, pos: usize) -> Result<(), SourceMapError> {
+        self.check_range(
+            pos,
+            size_of::<ArchivedReverseIndex>(),
+            align_of::<ArchivedReverseIndex>(),
+        )?;
+        let (start, len) = self.check_slice::<RelPtr<[ArchivedReverseMapping]>>(
+            pos + offset_of!(ArchivedReverseIndex, sources),
+        )?;
+        for i in 0..len {
+            self.check_slice::<ArchivedReverseMapping>(
+                start + i * size_of::<RelPtr<[ArchivedReverseMapping]>>(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// ArchivedOption is a u8 tag followed by the value, aligned for it
+fn option_payload<T>() -> usize {
+    align_of::<T>()
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_check_archive() {
+    use crate::mapping::OriginalLocation;
+    use crate::SourceMap;
+    use rkyv::AlignedVec;
+
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    map.set_source_content(a as usize, "let a").unwrap();
+    let name = map.add_name("main");
+    map.add_mapping(0, 10, Some(OriginalLocation::new(3, 0, a, None)));
+    map.add_mapping(0, 2, Some(OriginalLocation::new(1, 4, a, Some(name))));
+    map.add_mapping(4, 4, None);
+    map.build_reverse_index();
+    map.compute_source_hashes();
+    map.add_ignored_source(a).unwrap();
+    map.set_debug_id(Some("85314830-023f-4cf1-a267-535f4e37bb17"));
+    map.set_source_root(Some("webpack://app/"));
+    let mut buffer = AlignedVec::new();
+    let options = crate::ToBufferOptions {
+        include_reverse_index: true,
+        ..crate::ToBufferOptions::default()
+    };
+    map.to_buffer_with_options(&mut buffer, &options).unwrap();
+    assert!(SourceMap::from_buffer("/", &buffer)
+        .unwrap()
+        .get_reverse_index()
+        .is_some());
+
+    // Any single corrupted byte or truncation is either still a valid map or an error
+    for i in 0..buffer.len() {
+        for value in [0x00, 0x01, 0x7f, 0x80, 0xff] {
+            let mut corrupted = AlignedVec::new();
+            corrupted.extend_from_slice(&buffer);
+            corrupted[i] = value;
+            if let Ok(mut map) = SourceMap::from_buffer("/", &corrupted) {
+                map.get_mappings();
+                map.find_closest_mapping(0, 5);
+                let _ = map.to_json(&crate::ToJsonOptions::default());
+            }
+            if let Ok(view) = crate::SourceMapView::from_buffer(&corrupted) {
+                view.find_closest_mapping(0, 5);
+            }
+        }
+        let _ = SourceMap::from_buffer("/", &buffer[..i]);
+    }
+
+    // A mapping that points to a source that isn't there
+    let mut map = SourceMap::new("/");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, 3, None)));
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    let err = SourceMap::from_buffer("/", &buffer).unwrap_err();
+    assert!(matches!(err.error_type, SourceMapErrorType::BufferError));
+    assert_eq!(err.reason.as_deref(), Some("source index out of range"));
+}
@@ -0,0 +1,257 @@
+use crate::sourcemap_error::SourceMapError;
+use crate::{SourceMap, SourceMapView};
+use rkyv::AlignedVec;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// A buffer written by to_buffer that is read from a file. BufferFile::map memory-maps it on
+// 64-bit unix (see mmap::SUPPORTED), so only the pages that are actually used get read;
+// BufferFile::read reads the whole file into memory and is always safe.
+pub struct BufferFile {
+    storage: Storage,
+}
+
+enum Storage {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    Mapped(mmap::Mapping),
+    Owned(AlignedVec),
+}
+
+impl BufferFile {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<BufferFile, SourceMapError> {
+        let mut file = File::open(path)?;
+        let mut buffer = AlignedVec::new();
+        let mut chunk = [0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+        Ok(BufferFile {
+            storage: Storage::Owned(buffer),
+        })
+    }
+
+    /// Memory-maps the file, platforms without mmap support fall back to read.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified (by this or any other process) while the
+    /// BufferFile or anything borrowed from it is alive. The bytes are only validated when a
+    /// view gets created, changing the file afterwards is undefined behavior and truncating it
+    /// makes reads fault with SIGBUS.
+    pub unsafe fn map<P: AsRef<Path>>(path: P) -> Result<BufferFile, SourceMapError> {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        if mmap::SUPPORTED {
+            let file = File::open(path)?;
+            return Ok(BufferFile {
+                storage: Storage::Mapped(mmap::Mapping::new(&file)?),
+            });
+        }
+        BufferFile::read(path)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.storage {
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Storage::Mapped(mapping) => mapping.as_bytes(),
+            Storage::Owned(buffer) => buffer,
+        }
+    }
+
+    // Looks things up in the file without deserializing it
+    pub fn view(&self) -> Result<SourceMapView<'_>, SourceMapError> {
+        SourceMapView::from_buffer(self.as_bytes())
+    }
+}
+
+impl SourceMap {
+    /// Same as from_buffer with the contents of a file, which gets memory-mapped (see
+    /// BufferFile::map) and deserialized straight from the mapping. Use BufferFile::view to
+    /// query a file without deserializing it.
+    ///
+    /// # Safety
+    ///
+    /// Same as BufferFile::map, the file must not change until this returns.
+    pub unsafe fn from_buffer_file<P: AsRef<Path>>(
+        project_root: &str,
+        path: P,
+    ) -> Result<SourceMap, SourceMapError> {
+        let file = BufferFile::map(path)?;
+        SourceMap::from_buffer(project_root, file.as_bytes())
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod mmap {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+
+    // Constants from <sys/mman.h> for the targets they have been checked against, other unix
+    // targets read the file instead of mapping it
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod sys {
+        pub const SUPPORTED: bool = true;
+        pub const PROT_READ: super::c_int = 0x1;
+        pub const MAP_PRIVATE: super::c_int = 0x2;
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    mod sys {
+        pub const SUPPORTED: bool = true;
+        pub const PROT_READ: super::c_int = 0x01;
+        pub const MAP_PRIVATE: super::c_int = 0x0002;
+    }
+
+    #[cfg(any(
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "dragonfly"
+    ))]
+    mod sys {
+        pub const SUPPORTED: bool = true;
+        pub const PROT_READ: super::c_int = 0x01;
+        pub const MAP_PRIVATE: super::c_int = 0x0002;
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "dragonfly"
+    )))]
+    mod sys {
+        pub const SUPPORTED: bool = false;
+        pub const PROT_READ: super::c_int = 0;
+        pub const MAP_PRIVATE: super::c_int = 0;
+    }
+
+    pub use sys::SUPPORTED;
+    use sys::{MAP_PRIVATE, PROT_READ};
+
+    // off_t is 64 bits on all of these when pointers are
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    pub struct Mapping {
+        ptr: *mut c_void,
+        len: usize,
+    }
+
+    // The mapping is read-only
+    unsafe impl Send for Mapping {}
+    unsafe impl Sync for Mapping {}
+
+    impl Mapping {
+        // See BufferFile::map for what the caller has to guarantee
+        pub fn new(file: &File) -> io::Result<Mapping> {
+            let len = usize::try_from(file.metadata()?.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file is too large"))?;
+            // Empty files can't be mapped
+            if len == 0 {
+                return Ok(Mapping {
+                    ptr: std::ptr::null_mut(),
+                    len: 0,
+                });
+            }
+
+            let ptr = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    PROT_READ,
+                    MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Mapping { ptr, len })
+        }
+
+        pub fn as_bytes(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            // Mappings are page aligned, so the archive after the header is aligned as well
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            if self.len > 0 {
+                unsafe {
+                    munmap(self.ptr, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_buffer_file() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    map.add_mapping(0, 4, Some(OriginalLocation::new(2, 0, a, None)));
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+
+    let path = std::env::temp_dir().join(format!("buffer-file-{}.map", std::process::id()));
+    std::fs::write(&path, &buffer).unwrap();
+    // The file isn't touched while it is mapped
+    let mapped = unsafe { BufferFile::map(&path).unwrap() };
+    #[cfg(target_os = "linux")]
+    assert!(matches!(mapped.storage, Storage::Mapped(_)));
+    for file in [mapped, BufferFile::read(&path).unwrap()] {
+        assert_eq!(file.as_bytes(), &buffer[..]);
+        let view = file.view().unwrap();
+        assert_eq!(view.get_source(a).unwrap(), "a.js");
+        assert_eq!(
+            view.find_closest_mapping(0, 6)
+                .unwrap()
+                .original
+                .unwrap()
+                .original_line,
+            2
+        );
+    }
+    let mut read = unsafe { SourceMap::from_buffer_file("/", &path).unwrap() };
+    assert_eq!(
+        read.find_closest_mapping(0, 6)
+            .unwrap()
+            .original
+            .unwrap()
+            .original_line,
+        2
+    );
+
+    std::fs::write(&path, b"").unwrap();
+    assert!(unsafe { SourceMap::from_buffer_file("/", &path) }.is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(unsafe { SourceMap::from_buffer_file("/", &path) }.is_err());
+}
@@ -0,0 +1,118 @@
+use crate::buffer_check::{archived_strings, check_archive_layout, InnerLayout};
+use crate::mapping::DuplicateMappingPolicy;
+use crate::mapping_lines::MappingLines;
+use crate::sourcemap_error::SourceMapError;
+use crate::{SourceMapInner, SourceProvenance};
+use rkyv::{
+    archived_root, de::deserializers::AllocDeserializer, std_impl::ArchivedString, Archive,
+    Deserialize,
+};
+use std::mem::{align_of, offset_of, size_of};
+
+// from_buffer also reads buffers of the format version before the current one, so caches
+// written by the previous release survive an update. Replace this with the old SourceMapInner
+// whenever BUFFER_FORMAT_VERSION gets bumped.
+pub(crate) const PREVIOUS_BUFFER_FORMAT_VERSION: u32 = 9;
+
+// SourceMapInner as of version 9, which had no room for the reverse index
+#[derive(Archive, Deserialize)]
+pub(crate) struct SourceMapInnerV9 {
+    pub sources: Vec<String>,
+    pub sources_content: Vec<String>,
+    pub names: Vec<String>,
+    pub mapping_lines: MappingLines,
+    pub duplicate_mapping_policy: DuplicateMappingPolicy,
+    pub source_provenance: Vec<SourceProvenance>,
+    pub source_hashes: Vec<Option<u64>>,
+    pub ignore_list: Vec<u32>,
+    pub debug_id: Option<String>,
+    pub source_root: Option<String>,
+}
+
+const LAYOUT: InnerLayout = InnerLayout {
+    size: size_of::<ArchivedSourceMapInnerV9>(),
+    align: align_of::<ArchivedSourceMapInnerV9>(),
+    sources: offset_of!(ArchivedSourceMapInnerV9, sources),
+    sources_content: offset_of!(ArchivedSourceMapInnerV9, sources_content),
+    names: offset_of!(ArchivedSourceMapInnerV9, names),
+    mapping_lines: offset_of!(ArchivedSourceMapInnerV9, mapping_lines),
+    reverse_index: None,
+    duplicate_mapping_policy: offset_of!(ArchivedSourceMapInnerV9, duplicate_mapping_policy),
+    source_provenance: offset_of!(ArchivedSourceMapInnerV9, source_provenance),
+    source_hashes: offset_of!(ArchivedSourceMapInnerV9, source_hashes),
+    ignore_list: offset_of!(ArchivedSourceMapInnerV9, ignore_list),
+    debug_id: offset_of!(ArchivedSourceMapInnerV9, debug_id),
+    source_root: offset_of!(ArchivedSourceMapInnerV9, source_root),
+};
+
+// Checks and reads an aligned version 9 archive, the same way as the current format
+pub(crate) fn read_previous(archive: &[u8]) -> Result<SourceMapInner, SourceMapError> {
+    check_archive_layout(archive, &LAYOUT)?;
+    let archived = unsafe { archived_root::<SourceMapInnerV9>(archive) };
+    let mut deserializer = AllocDeserializer;
+    Ok(SourceMapInner {
+        sources: archived_strings(&archived.sources),
+        sources_content: archived_strings(&archived.sources_content),
+        names: archived_strings(&archived.names),
+        mapping_lines: archived.mapping_lines.deserialize(&mut deserializer)?,
+        duplicate_mapping_policy: archived
+            .duplicate_mapping_policy
+            .deserialize(&mut deserializer)?,
+        source_provenance: archived.source_provenance.deserialize(&mut deserializer)?,
+        source_hashes: archived.source_hashes.deserialize(&mut deserializer)?,
+        ignore_list: archived.ignore_list.deserialize(&mut deserializer)?,
+        debug_id: archived
+            .debug_id
+            .as_ref()
+            .map(|debug_id: &ArchivedString| String::from(debug_id.as_str())),
+        source_root: archived
+            .source_root
+            .as_ref()
+            .map(|source_root: &ArchivedString| String::from(source_root.as_str())),
+        reverse_index: None,
+    })
+}
+
+#[test]
+fn test_read_previous_format() {
+    use crate::SourceMap;
+
+    // Written by to_buffer with BUFFER_FORMAT_VERSION 9
+    let buffer = include_bytes!("../tests/fixtures/buffer-v9.bin");
+    assert_eq!(
+        SourceMap::get_buffer_format_version(buffer),
+        Some(PREVIOUS_BUFFER_FORMAT_VERSION)
+    );
+    assert!(SourceMap::is_buffer_compatible(buffer));
+
+    let mut map = SourceMap::from_buffer("/", buffer).unwrap();
+    assert_eq!(map.get_sources(), &["a.js", "node_modules/b.js"]);
+    assert_eq!(map.get_source_content(0).unwrap(), "let a = 1;\nfoo(a);");
+    assert_eq!(map.get_names(), &["foo", "a"]);
+    assert_eq!(map.get_ignore_list(), &[1]);
+    assert_eq!(
+        map.get_debug_id(),
+        Some("85314830-023f-4cf1-a267-535f4e37bb17")
+    );
+    assert_eq!(map.get_source_root(), Some("webpack://app/"));
+    assert!(map.get_reverse_index().is_none());
+    let mut vlq = vec![];
+    map.write_vlq(&mut vlq).unwrap();
+    assert_eq!(vlq, b"AAAA,IAAIC;AACJA,EAAE;;ACAF");
+
+    // Corrupted ones are rejected like current buffers
+    let mut corrupted = buffer.to_vec();
+    let len = corrupted.len();
+    corrupted[len - 40..].fill(0xff);
+    assert!(SourceMap::from_buffer("/", &corrupted).is_err());
+    // SourceMapView only reads the current format in place
+    let mut aligned = rkyv::AlignedVec::new();
+    aligned.extend_from_slice(buffer);
+    assert!(matches!(
+        crate::SourceMapView::from_buffer(&aligned)
+            .err()
+            .unwrap()
+            .error_type,
+        crate::SourceMapErrorType::BufferVersionMismatch
+    ));
+}
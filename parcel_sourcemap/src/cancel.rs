@@ -0,0 +1,76 @@
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Shared flag to abort long running operations from another thread, e.g. when a rebuild
+// supersedes the one that is still composing or serializing its maps
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<(), SourceMapError> {
+        if self.is_cancelled() {
+            return Err(SourceMapError::new(SourceMapErrorType::Cancelled));
+        }
+        Ok(())
+    }
+}
+
+impl SourceMap {
+    // Parsing, flattening and to_buffer check this token and fail with Cancelled once it is set
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation_token = token;
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), SourceMapError> {
+        match &self.cancellation_token {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_cancellation_token() {
+    use crate::json::ParseOptions;
+    use rkyv::AlignedVec;
+
+    let token = CancellationToken::new();
+    let options = ParseOptions {
+        cancellation: Some(token.clone()),
+        ..Default::default()
+    };
+    let input = r#"{"sources": ["a.js"], "mappings": "AAAA;AACA"}"#;
+    let mut map = SourceMap::from_json_with_options("/", input, &options).unwrap();
+
+    token.cancel();
+    let error = SourceMap::from_json_with_options("/", input, &options).unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::Cancelled));
+
+    // The token of the parse isn't kept on the map
+    let mut output = AlignedVec::new();
+    assert!(map.to_buffer(&mut output).is_ok());
+
+    map.set_cancellation_token(Some(token));
+    let error = map.to_buffer(&mut output).unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::Cancelled));
+    let error = map.flatten(&mut |_| None, &Default::default()).unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::Cancelled));
+
+    map.set_cancellation_token(None);
+    assert!(map.to_buffer(&mut output).is_ok());
+}
@@ -0,0 +1,100 @@
+use crate::line_mappings::LineMappings;
+use crate::SourceMap;
+
+// How much is about to be added to a map, so bulk ingestion doesn't keep reallocating
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CapacityHints {
+    pub sources: usize,
+    pub names: usize,
+    // Generated lines that will get mappings
+    pub lines: usize,
+    pub mappings: usize,
+}
+
+impl CapacityHints {
+    // Hands a share of the reserved mappings to a line that didn't allocate yet
+    #[inline]
+    pub(crate) fn reserve_line(&mut self, mappings: &mut LineMappings) {
+        if self.mappings == 0 || mappings.spilled() {
+            return;
+        }
+        let lines = self.lines.max(1);
+        let reserve = self.mappings.div_ceil(lines);
+        mappings.reserve(reserve);
+        self.mappings -= reserve;
+        self.lines = lines - 1;
+    }
+}
+
+impl SourceMap {
+    pub fn reserve(&mut self, hints: &CapacityHints) {
+        self.reserve_sources(hints.sources);
+        self.reserve_names(hints.names);
+        self.reserve_lines(hints.lines);
+        self.reserve_mappings(hints.mappings);
+    }
+
+    pub fn reserve_sources(&mut self, additional: usize) {
+        self.inner.sources.reserve(additional);
+        self.inner.sources_content.reserve(additional);
+        self.inner.source_provenance.reserve(additional);
+    }
+
+    pub fn reserve_names(&mut self, additional: usize) {
+        self.inner.names.reserve(additional);
+    }
+
+    pub fn reserve_lines(&mut self, additional: usize) {
+        self.inner.mapping_lines.reserve(additional);
+        self.pending_capacity.lines += additional;
+    }
+
+    // Mappings are stored per generated line, so the hint is spread over the lines that get
+    // created next (see reserve_lines, without it everything goes to the next new line)
+    pub fn reserve_mappings(&mut self, additional: usize) {
+        self.pending_capacity.mappings += additional;
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_reserve() {
+    use crate::json::ParseOptions;
+
+    let mut map = SourceMap::new("/");
+    map.reserve(&CapacityHints {
+        sources: 2,
+        names: 3,
+        lines: 2,
+        mappings: 1000,
+    });
+    assert!(map.inner.sources.capacity() >= 2);
+    assert!(map.inner.names.capacity() >= 3);
+
+    map.add_mapping(0, 0, None);
+    map.add_mapping(0, 5, None);
+    map.add_mapping(4, 0, None);
+    map.add_mapping(7, 0, None);
+    assert!(map.inner.mapping_lines.get(0).unwrap().mappings.capacity() >= 500);
+    assert!(map.inner.mapping_lines.get(4).unwrap().mappings.capacity() >= 500);
+    // The hint is used up
+    assert!(map.inner.mapping_lines.get(7).unwrap().mappings.capacity() < 500);
+    assert_eq!(map.pending_capacity, CapacityHints::default());
+
+    let options = ParseOptions {
+        capacity: CapacityHints {
+            mappings: 100,
+            ..CapacityHints::default()
+        },
+        ..ParseOptions::default()
+    };
+    let map = SourceMap::from_json_with_options(
+        "/",
+        r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA,CAAC"}"#,
+        &options,
+    )
+    .unwrap();
+    assert!(map.inner.mapping_lines.get(0).unwrap().mappings.capacity() >= 100);
+    assert_eq!(map.get_mappings().len(), 2);
+}
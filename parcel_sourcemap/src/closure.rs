@@ -0,0 +1,120 @@
+use crate::json::ParseOptions;
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// The parts of a Closure Compiler map that don't have a place in SourceMap itself.
+// Closure writes `file` and `lineCount` and puts its own data in `x_` prefixed fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClosureMapExtras {
+    pub file: Option<String>,
+    pub line_count: Option<u32>,
+    pub extensions: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClosureHeader {
+    file: Option<String>,
+    line_count: Option<u32>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClosureJson<'a> {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    line_count: u32,
+    mappings: &'a str,
+    sources: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sources_content: Option<&'a [String]>,
+    names: &'a [String],
+    #[serde(flatten)]
+    extensions: &'a BTreeMap<String, Value>,
+}
+
+impl SourceMap {
+    // Reads a Closure flavored map, lineCount is used to restore trailing lines without mappings
+    pub fn from_closure_json(
+        project_root: &str,
+        input: &str,
+        options: &ParseOptions,
+    ) -> Result<(SourceMap, ClosureMapExtras), SourceMapError> {
+        let mut sourcemap = SourceMap::from_json_with_options(project_root, input, options)?;
+        let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+        let header: ClosureHeader = serde_json::from_str(input)?;
+        if let Some(line_count) = header.line_count {
+            if line_count > 0 {
+                sourcemap.ensure_lines(line_count as usize - 1)?;
+            }
+        }
+
+        let extras = ClosureMapExtras {
+            file: header.file,
+            line_count: header.line_count,
+            extensions: header
+                .other
+                .into_iter()
+                .filter(|(key, _)| key.starts_with("x_"))
+                .collect(),
+        };
+        Ok((sourcemap, extras))
+    }
+
+    // Writes the field layout Closure expects, sourcesContent is left out when it is empty.
+    // lineCount always reflects the map, extras.line_count is ignored.
+    pub fn to_closure_json(&mut self, extras: &ClosureMapExtras) -> Result<String, SourceMapError> {
+        let mut mappings = Vec::new();
+        self.write_vlq(&mut mappings)?;
+        // write_vlq only writes VLQ characters and separators
+        let mappings = String::from_utf8(mappings).unwrap_or_default();
+        let sources_content = self.try_get_sources_content()?;
+        let has_sources_content = sources_content.iter().any(|c| !c.is_empty());
+        let json = ClosureJson {
+            version: 3,
+            file: extras.file.as_deref(),
+            line_count: self.generated_offset.line + self.inner.mapping_lines.len() as u32,
+            mappings: &mappings,
+            sources: &self.inner.sources,
+            sources_content: if has_sources_content {
+                Some(sources_content)
+            } else {
+                None
+            },
+            names: &self.inner.names,
+            extensions: &extras.extensions,
+        };
+        Ok(serde_json::to_string(&json)?)
+    }
+}
+
+#[test]
+fn test_closure_round_trip() {
+    let input = r#"{
+        "version": 3,
+        "file": "out.js",
+        "lineCount": 4,
+        "mappings": "AAAA;AACA",
+        "sources": ["a.js"],
+        "x_com_google_inputs": ["a.js"],
+        "unrelated": true
+    }"#;
+    let (mut map, extras) =
+        SourceMap::from_closure_json("/", input, &ParseOptions::default()).unwrap();
+    assert_eq!(extras.file.as_deref(), Some("out.js"));
+    assert_eq!(extras.line_count, Some(4));
+    assert_eq!(extras.extensions.len(), 1);
+    assert_eq!(map.inner.mapping_lines.len(), 4);
+
+    let output = map.to_closure_json(&extras).unwrap();
+    assert_eq!(
+        output,
+        r#"{"version":3,"file":"out.js","lineCount":4,"mappings":"AAAA;AACA;;","sources":["a.js"],"names":[],"x_com_google_inputs":["a.js"]}"#
+    );
+}
@@ -0,0 +1,94 @@
+// Number of lines shown above and below the highlighted line
+pub const DEFAULT_CONTEXT_LINES: u32 = 2;
+
+// Renders the lines around the zero-based `line` with a marker on it and a caret under the
+// zero-based `column`, None if there is no such line:
+//
+//    9 | let a = 1;
+// > 10 | a();
+//      |  ^
+//
+// Columns count UTF-16 code units, like source maps and JS engines do. A column past the end
+// of the line gets the caret after the last character and a note saying so.
+pub fn render_lines(
+    lines: &[&str],
+    line: u32,
+    column: u32,
+    context_lines: u32,
+) -> Option<Vec<String>> {
+    let line = line as usize;
+    if line >= lines.len() {
+        return None;
+    }
+
+    let start = line.saturating_sub(context_lines as usize);
+    let end = (line + context_lines as usize + 1).min(lines.len());
+    let gutter = end.to_string().len();
+    let mut frame = Vec::with_capacity(end - start + 1);
+    for (index, text) in lines.iter().enumerate().take(end).skip(start) {
+        let marker = if index == line { '>' } else { ' ' };
+        frame.push(
+            format!("{} {:>gutter$} | {}", marker, index + 1, text)
+                .trim_end()
+                .to_owned(),
+        );
+        if index == line {
+            let (padding, in_range) = caret_padding(text, column);
+            let note = if in_range {
+                String::new()
+            } else {
+                format!(" column {} is past the end of the line", column + 1)
+            };
+            frame.push(format!("  {:>gutter$} | {}^{}", "", padding, note));
+        }
+    }
+    Some(frame)
+}
+
+// A space for every character before the column, tabs stay tabs so the caret lines up.
+// Also returns whether the column is within the line, the end of the line included.
+fn caret_padding(text: &str, column: u32) -> (String, bool) {
+    let mut padding = String::new();
+    let mut units = 0;
+    for c in text.chars() {
+        if units >= column as usize {
+            break;
+        }
+        padding.push(if c == '\t' { '\t' } else { ' ' });
+        units += c.len_utf16();
+    }
+    (padding, units >= column as usize)
+}
+
+#[test]
+fn test_render_lines() {
+    let lines = ["let a = 1;", "\ta();", "let c = 3;"];
+    assert_eq!(
+        render_lines(&lines, 1, 2, 1).unwrap(),
+        vec![
+            "  1 | let a = 1;",
+            "> 2 | \ta();",
+            "    | \t ^",
+            "  3 | let c = 3;"
+        ]
+    );
+    assert!(render_lines(&lines, 3, 0, 1).is_none());
+    assert_eq!(
+        render_lines(&lines, 1, 5, 0).unwrap(),
+        vec!["> 2 | \ta();", "    | \t    ^"]
+    );
+    assert_eq!(
+        render_lines(&lines, 1, 6, 0).unwrap(),
+        vec![
+            "> 2 | \ta();",
+            "    | \t    ^ column 7 is past the end of the line"
+        ]
+    );
+
+    // x is at column 7 as the emoji is two UTF-16 code units, but it only gets a single space
+    let lines = ["'😀' + x"];
+    assert_eq!(
+        render_lines(&lines, 0, 7, 0).unwrap(),
+        vec!["> 1 | '😀' + x", "    |       ^"]
+    );
+}
@@ -0,0 +1,158 @@
+use crate::mapping::{Mapping, OriginalLocation};
+use crate::mapping_line::LineMapping;
+use crate::sourcemap_error::SourceMapError;
+use crate::vlq_utils::{apply_relative_vlq, write_vlq_line, MappingBytes, MappingInput, VlqState};
+
+// Encodes mappings into a VLQ mappings string without a SourceMap, source and name indexes
+// are written as is. The mappings don't have to be sorted.
+pub fn encode_mappings(mappings: &[Mapping]) -> String {
+    let mut sorted: Vec<&Mapping> = mappings.iter().collect();
+    sorted.sort_by_key(|m| (m.generated_line, m.generated_column));
+
+    let mut output = Vec::new();
+    let mut state = VlqState::default();
+    let mut line_mappings = Vec::new();
+    let mut generated_line = 0;
+    let mut remaining = sorted.into_iter().peekable();
+    loop {
+        while let Some(mapping) = remaining.next_if(|m| m.generated_line == generated_line) {
+            line_mappings.push(LineMapping {
+                generated_column: mapping.generated_column,
+                original: mapping.original,
+            });
+        }
+
+        // Writing into a Vec can't fail
+        let _ = write_vlq_line(&line_mappings, &mut state, &mut output);
+        line_mappings.clear();
+        if remaining.peek().is_none() {
+            break;
+        }
+        output.push(b';');
+        generated_line += 1;
+    }
+
+    // Only VLQ characters and separators are written
+    String::from_utf8(output).unwrap_or_default()
+}
+
+// Decodes a VLQ mappings string without a SourceMap, source and name indexes are not
+// checked against anything. Names of segments without a source are dropped. Segments are read
+// the same way add_vlq_map reads them, values that end up negative are rejected.
+pub fn decode_mappings(input: &str) -> Result<Vec<Mapping>, SourceMapError> {
+    let mut mappings = Vec::new();
+    let mut generated_line: u32 = 0;
+    let mut generated_column: i64 = 0;
+    let mut state = VlqState::default();
+    let mut fields = [0; 5];
+
+    let mut input = MappingBytes::new(input.as_bytes());
+    while let Some(byte) = input.peek() {
+        match byte {
+            b';' => {
+                generated_line += 1;
+                generated_column = 0;
+                input.next();
+            }
+            b',' => {
+                input.next();
+            }
+            _ => {
+                let count = input.read_segment(&mut fields)?;
+                apply_relative_vlq(&mut generated_column, fields[0])?;
+                let original = match count {
+                    1 => None,
+                    2 => {
+                        apply_relative_vlq(&mut state.name, fields[1])?;
+                        None
+                    }
+                    _ => {
+                        apply_relative_vlq(&mut state.source, fields[1])?;
+                        apply_relative_vlq(&mut state.original_line, fields[2])?;
+                        apply_relative_vlq(&mut state.original_column, fields[3])?;
+                        let name = if count == 5 {
+                            apply_relative_vlq(&mut state.name, fields[4])?;
+                            Some(state.name as u32)
+                        } else {
+                            None
+                        };
+                        Some(OriginalLocation::new(
+                            state.original_line as u32,
+                            state.original_column as u32,
+                            state.source as u32,
+                            name,
+                        ))
+                    }
+                };
+
+                mappings.push(Mapping {
+                    generated_line,
+                    generated_column: generated_column as u32,
+                    original,
+                });
+            }
+        }
+    }
+
+    Ok(mappings)
+}
+
+#[test]
+fn test_encode_decode_mappings() {
+    let mappings = vec![
+        Mapping {
+            generated_line: 2,
+            generated_column: 4,
+            original: Some(OriginalLocation::new(1, 0, 1, None)),
+        },
+        Mapping {
+            generated_line: 0,
+            generated_column: 0,
+            original: Some(OriginalLocation::new(0, 0, 0, Some(0))),
+        },
+        Mapping {
+            generated_line: 0,
+            generated_column: 8,
+            original: None,
+        },
+    ];
+
+    let encoded = encode_mappings(&mappings);
+    assert_eq!(encoded, "AAAAA,Q;;ICCA");
+
+    let decoded = decode_mappings(&encoded).unwrap();
+    assert_eq!(decoded.len(), 3);
+    assert_eq!(decoded[0].original.unwrap().name, Some(0));
+    assert!(decoded[1].original.is_none());
+    assert_eq!(
+        (decoded[2].generated_line, decoded[2].generated_column),
+        (2, 4)
+    );
+    assert_eq!(decoded[2].original.unwrap().source, 1);
+    assert_eq!(encode_mappings(&[]), "");
+    assert!(decode_mappings("A!").is_err());
+}
+
+#[test]
+fn test_decode_negative_values() {
+    use crate::sourcemap_error::SourceMapErrorType;
+
+    // Generated column, source, original line, original column and name below 0
+    for input in ["D", "ADAA", "AADA", "AAAD", "AAAAD", "AAAAA,AAAAD"] {
+        let error = decode_mappings(input).unwrap_err();
+        assert!(
+            matches!(
+                error.error_type,
+                SourceMapErrorType::UnexpectedNegativeNumber
+            ),
+            "{}",
+            input
+        );
+    }
+    // Relative values can go down as long as they stay positive
+    let decoded = decode_mappings("ACCC,ADDD").unwrap();
+    assert_eq!(
+        decoded[1].original.unwrap(),
+        OriginalLocation::new(0, 0, 0, None)
+    );
+}
@@ -0,0 +1,131 @@
+use crate::mapping::Mapping;
+use crate::mapping_line::MappingLine;
+use crate::mapping_lines::too_many_lines;
+use crate::observer::MutationEvent;
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+
+// Some devtools (most notably older Firefox versions) mishandle very large generated columns
+pub const DEFAULT_MAX_COLUMN: u32 = 65535;
+
+impl SourceMap {
+    // Mappings with a generated column above max_column, to warn about before emitting
+    pub fn find_columns_exceeding(&self, max_column: u32) -> Vec<Mapping> {
+        let mut exceeding = Vec::new();
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            for mapping in mapping_line.mappings.iter() {
+                if mapping.generated_column > max_column {
+                    exceeding.push(Mapping {
+                        generated_line: generated_line as u32,
+                        generated_column: mapping.generated_column,
+                        original: mapping.original,
+                    });
+                }
+            }
+        }
+        exceeding
+    }
+
+    // Splits a generated line in two, mappings at or after generated_column move to a new
+    // line right after it. The generated code has to get a line break at the same position.
+    pub fn split_line(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Result<(), SourceMapError> {
+        let line = generated_line as usize;
+        if line >= self.inner.mapping_lines.len() {
+            return Ok(());
+        }
+        if self.inner.mapping_lines.len() == u32::MAX as usize {
+            return Err(too_many_lines());
+        }
+
+        self.reverse_index = None;
+        self.lookup_index = None;
+        let policy = self.inner.duplicate_mapping_policy;
+        let new_line = match self.inner.mapping_lines.get_mut(line) {
+            Some(mapping_line) => {
+                mapping_line.ensure_sorted_with(policy);
+                mapping_line.split_off(generated_column)
+            }
+            None => MappingLine::new(),
+        };
+        self.inner.mapping_lines.insert_lines(line + 1, 1);
+        if !new_line.mappings.is_empty() {
+            self.inner.mapping_lines.replace(line + 1, new_line)?;
+        }
+        if let Some(vlq_cache) = &mut self.vlq_cache {
+            vlq_cache.invalidate_line(line);
+            vlq_cache.insert_lines(line + 1, 1);
+        }
+        self.notify(MutationEvent::LinesShifted {
+            generated_line: generated_line + 1,
+            offset: 1,
+        });
+        self.notify(MutationEvent::LineReplaced(generated_line));
+        self.notify(MutationEvent::LineReplaced(generated_line + 1));
+        Ok(())
+    }
+
+    // Splits every line with mappings beyond max_column at mapping boundaries, so no
+    // mapping ends up with a column above max_column (unless a single segment is longer).
+    // Returns the (generated line, generated column) break positions in terms of the map
+    // before splitting, in ascending order, so the same breaks can be applied to the code.
+    pub fn split_long_lines(&mut self, max_column: u32) -> Result<Vec<(u32, u32)>, SourceMapError> {
+        let policy = self.inner.duplicate_mapping_policy;
+        let mut breaks: Vec<(u32, u32)> = Vec::new();
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter_mut() {
+            if mapping_line.last_column <= max_column && mapping_line.is_sorted {
+                continue;
+            }
+
+            mapping_line.ensure_sorted_with(policy);
+            let mut segment_start = 0;
+            for mapping in mapping_line.mappings.iter() {
+                if mapping.generated_column - segment_start > max_column {
+                    segment_start = mapping.generated_column;
+                    breaks.push((generated_line as u32, segment_start));
+                }
+            }
+        }
+
+        // Split back to front, so the positions of earlier breaks stay valid
+        for (generated_line, generated_column) in breaks.iter().rev() {
+            self.split_line(*generated_line, *generated_column)?;
+        }
+
+        Ok(breaks)
+    }
+}
+
+#[test]
+fn test_split_long_lines() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    for column in [0, 40, 90, 150, 160] {
+        map.add_mapping(
+            0,
+            column,
+            Some(OriginalLocation::new(0, column, source, None)),
+        );
+    }
+    map.add_mapping(1, 5, Some(OriginalLocation::new(1, 0, source, None)));
+
+    assert_eq!(map.find_columns_exceeding(100).len(), 2);
+    let breaks = map.split_long_lines(50).unwrap();
+    assert_eq!(breaks, vec![(0, 90), (0, 150)]);
+    assert!(map.find_columns_exceeding(50).is_empty());
+
+    let mappings = map.get_mappings();
+    let positions: Vec<(u32, u32)> = mappings
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+    assert_eq!(
+        positions,
+        vec![(0, 0), (0, 40), (1, 0), (2, 0), (2, 10), (3, 5)]
+    );
+}
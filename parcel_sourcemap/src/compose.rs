@@ -0,0 +1,296 @@
+use crate::mapping::OriginalLocation;
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct ExtendsOptions {
+    // Minifiers that join lines often don't map the last segment of every line they join
+    // (the end of a statement), so it gets lost when composing. This adds a mapping for it
+    // if the segment of the outer map that comes before it still covers it, assuming the
+    // columns in between were copied as is.
+    pub synthesize_line_ends: bool,
+}
+
+// A mapping of the outer map and the generated column of the next one on its line
+#[derive(Debug, Clone, Copy)]
+struct OuterSegment {
+    original_column: u32,
+    generated_line: u32,
+    generated_column: u32,
+    next_generated_column: Option<u32>,
+}
+
+impl SourceMap {
+    pub fn extends_with_options(
+        &mut self,
+        original_sourcemap: &mut SourceMap,
+        options: &ExtendsOptions,
+    ) -> Result<(), SourceMapError> {
+        let line_ends = if options.synthesize_line_ends {
+            self.line_end_mappings(original_sourcemap)
+        } else {
+            Vec::new()
+        };
+
+        self.extends(original_sourcemap)?;
+
+        // extends added all sources and names of the original map, adding them again only
+        // looks up their index
+        for (generated_line, generated_column, original) in line_ends {
+            let source = self.add_source(original_sourcemap.get_source(original.source)?);
+            let name = match original.name {
+                Some(name) => Some(self.add_name(original_sourcemap.get_name(name)?)),
+                None => None,
+            };
+            self.add_mapping(
+                generated_line,
+                generated_column,
+                Some(OriginalLocation::new(
+                    original.original_line,
+                    original.original_column,
+                    source,
+                    name,
+                )),
+            );
+        }
+        Ok(())
+    }
+
+    // Composes the map of a transform with the map of its input (e.g. minifier -> babel ->
+    // typescript), so the mappings point to the true originals. Unlike extends, a column between
+    // two mappings of the input map is resolved from the mapping before it plus the distance to
+    // it, assuming the code in between was copied as is. Mappings that end up on an unmapped
+    // segment of the input map lose their original location.
+    pub fn apply(&mut self, original: &SourceMap) -> Result<(), SourceMapError> {
+        let mut original = original.clone();
+        self.remap_source_with(None, &mut original, resolve_column)
+    }
+
+    // The mappings to add for the line ends of the original map that aren't mapped, with
+    // locations in the original map
+    fn line_end_mappings(
+        &self,
+        original_sourcemap: &SourceMap,
+    ) -> Vec<(u32, u32, OriginalLocation)> {
+        let mappings = self.stored_mappings();
+        let mut outer: HashMap<u32, Vec<OuterSegment>> = HashMap::new();
+        for (i, mapping) in mappings.iter().enumerate() {
+            let original = match mapping.original {
+                Some(original) => original,
+                None => continue,
+            };
+            let next_generated_column = mappings
+                .get(i + 1)
+                .filter(|next| next.generated_line == mapping.generated_line)
+                .map(|next| next.generated_column);
+            outer
+                .entry(original.original_line)
+                .or_default()
+                .push(OuterSegment {
+                    original_column: original.original_column,
+                    generated_line: mapping.generated_line,
+                    generated_column: mapping.generated_column,
+                    next_generated_column,
+                });
+        }
+
+        let mut line_ends = Vec::new();
+        for (line, mapping_line) in original_sourcemap.inner.mapping_lines.iter() {
+            let last = match mapping_line
+                .mappings
+                .iter()
+                .filter(|m| m.original.is_some())
+                .max_by_key(|m| m.generated_column)
+            {
+                Some(last) => last,
+                None => continue,
+            };
+            let segments = match outer.get(&(line as u32)) {
+                Some(segments) => segments,
+                None => continue,
+            };
+            if segments
+                .iter()
+                .any(|s| s.original_column == last.generated_column)
+            {
+                continue;
+            }
+
+            let covering = match segments
+                .iter()
+                .filter(|s| s.original_column < last.generated_column)
+                .max_by_key(|s| s.original_column)
+            {
+                Some(covering) => covering,
+                None => continue,
+            };
+            let generated_column =
+                covering.generated_column + (last.generated_column - covering.original_column);
+            if covering
+                .next_generated_column
+                .is_some_and(|next| next <= generated_column)
+            {
+                continue;
+            }
+            if let Some(original) = last.original {
+                line_ends.push((covering.generated_line, generated_column, original));
+            }
+        }
+        line_ends
+    }
+}
+
+// The original location of a generated column of map, shifted by the distance to the mapping
+// it falls in. The name only belongs to the start of that mapping.
+fn resolve_column(map: &mut SourceMap, line: u32, column: u32) -> Option<OriginalLocation> {
+    let offset = map.generated_offset;
+    let line_index = line.checked_sub(offset.line)?;
+    let column_offset = if line_index == 0 { offset.column } else { 0 };
+    let column = column.checked_sub(column_offset)?;
+
+    let policy = map.inner.duplicate_mapping_policy;
+    let mapping_line = map.inner.mapping_lines.get_mut(line_index as usize)?;
+    mapping_line.ensure_sorted_with(policy);
+    let mappings = &mapping_line.mappings;
+    let before = match mappings.partition_point(|m| m.generated_column <= column) {
+        // Columns before the first mapping of the line resolve to it, like find_closest_mapping
+        0 => return mappings.first()?.original,
+        after => mappings[after - 1].generated_column,
+    };
+    // The first mapping of that column, so duplicates give stable results
+    let found = mappings[mappings.partition_point(|m| m.generated_column < before)];
+    let mut original = found.original?;
+    if found.generated_column < column {
+        original.original_column = original
+            .original_column
+            .saturating_add(column - found.generated_column);
+        original.name = None;
+    }
+    Some(original)
+}
+
+#[test]
+fn test_apply() {
+    // babel output -> typescript
+    let mut babel = SourceMap::new("/");
+    let ts = babel.add_source("src/a.ts");
+    let name = babel.add_name("answer");
+    babel.add_mapping(0, 2, Some(OriginalLocation::new(4, 0, ts, None)));
+    babel.add_mapping(0, 10, Some(OriginalLocation::new(4, 6, ts, Some(name))));
+    babel.add_mapping(0, 16, None);
+    babel.add_mapping(1, 0, Some(OriginalLocation::new(5, 0, ts, None)));
+
+    // minifier output -> babel output
+    let mut minified = SourceMap::new("/");
+    let js = minified.add_source("a.js");
+    let short = minified.add_name("a");
+    minified.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, js, None)));
+    minified.add_mapping(0, 3, Some(OriginalLocation::new(0, 5, js, None)));
+    minified.add_mapping(0, 6, Some(OriginalLocation::new(0, 10, js, None)));
+    minified.add_mapping(0, 8, Some(OriginalLocation::new(0, 12, js, Some(short))));
+    minified.add_mapping(0, 10, Some(OriginalLocation::new(0, 18, js, None)));
+    minified.add_mapping(0, 12, Some(OriginalLocation::new(1, 3, js, None)));
+    minified.add_mapping(0, 14, Some(OriginalLocation::new(2, 0, js, None)));
+
+    let mut extended = minified.clone();
+    extended.extends(&mut babel.clone()).unwrap();
+    minified.apply(&babel).unwrap();
+
+    // source:line:column name
+    let resolve = |map: &mut SourceMap| -> Vec<String> {
+        map.get_mappings()
+            .iter()
+            .map(|m| match m.original {
+                Some(original) => format!(
+                    "{}:{}:{} {}",
+                    map.get_source(original.source).unwrap(),
+                    original.original_line,
+                    original.original_column,
+                    original.name.map_or("", |n| map.get_name(n).unwrap())
+                ),
+                None => String::from("unmapped"),
+            })
+            .collect()
+    };
+    assert_eq!(
+        resolve(&mut minified),
+        vec![
+            // Before the first mapping of the line
+            "src/a.ts:4:0 ",
+            "src/a.ts:4:3 ",
+            "src/a.ts:4:6 answer",
+            "src/a.ts:4:8 a",
+            "unmapped",
+            "src/a.ts:5:3 ",
+            "unmapped",
+        ]
+    );
+    // extends resolves to the start of the mapping
+    assert_eq!(resolve(&mut extended)[1], "src/a.ts:4:0 ");
+}
+
+#[test]
+fn test_synthesize_line_ends() {
+    let build = || {
+        let mut inner = SourceMap::new("/");
+        let source = inner.add_source("src.js");
+        inner.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+        inner.add_mapping(0, 10, Some(OriginalLocation::new(0, 12, source, None)));
+        inner.add_mapping(1, 0, Some(OriginalLocation::new(1, 0, source, None)));
+        inner.add_mapping(1, 6, Some(OriginalLocation::new(1, 8, source, None)));
+        // Already mapped by the outer map
+        inner.add_mapping(2, 0, Some(OriginalLocation::new(2, 0, source, None)));
+        inner.add_mapping(2, 4, Some(OriginalLocation::new(2, 3, source, None)));
+
+        // Joins the three lines of the inner map into one
+        let mut outer = SourceMap::new("/");
+        let intermediate = outer.add_source("bundle.js");
+        outer.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, intermediate, None)));
+        outer.add_mapping(0, 14, Some(OriginalLocation::new(1, 0, intermediate, None)));
+        outer.add_mapping(0, 22, Some(OriginalLocation::new(2, 0, intermediate, None)));
+        outer.add_mapping(0, 26, Some(OriginalLocation::new(2, 4, intermediate, None)));
+        (outer, inner)
+    };
+
+    let (mut outer, mut inner) = build();
+    outer.extends(&mut inner).unwrap();
+    assert_eq!(outer.get_mappings().len(), 4);
+
+    let (mut outer, mut inner) = build();
+    outer
+        .extends_with_options(
+            &mut inner,
+            &ExtendsOptions {
+                synthesize_line_ends: true,
+            },
+        )
+        .unwrap();
+    let mut mappings: Vec<(u32, u32, u32)> = outer
+        .get_mappings()
+        .iter()
+        .map(|m| {
+            let original = m.original.unwrap();
+            (
+                m.generated_column,
+                original.original_line,
+                original.original_column,
+            )
+        })
+        .collect();
+    mappings.sort();
+    assert_eq!(
+        mappings,
+        vec![
+            (0, 0, 0),
+            (10, 0, 12),
+            (14, 1, 0),
+            (20, 1, 8),
+            (22, 2, 0),
+            (26, 2, 3)
+        ]
+    );
+    let line_end = outer.find_closest_mapping(0, 21).unwrap().original.unwrap();
+    assert_eq!(outer.get_source(line_end.source).unwrap(), "src.js");
+}
@@ -0,0 +1,458 @@
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+use std::sync::OnceLock;
+
+// sourcesContent is often the bulk of a map, but only needed once the map gets written.
+// Builds that carry maps around for a long time can keep the contents compressed (LZ4 block
+// format) inside the map, see SourceMap::compress_sources_content.
+#[derive(Debug, Default, Clone)]
+pub struct CompressedSourcesContent {
+    contents: Vec<CompressedContent>,
+    // All contents, decompressed the first time they are read together (writing the map)
+    decompressed: OnceLock<Vec<String>>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct CompressedContent {
+    len: usize,
+    data: Box<[u8]>,
+    // Decompressed the first time this content is read on its own
+    decompressed: OnceLock<String>,
+}
+
+fn corrupted_error() -> SourceMapError {
+    SourceMapError::new_with_reason(
+        SourceMapErrorType::BufferError,
+        "compressed source content is corrupted",
+    )
+}
+
+impl CompressedContent {
+    fn decompress(&self) -> Result<String, SourceMapError> {
+        decompress(&self.data, self.len)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(corrupted_error)
+    }
+}
+
+impl CompressedSourcesContent {
+    pub fn new(sources_content: &[String]) -> Self {
+        Self {
+            contents: sources_content
+                .iter()
+                .map(|content| CompressedContent {
+                    len: content.len(),
+                    data: compress(content.as_bytes()).into_boxed_slice(),
+                    decompressed: OnceLock::new(),
+                })
+                .collect(),
+            decompressed: OnceLock::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    pub fn get(&self, index: u32) -> Result<String, SourceMapError> {
+        self.contents
+            .get(index as usize)
+            .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))?
+            .decompress()
+    }
+
+    // get, but the content stays decompressed for the next read
+    pub fn get_str(&self, index: u32) -> Result<&str, SourceMapError> {
+        if let Some(contents) = self.decompressed.get() {
+            return contents
+                .get(index as usize)
+                .map(|c| c.as_str())
+                .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange));
+        }
+        let content = self
+            .contents
+            .get(index as usize)
+            .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))?;
+        if let Some(decompressed) = content.decompressed.get() {
+            return Ok(decompressed);
+        }
+        let decompressed = content.decompress()?;
+        Ok(content.decompressed.get_or_init(|| decompressed))
+    }
+
+    // Every content, decompressed once and kept for the next read
+    pub fn get_all(&self) -> Result<&Vec<String>, SourceMapError> {
+        if let Some(contents) = self.decompressed.get() {
+            return Ok(contents);
+        }
+        let contents = self
+            .contents
+            .iter()
+            .map(|content| match content.decompressed.get() {
+                Some(decompressed) => Ok(decompressed.clone()),
+                None => content.decompress(),
+            })
+            .collect::<Result<Vec<String>, SourceMapError>>()?;
+        Ok(self.decompressed.get_or_init(|| contents))
+    }
+
+    pub fn into_contents(mut self) -> Result<Vec<String>, SourceMapError> {
+        if let Some(contents) = self.decompressed.take() {
+            return Ok(contents);
+        }
+        self.contents
+            .into_iter()
+            .map(|mut content| match content.decompressed.take() {
+                Some(decompressed) => Ok(decompressed),
+                None => content.decompress(),
+            })
+            .collect()
+    }
+
+    pub fn compressed_size(&self) -> usize {
+        self.contents.iter().map(|c| c.data.len()).sum()
+    }
+
+    pub fn uncompressed_size(&self) -> usize {
+        self.contents.iter().map(|c| c.len).sum()
+    }
+
+    // Compressed data plus whatever is kept decompressed
+    pub(crate) fn heap_size(&self) -> usize {
+        self.contents.capacity() * std::mem::size_of::<CompressedContent>()
+            + self
+                .contents
+                .iter()
+                .map(|c| c.data.len() + c.decompressed.get().map_or(0, |d| d.capacity()))
+                .sum::<usize>()
+            + self
+                .decompressed
+                .get()
+                .map_or(0, |all| all.iter().map(|c| c.capacity()).sum())
+    }
+}
+
+impl SourceMap {
+    // Keeps the sourcesContent of the map LZ4 compressed until it gets read. Reading it
+    // (get_source_content, get_sources_content and writing the map) decompresses it
+    // transparently, changing it puts it back into the map uncompressed.
+    pub fn compress_sources_content(&mut self) {
+        if self.compressed_sources_content.is_some() {
+            return;
+        }
+        let contents = std::mem::take(&mut self.inner.sources_content);
+        self.compressed_sources_content = Some(CompressedSourcesContent::new(&contents));
+    }
+
+    // Puts contents kept compressed by compress_sources_content back into the map, on an
+    // error they stay compressed
+    pub fn decompress_sources_content(&mut self) -> Result<(), SourceMapError> {
+        if let Some(contents) = &self.compressed_sources_content {
+            contents.get_all()?;
+        }
+        if let Some(contents) = self.compressed_sources_content.take() {
+            self.inner.sources_content = contents.into_contents()?;
+        }
+        Ok(())
+    }
+
+    pub fn compressed_sources_content(&self) -> Option<&CompressedSourcesContent> {
+        self.compressed_sources_content.as_ref()
+    }
+}
+
+const MIN_MATCH: usize = 4;
+// The block format requires the last 5 bytes to be literals and the last match to start
+// at least 12 bytes before the end
+const LAST_LITERALS: usize = 5;
+const MATCH_FIND_LIMIT: usize = 12;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn read_u32(input: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes([
+        input[position],
+        input[position + 1],
+        input[position + 2],
+        input[position + 3],
+    ])
+}
+
+fn write_length(output: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        output.push(255);
+        length -= 255;
+    }
+    output.push(length as u8);
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], found: Option<(usize, usize)>) {
+    let literal_token = literals.len().min(15);
+    let match_token = found.map_or(0, |(_, len)| (len - MIN_MATCH).min(15));
+    output.push((literal_token << 4 | match_token) as u8);
+    if literals.len() >= 15 {
+        write_length(output, literals.len() - 15);
+    }
+    output.extend_from_slice(literals);
+    if let Some((offset, len)) = found {
+        output.extend_from_slice(&(offset as u16).to_le_bytes());
+        if len - MIN_MATCH >= 15 {
+            write_length(output, len - MIN_MATCH - 15);
+        }
+    }
+}
+
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 16);
+    if input.is_empty() {
+        return output;
+    }
+
+    // Last position + 1 of every hashed sequence, 0 is empty
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut position = 0;
+    let limit = input.len().saturating_sub(MATCH_FIND_LIMIT);
+    while position < limit {
+        let sequence = read_u32(input, position);
+        let slot = &mut table[hash(sequence)];
+        let candidate = *slot;
+        *slot = position + 1;
+        if candidate > 0 {
+            let candidate = candidate - 1;
+            if position - candidate <= MAX_OFFSET && read_u32(input, candidate) == sequence {
+                let end = input.len() - LAST_LITERALS;
+                let mut len = MIN_MATCH;
+                while position + len < end && input[candidate + len] == input[position + len] {
+                    len += 1;
+                }
+                write_sequence(
+                    &mut output,
+                    &input[anchor..position],
+                    Some((position - candidate, len)),
+                );
+                position += len;
+                anchor = position;
+                continue;
+            }
+        }
+        position += 1;
+    }
+    write_sequence(&mut output, &input[anchor..], None);
+    output
+}
+
+fn read_length(input: &[u8], position: &mut usize, mut length: usize) -> Option<usize> {
+    if length == 15 {
+        loop {
+            let byte = *input.get(*position)?;
+            *position += 1;
+            length = length.checked_add(byte as usize)?;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Some(length)
+}
+
+fn decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(len);
+    let mut position = 0;
+    while position < input.len() {
+        let token = input[position];
+        position += 1;
+
+        let literals = read_length(input, &mut position, (token >> 4) as usize)?;
+        let end = position.checked_add(literals)?;
+        output.extend_from_slice(input.get(position..end)?);
+        position = end;
+        if position == input.len() {
+            break;
+        }
+
+        let offset = u16::from_le_bytes([*input.get(position)?, *input.get(position + 1)?]);
+        position += 2;
+        let offset = offset as usize;
+        if offset == 0 || offset > output.len() {
+            return None;
+        }
+        let match_len = read_length(input, &mut position, (token & 15) as usize)? + MIN_MATCH;
+        if output.len() + match_len > len {
+            return None;
+        }
+        // Matches can overlap with what they copy
+        let start = output.len() - offset;
+        for i in 0..match_len {
+            output.push(output[start + i]);
+        }
+    }
+    (output.len() == len).then_some(output)
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_compress() {
+    let repeated = "function add(a, b) {\n  return a + b;\n}\n".repeat(200);
+    let mut varied = String::new();
+    for i in 0..2000u32 {
+        varied.push(char::from(b'a' + (i.wrapping_mul(7919) % 26) as u8));
+    }
+    for input in [
+        "",
+        "a",
+        "abcdabcdabcd",
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        repeated.as_str(),
+        varied.as_str(),
+    ] {
+        let compressed = compress(input.as_bytes());
+        assert_eq!(
+            decompress(&compressed, input.len()).unwrap(),
+            input.as_bytes()
+        );
+    }
+    assert!(compress(repeated.as_bytes()).len() < repeated.len() / 10);
+    assert!(decompress(&[0x10, b'a', 0, 0], 5).is_none());
+
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    let b = map.add_source("b.js");
+    map.add_source("c.js");
+    map.set_source_content(a as usize, &repeated).unwrap();
+    map.set_source_content(b as usize, "let b").unwrap();
+    map.add_mapping(
+        0,
+        0,
+        Some(crate::mapping::OriginalLocation::new(0, 0, a, None)),
+    );
+    let mut json = Vec::new();
+    map.write_json(&mut json, &Default::default()).unwrap();
+    let mut buffer = rkyv::AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+
+    map.compress_sources_content();
+    assert!(map.is_sources_content_compressed());
+    let contents = map.compressed_sources_content().unwrap();
+    assert_eq!(contents.len(), 2);
+    assert!(contents.compressed_size() < contents.uncompressed_size() / 10);
+    assert_eq!(contents.get(b).unwrap(), "let b");
+    assert!(contents.get(5).is_err());
+    assert!(map.memory_size() < repeated.len());
+
+    // Reads and writes see the contents as if they weren't compressed
+    assert_eq!(map.get_source_content(b).unwrap(), "let b");
+    assert!(map.get_source_content(5).is_err());
+    assert_eq!(
+        map.get_sources_content(),
+        &vec![repeated.clone(), String::from("let b")]
+    );
+    let mut compressed_json = Vec::new();
+    map.write_json(&mut compressed_json, &Default::default())
+        .unwrap();
+    assert_eq!(compressed_json, json);
+    let mut compressed_buffer = rkyv::AlignedVec::new();
+    map.to_buffer(&mut compressed_buffer).unwrap();
+    assert_eq!(&compressed_buffer[..], &buffer[..]);
+    assert!(map.is_sources_content_compressed());
+
+    // Changing them decompresses them
+    map.set_source_content(2, "let c").unwrap();
+    assert!(!map.is_sources_content_compressed());
+    assert_eq!(map.get_source_content(a).unwrap(), repeated);
+    assert_eq!(map.get_source_content(2).unwrap(), "let c");
+
+    map.compress_sources_content();
+    map.decompress_sources_content().unwrap();
+    assert_eq!(map.get_source_content(b).unwrap(), "let b");
+}
+
+#[test]
+fn test_corrupted_sources_content() {
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    map.set_source_content(a as usize, &"let a = 1;\n".repeat(100))
+        .unwrap();
+    map.compress_sources_content();
+    let contents = map.compressed_sources_content.as_mut().unwrap();
+    contents.contents[0].data = vec![0xf0].into_boxed_slice();
+
+    assert!(map.get_source_content(a).is_err());
+    assert!(map.try_get_sources_content().is_err());
+    assert!(map.to_buffer(&mut rkyv::AlignedVec::new()).is_err());
+    assert!(map.set_source_content(a as usize, "let a").is_err());
+    assert!(map.decompress_sources_content().is_err());
+    assert!(map.is_sources_content_compressed());
+}
+
+#[test]
+fn test_compress_round_trip_property() {
+    // Deterministic xorshift, so failures reproduce
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    let alphabets: [&[u8]; 4] = [b"a", b"ab", b"function (){};\n\t ", &[0, 1, 127, 128, 255]];
+    for case in 0..2000 {
+        let alphabet = alphabets[case % alphabets.len()];
+        // Mostly short inputs around the block format limits, some long enough for the
+        // extended lengths and offsets past the hash table size
+        let len = match rng.below(4) {
+            0 => rng.below(32),
+            1 => rng.below(300),
+            2 => rng.below(5000),
+            _ => 60000 + rng.below(20000),
+        };
+        let mut input = Vec::with_capacity(len);
+        while input.len() < len {
+            if !input.is_empty() && rng.below(3) == 0 {
+                // Copy an earlier run, so there is something to match
+                let start = rng.below(input.len());
+                let run = rng.below(300).min(len - input.len());
+                for i in 0..run {
+                    input.push(input[start + i % (input.len() - start)]);
+                }
+            } else {
+                input.push(alphabet[rng.below(alphabet.len())]);
+            }
+        }
+
+        let compressed = compress(&input);
+        assert_eq!(
+            decompress(&compressed, input.len()).as_deref(),
+            Some(input.as_slice()),
+            "case {} ({} bytes)",
+            case,
+            len
+        );
+        // A wrong length or a truncated block never decodes into something else
+        assert!(decompress(&compressed, input.len() + 1).is_none());
+        if !compressed.is_empty() {
+            let truncated = &compressed[..rng.below(compressed.len())];
+            assert_ne!(
+                decompress(truncated, input.len()).as_deref(),
+                Some(input.as_slice())
+            );
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::mapping::OriginalLocation;
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+struct Chunk {
+    id: Option<String>,
+    code: String,
+    map: Option<SourceMap>,
+    // Number of line breaks and the length of the last line (in UTF-16 code units),
+    // used to compute where the next chunk starts
+    line_breaks: u32,
+    last_line_length: u32,
+}
+
+impl Chunk {
+    fn new(id: Option<String>, code: &str, map: Option<SourceMap>) -> Self {
+        let line_breaks = code.matches('\n').count() as u32;
+        let last_line = code.rsplit('\n').next().unwrap_or("");
+        Self {
+            id,
+            code: String::from(code),
+            map,
+            line_breaks,
+            last_line_length: last_line.encode_utf16().count() as u32,
+        }
+    }
+}
+
+// Concatenates chunks of code and their maps into a single file and map. Chunks can be
+// tagged with an id, so a single chunk can be replaced when its module changes, the
+// offsets of all chunks after it are recomputed on the next build.
+#[derive(Debug, Clone)]
+pub struct Concatenator {
+    project_root: String,
+    chunks: Vec<Chunk>,
+    ids: HashMap<String, usize>,
+}
+
+impl Concatenator {
+    pub fn new(project_root: &str) -> Self {
+        Self {
+            project_root: String::from(project_root),
+            chunks: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, code: &str, map: Option<SourceMap>) {
+        self.chunks.push(Chunk::new(None, code, map));
+    }
+
+    pub fn push_with_id(
+        &mut self,
+        id: &str,
+        code: &str,
+        map: Option<SourceMap>,
+    ) -> Result<(), SourceMapError> {
+        if self.ids.contains_key(id) {
+            return Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::InvalidChunkId,
+                &format!("duplicate chunk id {}", id),
+            ));
+        }
+
+        self.ids.insert(String::from(id), self.chunks.len());
+        self.chunks
+            .push(Chunk::new(Some(String::from(id)), code, map));
+        Ok(())
+    }
+
+    // Replaces the code and map of a chunk that was pushed using push_with_id
+    pub fn replace(
+        &mut self,
+        id: &str,
+        code: &str,
+        map: Option<SourceMap>,
+    ) -> Result<(), SourceMapError> {
+        let index = match self.ids.get(id) {
+            Some(index) => *index,
+            None => {
+                return Err(SourceMapError::new_with_reason(
+                    SourceMapErrorType::InvalidChunkId,
+                    &format!("unknown chunk id {}", id),
+                ))
+            }
+        };
+
+        let id = self.chunks[index].id.take();
+        self.chunks[index] = Chunk::new(id, code, map);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn code(&self) -> String {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.code.as_str())
+            .collect()
+    }
+
+    pub fn build_map(&self) -> Result<SourceMap, SourceMapError> {
+        let mut output = SourceMap::new(&self.project_root);
+        let mut line: u32 = 0;
+        let mut column: u32 = 0;
+        for chunk in self.chunks.iter() {
+            if let Some(map) = &chunk.map {
+                append_map(&mut output, map, line, column)?;
+            }
+
+            if chunk.line_breaks > 0 {
+                line += chunk.line_breaks;
+                column = chunk.last_line_length;
+            } else {
+                column += chunk.last_line_length;
+            }
+        }
+
+        Ok(output)
+    }
+
+    pub fn build(&self) -> Result<(String, SourceMap), SourceMapError> {
+        Ok((self.code(), self.build_map()?))
+    }
+}
+
+// Copies all mappings of map into output, starting at the given generated position
+fn append_map(
+    output: &mut SourceMap,
+    map: &SourceMap,
+    line_offset: u32,
+    column_offset: u32,
+) -> Result<(), SourceMapError> {
+    let mut source_indexes = Vec::with_capacity(map.inner.sources.len());
+    for (index, source) in map.inner.sources.iter().enumerate() {
+        let source_index = match output.find_source(source) {
+            Some(source_index) => source_index,
+            None => {
+                output.inner.sources.push(source.clone());
+                output.inner.sources.len() - 1
+            }
+        };
+        output
+            .merge_source_provenance(source_index as u32, map.get_source_provenance(index as u32));
+        output.copy_ignored_source(source_index as u32, map, index as u32);
+        if let Some(content) = map.get_sources_content().get(index) {
+            if !content.is_empty() {
+                output.set_source_content(source_index, content)?;
+            }
+        }
+        output.copy_source_hash(source_index as u32, map, index as u32);
+        source_indexes.push(source_index as u32);
+    }
+    let name_indexes: Vec<u32> = map.inner.names.iter().map(|n| output.add_name(n)).collect();
+
+    for (generated_line, mapping_line) in map.inner.mapping_lines.iter() {
+        let column_offset = if generated_line == 0 {
+            column_offset
+        } else {
+            0
+        };
+        for mapping in mapping_line.mappings.iter() {
+            let original = match mapping.original {
+                Some(original) => Some(OriginalLocation::new(
+                    original.original_line,
+                    original.original_column,
+                    *source_indexes
+                        .get(original.source as usize)
+                        .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))?,
+                    match original.name {
+                        Some(name) => Some(*name_indexes.get(name as usize).ok_or_else(|| {
+                            SourceMapError::new(SourceMapErrorType::NameOutOfRange)
+                        })?),
+                        None => None,
+                    },
+                )),
+                None => None,
+            };
+            output.add_mapping(
+                line_offset + generated_line as u32,
+                mapping.generated_column + column_offset,
+                original,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_concatenator_replace() {
+    fn module_map(source: &str) -> SourceMap {
+        let mut map = SourceMap::new("/");
+        let source = map.add_source(source);
+        map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+        map.add_mapping(1, 2, Some(OriginalLocation::new(1, 0, source, None)));
+        map
+    }
+
+    let mut concatenator = Concatenator::new("/");
+    concatenator.push("// banner ", None);
+    concatenator
+        .push_with_id("a", "a();\n  a2();\n", Some(module_map("a.js")))
+        .unwrap();
+    concatenator
+        .push_with_id("b", "b();\n  b2();\n", Some(module_map("b.js")))
+        .unwrap();
+    assert!(concatenator.push_with_id("a", "", None).is_err());
+
+    let (code, map) = concatenator.build().unwrap();
+    assert_eq!(code, "// banner a();\n  a2();\nb();\n  b2();\n");
+    let positions: Vec<(u32, u32)> = map
+        .get_mappings()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+    assert_eq!(positions, vec![(0, 10), (1, 2), (2, 0), (3, 2)]);
+
+    concatenator
+        .replace("a", "a();\n\n  a2();\n", Some(module_map("a.js")))
+        .unwrap();
+    let map = concatenator.build_map().unwrap();
+    let b_mapping = &map.get_mappings()[2];
+    assert_eq!(
+        (b_mapping.generated_line, b_mapping.generated_column),
+        (3, 0)
+    );
+    assert_eq!(map.get_sources(), &vec!["a.js", "b.js"]);
+    assert!(concatenator.replace("c", "", None).is_err());
+}
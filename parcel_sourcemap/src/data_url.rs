@@ -0,0 +1,331 @@
+#[cfg(feature = "json")]
+use crate::json::{ParseOptions, ToJsonOptions};
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+#[cfg(feature = "json")]
+use crate::SourceMap;
+#[cfg(feature = "json")]
+use std::io;
+
+#[cfg(feature = "json")]
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn invalid(reason: &str) -> SourceMapError {
+    SourceMapError::new_with_reason(SourceMapErrorType::InvalidDataUrl, reason)
+}
+
+fn base64_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        // The url-safe alphabet shows up as well
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+// Padding is optional and whitespace is skipped, like browsers do for data urls
+fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    let mut padding = false;
+    for byte in input.iter().copied() {
+        if byte.is_ascii_whitespace() {
+            continue;
+        }
+        if byte == b'=' {
+            padding = true;
+            continue;
+        }
+        if padding {
+            return None;
+        }
+        buffer = buffer << 6 | base64_value(byte)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    // A single character left over can't be a byte
+    (bits < 6).then_some(output)
+}
+
+// Encodes everything written to it straight into the output, so serialized maps don't have to
+// be kept around until they get encoded. Only complete groups of 3 bytes get encoded, the
+// rest waits for the next write or finish.
+#[cfg(feature = "json")]
+struct Base64Writer {
+    output: String,
+    pending: [u8; 3],
+    pending_len: usize,
+}
+
+#[cfg(feature = "json")]
+impl Base64Writer {
+    fn new(output: String) -> Self {
+        Self {
+            output,
+            pending: [0; 3],
+            pending_len: 0,
+        }
+    }
+
+    fn encode(&mut self, chunk: &[u8]) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - i * 8)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let sextet = (group >> (18 - i * 6)) & 0x3f;
+                self.output.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                self.output.push('=');
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.pending_len > 0 {
+            let pending = self.pending;
+            self.encode(&pending[..self.pending_len]);
+        }
+        self.output
+    }
+}
+
+#[cfg(feature = "json")]
+impl io::Write for Base64Writer {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        if self.pending_len > 0 {
+            let needed = (3 - self.pending_len).min(buf.len());
+            self.pending[self.pending_len..self.pending_len + needed]
+                .copy_from_slice(&buf[..needed]);
+            self.pending_len += needed;
+            buf = &buf[needed..];
+            if self.pending_len < 3 {
+                return Ok(written);
+            }
+            let pending = self.pending;
+            self.encode(&pending);
+            self.pending_len = 0;
+        }
+
+        self.output.reserve(buf.len() / 3 * 4);
+        let mut chunks = buf.chunks_exact(3);
+        for chunk in chunks.by_ref() {
+            self.encode(chunk);
+        }
+        let rest = chunks.remainder();
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn decode_percent(input: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' {
+            let hex = std::str::from_utf8(input.get(i + 1..i + 3)?).ok()?;
+            output.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            output.push(input[i]);
+            i += 1;
+        }
+    }
+    Some(output)
+}
+
+// The content of a `data:` url holding a source map, e.g.
+// data:application/json;charset=utf-8;base64,eyJ2ZXJzaW9uIjozfQ==
+pub fn decode_data_url(url: &str) -> Result<Vec<u8>, SourceMapError> {
+    let url = url.trim();
+    let rest = match url.get(..5) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("data:") => &url[5..],
+        _ => return Err(invalid("url does not start with data:")),
+    };
+    let (header, data) = rest
+        .split_once(',')
+        .ok_or_else(|| invalid("data url has no data"))?;
+
+    let mut parameters = header.split(';').map(|p| p.trim());
+    let media_type = parameters.next().unwrap_or("").to_ascii_lowercase();
+    if !matches!(
+        media_type.as_str(),
+        "" | "application/json" | "text/json" | "text/plain"
+    ) {
+        return Err(invalid(&format!(
+            "data url has media type {}, expected application/json",
+            media_type
+        )));
+    }
+    let mut base64 = false;
+    for parameter in parameters {
+        if parameter.eq_ignore_ascii_case("base64") {
+            base64 = true;
+        } else if let Some((key, value)) = parameter.split_once('=') {
+            // Other encodings get detected from the content, see from_json_bytes
+            let value = value.trim_matches('"');
+            if key.trim().eq_ignore_ascii_case("charset")
+                && !value.eq_ignore_ascii_case("utf-8")
+                && !value.eq_ignore_ascii_case("utf8")
+                && !value.eq_ignore_ascii_case("utf-16")
+            {
+                return Err(invalid(&format!("unsupported charset {}", value)));
+            }
+        }
+    }
+
+    // Even base64 data is sometimes percent-encoded when the url itself was escaped
+    let data =
+        decode_percent(data.as_bytes()).ok_or_else(|| invalid("invalid percent-encoding"))?;
+    if base64 {
+        decode_base64(&data).ok_or_else(|| invalid("invalid base64"))
+    } else {
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "json")]
+impl SourceMap {
+    // Parses an inline map, as appended to generated code by esbuild, babel and others
+    pub fn from_data_url(project_root: &str, url: &str) -> Result<SourceMap, SourceMapError> {
+        let bytes = decode_data_url(url)?;
+        let (sourcemap, _) =
+            SourceMap::from_json_bytes(project_root, &bytes, &ParseOptions::default())?;
+        Ok(sourcemap)
+    }
+
+    // The map as an inline data: url, to append to generated code as
+    // //# sourceMappingURL=<url>
+    pub fn to_data_url(&mut self, options: &ToJsonOptions) -> Result<String, SourceMapError> {
+        let mut writer =
+            Base64Writer::new(String::from("data:application/json;charset=utf-8;base64,"));
+        self.write_json(&mut writer, options)?;
+        Ok(writer.finish())
+    }
+}
+
+#[test]
+fn test_decode_base64() {
+    for (input, expected) in [
+        ("", ""),
+        ("Zg==", "f"),
+        ("Zm8=", "fo"),
+        ("Zm9v", "foo"),
+        ("Zm9vYg", "foob"),
+        ("Zm9v\nYmFy", "foobar"),
+    ] {
+        assert_eq!(
+            decode_base64(input.as_bytes()).unwrap(),
+            expected.as_bytes(),
+            "{}",
+            input
+        );
+    }
+    assert!(decode_base64(b"Zm9vY").is_none());
+    assert!(decode_base64(b"Zg==Zg").is_none());
+    assert!(decode_base64(b"Zm9v!").is_none());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_base64_writer() {
+    use std::io::Write;
+
+    for input in [
+        "",
+        "f",
+        "fo",
+        "foo",
+        "foob",
+        "fooba",
+        "foobar",
+        "{\"version\":3}",
+    ] {
+        let input = input.as_bytes();
+        // Split up in every possible way, to cover bytes that wait for the next write
+        for split in 0..=input.len() {
+            let mut writer = Base64Writer::new(String::new());
+            writer.write_all(&input[..split]).unwrap();
+            writer.write_all(&input[split..]).unwrap();
+            let encoded = writer.finish();
+            assert_eq!(encoded.len(), input.len().div_ceil(3) * 4);
+            assert_eq!(decode_base64(encoded.as_bytes()).unwrap(), input);
+        }
+    }
+
+    let mut writer = Base64Writer::new(String::new());
+    writer.write_all(b"foobar").unwrap();
+    assert_eq!(writer.finish(), "Zm9vYmFy");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_to_data_url() {
+    let mut map = SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":["a.js"],"sourcesContent":["let a = \"\u00e9\";"],"names":["a"],"mappings":"AAAAA"}"#,
+    )
+    .unwrap();
+    let url = map.to_data_url(&ToJsonOptions::default()).unwrap();
+    assert!(url.starts_with("data:application/json;charset=utf-8;base64,"));
+    let mut parsed = SourceMap::from_data_url("/", &url).unwrap();
+    assert_eq!(
+        parsed.to_json(&ToJsonOptions::default()).unwrap(),
+        map.to_json(&ToJsonOptions::default()).unwrap()
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_from_data_url() {
+    // {"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}
+    let base64 =
+        "eyJ2ZXJzaW9uIjozLCJzb3VyY2VzIjpbImEuanMiXSwibmFtZXMiOltdLCJtYXBwaW5ncyI6IkFBQUEifQ==";
+    for url in [
+        format!("data:application/json;base64,{}", base64),
+        format!("data:application/json;charset=utf-8;base64,{}", base64),
+        format!("DATA:application/json;charset=UTF-8;base64,{}\n", base64),
+        String::from(
+            r#"data:application/json,{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}"#,
+        ),
+        String::from("data:application/json;charset=utf-8,%7B%22version%22%3A3%2C%22sources%22%3A%5B%22a.js%22%5D%2C%22names%22%3A%5B%5D%2C%22mappings%22%3A%22AAAA%22%7D"),
+    ] {
+        let map = SourceMap::from_data_url("/", &url).unwrap();
+        assert_eq!(map.get_sources(), &vec!["a.js"], "{}", url);
+        assert_eq!(map.get_mappings().len(), 1);
+    }
+
+    for url in [
+        "https://example.com/a.js.map",
+        "data:application/json;base64",
+        "data:image/png;base64,Zm9v",
+        "data:application/json;charset=latin1,{}",
+        "data:application/json;base64,Zm9v!",
+    ] {
+        let error = SourceMap::from_data_url("/", url).unwrap_err();
+        assert!(
+            matches!(error.error_type, SourceMapErrorType::InvalidDataUrl),
+            "{}",
+            url
+        );
+    }
+    assert!(matches!(
+        SourceMap::from_data_url("/", "data:application/json,{")
+            .unwrap_err()
+            .error_type,
+        SourceMapErrorType::InvalidJson
+    ));
+}
@@ -0,0 +1,63 @@
+use crate::SourceMap;
+
+impl SourceMap {
+    // The debugId (a UUID) that ties the map to its bundle, symbol servers use it to find the
+    // map of a minified stack trace. It belongs to the map, so it is not copied when other maps
+    // get added to or extend this one.
+    pub fn get_debug_id(&self) -> Option<&str> {
+        self.inner.debug_id.as_deref()
+    }
+
+    pub fn set_debug_id(&mut self, debug_id: Option<&str>) {
+        self.inner.debug_id = debug_id.map(String::from);
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_debug_id() {
+    use crate::json::ToJsonOptions;
+    use rkyv::AlignedVec;
+
+    let debug_id = "85314830-023f-4cf1-a267-535f4e37bb17";
+    let mut map = SourceMap::from_json(
+        "/",
+        &format!(
+            r#"{{"version":3,"sources":[],"names":[],"mappings":"","debugId":"{}"}}"#,
+            debug_id
+        ),
+    )
+    .unwrap();
+    assert_eq!(map.get_debug_id(), Some(debug_id));
+    let json = map.to_json(&ToJsonOptions::default()).unwrap();
+    assert!(json.contains(&format!(r#""debugId":"{}""#, debug_id)));
+    assert_eq!(
+        SourceMap::from_json("/", &json).unwrap().get_debug_id(),
+        Some(debug_id)
+    );
+
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    assert_eq!(
+        SourceMap::from_buffer("/", &buffer).unwrap().get_debug_id(),
+        Some(debug_id)
+    );
+
+    // Older tools wrote debug_id
+    let map = SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":[],"names":[],"mappings":"","debug_id":"abc"}"#,
+    )
+    .unwrap();
+    assert_eq!(map.get_debug_id(), Some("abc"));
+
+    let mut map = SourceMap::new("/");
+    assert_eq!(map.get_debug_id(), None);
+    assert!(!map
+        .to_json(&ToJsonOptions::default())
+        .unwrap()
+        .contains("debugId"));
+    map.set_debug_id(Some(debug_id));
+    map.set_debug_id(None);
+    assert_eq!(map.get_debug_id(), None);
+}
@@ -0,0 +1,134 @@
+use crate::mapping::OriginalLocation;
+use crate::registry::strip_query_and_hash;
+use crate::sourcemap_error::SourceMapError;
+use crate::url::resolve_url;
+use crate::SourceMap;
+
+// TypeScript declaration files, including the ones for ES modules and CommonJS
+pub fn is_declaration_file(path: &str) -> bool {
+    let path = strip_query_and_hash(path);
+    [".d.ts", ".d.mts", ".d.cts"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+impl SourceMap {
+    // For the map of bundled declaration files (rollup-plugin-dts, api-extractor, ...):
+    // mappings pointing into a .d.ts file get remapped through its declaration map (returned
+    // by resolve, as written by tsc with declarationMap), so go to definition ends up in the
+    // .ts sources. Declaration files without a map are kept as sources.
+    //
+    // Declaration maps differ from regular maps in two ways. Their sources are relative to
+    // the .d.ts file, which tsc writes the map next to. They only map the start and names of
+    // declarations, so positions on lines without any (members, closing braces) resolve to
+    // the last declaration above them instead of losing their location.
+    pub fn compose_declaration_maps<F>(&mut self, resolve: &mut F) -> Result<(), SourceMapError>
+    where
+        F: FnMut(&str) -> Option<SourceMap>,
+    {
+        let source_count = self.inner.sources.len();
+        for source_index in 0..source_count {
+            self.check_cancelled()?;
+            let source = self.inner.sources[source_index].clone();
+            if !is_declaration_file(&source) {
+                continue;
+            }
+
+            let mut declaration_map = match resolve(&source) {
+                Some(declaration_map) => declaration_map,
+                None => continue,
+            };
+            for declaration_source in declaration_map.inner.sources.iter_mut() {
+                *declaration_source = resolve_url(&source, declaration_source);
+            }
+
+            // The last mapping of every line that has any, to fall back to
+            let line_ends: Vec<(u32, OriginalLocation)> = declaration_map
+                .inner
+                .mapping_lines
+                .iter()
+                .filter_map(|(line, mapping_line)| {
+                    let last = mapping_line
+                        .mappings
+                        .iter()
+                        .filter(|m| m.original.is_some())
+                        .max_by_key(|m| m.generated_column)?;
+                    Some((line as u32, last.original?))
+                })
+                .collect();
+            self.remap_source_with(
+                Some(source_index as u32),
+                &mut declaration_map,
+                |declaration_map, line, column| {
+                    if let Some(original) = declaration_map
+                        .find_closest_mapping(line, column)
+                        .and_then(|m| m.original)
+                    {
+                        return Some(original);
+                    }
+                    let above = line_ends.partition_point(|(end_line, _)| *end_line < line);
+                    above.checked_sub(1).map(|index| line_ends[index].1)
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_compose_declaration_maps() {
+    let mut bundle = SourceMap::new("/");
+    let a = bundle.add_source("types/a.d.ts");
+    let b = bundle.add_source("types/b.d.ts");
+    let helper = bundle.add_source("types/helper.js");
+    let foo = bundle.add_name("foo");
+    bundle.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, a, None)));
+    bundle.add_mapping(0, 17, Some(OriginalLocation::new(0, 24, a, Some(foo))));
+    bundle.add_mapping(1, 4, Some(OriginalLocation::new(2, 4, a, None)));
+    bundle.add_mapping(2, 0, Some(OriginalLocation::new(0, 0, b, None)));
+    bundle.add_mapping(3, 0, Some(OriginalLocation::new(0, 0, helper, None)));
+
+    let mut resolved = Vec::new();
+    let mut resolve = |source: &str| {
+        resolved.push(String::from(source));
+        if source != "types/a.d.ts" {
+            return None;
+        }
+        SourceMap::from_json(
+            "/",
+            r#"{"version":3,"file":"a.d.ts","sourceRoot":"","sources":["../src/a.ts"],"names":[],"mappings":"AAAA,wBAAgB;IAGd"}"#,
+        )
+        .ok()
+    };
+    bundle.compose_declaration_maps(&mut resolve).unwrap();
+    assert_eq!(resolved, vec!["types/a.d.ts", "types/b.d.ts"]);
+
+    let mut originals: Vec<(u32, u32, u32, u32, Option<u32>)> = bundle
+        .get_mappings()
+        .iter()
+        .map(|m| {
+            let original = m.original.unwrap();
+            (
+                m.generated_line,
+                original.source,
+                original.original_line,
+                original.original_column,
+                original.name,
+            )
+        })
+        .collect();
+    originals.sort();
+    let src = bundle.get_source_index("src/a.ts").unwrap().unwrap();
+    assert_eq!(
+        originals,
+        vec![
+            (0, src, 0, 0, None),
+            (0, src, 0, 16, Some(foo)),
+            // Line 2 of a.d.ts has no mappings, so this resolves to line 1
+            (1, src, 3, 2, None),
+            (2, b, 0, 0, None),
+            (3, helper, 0, 0, None),
+        ]
+    );
+}
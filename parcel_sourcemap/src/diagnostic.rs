@@ -0,0 +1,108 @@
+use crate::code_frame::{render_lines, DEFAULT_CONTEXT_LINES};
+use crate::mapping::Mapping;
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+
+// Renders a code frame with a caret under the given (zero-based) line and column, e.g.
+//
+//   1 | let a = 1;
+// > 2 | foo(a);
+//     |     ^
+//   3 | bar();
+//
+// Positions outside of the content are reported instead of being moved into it, which
+// usually means the map doesn't belong to this content.
+pub fn code_frame(content: &str, line: u32, column: u32, context_lines: u32) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let frame = match render_lines(&lines, line, column, context_lines) {
+        Some(frame) => frame,
+        None => {
+            return format!(
+                "line {} is past the end of the source, which has {} line{}\n",
+                line + 1,
+                lines.len(),
+                if lines.len() == 1 { "" } else { "s" }
+            )
+        }
+    };
+
+    let mut output = String::new();
+    for frame_line in frame {
+        output.push_str(&frame_line);
+        output.push('\n');
+    }
+    output
+}
+
+fn describe_error(error: &SourceMapError) -> String {
+    match &error.reason {
+        Some(reason) => format!("{:?}: {}", error.error_type, reason),
+        None => format!("{:?}", error.error_type),
+    }
+}
+
+impl SourceMap {
+    // Renders the original location of a mapping as `source:line:column` (one-based)
+    // followed by a code frame, None if the mapping has no original location
+    pub fn render_mapping(&self, mapping: &Mapping) -> Option<String> {
+        let original = mapping.original?;
+        let source = self.get_source(original.source).ok()?;
+        let mut output = format!(
+            "{}:{}:{}\n",
+            source,
+            original.original_line + 1,
+            original.original_column + 1
+        );
+
+        // Only render a frame when the source content is available
+        if let Ok(content) = self.get_source_content(original.source) {
+            output.push_str(&code_frame(
+                content,
+                original.original_line,
+                original.original_column,
+                DEFAULT_CONTEXT_LINES,
+            ));
+        }
+        Some(output)
+    }
+
+    // Renders an error, optionally with the location it relates to
+    pub fn render_error(&self, error: &SourceMapError, mapping: Option<&Mapping>) -> String {
+        let mut output = format!("[parcel-sourcemap] {}\n", describe_error(error));
+        if let Some(location) = mapping.and_then(|m| self.render_mapping(m)) {
+            output.push_str(&location);
+        }
+        output
+    }
+}
+
+#[test]
+fn test_render_mapping() {
+    use crate::mapping::OriginalLocation;
+    use crate::sourcemap_error::SourceMapErrorType;
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.set_source_content(source as usize, "let a = 1;\nfoo(a);\nbar();")
+        .unwrap();
+    map.add_mapping(0, 0, Some(OriginalLocation::new(1, 4, source, None)));
+
+    let mapping = map.find_closest_mapping(0, 0).unwrap();
+    assert_eq!(
+        map.render_mapping(&mapping).unwrap(),
+        "a.js:2:5\n  1 | let a = 1;\n> 2 | foo(a);\n    |     ^\n  3 | bar();\n"
+    );
+
+    map.add_mapping(0, 4, Some(OriginalLocation::new(5, 0, source, None)));
+    let mapping = map.find_closest_mapping(0, 4).unwrap();
+    assert_eq!(
+        map.render_mapping(&mapping).unwrap(),
+        "a.js:6:1\nline 6 is past the end of the source, which has 3 lines\n"
+    );
+
+    let mapping = map.find_closest_mapping(0, 0).unwrap();
+    let error = SourceMapError::new_with_reason(SourceMapErrorType::NameOutOfRange, "name 3");
+    assert!(map
+        .render_error(&error, Some(&mapping))
+        .starts_with("[parcel-sourcemap] NameOutOfRange: name 3\na.js:2:5\n"));
+}
@@ -0,0 +1,169 @@
+#[cfg(feature = "json")]
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+#[cfg(feature = "json")]
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
+
+// Detects the encoding using the BOM, falling back to the position of the zero bytes
+// for UTF-16 without BOM (JSON always starts with an ASCII character)
+pub fn detect_encoding(input: &[u8]) -> InputEncoding {
+    match input {
+        [0xEF, 0xBB, 0xBF, ..] => InputEncoding::Utf8Bom,
+        [0xFF, 0xFE, ..] => InputEncoding::Utf16Le,
+        [0xFE, 0xFF, ..] => InputEncoding::Utf16Be,
+        [first, 0, ..] if *first != 0 => InputEncoding::Utf16Le,
+        [0, second, ..] if *second != 0 => InputEncoding::Utf16Be,
+        _ => InputEncoding::Utf8,
+    }
+}
+
+#[cfg(feature = "json")]
+fn decode_utf16(input: &[u8], little_endian: bool) -> Result<String, SourceMapError> {
+    if !input.len().is_multiple_of(2) {
+        return Err(SourceMapError::new_with_reason(
+            SourceMapErrorType::InvalidEncoding,
+            "UTF-16 input has an odd number of bytes",
+        ));
+    }
+
+    let units: Vec<u16> = input
+        .chunks_exact(2)
+        .map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+    String::from_utf16(&units).map_err(|_| SourceMapError::new(SourceMapErrorType::InvalidEncoding))
+}
+
+// Strips BOMs and transcodes UTF-16 to UTF-8, UTF-8 input without BOM is borrowed
+#[cfg(feature = "json")]
+pub fn sanitize_input(input: &[u8]) -> Result<(Cow<'_, str>, InputEncoding), SourceMapError> {
+    let encoding = detect_encoding(input);
+    let decoded = match encoding {
+        InputEncoding::Utf8 | InputEncoding::Utf8Bom => {
+            let bytes = if encoding == InputEncoding::Utf8Bom {
+                &input[3..]
+            } else {
+                input
+            };
+            Cow::Borrowed(
+                std::str::from_utf8(bytes)
+                    .map_err(|_| SourceMapError::new(SourceMapErrorType::InvalidEncoding))?,
+            )
+        }
+        InputEncoding::Utf16Le | InputEncoding::Utf16Be => {
+            let little_endian = encoding == InputEncoding::Utf16Le;
+            let mut decoded = decode_utf16(input, little_endian)?;
+            if decoded.starts_with('\u{feff}') {
+                decoded.remove(0);
+            }
+            Cow::Owned(decoded)
+        }
+    };
+
+    Ok((decoded, encoding))
+}
+
+enum LineTable {
+    Ascii,
+    // Byte offset of the start of every line
+    Lines(Vec<usize>),
+}
+
+// Converts original columns counted in UTF-8 bytes (what some Rust and Go based tools
+// write) into UTF-16 code units using the sources content. Sources without content or
+// with only ASCII content keep their columns.
+pub(crate) struct Utf8ColumnConverter<'a> {
+    sources_content: &'a [&'a str],
+    tables: Vec<Option<LineTable>>,
+}
+
+impl<'a> Utf8ColumnConverter<'a> {
+    pub fn new(sources_content: &'a [&'a str]) -> Self {
+        Self {
+            sources_content,
+            tables: sources_content.iter().map(|_| None).collect(),
+        }
+    }
+
+    pub fn convert(&mut self, source: usize, line: u32, column: u32) -> u32 {
+        let content = match self.sources_content.get(source) {
+            Some(content) => *content,
+            None => return column,
+        };
+        let table = self.tables[source].get_or_insert_with(|| {
+            if content.is_ascii() {
+                LineTable::Ascii
+            } else {
+                let mut starts = vec![0];
+                starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+                LineTable::Lines(starts)
+            }
+        });
+        let starts = match table {
+            LineTable::Ascii => return column,
+            LineTable::Lines(starts) => starts,
+        };
+
+        let start = match starts.get(line as usize) {
+            Some(start) => *start,
+            None => return column,
+        };
+        let end = starts
+            .get(line as usize + 1)
+            .map_or(content.len(), |next| next - 1);
+        let text = &content[start..end];
+        let mut byte = (column as usize).min(text.len());
+        while !text.is_char_boundary(byte) {
+            byte -= 1;
+        }
+        // Columns past the end of the line keep their distance to it
+        let past_end = (column as usize).saturating_sub(text.len());
+        (text[..byte].encode_utf16().count() + past_end) as u32
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_sanitize_input() {
+    let (decoded, encoding) = sanitize_input(b"\xEF\xBB\xBF{}").unwrap();
+    assert_eq!((decoded.as_ref(), encoding), ("{}", InputEncoding::Utf8Bom));
+
+    let utf16_le: Vec<u8> = "\u{feff}{}"
+        .encode_utf16()
+        .flat_map(|u| u.to_le_bytes())
+        .collect();
+    let (decoded, encoding) = sanitize_input(&utf16_le).unwrap();
+    assert_eq!((decoded.as_ref(), encoding), ("{}", InputEncoding::Utf16Le));
+
+    let utf16_be: Vec<u8> = "{}".encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+    let (decoded, encoding) = sanitize_input(&utf16_be).unwrap();
+    assert_eq!((decoded.as_ref(), encoding), ("{}", InputEncoding::Utf16Be));
+
+    assert!(sanitize_input(b"{\xFF}").is_err());
+}
+
+#[test]
+fn test_utf8_column_converter() {
+    let content = ["let é = 1;\nconst 𝒳 = é;", "ascii"];
+    let mut converter = Utf8ColumnConverter::new(&content);
+    // `=` is at byte 7 and UTF-16 column 6
+    assert_eq!(converter.convert(0, 0, 7), 6);
+    // `é` on the second line is at byte 13 and UTF-16 column 11
+    assert_eq!(converter.convert(0, 1, 13), 11);
+    assert_eq!(converter.convert(0, 1, 20), 17);
+    assert_eq!(converter.convert(0, 5, 3), 3);
+    assert_eq!(converter.convert(1, 0, 3), 3);
+    assert_eq!(converter.convert(2, 0, 3), 3);
+}
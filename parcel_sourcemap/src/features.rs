@@ -0,0 +1,54 @@
+use crate::SourceMap;
+
+// Optional capabilities compiled into this build, so bindings can check for them
+// instead of calling methods that don't exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct Features {
+    pub diagnostics: bool,
+    pub native: bool,
+    pub wasm: bool,
+    pub simd: bool,
+    pub compressed_sources_content: bool,
+    pub parallel: bool,
+    pub mmap: bool,
+    pub json: bool,
+    pub serde: bool,
+    pub lsp_types: bool,
+}
+
+impl SourceMap {
+    pub fn features() -> Features {
+        Features {
+            diagnostics: cfg!(feature = "diagnostics"),
+            native: cfg!(feature = "native"),
+            wasm: cfg!(feature = "wasm"),
+            simd: cfg!(feature = "simd"),
+            compressed_sources_content: cfg!(feature = "compressed_sources_content"),
+            parallel: cfg!(feature = "parallel"),
+            mmap: cfg!(feature = "mmap"),
+            json: cfg!(feature = "json"),
+            serde: cfg!(feature = "serde"),
+            lsp_types: cfg!(feature = "lsp-types"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_features() {
+    let features = SourceMap::features();
+    assert_eq!(features.diagnostics, cfg!(feature = "diagnostics"));
+    assert_eq!(
+        features.compressed_sources_content,
+        cfg!(feature = "compressed_sources_content")
+    );
+    assert_eq!(features.simd, cfg!(feature = "simd"));
+    let json = serde_json::to_string(&features).unwrap();
+    assert!(json.contains("\"diagnostics\""));
+    assert!(json.contains("\"compressedSourcesContent\""));
+}
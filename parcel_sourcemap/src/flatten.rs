@@ -0,0 +1,220 @@
+use crate::mapping::OriginalLocation;
+use crate::memory_budget::strings_size;
+use crate::observer::MutationEvent;
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FlattenOptions {
+    // Maximum number of maps that are chained below the map being flattened
+    pub max_depth: usize,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        Self { max_depth: 32 }
+    }
+}
+
+impl SourceMap {
+    // Recursively resolves sources that are generated files themselves through their own map
+    // (returned by resolve), so mappings end up pointing to the true original sources.
+    // Fails on cycles and on chains deeper than options.max_depth instead of looping forever.
+    pub fn flatten<F>(
+        &mut self,
+        resolve: &mut F,
+        options: &FlattenOptions,
+    ) -> Result<(), SourceMapError>
+    where
+        F: FnMut(&str) -> Option<SourceMap>,
+    {
+        let mut stack = Vec::new();
+        self.flatten_recursive(resolve, options, &mut stack)
+    }
+
+    fn flatten_recursive<F>(
+        &mut self,
+        resolve: &mut F,
+        options: &FlattenOptions,
+        stack: &mut Vec<String>,
+    ) -> Result<(), SourceMapError>
+    where
+        F: FnMut(&str) -> Option<SourceMap>,
+    {
+        let source_count = self.inner.sources.len();
+        for source_index in 0..source_count {
+            self.check_cancelled()?;
+            let source = self.inner.sources[source_index].clone();
+            if stack.contains(&source) {
+                stack.push(source);
+                return Err(SourceMapError::new_with_reason(
+                    SourceMapErrorType::CompositionCycle,
+                    &stack.join(" -> "),
+                ));
+            }
+
+            let mut source_map = match resolve(&source) {
+                Some(source_map) => source_map,
+                None => continue,
+            };
+
+            if stack.len() >= options.max_depth {
+                stack.push(source);
+                return Err(SourceMapError::new_with_reason(
+                    SourceMapErrorType::CompositionDepthExceeded,
+                    &stack.join(" -> "),
+                ));
+            }
+
+            stack.push(source);
+            source_map.flatten_recursive(resolve, options, stack)?;
+            stack.pop();
+
+            self.remap_source(source_index as u32, &mut source_map)?;
+        }
+
+        Ok(())
+    }
+
+    // Remaps all mappings pointing to source_index through the map of that source
+    fn remap_source(
+        &mut self,
+        source_index: u32,
+        source_map: &mut SourceMap,
+    ) -> Result<(), SourceMapError> {
+        self.remap_source_with(
+            Some(source_index),
+            source_map,
+            |source_map, line, column| {
+                source_map
+                    .find_closest_mapping(line, column)
+                    .and_then(|m| m.original)
+            },
+        )
+    }
+
+    // Same as remap_source, with the lookup of an original location in the map of the source
+    // left to find. A source_index of None remaps the mappings of every source.
+    pub(crate) fn remap_source_with<F>(
+        &mut self,
+        source_index: Option<u32>,
+        source_map: &mut SourceMap,
+        mut find: F,
+    ) -> Result<(), SourceMapError>
+    where
+        F: FnMut(&mut SourceMap, u32, u32) -> Option<OriginalLocation>,
+    {
+        // Only sources, names and content get added, the content is counted by
+        // set_source_content
+        let added = strings_size(&source_map.inner.sources) + strings_size(&source_map.inner.names);
+        self.ensure_memory(added + source_map.content_memory_size())?;
+        let source_indexes = self.add_sources(
+            source_map
+                .inner
+                .sources
+                .iter()
+                .map(|s| s.as_str())
+                .collect(),
+        );
+        let names_indexes =
+            self.add_names(source_map.inner.names.iter().map(|n| n.as_str()).collect());
+        for (i, source_content) in source_map.get_sources_content().iter().enumerate() {
+            if let Some(new_source_index) = source_indexes.get(i) {
+                self.set_source_content(*new_source_index as usize, source_content)?;
+            }
+        }
+
+        self.reverse_index = None;
+        self.lookup_index = None;
+        self.invalidate_vlq_cache();
+        self.notify(MutationEvent::Reset);
+        for mapping_line in self.inner.mapping_lines.lines_mut() {
+            for mapping in mapping_line.mappings.iter_mut() {
+                let original = match mapping.original {
+                    Some(original)
+                        if source_index.unwrap_or(original.source) == original.source =>
+                    {
+                        original
+                    }
+                    _ => continue,
+                };
+
+                mapping.original =
+                    match find(source_map, original.original_line, original.original_column) {
+                        Some(found) => Some(OriginalLocation::new(
+                            found.original_line,
+                            found.original_column,
+                            *source_indexes.get(found.source as usize).ok_or_else(|| {
+                                SourceMapError::new(SourceMapErrorType::SourceOutOfRange)
+                            })?,
+                            match found.name {
+                                Some(name) => {
+                                    Some(*names_indexes.get(name as usize).ok_or_else(|| {
+                                        SourceMapError::new(SourceMapErrorType::NameOutOfRange)
+                                    })?)
+                                }
+                                None => original.name,
+                            },
+                        )),
+                        None => None,
+                    };
+            }
+        }
+
+        self.charge_memory(added);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_flatten_cycle() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+
+    let mut resolve = |source: &str| {
+        let mut map = SourceMap::new("/");
+        let next = if source == "a.js" { "b.js" } else { "a.js" };
+        let next_source = map.add_source(next);
+        map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, next_source, None)));
+        Some(map)
+    };
+    let err = map
+        .flatten(&mut resolve, &FlattenOptions::default())
+        .unwrap_err();
+    assert!(matches!(
+        err.error_type,
+        SourceMapErrorType::CompositionCycle
+    ));
+    assert_eq!(err.reason.unwrap(), "a.js -> b.js -> a.js");
+}
+
+#[test]
+fn test_flatten() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(1, 2, source, None)));
+
+    let mut resolve = |source: &str| {
+        if source != "a.js" {
+            return None;
+        }
+
+        let mut map = SourceMap::new("/");
+        let original_source = map.add_source("a.ts");
+        let name = map.add_name("main");
+        map.add_mapping(
+            1,
+            0,
+            Some(OriginalLocation::new(5, 4, original_source, Some(name))),
+        );
+        Some(map)
+    };
+    map.flatten(&mut resolve, &FlattenOptions::default())
+        .unwrap();
+    let original = map.get_mappings()[0].original.unwrap();
+    assert_eq!(map.get_source(original.source).unwrap(), "a.ts");
+    assert_eq!((original.original_line, original.original_column), (5, 4));
+    assert_eq!(map.get_name(original.name.unwrap()).unwrap(), "main");
+}
@@ -0,0 +1,138 @@
+use crate::SourceMap;
+use std::fmt::Write;
+
+// Values from min to max (inclusive), buckets double in size: 0, 1, 2-3, 4-7, 8-15, ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct HistogramBucket {
+    pub min: u64,
+    pub max: u64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct DensityHistogram {
+    pub lines: u64,
+    pub mappings: u64,
+    pub max_mappings_per_line: u64,
+    // Every generated line, including the ones without mappings
+    pub mappings_per_line: Vec<HistogramBucket>,
+    // Generated columns from a mapping to the next one on its line. The last mapping of a
+    // line isn't counted, it isn't known where it ends.
+    pub segment_lengths: Vec<HistogramBucket>,
+}
+
+fn bucket_index(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()) as usize
+}
+
+fn add_to_buckets(buckets: &mut Vec<HistogramBucket>, value: u64) {
+    let index = bucket_index(value);
+    while buckets.len() <= index {
+        let (min, max) = match buckets.len() {
+            0 => (0, 0),
+            i => (1 << (i - 1), u64::MAX >> (64 - i)),
+        };
+        buckets.push(HistogramBucket { min, max, count: 0 });
+    }
+    buckets[index].count += 1;
+}
+
+impl DensityHistogram {
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    // One row per bucket: histogram,min,max,count
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("histogram,min,max,count\n");
+        for (name, buckets) in [
+            ("mappingsPerLine", &self.mappings_per_line),
+            ("segmentLengths", &self.segment_lengths),
+        ] {
+            for bucket in buckets {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{}",
+                    name, bucket.min, bucket.max, bucket.count
+                );
+            }
+        }
+        csv
+    }
+}
+
+impl SourceMap {
+    // How the mappings are spread over the generated code, to find out what makes a map slow
+    // to process: a few huge minified lines behave very differently from many short ones
+    pub fn mapping_density_histogram(&self) -> DensityHistogram {
+        let mut histogram = DensityHistogram::default();
+        let mut columns: Vec<u32> = Vec::new();
+        for line in 0..self.inner.mapping_lines.len() {
+            columns.clear();
+            if let Some(mapping_line) = self.inner.mapping_lines.get(line) {
+                columns.extend(mapping_line.mappings.iter().map(|m| m.generated_column));
+            }
+            columns.sort_unstable();
+
+            let count = columns.len() as u64;
+            histogram.lines += 1;
+            histogram.mappings += count;
+            histogram.max_mappings_per_line = histogram.max_mappings_per_line.max(count);
+            add_to_buckets(&mut histogram.mappings_per_line, count);
+            for pair in columns.windows(2) {
+                add_to_buckets(&mut histogram.segment_lengths, (pair[1] - pair[0]) as u64);
+            }
+        }
+        histogram
+    }
+}
+
+#[test]
+fn test_mapping_density_histogram() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(
+        b"AAAA,IAAA,EAAA,UAAA;;AAAA,AAAA",
+        vec!["a.js"],
+        vec![],
+        vec![],
+        0,
+        0,
+    )
+    .unwrap();
+    let histogram = map.mapping_density_histogram();
+    assert_eq!(histogram.lines, 3);
+    assert_eq!(histogram.mappings, 6);
+    assert_eq!(histogram.max_mappings_per_line, 4);
+    let buckets = |buckets: &[HistogramBucket]| -> Vec<(u64, u64, u64)> {
+        buckets.iter().map(|b| (b.min, b.max, b.count)).collect()
+    };
+    assert_eq!(
+        buckets(&histogram.mappings_per_line),
+        vec![(0, 0, 1), (1, 1, 0), (2, 3, 1), (4, 7, 1)]
+    );
+    // Columns 0, 4, 6, 16 and 0, 0
+    assert_eq!(
+        buckets(&histogram.segment_lengths),
+        vec![(0, 0, 1), (1, 1, 0), (2, 3, 1), (4, 7, 1), (8, 15, 1)]
+    );
+
+    #[cfg(feature = "json")]
+    assert!(histogram
+        .to_json()
+        .starts_with(r#"{"lines":3,"mappings":6,"maxMappingsPerLine":4,"mappingsPerLine":[{"min":0,"max":0,"count":1}"#));
+    let csv = histogram.to_csv();
+    assert!(csv.starts_with("histogram,min,max,count\nmappingsPerLine,0,0,1\n"));
+    assert!(csv.ends_with("segmentLengths,8,15,1\n"));
+
+    assert_eq!(
+        SourceMap::new("/").mapping_density_histogram(),
+        DensityHistogram::default()
+    );
+}
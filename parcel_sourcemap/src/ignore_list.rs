@@ -0,0 +1,109 @@
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+
+impl SourceMap {
+    // Marks a source as third party code (a framework, vendored library, ...) that debuggers
+    // hide from stack traces, it gets written to the ignoreList field
+    pub fn add_ignored_source(&mut self, source_index: u32) -> Result<(), SourceMapError> {
+        if source_index as usize >= self.inner.sources.len() {
+            return Err(SourceMapError::new(SourceMapErrorType::SourceOutOfRange));
+        }
+
+        self.insert_ignored_source(source_index);
+        Ok(())
+    }
+
+    fn insert_ignored_source(&mut self, source_index: u32) {
+        if let Err(position) = self.inner.ignore_list.binary_search(&source_index) {
+            self.inner.ignore_list.insert(position, source_index);
+        }
+    }
+
+    pub fn is_source_ignored(&self, source_index: u32) -> bool {
+        self.inner.ignore_list.binary_search(&source_index).is_ok()
+    }
+
+    // Indexes of the ignored sources, sorted
+    pub fn get_ignore_list(&self) -> &[u32] {
+        &self.inner.ignore_list
+    }
+
+    // Sources copied from another map stay ignored
+    pub(crate) fn copy_ignored_source(&mut self, source_index: u32, from: &SourceMap, index: u32) {
+        if from.is_source_ignored(index) {
+            self.insert_ignored_source(source_index);
+        }
+    }
+
+    // Updates the ignored sources after the sources table got rebuilt, see retain_sources_table
+    pub(crate) fn remap_ignore_list(&mut self, sources_indexes: &[Option<u32>]) {
+        let mut ignore_list: Vec<u32> = std::mem::take(&mut self.inner.ignore_list)
+            .into_iter()
+            .filter_map(|index| sources_indexes.get(index as usize).copied().flatten())
+            .collect();
+        ignore_list.sort_unstable();
+        self.inner.ignore_list = ignore_list;
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_ignore_list() {
+    use crate::json::ToJsonOptions;
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":["app.js","node_modules/react.js"],"names":[],"mappings":"AAAA,ECAA","x_google_ignoreList":[1]}"#,
+    )
+    .unwrap();
+    assert_eq!(map.get_ignore_list(), &[1]);
+    assert!(map.is_source_ignored(1) && !map.is_source_ignored(0));
+    assert!(map
+        .to_json(&ToJsonOptions::default())
+        .unwrap()
+        .contains(r#""ignoreList":[1]"#));
+    assert!(matches!(
+        map.add_ignored_source(2).unwrap_err().error_type,
+        SourceMapErrorType::SourceOutOfRange
+    ));
+
+    // ignoreList wins over the old name
+    let map = SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":["a.js","b.js"],"names":[],"mappings":"","ignoreList":[0],"x_google_ignoreList":[1]}"#,
+    )
+    .unwrap();
+    assert_eq!(map.get_ignore_list(), &[0]);
+    assert!(SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"","ignoreList":[1]}"#,
+    )
+    .is_err());
+
+    // Indexes follow the sources into other maps
+    let mut vendor = SourceMap::new("/");
+    vendor.add_source("b.js");
+    let react = vendor.add_source("react.js");
+    vendor.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, react, None)));
+    vendor.add_ignored_source(react).unwrap();
+    let mut bundle = SourceMap::new("/");
+    bundle.add_source("z.js");
+    bundle.add_sourcemap(&mut vendor.clone(), 1).unwrap();
+    assert_eq!(
+        bundle.get_source(bundle.get_ignore_list()[0]).unwrap(),
+        "react.js"
+    );
+
+    let mut minified = SourceMap::new("/");
+    let bundled = minified.add_source("bundle.js");
+    minified.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, bundled, None)));
+    minified.extends(&mut vendor).unwrap();
+    let ignored = minified.get_ignore_list();
+    assert_eq!(ignored.len(), 1);
+    assert_eq!(minified.get_source(ignored[0]).unwrap(), "react.js");
+
+    bundle.sort_sources().unwrap();
+    assert_eq!(bundle.get_ignore_list(), &[1]);
+    assert_eq!(bundle.get_source(1).unwrap(), "react.js");
+}
@@ -0,0 +1,174 @@
+use crate::utils::resolve_path;
+use crate::SourceMap;
+use std::path::{Path, PathBuf};
+
+pub fn hash_source_content(content: &str) -> u64 {
+    seahash::hash(content.as_bytes())
+}
+
+// Reads the current content of a source, usually from disk
+pub trait SourceProvider {
+    // None means the source doesn't exist (anymore)
+    fn read(&self, path: &Path) -> Option<String>;
+}
+
+impl<F> SourceProvider for F
+where
+    F: Fn(&Path) -> Option<String>,
+{
+    fn read(&self, path: &Path) -> Option<String> {
+        self(path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceMismatchKind {
+    Changed,
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMismatch {
+    pub source_index: u32,
+    pub path: PathBuf,
+    pub kind: SourceMismatchKind,
+}
+
+impl SourceMap {
+    // Stores a hash of every non-empty sourcesContent entry, these are kept in buffers so
+    // verify_sources still works after sourcesContent has been stripped. Sources copied from
+    // other maps keep their hash, setting new content drops the hash of that source (call this
+    // again to hash the new content).
+    pub fn compute_source_hashes(&mut self) {
+        self.inner.source_hashes = self
+            .get_sources_content()
+            .iter()
+            .map(|content| {
+                if content.is_empty() {
+                    None
+                } else {
+                    Some(hash_source_content(content))
+                }
+            })
+            .collect();
+    }
+
+    // Sources copied from another map keep their hash
+    pub(crate) fn copy_source_hash(&mut self, source_index: u32, from: &SourceMap, index: u32) {
+        if let Some(hash) = from.get_source_hash(index) {
+            let hashes = &mut self.inner.source_hashes;
+            if hashes.len() <= source_index as usize {
+                hashes.resize(source_index as usize + 1, None);
+            }
+            hashes[source_index as usize] = Some(hash);
+        }
+    }
+
+    pub fn get_source_hash(&self, index: u32) -> Option<u64> {
+        self.inner
+            .source_hashes
+            .get(index as usize)
+            .copied()
+            .flatten()
+    }
+
+    // Compares the content of every source the provider returns against the stored hash,
+    // or sourcesContent when there is no hash. Sources with neither are skipped.
+    pub fn verify_sources<P: SourceProvider>(&self, provider: &P) -> Vec<SourceMismatch> {
+        let mut mismatches = Vec::new();
+        for (index, source) in self.inner.sources.iter().enumerate() {
+            let expected = match self.get_source_hash(index as u32) {
+                Some(hash) => hash,
+                None => match self.get_sources_content().get(index) {
+                    Some(content) if !content.is_empty() => hash_source_content(content),
+                    _ => continue,
+                },
+            };
+
+            let path = PathBuf::from(resolve_path(&self.project_root, source));
+            let kind = match provider.read(&path) {
+                Some(content) if hash_source_content(&content) == expected => continue,
+                Some(_) => SourceMismatchKind::Changed,
+                None => SourceMismatchKind::Missing,
+            };
+            mismatches.push(SourceMismatch {
+                source_index: index as u32,
+                path,
+                kind,
+            });
+        }
+        mismatches
+    }
+}
+
+#[test]
+fn test_verify_sources() {
+    let mut map = SourceMap::new("/project");
+    let a = map.add_source("/project/a.js");
+    let b = map.add_source("/project/b.js");
+    let c = map.add_source("/project/c.js");
+    map.add_source("/project/d.js");
+    map.set_source_content(a as usize, "a").unwrap();
+    map.set_source_content(b as usize, "b").unwrap();
+    map.set_source_content(c as usize, "c").unwrap();
+    map.compute_source_hashes();
+
+    // Hashes survive stripping the content
+    map.set_source_content(c as usize, "").unwrap();
+    let provider = |path: &Path| match path.to_str() {
+        Some("/project/a.js") => Some(String::from("a")),
+        Some("/project/c.js") => Some(String::from("changed")),
+        _ => None,
+    };
+    let mismatches = map.verify_sources(&provider);
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].source_index, b);
+    assert_eq!(mismatches[0].kind, SourceMismatchKind::Missing);
+    assert_eq!(mismatches[1].source_index, c);
+    assert_eq!(mismatches[1].kind, SourceMismatchKind::Changed);
+}
+
+#[test]
+fn test_source_hashes_are_kept() {
+    use crate::concat::Concatenator;
+    use crate::mapping::{OriginalLocation, Position};
+
+    let mut map = SourceMap::new("/project");
+    let a = map.add_source("/project/a.js");
+    let b = map.add_source("/project/b.js");
+    map.set_source_content(a as usize, "a").unwrap();
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, a, None)));
+    map.add_mapping(1, 0, Some(OriginalLocation::new(0, 0, b, None)));
+    map.compute_source_hashes();
+    let hash = map.get_source_hash(a);
+    assert!(hash.is_some());
+
+    let mut merged = SourceMap::new("/project");
+    merged.add_source("/project/other.js");
+    merged.add_sourcemap(&mut map.clone(), 0).unwrap();
+    assert_eq!(merged.get_source_hash(1), hash);
+    assert_eq!(merged.get_source_hash(2), None);
+
+    let mut extended = SourceMap::new("/project");
+    extended.extend_many(&[(0, &map)]).unwrap();
+    assert_eq!(extended.get_source_hash(a), hash);
+
+    let split = map.split_by_source().unwrap();
+    assert_eq!(split[0].get_source_hash(0), hash);
+    assert_eq!(split[1].get_source_hash(0), None);
+    let split = map
+        .clone()
+        .split_at(&[Position::new(0, 0), Position::new(1, 0)])
+        .unwrap();
+    assert_eq!(split[0].get_source_hash(0), hash);
+    assert_eq!(split[1].get_source_hash(0), None);
+
+    let mut concat = Concatenator::new("/project");
+    concat.push("b;\n", None);
+    concat.push("a;\nb;\n", Some(map.clone()));
+    assert_eq!(concat.build_map().unwrap().get_source_hash(0), hash);
+
+    // The hash belongs to the previous content
+    map.set_source_content(a as usize, "changed").unwrap();
+    assert_eq!(map.get_source_hash(a), None);
+}
@@ -0,0 +1,148 @@
+use crate::mapping::FlatMappings;
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+
+// Position of the next mapping to read, so mappings can be read in batches
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MappingCursor {
+    pub line: u32,
+    pub index: u32,
+}
+
+impl SourceMap {
+    // Appends up to batch_size mappings starting at the cursor and moves the cursor past
+    // them, returns false once the cursor has reached the end of the map. Fails like
+    // FlatMappings::push, with the cursor left at the mapping that didn't fit.
+    pub fn read_mappings(
+        &mut self,
+        cursor: &mut MappingCursor,
+        batch_size: usize,
+        output: &mut FlatMappings,
+    ) -> Result<bool, SourceMapError> {
+        let policy = self.inner.duplicate_mapping_policy;
+        let mut remaining = batch_size;
+        let line_count = self.inner.mapping_lines.len();
+        while (cursor.line as usize) < line_count {
+            // Skip ahead over lines without mappings
+            let mapping_line = match self.inner.mapping_lines.get_mut(cursor.line as usize) {
+                Some(mapping_line) => mapping_line,
+                None => {
+                    cursor.line =
+                        self.inner
+                            .mapping_lines
+                            .range_from(cursor.line as usize)
+                            .next()
+                            .map_or(line_count, |(line, _)| line) as u32;
+                    cursor.index = 0;
+                    continue;
+                }
+            };
+            mapping_line.ensure_sorted_with(policy);
+            let start = cursor.index as usize;
+            let end = mapping_line.mappings.len().min(start + remaining);
+            for (i, mapping) in mapping_line
+                .mappings
+                .get(start..end)
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+            {
+                if let Err(error) =
+                    output.push(cursor.line, mapping.generated_column, mapping.original)
+                {
+                    cursor.index = (start + i) as u32;
+                    return Err(error);
+                }
+            }
+
+            remaining -= end.saturating_sub(start);
+            if end < mapping_line.mappings.len() {
+                cursor.index = end as u32;
+                return Ok(true);
+            }
+
+            cursor.line += 1;
+            cursor.index = 0;
+            if remaining == 0 {
+                break;
+            }
+        }
+
+        Ok((cursor.line as usize) < self.inner.mapping_lines.len())
+    }
+
+    // Iterates over all mappings in batches of (at most) batch_size mappings
+    pub fn mapping_batches(&mut self, batch_size: usize) -> MappingBatches<'_> {
+        MappingBatches {
+            sourcemap: self,
+            cursor: MappingCursor::default(),
+            batch_size: batch_size.max(1),
+            failed: false,
+        }
+    }
+}
+
+pub struct MappingBatches<'a> {
+    sourcemap: &'a mut SourceMap,
+    cursor: MappingCursor,
+    batch_size: usize,
+    failed: bool,
+}
+
+// Stops after the first error
+impl Iterator for MappingBatches<'_> {
+    type Item = Result<FlatMappings, SourceMapError>;
+
+    fn next(&mut self) -> Option<Result<FlatMappings, SourceMapError>> {
+        if self.failed {
+            return None;
+        }
+        let mut batch = FlatMappings::with_capacity(self.batch_size);
+        if let Err(error) =
+            self.sourcemap
+                .read_mappings(&mut self.cursor, self.batch_size, &mut batch)
+        {
+            self.failed = true;
+            return Some(Err(error));
+        }
+        if batch.is_empty() {
+            None
+        } else {
+            Some(Ok(batch))
+        }
+    }
+}
+
+#[test]
+fn test_mapping_batches() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(
+        b"AAAA,CAAC,CAAC;;AACA,CAAC",
+        vec!["a.js"],
+        vec![],
+        vec![],
+        0,
+        0,
+    )
+    .unwrap();
+
+    let batch_sizes: Vec<usize> = map
+        .mapping_batches(2)
+        .map(|batch| batch.unwrap().len())
+        .collect();
+    assert_eq!(batch_sizes, vec![2, 2, 1]);
+
+    let batches: Vec<FlatMappings> = map.mapping_batches(3).map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1].generated_lines, vec![2, 2]);
+    assert_eq!(batches[1].generated_columns, vec![0, 1]);
+
+    // The batch with a mapping that doesn't fit fails, the iteration ends after it
+    map.add_mapping(
+        3,
+        0,
+        Some(crate::mapping::OriginalLocation::new(u32::MAX, 0, 0, None)),
+    );
+    let results: Vec<bool> = map.mapping_batches(3).map(|b| b.is_ok()).collect();
+    assert_eq!(results, vec![true, false]);
+}
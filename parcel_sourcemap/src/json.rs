@@ -0,0 +1,836 @@
+use crate::cancel::CancellationToken;
+use crate::capacity::CapacityHints;
+use crate::encoding::{sanitize_input, InputEncoding};
+use crate::mapping::{NameWithoutSourcePolicy, Position, SourceRootHandling, SourceRootOutput};
+use crate::memory_budget::MemoryBudget;
+use crate::provenance::SourceProvenance;
+use crate::source_root::join_source_root;
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::{LazySourceMap, SourceMap};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    // Keep the input around so it can be retrieved using raw_input()
+    pub keep_raw_input: bool,
+    // See SourceMap::set_repair_missing_sources
+    pub repair_missing_sources: bool,
+    pub name_without_source: NameWithoutSourcePolicy,
+    // Original columns are UTF-8 byte offsets, see SourceMap::set_utf8_original_columns
+    pub utf8_original_columns: bool,
+    // Aborts parsing once cancelled, only applies to this parse (see
+    // SourceMap::set_cancellation_token to keep one on the map)
+    pub cancellation: Option<CancellationToken>,
+    // Reserved up front, for callers that know roughly how big the map is
+    pub capacity: CapacityHints,
+    // Counted against this budget instead of the global one, see SourceMap::set_memory_budget
+    pub memory_budget: Option<MemoryBudget>,
+    // Only applies to regular maps, the sourceRoot of index map sections is always joined
+    pub source_root: SourceRootHandling,
+    // Accept numbers written as floats (3.0) or strings ("3") where the spec wants integers,
+    // as some generators do. Fractions are rounded down and reported in parse_diagnostics,
+    // negative, too large and non-numeric values are still rejected.
+    pub lenient_numbers: bool,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ToJsonOptions {
+    // Without names every segment is written with 4 fields, which is smaller but lossy:
+    // parsing the output again gives a map without any names
+    pub include_names: bool,
+    pub source_root: SourceRootOutput,
+}
+
+impl Default for ToJsonOptions {
+    fn default() -> Self {
+        Self {
+            include_names: true,
+            source_root: SourceRootOutput::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JsonSourceMap<'a> {
+    // Index maps have sections instead of mappings
+    #[serde(default, borrow)]
+    mappings: Cow<'a, str>,
+    #[serde(default, borrow)]
+    source_root: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    sources: Vec<Option<Cow<'a, str>>>,
+    #[serde(default, borrow)]
+    sources_content: Vec<Option<Cow<'a, str>>>,
+    #[serde(default, borrow)]
+    names: Vec<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    sections: Vec<JsonSection<'a>>,
+    #[serde(default)]
+    ignore_list: Option<Vec<JsonNumber>>,
+    // What ignoreList was called before it became part of the spec
+    #[serde(default, rename = "x_google_ignoreList")]
+    x_google_ignore_list: Option<Vec<JsonNumber>>,
+    #[serde(default, borrow)]
+    debug_id: Option<Cow<'a, str>>,
+    // What debugId was called before the proposal settled on a name
+    #[serde(default, borrow, rename = "debug_id")]
+    legacy_debug_id: Option<Cow<'a, str>>,
+}
+
+#[derive(Deserialize)]
+struct JsonSection<'a> {
+    offset: JsonInputOffset,
+    #[serde(borrow)]
+    map: Option<JsonSourceMap<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonOffset {
+    line: u32,
+    column: u32,
+}
+
+#[derive(Deserialize)]
+struct JsonInputOffset {
+    line: JsonNumber,
+    column: JsonNumber,
+}
+
+// A number as generators write them, which isn't always an integer
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonNumber {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    String(String),
+}
+
+impl JsonNumber {
+    // Floats and strings are only accepted with lenient, fractions are rounded down and
+    // reported in diagnostics. Errors and diagnostics name the field, e.g.
+    // sections[1].offset.line.
+    fn to_u32(
+        &self,
+        field: &str,
+        lenient: bool,
+        diagnostics: &mut Vec<String>,
+    ) -> Result<u32, SourceMapError> {
+        let invalid = |reason: String| {
+            SourceMapError::new_with_reason(
+                SourceMapErrorType::InvalidJson,
+                &format!("{} {}", field, reason),
+            )
+        };
+        let mut from_float = |value: f64| {
+            if !value.is_finite() {
+                Err(invalid(format!("is {}, which is not a number", value)))
+            } else if value < 0.0 {
+                Err(invalid(format!("is {}, which is negative", value)))
+            } else if value >= u32::MAX as f64 + 1.0 {
+                Err(invalid(format!("is {}, which is too large", value)))
+            } else {
+                let coerced = value.trunc();
+                if coerced != value {
+                    diagnostics.push(format!(
+                        "{} is {}, which is not a whole number, used {}",
+                        field, value, coerced
+                    ));
+                }
+                Ok(coerced as u32)
+            }
+        };
+
+        match self {
+            JsonNumber::Unsigned(value) => u32::try_from(*value)
+                .map_err(|_| invalid(format!("is {}, which is too large", value))),
+            JsonNumber::Signed(value) => Err(invalid(format!("is {}, which is negative", value))),
+            JsonNumber::Float(value) if lenient => from_float(*value),
+            JsonNumber::String(value) if lenient => match value.trim().parse::<f64>() {
+                Ok(number) => from_float(number),
+                Err(_) => Err(invalid(format!("is {:?}, which is not a number", value))),
+            },
+            JsonNumber::Float(value) => Err(invalid(format!(
+                "is {}, expected an integer (see lenient_numbers)",
+                value
+            ))),
+            JsonNumber::String(value) => Err(invalid(format!(
+                "is {:?}, expected an integer (see lenient_numbers)",
+                value
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonIndexMap<'a> {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug_id: Option<&'a str>,
+    sections: Vec<JsonSectionOutput<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonSectionOutput<'a> {
+    offset: JsonOffset,
+    map: JsonSectionMap<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSectionMap<'a> {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_root: Option<&'a str>,
+    sources: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sources_content: Option<&'a [String]>,
+    names: &'a [String],
+    mappings: String,
+    #[serde(skip_serializing_if = "<[u32]>::is_empty")]
+    ignore_list: &'a [u32],
+}
+
+fn as_strs<'a>(values: &'a [Option<Cow<'a, str>>]) -> Vec<&'a str> {
+    values
+        .iter()
+        .map(|v| v.as_ref().map(|v| v.as_ref()).unwrap_or(""))
+        .collect()
+}
+
+impl SourceMap {
+    pub fn from_json(project_root: &str, input: &str) -> Result<SourceMap, SourceMapError> {
+        SourceMap::from_json_with_options(project_root, input, &ParseOptions::default())
+    }
+
+    pub fn from_json_with_options(
+        project_root: &str,
+        input: &str,
+        options: &ParseOptions,
+    ) -> Result<SourceMap, SourceMapError> {
+        let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+        let json: JsonSourceMap = serde_json::from_str(input)?;
+        let mut sourcemap = SourceMap::from_json_source_map(project_root, &json, options)?;
+        if options.keep_raw_input {
+            sourcemap.raw_input = Some(String::from(input));
+            sourcemap.check_memory_budget()?;
+        }
+        Ok(sourcemap)
+    }
+
+    // Builds the map from the parsed JSON, also used to deserialize maps (see serde_support.rs)
+    pub(crate) fn from_json_source_map(
+        project_root: &str,
+        json: &JsonSourceMap,
+        options: &ParseOptions,
+    ) -> Result<SourceMap, SourceMapError> {
+        let mut sourcemap = SourceMap::new(project_root);
+        sourcemap.apply_parse_options(options);
+        sourcemap.reserve(&options.capacity);
+        sourcemap.check_cancelled()?;
+        if json.sections.is_empty() {
+            let join_root = options.source_root == SourceRootHandling::Join;
+            sourcemap.add_json_map(json, join_root, options.lenient_numbers, "")?;
+            if !join_root {
+                sourcemap.set_source_root(json.source_root.as_deref());
+            }
+        } else {
+            for (i, section) in json.sections.iter().enumerate() {
+                // Sections can also point to a map using a url, this would need a loader
+                let map = section.map.as_ref().ok_or_else(|| {
+                    SourceMapError::new_with_reason(
+                        SourceMapErrorType::InvalidJson,
+                        "index map sections with a url are not supported",
+                    )
+                })?;
+                if !map.sections.is_empty() {
+                    return Err(SourceMapError::new_with_reason(
+                        SourceMapErrorType::InvalidJson,
+                        "index map sections cannot be nested",
+                    ));
+                }
+
+                let mut section_map = SourceMap::new(project_root);
+                section_map.apply_parse_options(options);
+                section_map.add_json_map(
+                    map,
+                    true,
+                    options.lenient_numbers,
+                    &format!("sections[{}].map.", i),
+                )?;
+                sourcemap
+                    .parse_diagnostics
+                    .append(&mut section_map.parse_diagnostics);
+                let offset = Position::new(
+                    section.offset.line.to_u32(
+                        &format!("sections[{}].offset.line", i),
+                        options.lenient_numbers,
+                        &mut sourcemap.parse_diagnostics,
+                    )?,
+                    section.offset.column.to_u32(
+                        &format!("sections[{}].offset.column", i),
+                        options.lenient_numbers,
+                        &mut sourcemap.parse_diagnostics,
+                    )?,
+                );
+                sourcemap.add_section(&section_map, offset)?;
+            }
+        }
+
+        sourcemap.set_debug_id(
+            json.debug_id
+                .as_ref()
+                .or(json.legacy_debug_id.as_ref())
+                .map(|d| d.as_ref()),
+        );
+        sourcemap.check_memory_budget()?;
+        sourcemap.set_cancellation_token(None);
+
+        Ok(sourcemap)
+    }
+
+    fn apply_parse_options(&mut self, options: &ParseOptions) {
+        self.set_repair_missing_sources(options.repair_missing_sources);
+        self.set_name_without_source_policy(options.name_without_source);
+        self.set_utf8_original_columns(options.utf8_original_columns);
+        self.set_cancellation_token(options.cancellation.clone());
+        if let Some(budget) = &options.memory_budget {
+            self.set_memory_budget(Some(budget.clone()));
+        }
+    }
+
+    fn add_json_map(
+        &mut self,
+        json: &JsonSourceMap,
+        join_root: bool,
+        lenient_numbers: bool,
+        // Where json is in the input, for errors and diagnostics
+        path: &str,
+    ) -> Result<(), SourceMapError> {
+        let joined: Vec<String>;
+        let mut sources = as_strs(&json.sources);
+        if join_root && json.source_root.as_deref().is_some_and(|r| !r.is_empty()) {
+            joined = sources
+                .iter()
+                .map(|source| join_source_root(json.source_root.as_deref(), source))
+                .collect();
+            sources = joined.iter().map(|s| s.as_str()).collect();
+        }
+        self.add_vlq_map(
+            json.mappings.as_bytes(),
+            sources.clone(),
+            as_strs(&json.sources_content),
+            json.names.iter().map(|n| n.as_ref()).collect(),
+            0,
+            0,
+        )?;
+
+        let (ignore_list, field) = match json.ignore_list.as_ref() {
+            Some(ignore_list) => (Some(ignore_list), "ignoreList"),
+            None => (json.x_google_ignore_list.as_ref(), "x_google_ignoreList"),
+        };
+        for (i, index) in ignore_list.into_iter().flatten().enumerate() {
+            let index = index.to_u32(
+                &format!("{}{}[{}]", path, field, i),
+                lenient_numbers,
+                &mut self.parse_diagnostics,
+            )?;
+            let source = sources.get(index as usize).ok_or_else(|| {
+                SourceMapError::new_with_reason(SourceMapErrorType::SourceOutOfRange, "ignoreList")
+            })?;
+            // Sources got deduplicated while they were added
+            let normalized = self.normalize_source(source);
+            if let Some(source_index) = self.find_source(&normalized) {
+                self.add_ignored_source(source_index as u32)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Folds a section of an index map into the flat mappings. Unlike add_sourcemap the lines
+    // are not replaced, a section can start halfway a line that the previous section ends on.
+    // The column offset only applies to the first line of the section.
+    fn add_section(&mut self, section: &SourceMap, offset: Position) -> Result<(), SourceMapError> {
+        let mut source_indexes = Vec::with_capacity(section.inner.sources.len());
+        for (i, source) in section.inner.sources.iter().enumerate() {
+            let provenance = section.get_source_provenance(i as u32);
+            // Placeholders from repair_missing_sources are already final
+            let source_index = match provenance {
+                SourceProvenance::Synthesized => self.add_source_unnormalized(source.clone()),
+                _ => self.add_source(source),
+            };
+            self.merge_source_provenance(source_index, provenance);
+            self.copy_ignored_source(source_index, section, i as u32);
+            source_indexes.push(source_index);
+        }
+        for (i, source_content) in section.try_get_sources_content()?.iter().enumerate() {
+            if !source_content.is_empty() {
+                self.set_source_content(source_indexes[i] as usize, source_content)?;
+            }
+        }
+        let names_indexes: Vec<u32> = section
+            .inner
+            .names
+            .iter()
+            .map(|name| self.add_name(name))
+            .collect();
+
+        let too_big = || {
+            SourceMapError::new_with_reason(
+                SourceMapErrorType::UnexpectedlyBigNumber,
+                "section offset",
+            )
+        };
+        for (line, mapping_line) in section.inner.mapping_lines.iter() {
+            let column_offset = if line == 0 { offset.column } else { 0 };
+            let generated_line = offset.line.checked_add(line as u32).ok_or_else(too_big)?;
+            for mapping in mapping_line.mappings.iter() {
+                let original = match mapping.original {
+                    Some(mut original) => {
+                        original.source = *source_indexes
+                            .get(original.source as usize)
+                            .ok_or_else(|| {
+                                SourceMapError::new(SourceMapErrorType::SourceOutOfRange)
+                            })?;
+                        original.name = match original.name {
+                            Some(name) => {
+                                Some(*names_indexes.get(name as usize).ok_or_else(|| {
+                                    SourceMapError::new(SourceMapErrorType::NameOutOfRange)
+                                })?)
+                            }
+                            None => None,
+                        };
+                        Some(original)
+                    }
+                    None => None,
+                };
+                let generated_column = mapping
+                    .generated_column
+                    .checked_add(column_offset)
+                    .ok_or_else(too_big)?;
+                self.add_mapping(generated_line, generated_column, original);
+            }
+        }
+        Ok(())
+    }
+
+    // Writes a regular map, sources without content get null in sourcesContent
+    pub fn to_json(&mut self, options: &ToJsonOptions) -> Result<String, SourceMapError> {
+        let mut output = Vec::new();
+        self.write_json(&mut output, options)?;
+        // serde_json and write_vlq only write valid UTF-8
+        Ok(String::from_utf8(output).unwrap_or_default())
+    }
+
+    // Same output as to_json, but every part is written to the writer as soon as it is
+    // encoded, so the whole JSON never has to be in memory. Small writes are buffered.
+    pub fn write_json<W: io::Write>(
+        &mut self,
+        writer: W,
+        options: &ToJsonOptions,
+    ) -> Result<(), SourceMapError> {
+        let mut writer = io::BufWriter::new(writer);
+        writer.write_all(b"{\"version\":3,")?;
+        let mut resolved_sources = None;
+        if let Some(source_root) = self.get_source_root() {
+            match options.source_root {
+                SourceRootOutput::Emit => {
+                    writer.write_all(b"\"sourceRoot\":")?;
+                    serde_json::to_writer(&mut writer, source_root)?;
+                    writer.write_all(b",")?;
+                }
+                SourceRootOutput::Resolve => {
+                    resolved_sources = Some(
+                        self.inner
+                            .sources
+                            .iter()
+                            .map(|source| join_source_root(Some(source_root), source))
+                            .collect::<Vec<String>>(),
+                    );
+                }
+            }
+        }
+        writer.write_all(b"\"sources\":")?;
+        match &resolved_sources {
+            Some(sources) => serde_json::to_writer(&mut writer, sources)?,
+            None => serde_json::to_writer(&mut writer, &self.inner.sources)?,
+        }
+
+        writer.write_all(b",\"sourcesContent\":[")?;
+        for i in 0..self.inner.sources.len() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            let content = self
+                .try_get_sources_content()?
+                .get(i)
+                .filter(|c| !c.is_empty());
+            serde_json::to_writer(&mut writer, &content)?;
+        }
+
+        writer.write_all(b"],\"names\":")?;
+        let names: &[String] = if options.include_names {
+            &self.inner.names
+        } else {
+            &[]
+        };
+        serde_json::to_writer(&mut writer, names)?;
+
+        if !self.inner.ignore_list.is_empty() {
+            writer.write_all(b",\"ignoreList\":")?;
+            serde_json::to_writer(&mut writer, &self.inner.ignore_list)?;
+        }
+
+        if let Some(debug_id) = &self.inner.debug_id {
+            writer.write_all(b",\"debugId\":")?;
+            serde_json::to_writer(&mut writer, debug_id)?;
+        }
+
+        // write_vlq only writes VLQ characters and separators, which never need escaping
+        writer.write_all(b",\"mappings\":\"")?;
+        self.write_vlq_with_names(&mut writer, options.include_names)?;
+        writer.write_all(b"\"}")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Writes an index map with a section starting at every offset (sorted, the first one
+    // usually being 0:0), mappings before the first offset are dropped. Each section only
+    // holds the sources and names it references and is encoded on its own, so unchanged
+    // chunks of a bundle produce the same section text.
+    pub fn to_json_sections(&mut self, offsets: &[Position]) -> Result<String, SourceMapError> {
+        let mut chunks = self.split_at(offsets)?;
+        let mut encoded = Vec::with_capacity(chunks.len());
+        for chunk in chunks.iter_mut() {
+            self.check_cancelled()?;
+            let mut mappings = Vec::new();
+            chunk.write_vlq(&mut mappings)?;
+            // write_vlq only writes VLQ characters and separators
+            encoded.push(String::from_utf8(mappings).unwrap_or_default());
+        }
+
+        let sections = chunks
+            .iter()
+            .zip(encoded)
+            .zip(offsets)
+            .map(|((chunk, mappings), offset)| {
+                let has_sources_content = chunk.get_sources_content().iter().any(|c| !c.is_empty());
+                let offset = self.generated_offset.then(*offset);
+                JsonSectionOutput {
+                    offset: JsonOffset {
+                        line: offset.line,
+                        column: offset.column,
+                    },
+                    map: JsonSectionMap {
+                        version: 3,
+                        source_root: self.get_source_root(),
+                        sources: &chunk.inner.sources,
+                        sources_content: if has_sources_content {
+                            Some(chunk.get_sources_content())
+                        } else {
+                            None
+                        },
+                        names: &chunk.inner.names,
+                        mappings,
+                        ignore_list: &chunk.inner.ignore_list,
+                    },
+                }
+            })
+            .collect();
+        Ok(serde_json::to_string(&JsonIndexMap {
+            version: 3,
+            debug_id: self.inner.debug_id.as_deref(),
+            sections,
+        })?)
+    }
+
+    // Parses a map that might start with a BOM or be UTF-16 encoded,
+    // also returns the encoding that was detected
+    pub fn from_json_bytes(
+        project_root: &str,
+        input: &[u8],
+        options: &ParseOptions,
+    ) -> Result<(SourceMap, InputEncoding), SourceMapError> {
+        let (input, encoding) = sanitize_input(input)?;
+        let sourcemap = SourceMap::from_json_with_options(project_root, &input, options)?;
+        Ok((sourcemap, encoding))
+    }
+
+    // The JSON this map was parsed from, only kept when ParseOptions::keep_raw_input is set
+    pub fn raw_input(&self) -> Option<&str> {
+        self.raw_input.as_deref()
+    }
+
+    // Numbers that ParseOptions::lenient_numbers had to round down, e.g.
+    // "sections[1].offset.line is 2.5, which is not a whole number, used 2"
+    pub fn parse_diagnostics(&self) -> &[String] {
+        &self.parse_diagnostics
+    }
+}
+
+impl LazySourceMap {
+    // Index maps aren't supported, their sections would have to be decoded up front
+    pub fn from_json(project_root: &str, input: &str) -> Result<LazySourceMap, SourceMapError> {
+        let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+        let json: JsonSourceMap = serde_json::from_str(input)?;
+        if !json.sections.is_empty() {
+            return Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::InvalidJson,
+                "index maps cannot be parsed lazily",
+            ));
+        }
+        LazySourceMap::new(
+            project_root,
+            json.mappings.as_bytes(),
+            as_strs(&json.sources),
+            as_strs(&json.sources_content),
+            json.names.iter().map(|n| n.as_ref()).collect(),
+        )
+    }
+}
+
+#[test]
+fn test_from_json() {
+    let input = r#"{
+        "version": 3,
+        "sources": ["a.js", null],
+        "sourcesContent": [null, "b"],
+        "names": ["foo"],
+        "mappings": "AAAAA;ACAA"
+    }"#;
+    let map = SourceMap::from_json("/", input).unwrap();
+    assert_eq!(map.get_sources(), &vec!["a.js", ""]);
+    assert_eq!(map.get_mappings().len(), 2);
+    assert!(map.raw_input().is_none());
+
+    let options = ParseOptions {
+        keep_raw_input: true,
+        ..Default::default()
+    };
+    let map = SourceMap::from_json_with_options("/", input, &options).unwrap();
+    assert_eq!(map.raw_input(), Some(input));
+
+    let with_bom = format!("\u{feff}{}", input);
+    let (map, encoding) =
+        SourceMap::from_json_bytes("/", with_bom.as_bytes(), &ParseOptions::default()).unwrap();
+    assert_eq!(map.get_mappings().len(), 2);
+    assert_eq!(encoding, InputEncoding::Utf8Bom);
+}
+
+#[test]
+fn test_from_json_index_map() {
+    let input = r#"{
+        "version": 3,
+        "sections": [
+            {
+                "offset": { "line": 0, "column": 0 },
+                "map": { "version": 3, "sources": ["a.js"], "names": ["a"], "mappings": "AAAAA,EAAE" }
+            },
+            {
+                "offset": { "line": 0, "column": 10 },
+                "map": { "version": 3, "sources": ["b.js"], "sourcesContent": ["b"], "mappings": "AAAA;AACA" }
+            },
+            {
+                "offset": { "line": 3, "column": 0 },
+                "map": { "version": 3, "sources": ["a.js"], "mappings": "AAEA" }
+            }
+        ]
+    }"#;
+    let mut map = SourceMap::from_json("/", input).unwrap();
+    assert_eq!(map.get_sources(), &vec!["a.js", "b.js"]);
+    assert_eq!(map.get_source_content(1).unwrap(), "b");
+    assert_eq!(map.get_names(), &vec!["a"]);
+
+    let generated: Vec<(u32, u32, u32)> = map
+        .get_mappings()
+        .iter()
+        .map(|m| {
+            let original = m.original.unwrap();
+            (m.generated_line, m.generated_column, original.source)
+        })
+        .collect();
+    assert_eq!(
+        generated,
+        vec![(0, 0, 0), (0, 2, 0), (0, 10, 1), (1, 0, 1), (3, 0, 0)]
+    );
+    let found = map.find_closest_mapping(3, 4).unwrap();
+    assert_eq!(found.original.unwrap().original_line, 2);
+
+    let with_url =
+        r#"{"version": 3, "sections": [{"offset": {"line": 0, "column": 0}, "url": "a.js.map"}]}"#;
+    let error = SourceMap::from_json("/", with_url).unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::InvalidJson));
+}
+
+#[test]
+fn test_to_json_sections() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    let b = map.add_source("b.js");
+    map.set_source_content(b as usize, "b").unwrap();
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, a, None)));
+    map.add_mapping(0, 10, Some(OriginalLocation::new(0, 0, b, None)));
+    map.add_mapping(1, 0, Some(OriginalLocation::new(1, 0, b, None)));
+
+    let offsets = [Position::new(0, 0), Position::new(0, 10)];
+    let output = map.to_json_sections(&offsets).unwrap();
+    assert_eq!(
+        output,
+        r#"{"version":3,"sections":[{"offset":{"line":0,"column":0},"map":{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}},{"offset":{"line":0,"column":10},"map":{"version":3,"sources":["b.js"],"sourcesContent":["b"],"names":[],"mappings":"AAAA;AACA"}}]}"#
+    );
+
+    let mut parsed = SourceMap::from_json("/", &output).unwrap();
+    assert_eq!(
+        format!("{:?}", parsed.get_mappings()),
+        format!("{:?}", map.get_mappings())
+    );
+    assert!(parsed.find_closest_mapping(0, 12).is_some());
+}
+
+#[test]
+fn test_from_json_utf8_original_columns() {
+    // The second segment points at byte 7 of `let é = 1;`, which is UTF-16 column 6
+    let input = r#"{
+        "version": 3,
+        "sources": ["a.rs"],
+        "sourcesContent": ["let é = 1;"],
+        "mappings": "AAAA,EAAO"
+    }"#;
+    let options = ParseOptions {
+        utf8_original_columns: true,
+        ..Default::default()
+    };
+    let map = SourceMap::from_json_with_options("/", input, &options).unwrap();
+    let columns: Vec<u32> = map
+        .get_mappings()
+        .iter()
+        .map(|m| m.original.unwrap().original_column)
+        .collect();
+    assert_eq!(columns, vec![0, 6]);
+
+    let map = SourceMap::from_json("/", input).unwrap();
+    assert_eq!(map.get_mappings()[1].original.unwrap().original_column, 7);
+}
+
+#[test]
+fn test_to_json() {
+    let input = r#"{"version":3,"sources":["a.js","b.js"],"sourcesContent":["a",null],"names":["foo"],"mappings":"AAAAA,EACA;ACAA"}"#;
+    let mut map = SourceMap::from_json("/", input).unwrap();
+    assert_eq!(map.to_json(&ToJsonOptions::default()).unwrap(), input);
+
+    let options = ToJsonOptions {
+        include_names: false,
+        ..Default::default()
+    };
+    let output = map.to_json(&options).unwrap();
+    assert_eq!(
+        output,
+        r#"{"version":3,"sources":["a.js","b.js"],"sourcesContent":["a",null],"names":[],"mappings":"AAAA,EACA;ACAA"}"#
+    );
+    let map = SourceMap::from_json("/", &output).unwrap();
+    assert!(map
+        .get_mappings()
+        .iter()
+        .all(|m| m.original.unwrap().name.is_none()));
+}
+
+#[test]
+fn test_write_json() {
+    struct FullDisk;
+    impl io::Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let input = r#"{"version":3,"sources":["a.js","b \"c\".js"],"sourcesContent":["let a = \"\u00e9\";\n",null],"names":["foo"],"mappings":"AAAAA,EACA;;ACAA"}"#;
+    let mut map = SourceMap::from_json("/", input).unwrap();
+    let mut output = Vec::new();
+    map.write_json(&mut output, &ToJsonOptions::default())
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        map.to_json(&ToJsonOptions::default()).unwrap()
+    );
+    assert_eq!(map.get_source_content(0).unwrap(), "let a = \"\u{e9}\";\n");
+
+    let error = map
+        .write_json(FullDisk, &ToJsonOptions::default())
+        .unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::IOError));
+}
+
+#[test]
+fn test_lenient_numbers() {
+    let input = r#"{"version":3,"sections":[
+        {"offset":{"line":0,"column":0},"map":{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}},
+        {"offset":{"line":2.0,"column":"4"},"map":{"version":3,"sources":["b.js"],"names":[],"mappings":"AAAA","ignoreList":["0"]}}
+    ]}"#;
+    let error = SourceMap::from_json("/", input).unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::InvalidJson));
+    assert_eq!(
+        error.reason.as_deref(),
+        Some("sections[1].map.ignoreList[0] is \"0\", expected an integer (see lenient_numbers)")
+    );
+
+    let options = ParseOptions {
+        lenient_numbers: true,
+        ..Default::default()
+    };
+    let mut map = SourceMap::from_json_with_options("/", input, &options).unwrap();
+    let mapping = map.find_closest_mapping(2, 4).unwrap();
+    assert_eq!(mapping.generated_column, 4);
+    assert_eq!(
+        map.get_source(mapping.original.unwrap().source).unwrap(),
+        "b.js"
+    );
+    assert_eq!(map.get_ignore_list(), &[1]);
+
+    assert!(map.parse_diagnostics().is_empty());
+
+    // Fractions are rounded down instead of failing the whole map
+    let fractional = input
+        .replace("\"line\":2.0", "\"line\":2.5")
+        .replace("\"column\":\"4\"", "\"column\":\"4.9\"");
+    let mut map = SourceMap::from_json_with_options("/", &fractional, &options).unwrap();
+    assert_eq!(map.find_closest_mapping(2, 4).unwrap().generated_column, 4);
+    assert_eq!(
+        map.parse_diagnostics(),
+        &[
+            "sections[1].offset.line is 2.5, which is not a whole number, used 2",
+            "sections[1].offset.column is 4.9, which is not a whole number, used 4",
+        ]
+    );
+
+    for (line, reason) in [
+        ("-1", "sections[1].offset.line is -1, which is negative"),
+        (
+            "\"-1.0\"",
+            "sections[1].offset.line is -1, which is negative",
+        ),
+        (
+            "1e10",
+            "sections[1].offset.line is 10000000000, which is too large",
+        ),
+        (
+            "\"two\"",
+            "sections[1].offset.line is \"two\", which is not a number",
+        ),
+    ] {
+        let input = input.replace("\"line\":2.0", &format!("\"line\":{}", line));
+        let error = SourceMap::from_json_with_options("/", &input, &options).unwrap_err();
+        assert_eq!(error.reason.as_deref(), Some(reason));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::mapping::{Mapping, OriginalLocation};
+use crate::sourcemap_error::{ErrorLocation, SourceMapError, SourceMapErrorType};
+use crate::vlq_utils::{apply_relative_vlq, MappingBytes, MappingInput, VlqState};
+use crate::SourceMap;
+
+// Keeps the mappings string and only decodes a generated line once it gets looked up, for
+// dev servers that query a handful of lines of maps with hundreds of thousands. Decoded lines
+// end up in a regular SourceMap, so lookups behave exactly the same.
+pub struct LazySourceMap {
+    map: SourceMap,
+    mappings: Vec<u8>,
+    source_indexes: Vec<u32>,
+    name_indexes: Vec<u32>,
+    // Where every generated line starts in mappings
+    line_starts: Vec<usize>,
+    // Relative state at the start of every line, as far as the mappings have been scanned
+    line_states: Vec<VlqState>,
+    decoded: Vec<bool>,
+}
+
+impl LazySourceMap {
+    pub fn new(
+        project_root: &str,
+        mappings: &[u8],
+        sources: Vec<&str>,
+        sources_content: Vec<&str>,
+        names: Vec<&str>,
+    ) -> Result<Self, SourceMapError> {
+        let mut map = SourceMap::new(project_root);
+        let (source_indexes, name_indexes) =
+            map.add_vlq_sources(&sources, &sources_content, names)?;
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(
+                mappings
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, b)| **b == b';')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        Ok(Self {
+            map,
+            mappings: mappings.to_vec(),
+            source_indexes,
+            name_indexes,
+            decoded: vec![false; line_starts.len()],
+            line_starts,
+            line_states: vec![VlqState::default()],
+        })
+    }
+
+    pub fn find_closest_mapping(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Result<Option<Mapping>, SourceMapError> {
+        self.decode_line(generated_line as usize)?;
+        Ok(self
+            .map
+            .find_closest_mapping(generated_line, generated_column))
+    }
+
+    pub fn get_source(&self, index: u32) -> Result<&str, SourceMapError> {
+        self.map.get_source(index)
+    }
+
+    pub fn get_sources(&self) -> &Vec<String> {
+        self.map.get_sources()
+    }
+
+    pub fn get_source_content(&self, index: u32) -> Result<&str, SourceMapError> {
+        self.map.get_source_content(index)
+    }
+
+    pub fn get_name(&self, index: u32) -> Result<&str, SourceMapError> {
+        self.map.get_name(index)
+    }
+
+    // Generated lines in the mappings, including the ones without mappings
+    pub fn get_line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    pub fn is_line_decoded(&self, generated_line: u32) -> bool {
+        self.decoded
+            .get(generated_line as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // Decodes everything that is left, the result is the same as parsing the map eagerly
+    pub fn into_source_map(mut self) -> Result<SourceMap, SourceMapError> {
+        for line in 0..self.line_starts.len() {
+            self.decode_line(line)?;
+        }
+        if self.line_starts.len() > 1 {
+            self.map.ensure_lines(self.line_starts.len() - 1)?;
+        }
+        Ok(self.map)
+    }
+
+    fn decode_line(&mut self, line: usize) -> Result<(), SourceMapError> {
+        if line >= self.line_starts.len() || self.decoded[line] {
+            return Ok(());
+        }
+
+        // Lines before it only have to be read for the state they end with
+        while self.line_states.len() <= line {
+            let scanned = self.line_states.len() - 1;
+            let mut state = self.line_states[scanned];
+            self.read_line(scanned, &mut state, false)?;
+            self.line_states.push(state);
+        }
+
+        let mut state = self.line_states[line];
+        self.read_line(line, &mut state, true)?;
+        if self.line_states.len() == line + 1 {
+            self.line_states.push(state);
+        }
+        self.decoded[line] = true;
+        Ok(())
+    }
+
+    fn read_line(
+        &mut self,
+        line: usize,
+        state: &mut VlqState,
+        add_mappings: bool,
+    ) -> Result<(), SourceMapError> {
+        let start = self.line_starts[line];
+        let end = match self.line_starts.get(line + 1) {
+            Some(next) => next - 1,
+            None => self.mappings.len(),
+        };
+        let mut input = MappingBytes::new(&self.mappings[start..end]);
+        let mut generated_column = 0;
+        let mut location = ErrorLocation {
+            generated_line: line as u32,
+            ..ErrorLocation::default()
+        };
+        let mut read = || -> Result<(), SourceMapError> {
+            while let Some(byte) = input.peek() {
+                if byte == b',' {
+                    location.segment_index += 1;
+                    input.next();
+                    continue;
+                }
+
+                location.byte_offset = start + input.byte_offset();
+                let mut fields = [0; 5];
+                let count = input.read_segment(&mut fields)?;
+                apply_relative_vlq(&mut generated_column, fields[0])?;
+                let original =
+                    match count {
+                        1 => None,
+                        2 => {
+                            apply_relative_vlq(&mut state.name, fields[1])?;
+                            if !add_mappings {
+                                continue;
+                            }
+                            self.map
+                                .name_without_source(state.name, &self.name_indexes)?
+                        }
+                        _ => {
+                            apply_relative_vlq(&mut state.source, fields[1])?;
+                            apply_relative_vlq(&mut state.original_line, fields[2])?;
+                            apply_relative_vlq(&mut state.original_column, fields[3])?;
+                            if count == 5 {
+                                apply_relative_vlq(&mut state.name, fields[4])?;
+                            }
+                            if !add_mappings {
+                                continue;
+                            }
+                            let source =
+                                *self.source_indexes.get(state.source as usize).ok_or_else(
+                                    || SourceMapError::new(SourceMapErrorType::SourceOutOfRange),
+                                )?;
+                            let name = match count {
+                                5 => Some(*self.name_indexes.get(state.name as usize).ok_or_else(
+                                    || SourceMapError::new(SourceMapErrorType::NameOutOfRange),
+                                )?),
+                                _ => None,
+                            };
+                            Some(OriginalLocation::new(
+                                state.original_line as u32,
+                                state.original_column as u32,
+                                source,
+                                name,
+                            ))
+                        }
+                    };
+                if add_mappings {
+                    self.map
+                        .add_mapping(line as u32, generated_column as u32, original);
+                }
+            }
+            Ok(())
+        };
+        read().map_err(|error| error.with_location(location))
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_lazy_source_map() {
+    use crate::json::ToJsonOptions;
+
+    let mappings = "AAAA,EAAEA;;ACCA,KAAK;AAAAC,EAAE;IAEA";
+    let sources = vec!["a.js", "b.js"];
+    let names = vec!["x", "y"];
+    let mut eager = SourceMap::new("/");
+    eager
+        .add_vlq_map(
+            mappings.as_bytes(),
+            sources.clone(),
+            vec![],
+            names.clone(),
+            0,
+            0,
+        )
+        .unwrap();
+
+    let mut lazy = LazySourceMap::new(
+        "/",
+        mappings.as_bytes(),
+        sources.clone(),
+        vec![],
+        names.clone(),
+    )
+    .unwrap();
+    assert_eq!(lazy.get_line_count(), 5);
+    assert_eq!(lazy.get_source(1).unwrap(), "b.js");
+    for (line, column) in [(3, 3), (0, 1), (4, 10), (2, 6), (1, 0), (7, 0)] {
+        assert_eq!(
+            lazy.find_closest_mapping(line, column)
+                .unwrap()
+                .map(|m| (m.generated_column, m.original)),
+            eager
+                .find_closest_mapping(line, column)
+                .map(|m| (m.generated_column, m.original))
+        );
+    }
+
+    // Only the lines that got looked up are decoded
+    let mut lazy = LazySourceMap::new("/", mappings.as_bytes(), sources, vec![], names).unwrap();
+    lazy.find_closest_mapping(3, 0).unwrap();
+    assert!(lazy.is_line_decoded(3));
+    assert!(!lazy.is_line_decoded(0) && !lazy.is_line_decoded(2));
+    let mut map = lazy.into_source_map().unwrap();
+    assert_eq!(
+        map.to_json(&ToJsonOptions::default()).unwrap(),
+        eager.to_json(&ToJsonOptions::default()).unwrap()
+    );
+
+    let mut lazy = LazySourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":["a.js"],"names":[],"mappings":";AACA"}"#,
+    )
+    .unwrap();
+    let mapping = lazy.find_closest_mapping(1, 0).unwrap().unwrap();
+    assert_eq!(mapping.original.unwrap().original_line, 1);
+
+    // Errors show up once the line gets decoded
+    let mut lazy = LazySourceMap::new("/", b"AAAA;AKAA", vec!["a.js"], vec![], vec![]).unwrap();
+    assert!(lazy.find_closest_mapping(0, 0).unwrap().is_some());
+    let error = lazy.find_closest_mapping(1, 0).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::SourceOutOfRange
+    ));
+    assert_eq!(error.location.unwrap().byte_offset, 5);
+}
@@ -1,59 +1,333 @@
 #![deny(clippy::all)]
 
+mod bindings;
+mod buffer_check;
+#[cfg(feature = "mmap")]
+mod buffer_file;
+mod buffer_legacy;
+mod cancel;
+mod capacity;
+#[cfg(feature = "json")]
+mod closure;
+mod code_frame;
+mod codec;
+mod column_limit;
+mod compose;
+#[cfg(feature = "compressed_sources_content")]
+mod compressed_content;
+mod concat;
+mod data_url;
+mod debug_id;
+mod declaration;
+#[cfg(feature = "diagnostics")]
+mod diagnostic;
+mod encoding;
+mod features;
+mod flatten;
+mod histogram;
+mod ignore_list;
+mod integrity;
+mod iterate;
+#[cfg(feature = "json")]
+mod json;
+mod lazy;
+mod line_mappings;
+mod lookup;
+mod lookup_index;
+#[cfg(feature = "lsp-types")]
+mod lsp;
 pub mod mapping;
-pub mod mapping_line;
+mod mapping_line;
+mod mapping_lines;
+mod memory_budget;
+mod merge;
+mod names;
+mod observer;
+#[cfg(feature = "json")]
+mod options;
+mod overlay;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod pipeline;
+pub mod prelude;
+mod profiler;
+mod progress;
+mod provenance;
+mod registry;
+mod reverse_index;
+#[cfg(feature = "serde")]
+mod serde_support;
+#[cfg(feature = "simd")]
+mod simd;
+mod size;
+mod source_mapping_url;
+mod source_root;
 pub mod sourcemap_error;
+mod split;
+mod stack_trace;
+#[cfg(feature = "test_utils")]
+pub mod test_utils;
+mod tsconfig;
+mod url;
 pub mod utils;
+mod view;
+mod vlq_cache;
 mod vlq_utils;
 
-use crate::utils::make_relative_path;
-pub use mapping::{Mapping, OriginalLocation};
-use mapping_line::MappingLine;
-pub use sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::encoding::Utf8ColumnConverter;
+use crate::source_root::join_source_root;
+use crate::url::resolve_url;
+use crate::utils::{eq_ignore_case, make_relative_path_with_case};
+#[cfg(feature = "mmap")]
+pub use buffer_file::BufferFile;
+use buffer_legacy::PREVIOUS_BUFFER_FORMAT_VERSION;
+pub use cancel::CancellationToken;
+pub use capacity::CapacityHints;
+#[cfg(feature = "json")]
+pub use closure::ClosureMapExtras;
+#[cfg(feature = "diagnostics")]
+pub use code_frame::DEFAULT_CONTEXT_LINES;
+pub use codec::{decode_mappings, encode_mappings};
+pub use column_limit::DEFAULT_MAX_COLUMN;
+pub use compose::ExtendsOptions;
+#[cfg(feature = "compressed_sources_content")]
+pub use compressed_content::CompressedSourcesContent;
+pub use concat::Concatenator;
+pub use data_url::decode_data_url;
+pub use declaration::is_declaration_file;
+#[cfg(feature = "diagnostics")]
+pub use diagnostic::code_frame;
+pub use encoding::{detect_encoding, InputEncoding};
+pub use features::Features;
+pub use flatten::FlattenOptions;
+pub use histogram::{DensityHistogram, HistogramBucket};
+pub use integrity::{hash_source_content, SourceMismatch, SourceMismatchKind, SourceProvider};
+pub use iterate::{MappingBatches, MappingCursor};
+#[cfg(feature = "json")]
+pub use json::{ParseOptions, ToJsonOptions};
+pub use lazy::LazySourceMap;
+pub use lookup::{LookupOptions, RawMapping, ResolvedLocation, ResolvedLocationRef};
+pub use lookup_index::LookupIndex;
+#[cfg(feature = "lsp-types")]
+pub use lsp::{LspPosition, LspRange};
+pub use mapping::{
+    AddSourceMode, DuplicateMappingPolicy, FlatMappings, LookupBias, LookupPosition, Mapping,
+    NameWithoutSourcePolicy, OneBasedPosition, OriginalLocation, Position, SourceRootHandling,
+    SourceRootOutput,
+};
+use mapping_line::LineMapping;
+use mapping_lines::{too_many_lines, MappingLines};
+pub use memory_budget::MemoryBudget;
+use memory_budget::{mappings_size, strings_size, BudgetCharge};
+pub use names::NameNormalizer;
+pub use observer::{MutationEvent, MutationObserver};
+#[cfg(feature = "json")]
+pub use options::{
+    FlatArraysOptions, FromOptions, IterateOptions, LineOffsetOptions, OffsetOptions,
+    OptionsReader, ProgressOptions,
+};
+pub use overlay::OverlayFrame;
+pub use pipeline::{MapPipeline, PipelineDescription, PipelineStep, SourceFilter};
+pub use profiler::{PipelineProfiler, StageProfile, PROFILE_ENV_VAR};
+pub use progress::{Progress, ProgressReporter, ProgressStage};
+pub use provenance::SourceProvenance;
+pub use registry::{FileNormalizer, LoadingMapRegistry, MapLoader, MapRegistry};
+pub use reverse_index::{ReverseIndex, ReverseMapping};
+pub use source_mapping_url::{
+    append_source_map_comment, extract_source_map_url, strip_source_map_comment, SourceMapRef,
+    Syntax,
+};
+pub use sourcemap_error::{
+    catch_panic, set_error_format, ErrorFormat, ErrorLocation, LookupError, MergeError, ParseError,
+    RuntimeError, SerializeError, SourceMapError, SourceMapErrorKind, SourceMapErrorType, VlqError,
+};
+pub use stack_trace::{
+    parse_stack_trace, remap_frames, render_stack_trace, StackFormat, StackFrame, StackLine,
+};
 use std::io;
+use std::path::PathBuf;
+pub use tsconfig::TsConfigLayout;
+pub use view::SourceMapView;
 
 use rkyv::{
     archived_root,
-    de::deserializers::AllocDeserializer,
     ser::{serializers::AlignedSerializer, Serializer},
     AlignedVec, Archive, Deserialize, Serialize,
 };
 
-use vlq_utils::{is_mapping_separator, read_relative_vlq};
+use vlq_cache::VlqLineCache;
+use vlq_utils::{
+    apply_relative_vlq, write_line_separators, write_vlq_line, MappingBytes, MappingInput,
+    ReaderBytes, VlqState,
+};
+
+// Version of the to_buffer layout, gets stored as a little-endian u32 in the header.
+// Bump this whenever anything that is part of SourceMapInner changes, see README.md for
+// the compatibility table.
+pub const BUFFER_FORMAT_VERSION: u32 = 10;
+const BUFFER_MAGIC: [u8; 8] = *b"PSMAPBUF";
+// Magic, format version and flags, 16 bytes so the archive after it stays aligned
+pub(crate) const BUFFER_HEADER_SIZE: usize = 16;
+// Every line in the archive is sorted, with duplicates resolved by its duplicate mapping
+// policy. Buffers written before the flags were introduced have them all unset.
+pub(crate) const BUFFER_FLAG_SORTED: u32 = 1;
+// Buffers without a header had their version after the archive, these are still recognized
+// so they get rejected with a version mismatch
+const LEGACY_BUFFER_FORMAT_VERSION: u32 = 4;
+const BUFFER_VERSION_SIZE: usize = std::mem::size_of::<u32>();
 
 #[derive(Archive, Serialize, Deserialize, Debug, Default, Clone)]
-pub struct SourceMapInner {
+pub(crate) struct SourceMapInner {
     pub sources: Vec<String>,
     pub sources_content: Vec<String>,
     pub names: Vec<String>,
-    pub mapping_lines: Vec<MappingLine>,
+    pub mapping_lines: MappingLines,
+    pub duplicate_mapping_policy: DuplicateMappingPolicy,
+    // See SourceMap::get_source_provenance
+    pub source_provenance: Vec<SourceProvenance>,
+    // See SourceMap::compute_source_hashes
+    pub source_hashes: Vec<Option<u64>>,
+    // Sorted indexes of the sources in ignoreList, see SourceMap::add_ignored_source
+    pub ignore_list: Vec<u32>,
+    // See SourceMap::get_debug_id
+    pub debug_id: Option<String>,
+    // See SourceMap::get_source_root
+    pub source_root: Option<String>,
+    // Only set in the copy that gets archived when ToBufferOptions::include_reverse_index is
+    // on, the map itself keeps its index in SourceMap::reverse_index
+    pub reverse_index: Option<ReverseIndex>,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ToBufferOptions {
+    // Leaving out names makes the buffer smaller, but they are gone once it gets read back
+    pub include_names: bool,
+    // Archive the reverse index as well, so maps read back from the buffer don't have to
+    // build it again for their first original position lookup
+    pub include_reverse_index: bool,
+}
+
+impl Default for ToBufferOptions {
+    fn default() -> Self {
+        Self {
+            include_names: true,
+            include_reverse_index: false,
+        }
+    }
+}
+
+fn generated_offset_error() -> SourceMapError {
+    SourceMapError::new_with_reason(
+        SourceMapErrorType::UnexpectedlyBigNumber,
+        "mapping + generated offset does not fit in a u32",
+    )
 }
 
 #[derive(Debug, Clone)]
 pub struct SourceMap {
     pub project_root: String,
     inner: SourceMapInner,
+    // Only present after build_reverse_index or when read from a buffer that has one
+    reverse_index: Option<ReverseIndex>,
+    // Only present after build_lookup_index
+    lookup_index: Option<LookupIndex>,
+    vlq_cache: Option<VlqLineCache>,
+    raw_input: Option<String>,
+    // What lenient_numbers had to coerce while parsing, see parse_diagnostics
+    #[cfg(feature = "json")]
+    parse_diagnostics: Vec<String>,
+    name_normalizer: Option<NameNormalizer>,
+    case_insensitive_sources: bool,
+    add_source_mode: AddSourceMode,
+    repair_missing_sources: bool,
+    merge_equivalent_sources: bool,
+    utf8_original_columns: bool,
+    generated_offset: Position,
+    name_without_source_policy: NameWithoutSourcePolicy,
+    cancellation_token: Option<CancellationToken>,
+    progress_reporter: Option<ProgressReporter>,
+    mutation_observer: Option<MutationObserver>,
+    // What is left of reserve_lines and reserve_mappings
+    pending_capacity: CapacityHints,
+    memory_budget: Option<BudgetCharge>,
+    // sourcesContent while it is kept compressed, inner.sources_content is empty meanwhile
+    #[cfg(feature = "compressed_sources_content")]
+    compressed_sources_content: Option<CompressedSourcesContent>,
 }
 
+// Maps get processed on thread pools (rayon, tokio) by Rust consumers, so everything that
+// holds one has to stay Send + Sync. Callbacks are Arc<dyn Fn + Send + Sync> for this reason,
+// this stops compiling as soon as a field that isn't (Rc, RefCell, ...) gets added.
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[allow(dead_code)]
+    fn assert_thread_safe<L: MapLoader + Send + Sync>() {
+        assert_send_sync::<SourceMap>();
+        assert_send_sync::<SourceMapView<'static>>();
+        assert_send_sync::<ArchivedSourceMapInner>();
+        assert_send_sync::<LazySourceMap>();
+        assert_send_sync::<MemoryBudget>();
+        #[cfg(feature = "mmap")]
+        assert_send_sync::<BufferFile>();
+        assert_send_sync::<MapRegistry>();
+        assert_send_sync::<LoadingMapRegistry<L>>();
+        assert_send_sync::<MapPipeline>();
+        assert_send_sync::<Concatenator>();
+        assert_send_sync::<SourceMapError>();
+    }
+};
+
 impl SourceMap {
     pub fn new(project_root: &str) -> Self {
         Self {
             project_root: String::from(project_root),
             inner: SourceMapInner::default(),
+            reverse_index: None,
+            lookup_index: None,
+            vlq_cache: None,
+            raw_input: None,
+            #[cfg(feature = "json")]
+            parse_diagnostics: Vec::new(),
+            name_normalizer: None,
+            case_insensitive_sources: false,
+            add_source_mode: AddSourceMode::default(),
+            repair_missing_sources: false,
+            merge_equivalent_sources: true,
+            utf8_original_columns: false,
+            generated_offset: Position::default(),
+            name_without_source_policy: NameWithoutSourcePolicy::default(),
+            cancellation_token: None,
+            progress_reporter: None,
+            mutation_observer: None,
+            pending_capacity: CapacityHints::default(),
+            memory_budget: MemoryBudget::global().map(BudgetCharge::new),
+            #[cfg(feature = "compressed_sources_content")]
+            compressed_sources_content: None,
         }
     }
 
-    fn ensure_lines(&mut self, generated_line: usize) {
-        let mut line = self.inner.mapping_lines.len();
-        if line <= generated_line {
-            self.inner
-                .mapping_lines
-                .reserve(generated_line - self.inner.mapping_lines.len() + 1);
-            while line <= generated_line {
-                self.inner.mapping_lines.push(MappingLine::new());
-                line += 1;
-            }
+    fn ensure_lines(&mut self, generated_line: usize) -> Result<(), SourceMapError> {
+        let line_count = generated_line.checked_add(1).ok_or_else(too_many_lines)?;
+        self.inner.mapping_lines.ensure_len(line_count)
+    }
+
+    pub fn get_duplicate_mapping_policy(&self) -> DuplicateMappingPolicy {
+        self.inner.duplicate_mapping_policy
+    }
+
+    // Duplicates get resolved lazily, the next time a line gets sorted for lookups or emitting
+    pub fn set_duplicate_mapping_policy(&mut self, policy: DuplicateMappingPolicy) {
+        if self.inner.duplicate_mapping_policy == policy {
+            return;
         }
+
+        self.inner.duplicate_mapping_policy = policy;
+        self.reverse_index = None;
+        self.lookup_index = None;
+        self.invalidate_vlq_cache();
+        self.notify(MutationEvent::Reset);
     }
 
     pub fn add_mapping(
@@ -63,8 +337,40 @@ impl SourceMap {
         original: Option<OriginalLocation>,
     ) {
         // TODO: Create new public function that validates if source and name exist?
-        self.ensure_lines(generated_line as usize);
-        self.inner.mapping_lines[generated_line as usize].add_mapping(generated_column, original);
+        self.reverse_index = None;
+        self.lookup_index = None;
+        self.invalidate_vlq_line(generated_line as usize);
+        // Only line u32::MAX fails, try_add_mapping returns that as an error
+        let line = match self
+            .inner
+            .mapping_lines
+            .get_or_insert(generated_line as usize)
+        {
+            Ok(line) => line,
+            Err(err) => panic!("{}", err),
+        };
+        self.pending_capacity.reserve_line(&mut line.mappings);
+        line.add_mapping(generated_column, original);
+        self.notify(MutationEvent::MappingAdded {
+            generated_line,
+            generated_column,
+            original,
+        });
+    }
+
+    // add_mapping that counts the mapping against the memory budget first
+    pub fn try_add_mapping(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        original: Option<OriginalLocation>,
+    ) -> Result<(), SourceMapError> {
+        if generated_line == u32::MAX {
+            return Err(too_many_lines());
+        }
+        self.reserve_mapping_memory(1)?;
+        self.add_mapping(generated_line, generated_column, original);
+        Ok(())
     }
 
     pub fn add_mapping_with_offset(
@@ -113,16 +419,73 @@ impl SourceMap {
         Ok(())
     }
 
+    // Registers content (e.g. a banner) that gets prepended to the generated file when
+    // it is written. Lookups take generated positions in the final file and write_vlq
+    // emits the shifted mappings, the stored mappings are left as is.
+    // Buffers store the mappings shifted by the offset, without the offset itself.
+    pub fn set_generated_offset(&mut self, lines: u32, columns: u32) {
+        self.generated_offset = Position::new(lines, columns);
+    }
+
+    pub fn get_generated_offset(&self) -> Position {
+        self.generated_offset
+    }
+
     pub fn find_closest_mapping(
         &mut self,
         generated_line: u32,
         generated_column: u32,
     ) -> Option<Mapping> {
-        if let Some(line) = self.inner.mapping_lines.get_mut(generated_line as usize) {
-            if let Some(line_mapping) = line.find_closest_mapping(generated_column) {
+        self.find_closest_mapping_with_bias(
+            generated_line,
+            generated_column,
+            LookupBias::GreatestLowerBound,
+        )
+    }
+
+    pub fn find_closest_mapping_with_bias(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: LookupBias,
+    ) -> Option<Mapping> {
+        let offset = self.generated_offset;
+        if generated_line < offset.line {
+            return None;
+        }
+
+        // Only the first line of the map shares its line with the end of the prepended content
+        let line_index = generated_line - offset.line;
+        let column_offset = if line_index == 0 { offset.column } else { 0 };
+        if generated_column < column_offset {
+            return None;
+        }
+
+        if let (Some(lookup_index), LookupBias::GreatestLowerBound) = (&self.lookup_index, bias) {
+            let line_mapping =
+                lookup_index.find_closest_mapping(line_index, generated_column - column_offset)?;
+            return Some(Mapping {
+                generated_line,
+                generated_column: line_mapping.generated_column + column_offset,
+                original: line_mapping.original,
+            });
+        }
+
+        let policy = self.inner.duplicate_mapping_policy;
+        if let Some(line) = self.inner.mapping_lines.get_mut(line_index as usize) {
+            line.ensure_sorted_with(policy);
+            let line_mapping = match bias {
+                LookupBias::GreatestLowerBound => {
+                    line.find_closest_mapping(generated_column - column_offset)
+                }
+                LookupBias::LeastUpperBound => {
+                    line.find_least_upper_bound(generated_column - column_offset)
+                }
+            };
+            if let Some(line_mapping) = line_mapping {
                 return Some(Mapping {
                     generated_line,
-                    generated_column: line_mapping.generated_column,
+                    generated_column: line_mapping.generated_column + column_offset,
                     original: line_mapping.original,
                 });
             }
@@ -131,10 +494,48 @@ impl SourceMap {
         None
     }
 
+    // Where a stored mapping ends up in the generated file (see set_generated_offset), None if
+    // the offset pushes it past what a u32 can hold
+    pub(crate) fn generated_position(&self, line: usize, column: u32) -> Option<(u32, u32)> {
+        let offset = self.generated_offset;
+        let generated_line = u32::try_from(line).ok()?.checked_add(offset.line)?;
+        let generated_column = if line == 0 {
+            column.checked_add(offset.column)?
+        } else {
+            column
+        };
+        Some((generated_line, generated_column))
+    }
+
+    // Mappings at their position in the generated file, including the generated offset,
+    // sorted and with duplicates resolved by the duplicate mapping policy.
+    // Mappings the offset pushes out of the u32 range are left out, to_flat_arrays,
+    // to_buffer and write_vlq fail on them instead.
     pub fn get_mappings(&self) -> Vec<Mapping> {
         let mut mappings = Vec::new();
-        for (generated_line, mapping_line) in self.inner.mapping_lines.iter().enumerate() {
-            for mapping in mapping_line.mappings.iter() {
+        let policy = self.inner.duplicate_mapping_policy;
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            for mapping in mapping_line.resolved_mappings(policy).iter() {
+                if let Some((generated_line, generated_column)) =
+                    self.generated_position(generated_line, mapping.generated_column)
+                {
+                    mappings.push(Mapping {
+                        generated_line,
+                        generated_column,
+                        original: mapping.original,
+                    });
+                }
+            }
+        }
+        mappings
+    }
+
+    // The mappings as they are stored, without the generated offset
+    pub(crate) fn stored_mappings(&self) -> Vec<Mapping> {
+        let mut mappings = Vec::new();
+        let policy = self.inner.duplicate_mapping_policy;
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            for mapping in mapping_line.resolved_mappings(policy).iter() {
                 mappings.push(Mapping {
                     generated_line: generated_line as u32,
                     generated_column: mapping.generated_column,
@@ -145,86 +546,369 @@ impl SourceMap {
         mappings
     }
 
+    // Includes the generated offset, like get_mappings. Fails with UnexpectedlyBigNumber when
+    // a position doesn't fit in the arrays.
+    pub fn to_flat_arrays(&self) -> Result<FlatMappings, SourceMapError> {
+        let mappings_count = self.inner.mapping_lines.mappings_len();
+        let mut flat_mappings = FlatMappings::with_capacity(mappings_count);
+        let policy = self.inner.duplicate_mapping_policy;
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            for mapping in mapping_line.resolved_mappings(policy).iter() {
+                let (generated_line, generated_column) = self
+                    .generated_position(generated_line, mapping.generated_column)
+                    .ok_or_else(generated_offset_error)?;
+                flat_mappings.push(generated_line, generated_column, mapping.original)?;
+            }
+        }
+        Ok(flat_mappings)
+    }
+
+    // Appends mappings from flat arrays (see to_flat_arrays), source and name indexes get
+    // shifted by the given offsets so batches from other maps can be merged in
+    pub fn add_flat_arrays(
+        &mut self,
+        flat_mappings: &FlatMappings,
+        line_offset: i64,
+        column_offset: i64,
+        source_index_offset: u32,
+        name_index_offset: u32,
+    ) -> Result<(), SourceMapError> {
+        let mappings_count = flat_mappings.len();
+        if flat_mappings.generated_columns.len() != mappings_count
+            || flat_mappings.sources.len() != mappings_count
+            || flat_mappings.original_lines.len() != mappings_count
+            || flat_mappings.original_columns.len() != mappings_count
+            || flat_mappings.names.len() != mappings_count
+        {
+            return Err(SourceMapError::new(
+                SourceMapErrorType::FlatArraysLengthMismatch,
+            ));
+        }
+        self.reserve_mapping_memory(mappings_count)?;
+
+        for i in 0..mappings_count {
+            let original = match flat_mappings.original(i)? {
+                Some(original) => Some(OriginalLocation::new(
+                    original.original_line,
+                    original.original_column,
+                    original
+                        .source
+                        .checked_add(source_index_offset)
+                        .ok_or_else(|| {
+                            SourceMapError::new_with_reason(
+                                SourceMapErrorType::UnexpectedlyBigNumber,
+                                "source + source_index_offset",
+                            )
+                        })?,
+                    match original.name {
+                        Some(name) => {
+                            Some(name.checked_add(name_index_offset).ok_or_else(|| {
+                                SourceMapError::new_with_reason(
+                                    SourceMapErrorType::UnexpectedlyBigNumber,
+                                    "name + name_index_offset",
+                                )
+                            })?)
+                        }
+                        None => None,
+                    },
+                )),
+                None => None,
+            };
+
+            self.add_mapping_with_offset(
+                Mapping {
+                    generated_line: flat_mappings.generated_lines[i],
+                    generated_column: flat_mappings.generated_columns[i],
+                    original,
+                },
+                line_offset,
+                column_offset,
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub fn write_vlq<W>(&mut self, output: &mut W) -> Result<(), SourceMapError>
     where
         W: io::Write,
     {
-        let mut last_generated_line: u32 = 0;
-        let mut previous_source: i64 = 0;
-        let mut previous_original_line: i64 = 0;
-        let mut previous_original_column: i64 = 0;
-        let mut previous_name: i64 = 0;
-
-        for (generated_line, line_content) in self.inner.mapping_lines.iter_mut().enumerate() {
-            let mut previous_generated_column: u32 = 0;
-            let cloned_generated_line = generated_line as u32;
-            if cloned_generated_line > 0 {
-                // Write a ';' for each line between this and last line, way more efficient than storing empty lines or looping...
-                output.write_all(
-                    &b";".repeat((cloned_generated_line - last_generated_line) as usize),
-                )?;
-            }
+        self.write_vlq_with_names(output, true)
+    }
 
-            line_content.ensure_sorted();
+    // Without names every segment gets written with 4 fields, reading it back won't
+    // restore the names
+    pub(crate) fn write_vlq_with_names<W>(
+        &mut self,
+        output: &mut W,
+        include_names: bool,
+    ) -> Result<(), SourceMapError>
+    where
+        W: io::Write,
+    {
+        let policy = self.inner.duplicate_mapping_policy;
+        for line_content in self.inner.mapping_lines.lines_mut() {
+            line_content.ensure_sorted_with(policy);
+        }
 
-            let mut is_first_mapping: bool = true;
-            for mapping in &line_content.mappings {
-                let generated_column = mapping.generated_column;
-                let original_location_option = &mapping.original;
-                if !is_first_mapping {
-                    output.write_all(b",")?;
-                }
+        let mut vlq_cache = self.vlq_cache.take();
+        let result = self.write_sorted_vlq(
+            &self.inner.mapping_lines,
+            output,
+            include_names,
+            vlq_cache.as_mut(),
+        );
+        self.vlq_cache = vlq_cache;
+        result
+    }
 
-                vlq::encode(
-                    (generated_column - previous_generated_column) as i64,
-                    output,
-                )?;
-                previous_generated_column = generated_column;
-
-                // Source should only be written if there is any
-                if let Some(original) = &original_location_option {
-                    let original_source = original.source as i64;
-                    vlq::encode(original_source - previous_source, output)?;
-                    previous_source = original_source;
-
-                    let original_line = original.original_line as i64;
-                    vlq::encode((original_line - previous_original_line) as i64, output)?;
-                    previous_original_line = original_line;
-
-                    let original_column = original.original_column as i64;
-                    vlq::encode(original_column - previous_original_column, output)?;
-                    previous_original_column = original_column;
-
-                    if let Some(name) = original.name {
-                        let original_name = name as i64;
-                        vlq::encode(original_name - previous_name, output)?;
-                        previous_name = original_name;
-                    }
-                }
+    // Every line has to be sorted with the duplicate mapping policy of the map already
+    pub(crate) fn write_sorted_vlq<W>(
+        &self,
+        mapping_lines: &MappingLines,
+        output: &mut W,
+        include_names: bool,
+        mut vlq_cache: Option<&mut VlqLineCache>,
+    ) -> Result<(), SourceMapError>
+    where
+        W: io::Write,
+    {
+        let mut state = VlqState::default();
+        let offset = self.generated_offset;
+        // Only count the mappings upfront when someone is listening
+        let total = self
+            .progress_reporter
+            .as_ref()
+            .map(|_| mapping_lines.mappings_len());
+        let mut progress = self.progress_tracker(ProgressStage::Serialize, total);
+        for _ in 0..offset.line {
+            output.write_all(b";")?;
+        }
 
-                is_first_mapping = false;
+        let mut rewritten: Vec<LineMapping> = Vec::new();
+        // Line the output is at, empty lines that aren't stored only get their separator
+        let mut current_line = 0;
+        for (generated_line, line_content) in mapping_lines.iter() {
+            write_line_separators(output, generated_line - current_line)?;
+            current_line = generated_line;
+
+            progress.advance(line_content.mappings.len());
+            let column_offset = if generated_line == 0 {
+                offset.column
+            } else {
+                0
+            };
+            if column_offset > 0 || !include_names {
+                rewritten.clear();
+                for m in line_content.mappings.iter() {
+                    rewritten.push(LineMapping {
+                        generated_column: m
+                            .generated_column
+                            .checked_add(column_offset)
+                            .ok_or_else(generated_offset_error)?,
+                        original: m.original.map(|mut original| {
+                            if !include_names {
+                                original.name = None;
+                            }
+                            original
+                        }),
+                    });
+                }
+                write_vlq_line(&rewritten, &mut state, output)?;
+                continue;
             }
 
-            last_generated_line = cloned_generated_line;
+            match &mut vlq_cache {
+                Some(vlq_cache) => vlq_cache.write_line(
+                    generated_line,
+                    &line_content.mappings,
+                    &mut state,
+                    output,
+                )?,
+                None => write_vlq_line(&line_content.mappings, &mut state, output)?,
+            }
         }
+        write_line_separators(output, mapping_lines.len().saturating_sub(current_line + 1))?;
 
+        progress.finish();
         Ok(())
     }
 
+    // Keeps the encoded mappings of each line around between write_vlq calls, so only
+    // lines that changed get encoded again. Useful for watch mode rebuilds.
+    pub fn enable_vlq_cache(&mut self) {
+        if self.vlq_cache.is_none() {
+            self.vlq_cache = Some(VlqLineCache::default());
+        }
+    }
+
+    pub fn disable_vlq_cache(&mut self) {
+        self.vlq_cache = None;
+    }
+
+    fn invalidate_vlq_line(&mut self, generated_line: usize) {
+        if let Some(vlq_cache) = &mut self.vlq_cache {
+            vlq_cache.invalidate_line(generated_line);
+        }
+    }
+
+    fn invalidate_vlq_cache(&mut self) {
+        if let Some(vlq_cache) = &mut self.vlq_cache {
+            vlq_cache.invalidate_all();
+        }
+    }
+
+    fn normalize_source(&self, source: &str) -> String {
+        make_relative_path_with_case(
+            self.project_root.as_str(),
+            source,
+            self.case_insensitive_sources,
+        )
+    }
+
+    fn find_source(&self, normalized_source: &str) -> Option<usize> {
+        if self.case_insensitive_sources {
+            self.inner
+                .sources
+                .iter()
+                .position(|s| eq_ignore_case(normalized_source, s))
+        } else {
+            self.inner
+                .sources
+                .iter()
+                .position(|s| normalized_source.eq(s))
+        }
+    }
+
+    // Treat sources that only differ in casing as the same file (e.g. on Windows),
+    // the casing of the first occurrence is kept
+    pub fn set_case_insensitive_sources(&mut self, case_insensitive: bool) {
+        self.case_insensitive_sources = case_insensitive;
+    }
+
+    pub fn set_add_source_mode(&mut self, mode: AddSourceMode) {
+        self.add_source_mode = mode;
+    }
+
+    // Mappings that reference a source index past the end of the sources array get
+    // a placeholder source (`<unknown:N>`) instead of failing with SourceOutOfRange
+    pub fn set_repair_missing_sources(&mut self, repair: bool) {
+        self.repair_missing_sources = repair;
+    }
+
+    // add_sourcemap names the sources of the added map the way this map would (see
+    // merged_source_name), so the same file written as src/a.js, ./src/a.js or with another
+    // sourceRoot ends up as one source. Turning this off adds them as they are.
+    pub fn set_merge_equivalent_sources(&mut self, merge: bool) {
+        self.merge_equivalent_sources = merge;
+    }
+
+    // The original columns of VLQ mappings that get added are UTF-8 byte offsets, they are
+    // converted to UTF-16 code units using the sourcesContent passed along with them
+    pub fn set_utf8_original_columns(&mut self, utf8_original_columns: bool) {
+        self.utf8_original_columns = utf8_original_columns;
+    }
+
+    fn add_placeholder_source(&mut self, index: i64) -> u32 {
+        let placeholder = format!("<unknown:{}>", index);
+        log::warn!(
+            "Mapping references missing source {}, using {} instead",
+            index,
+            placeholder
+        );
+        self.add_source_unnormalized(placeholder)
+    }
+
+    // Placeholders are not paths, so they should not be made relative to the project root
+    pub(crate) fn add_source_unnormalized(&mut self, source: String) -> u32 {
+        let index = match self.find_source(&source) {
+            Some(i) => i as u32,
+            None => {
+                self.inner.sources.push(source);
+                let index = (self.inner.sources.len() - 1) as u32;
+                self.notify(MutationEvent::SourceAdded(index));
+                index
+            }
+        };
+        self.merge_source_provenance(index, SourceProvenance::Synthesized);
+        index
+    }
+
+    pub fn set_name_without_source_policy(&mut self, policy: NameWithoutSourcePolicy) {
+        self.name_without_source_policy = policy;
+    }
+
+    fn name_without_source(
+        &mut self,
+        name: i64,
+        name_indexes: &[u32],
+    ) -> Result<Option<OriginalLocation>, SourceMapError> {
+        let name = match name_indexes.get(name as usize) {
+            Some(name) => *name,
+            None => return Err(SourceMapError::new(SourceMapErrorType::NameOutOfRange)),
+        };
+
+        match self.name_without_source_policy {
+            NameWithoutSourcePolicy::Error => {
+                Err(SourceMapError::new(SourceMapErrorType::NameWithoutSource))
+            }
+            NameWithoutSourcePolicy::DropName => Ok(None),
+            NameWithoutSourcePolicy::SyntheticSource => {
+                let source = self.add_source_unnormalized(String::from("<unknown>"));
+                Ok(Some(OriginalLocation::new(0, 0, source, Some(name))))
+            }
+        }
+    }
+
     pub fn add_source(&mut self, source: &str) -> u32 {
-        let relative_source = make_relative_path(self.project_root.as_str(), source);
-        match self
-            .inner
-            .sources
-            .iter()
-            .position(|s| relative_source.eq(s))
-        {
+        let relative_source = self.normalize_source(source);
+        let existing = match self.add_source_mode {
+            AddSourceMode::AlwaysAppend => None,
+            _ => self.find_source(&relative_source),
+        };
+        match existing {
             Some(i) => i as u32,
             None => {
                 self.inner.sources.push(relative_source);
-                (self.inner.sources.len() - 1) as u32
+                let index = (self.inner.sources.len() - 1) as u32;
+                self.notify(MutationEvent::SourceAdded(index));
+                index
+            }
+        }
+    }
+
+    // Adds a source and sets its content, see set_add_source_mode for when an existing
+    // source gets reused. Like add_source this scans the sources, with
+    // DedupeByPathAndContent the content is only read for sources with the same path.
+    pub fn add_source_with_content(
+        &mut self,
+        source: &str,
+        content: &str,
+    ) -> Result<u32, SourceMapError> {
+        if self.add_source_mode == AddSourceMode::DedupeByPathAndContent {
+            let relative_source = self.normalize_source(source);
+            let case_insensitive = self.case_insensitive_sources;
+            let existing = self.inner.sources.iter().enumerate().position(|(i, s)| {
+                let same_path = if case_insensitive {
+                    eq_ignore_case(&relative_source, s)
+                } else {
+                    relative_source.eq(s)
+                };
+                same_path && self.get_source_content(i as u32).unwrap_or("") == content
+            });
+            if let Some(i) = existing {
+                return Ok(i as u32);
             }
+            self.inner.sources.push(relative_source);
+            let index = self.inner.sources.len() - 1;
+            self.notify(MutationEvent::SourceAdded(index as u32));
+            self.set_source_content(index, content)?;
+            return Ok(index as u32);
         }
+
+        let index = self.add_source(source);
+        self.set_source_content(index as usize, content)?;
+        Ok(index)
     }
 
     pub fn add_sources(&mut self, sources: Vec<&str>) -> Vec<u32> {
@@ -237,13 +921,13 @@ impl SourceMap {
     }
 
     pub fn get_source_index(&self, source: &str) -> Result<Option<u32>, SourceMapError> {
-        let normalized_source = make_relative_path(self.project_root.as_str(), source);
-        Ok(self
-            .inner
-            .sources
-            .iter()
-            .position(|s| normalized_source.eq(s))
-            .map(|v| v as u32))
+        // Sources are usually looked up using a string that came out of the map, those are
+        // already normalized so this skips allocating the normalized path
+        if let Some(i) = self.find_source(source) {
+            return Ok(Some(i as u32));
+        }
+        let normalized_source = self.normalize_source(source);
+        Ok(self.find_source(&normalized_source).map(|v| v as u32))
     }
 
     pub fn get_source(&self, index: u32) -> Result<&str, SourceMapError> {
@@ -258,19 +942,51 @@ impl SourceMap {
         &self.inner.sources
     }
 
+    // Sources are stored relative to the project root, this prepends sourceRoot and resolves
+    // them against the project root the same way a consumer resolves them against the url
+    // of the map (see url.rs), so sources that are paths can be opened directly
+    pub fn get_resolved_sources(&self) -> Vec<PathBuf> {
+        let mut project_root = self.project_root.clone();
+        if !project_root.ends_with(['/', '\\']) {
+            project_root.push('/');
+        }
+        self.inner
+            .sources
+            .iter()
+            .map(|source| {
+                let source = join_source_root(self.get_source_root(), source);
+                if source.is_empty() {
+                    return PathBuf::new();
+                }
+                PathBuf::from(resolve_url(&project_root, &source))
+            })
+            .collect()
+    }
+
     pub fn add_name(&mut self, name: &str) -> u32 {
-        return match self.inner.names.iter().position(|s| name.eq(s)) {
+        let normalized_name;
+        let name = match &self.name_normalizer {
+            Some(name_normalizer) => {
+                normalized_name = name_normalizer.normalize(name);
+                normalized_name.as_str()
+            }
+            None => name,
+        };
+
+        match self.inner.names.iter().position(|s| name.eq(s)) {
             Some(i) => i as u32,
             None => {
                 self.inner.names.push(String::from(name));
-                (self.inner.names.len() - 1) as u32
+                let index = (self.inner.names.len() - 1) as u32;
+                self.notify(MutationEvent::NameAdded(index));
+                index
             }
-        };
+        }
     }
 
     pub fn add_names(&mut self, names: Vec<&str>) -> Vec<u32> {
         self.inner.names.reserve(names.len());
-        return names.iter().map(|n| self.add_name(n)).collect();
+        names.iter().map(|n| self.add_name(n)).collect()
     }
 
     pub fn get_name_index(&self, name: &str) -> Option<u32> {
@@ -302,9 +1018,15 @@ impl SourceMap {
             return Err(SourceMapError::new(SourceMapErrorType::SourceOutOfRange));
         }
 
-        let sources_content_len = self.inner.sources_content.len();
+        self.reserve_memory(source_content.len())?;
+        let sources_content_len = self.sources_content_mut()?.len();
+        let mut removed = 0;
         if sources_content_len > source_index {
-            self.inner.sources_content[source_index] = String::from(source_content);
+            let previous = std::mem::replace(
+                &mut self.inner.sources_content[source_index],
+                String::from(source_content),
+            );
+            removed = previous.capacity();
         } else {
             self.inner
                 .sources_content
@@ -318,10 +1040,22 @@ impl SourceMap {
                 .push(String::from(source_content));
         }
 
+        // New content makes the hash stale, stripping the content keeps it (see
+        // compute_source_hashes)
+        if !source_content.is_empty() {
+            if let Some(hash) = self.inner.source_hashes.get_mut(source_index) {
+                *hash = None;
+            }
+        }
+        self.release_memory(removed);
         Ok(())
     }
 
     pub fn get_source_content(&self, index: u32) -> Result<&str, SourceMapError> {
+        #[cfg(feature = "compressed_sources_content")]
+        if let Some(contents) = &self.compressed_sources_content {
+            return contents.get_str(index);
+        }
         self.inner
             .sources_content
             .get(index as usize)
@@ -329,109 +1063,393 @@ impl SourceMap {
             .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))
     }
 
+    // Contents kept compressed can only fail to decompress if their memory got corrupted,
+    // try_get_sources_content returns that as an error instead of panicking
     pub fn get_sources_content(&self) -> &Vec<String> {
-        &self.inner.sources_content
+        match self.try_get_sources_content() {
+            Ok(sources_content) => sources_content,
+            Err(err) => panic!("{}", err),
+        }
     }
 
-    // Write the sourcemap instance to a buffer
-    pub fn to_buffer(&self, output: &mut AlignedVec) -> Result<(), SourceMapError> {
-        output.clear();
-        let mut serializer = AlignedSerializer::new(output);
-        serializer.serialize_value(&self.inner)?;
-        Ok(())
+    pub fn try_get_sources_content(&self) -> Result<&Vec<String>, SourceMapError> {
+        #[cfg(feature = "compressed_sources_content")]
+        if let Some(contents) = &self.compressed_sources_content {
+            return contents.get_all();
+        }
+        Ok(&self.inner.sources_content)
     }
 
-    // Create a sourcemap instance from a buffer
-    pub fn from_buffer(project_root: &str, buf: &[u8]) -> Result<SourceMap, SourceMapError> {
-        let archived = unsafe { archived_root::<SourceMapInner>(buf) };
-        // TODO: see if we can use the archived data directly rather than deserializing at all...
-        let mut deserializer = AllocDeserializer;
-        let inner = archived.deserialize(&mut deserializer)?;
-        Ok(SourceMap {
-            project_root: String::from(project_root),
-            inner,
-        })
+    pub fn is_sources_content_compressed(&self) -> bool {
+        #[cfg(feature = "compressed_sources_content")]
+        return self.compressed_sources_content.is_some();
+        #[cfg(not(feature = "compressed_sources_content"))]
+        false
     }
 
-    pub fn add_sourcemap(
+    // Contents kept compressed go back into the map before anything changes them
+    pub(crate) fn sources_content_mut(&mut self) -> Result<&mut Vec<String>, SourceMapError> {
+        #[cfg(feature = "compressed_sources_content")]
+        self.decompress_sources_content()?;
+        Ok(&mut self.inner.sources_content)
+    }
+
+    // Builds the original -> generated index, it gets dropped again on any mapping mutation
+    pub fn build_reverse_index(&mut self) -> &ReverseIndex {
+        let reverse_index =
+            ReverseIndex::build(&self.inner.mapping_lines, self.inner.sources.len());
+        self.reverse_index.insert(reverse_index)
+    }
+
+    pub fn get_reverse_index(&self) -> Option<&ReverseIndex> {
+        self.reverse_index.as_ref()
+    }
+
+    // Every generated position that maps to exactly this original position, in generated order.
+    // The reverse index is built on the first call and reused until the mappings change.
+    pub fn find_generated_locations(
         &mut self,
-        sourcemap: &mut SourceMap,
-        line_offset: i64,
-    ) -> Result<(), SourceMapError> {
-        self.inner.sources.reserve(sourcemap.inner.sources.len());
-        let mut source_indexes = Vec::with_capacity(sourcemap.inner.sources.len());
-        let sources = std::mem::take(&mut sourcemap.inner.sources);
-        for s in sources.iter() {
-            source_indexes.push(self.add_source(s));
+        source: u32,
+        original_line: u32,
+        original_column: u32,
+    ) -> Vec<Position> {
+        let reverse_index = match &self.reverse_index {
+            Some(reverse_index) => reverse_index,
+            None => self.build_reverse_index(),
+        };
+        reverse_index
+            .generated_locations(source, original_line, original_column)
+            .iter()
+            .map(|m| Position::new(m.generated_line, m.generated_column))
+            .collect()
+    }
+
+    // Drop the reverse index, it gets built again on the next original position lookup
+    pub fn clear_reverse_index(&mut self) {
+        self.reverse_index = None;
+    }
+
+    // Builds the index find_closest_mapping uses instead of the lines, worth it when a map
+    // gets many lookups. It gets dropped again on any mapping mutation.
+    pub fn build_lookup_index(&mut self) -> &LookupIndex {
+        let policy = self.inner.duplicate_mapping_policy;
+        for mapping_line in self.inner.mapping_lines.lines_mut() {
+            mapping_line.ensure_sorted_with(policy);
         }
+        let lookup_index = LookupIndex::build(&self.inner.mapping_lines);
+        self.lookup_index.insert(lookup_index)
+    }
 
-        self.inner.names.reserve(sourcemap.inner.names.len());
-        let mut names_indexes = Vec::with_capacity(sourcemap.inner.names.len());
-        let names = std::mem::take(&mut sourcemap.inner.names);
-        for n in names.iter() {
-            names_indexes.push(self.add_name(n));
+    pub fn get_lookup_index(&self) -> Option<&LookupIndex> {
+        self.lookup_index.as_ref()
+    }
+
+    pub fn clear_lookup_index(&mut self) {
+        self.lookup_index = None;
+    }
+
+    // Mappings whose original position goes backwards compared to the previous mapping
+    // (in generated order) of the same source
+    pub fn find_unordered_mappings(&self) -> Vec<Mapping> {
+        let mut last_positions: Vec<Option<(u32, u32)>> = vec![None; self.inner.sources.len()];
+        let mut suspects = Vec::new();
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            let mut sorted_mappings;
+            let mut mappings = &mapping_line.mappings;
+            if !mapping_line.is_sorted {
+                sorted_mappings = mapping_line.mappings.clone();
+                sorted_mappings.sort_by_key(|m| m.generated_column);
+                mappings = &sorted_mappings;
+            }
+
+            for mapping in mappings.iter() {
+                if let Some(original) = mapping.original {
+                    let source = original.source as usize;
+                    if source >= last_positions.len() {
+                        last_positions.resize(source + 1, None);
+                    }
+
+                    let position = (original.original_line, original.original_column);
+                    if let Some(last_position) = last_positions[source] {
+                        if position < last_position {
+                            suspects.push(Mapping {
+                                generated_line: generated_line as u32,
+                                generated_column: mapping.generated_column,
+                                original: mapping.original,
+                            });
+                        }
+                    }
+                    last_positions[source] = Some(position);
+                }
+            }
         }
+        suspects
+    }
 
-        self.inner
-            .sources_content
-            .reserve(sourcemap.inner.sources_content.len());
-        let sources_content = std::mem::take(&mut sourcemap.inner.sources_content);
-        for (i, source_content_str) in sources_content.iter().enumerate() {
-            if let Some(source_index) = source_indexes.get(i) {
-                self.set_source_content(*source_index as usize, source_content_str)?;
+    // Sorts all mapping lines and the reverse index (if any) and returns the suspect mappings
+    pub fn repair(&mut self) -> Vec<Mapping> {
+        self.invalidate_vlq_cache();
+        self.notify(MutationEvent::Reset);
+        for mapping_line in self.inner.mapping_lines.lines_mut() {
+            mapping_line.ensure_sorted_with(self.inner.duplicate_mapping_policy);
+        }
+
+        if let Some(reverse_index) = &mut self.reverse_index {
+            if !reverse_index.is_sorted() {
+                reverse_index.sort();
             }
         }
 
-        let mapping_lines = std::mem::take(&mut sourcemap.inner.mapping_lines);
-        for (line, mapping_line) in mapping_lines.into_iter().enumerate() {
-            let generated_line = (line as i64) + line_offset;
-            if generated_line >= 0 {
-                let mut line = mapping_line;
-                for mapping in line.mappings.iter_mut() {
-                    match &mut mapping.original {
-                        Some(original_mapping_location) => {
-                            original_mapping_location.source = match source_indexes
-                                .get(original_mapping_location.source as usize)
-                            {
-                                Some(new_source_index) => *new_source_index,
-                                None => {
-                                    return Err(SourceMapError::new(
-                                        SourceMapErrorType::SourceOutOfRange,
-                                    ));
-                                }
-                            };
-
-                            original_mapping_location.name = match original_mapping_location.name {
-                                Some(name_index) => match names_indexes.get(name_index as usize) {
-                                    Some(new_name_index) => Some(*new_name_index),
-                                    None => {
-                                        return Err(SourceMapError::new(
-                                            SourceMapErrorType::NameOutOfRange,
-                                        ));
-                                    }
-                                },
-                                None => None,
-                            };
+        self.find_unordered_mappings()
+    }
+
+    // Write the sourcemap instance to a buffer, the reverse index is left out (it gets rebuilt
+    // on the first original position lookup after reading the buffer)
+    pub fn to_buffer(&self, output: &mut AlignedVec) -> Result<(), SourceMapError> {
+        self.to_buffer_with_options(output, &ToBufferOptions::default())
+    }
+
+    pub fn to_buffer_with_options(
+        &self,
+        output: &mut AlignedVec,
+        options: &ToBufferOptions,
+    ) -> Result<(), SourceMapError> {
+        self.check_cancelled()?;
+        output.clear();
+        output.extend_from_slice(&BUFFER_MAGIC);
+        output.extend_from_slice(&BUFFER_FORMAT_VERSION.to_le_bytes());
+        output.extend_from_slice(&BUFFER_FLAG_SORTED.to_le_bytes());
+        let mut serializer = AlignedSerializer::new(output);
+        let offset = self.generated_offset;
+        let compressed = self.is_sources_content_compressed();
+        let policy = self.inner.duplicate_mapping_policy;
+        let unsorted = self
+            .inner
+            .mapping_lines
+            .iter()
+            .any(|(_, line)| line.needs_sorting(policy));
+        if options.include_names
+            && !options.include_reverse_index
+            && offset == Position::default()
+            && !compressed
+            && !unsorted
+        {
+            serializer.serialize_value(&self.inner)?;
+        } else {
+            // Dropping the names, applying the offset, decompressing the contents, sorting or
+            // adding the reverse index needs a copy, the map itself stays as it is
+            let mut inner = self.inner.clone();
+            if unsorted {
+                for mapping_line in inner.mapping_lines.lines_mut() {
+                    mapping_line.ensure_sorted_with(policy);
+                }
+            }
+            if compressed {
+                inner.sources_content = self.try_get_sources_content()?.clone();
+            }
+            if !options.include_names {
+                inner.names.clear();
+                for mapping_line in inner.mapping_lines.lines_mut() {
+                    for mapping in mapping_line.mappings.iter_mut() {
+                        if let Some(original) = &mut mapping.original {
+                            original.name = None;
                         }
-                        None => {}
                     }
                 }
+            }
+            // Buffers have no room for the offset, the mappings are written where it puts them
+            if offset != Position::default() {
+                if inner.mapping_lines.len() as u64 + offset.line as u64 > u32::MAX as u64 {
+                    return Err(generated_offset_error());
+                }
+                if let Some(first_line) = inner.mapping_lines.get_mut(0) {
+                    for mapping in first_line.mappings.iter_mut() {
+                        mapping.generated_column = mapping
+                            .generated_column
+                            .checked_add(offset.column)
+                            .ok_or_else(generated_offset_error)?;
+                    }
+                }
+                inner.mapping_lines.insert_lines(0, offset.line as usize);
+            }
+            // Built from the copy, so it matches the mappings as they are written
+            if options.include_reverse_index {
+                inner.reverse_index = Some(ReverseIndex::build(
+                    &inner.mapping_lines,
+                    inner.sources.len(),
+                ));
+            }
+            serializer.serialize_value(&inner)?;
+        }
+        Ok(())
+    }
 
-                self.ensure_lines(generated_line as usize);
-                self.inner.mapping_lines[generated_line as usize] = line;
+    pub fn buffer_format_version() -> u32 {
+        BUFFER_FORMAT_VERSION
+    }
+
+    // Format version and archive of a buffer, buffers from before the header was introduced
+    // have the version after the archive and older ones won't have a meaningful version
+    fn split_buffer(buf: &[u8]) -> Option<(u32, &[u8])> {
+        if buf.len() >= BUFFER_HEADER_SIZE && buf.starts_with(&BUFFER_MAGIC) {
+            let mut version_bytes = [0; BUFFER_VERSION_SIZE];
+            version_bytes.copy_from_slice(&buf[BUFFER_MAGIC.len()..][..BUFFER_VERSION_SIZE]);
+            return Some((
+                u32::from_le_bytes(version_bytes),
+                &buf[BUFFER_HEADER_SIZE..],
+            ));
+        }
+        if buf.len() < BUFFER_VERSION_SIZE {
+            return None;
+        }
+
+        let (archive, version) = buf.split_at(buf.len() - BUFFER_VERSION_SIZE);
+        let mut version_bytes = [0; BUFFER_VERSION_SIZE];
+        version_bytes.copy_from_slice(version);
+        match u32::from_le_bytes(version_bytes) {
+            // Versions from before the header can only be read by the versions that wrote them
+            version if version <= LEGACY_BUFFER_FORMAT_VERSION => Some((version, archive)),
+            _ => None,
+        }
+    }
+
+    // Format version of a buffer written by to_buffer
+    pub fn get_buffer_format_version(buf: &[u8]) -> Option<u32> {
+        SourceMap::split_buffer(buf).map(|(version, _)| version)
+    }
+
+    // Whether from_buffer can read the buffer: the current format or the one before it.
+    // SourceMapView only reads the current one.
+    pub fn is_buffer_compatible(buf: &[u8]) -> bool {
+        matches!(
+            SourceMap::get_buffer_format_version(buf),
+            Some(BUFFER_FORMAT_VERSION | PREVIOUS_BUFFER_FORMAT_VERSION)
+        )
+    }
+
+    // The archive of a buffer, if this version can read it
+    // The BUFFER_FLAG_* flags in the header of a buffer
+    pub(crate) fn buffer_flags(buf: &[u8]) -> u32 {
+        match buf.get(BUFFER_MAGIC.len() + BUFFER_VERSION_SIZE..BUFFER_HEADER_SIZE) {
+            Some(flags) if buf.starts_with(&BUFFER_MAGIC) => {
+                u32::from_le_bytes([flags[0], flags[1], flags[2], flags[3]])
             }
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn buffer_archive(buf: &[u8]) -> Result<&[u8], SourceMapError> {
+        match SourceMap::split_buffer(buf) {
+            Some((BUFFER_FORMAT_VERSION, archive)) => Ok(archive),
+            Some((version, _)) => Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::BufferVersionMismatch,
+                &format!(
+                    "buffer has format version {}, expected {}",
+                    version, BUFFER_FORMAT_VERSION
+                ),
+            )),
+            None => Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::BufferVersionMismatch,
+                "buffer has no format version",
+            )),
         }
+    }
 
+    // Create a sourcemap instance from a buffer
+    pub fn from_buffer(project_root: &str, buf: &[u8]) -> Result<SourceMap, SourceMapError> {
+        let (version, archive) = match SourceMap::split_buffer(buf) {
+            Some((PREVIOUS_BUFFER_FORMAT_VERSION, archive)) => {
+                (PREVIOUS_BUFFER_FORMAT_VERSION, archive)
+            }
+            _ => (BUFFER_FORMAT_VERSION, SourceMap::buffer_archive(buf)?),
+        };
+        // The archive has to be aligned for rkyv to read it in place
+        let mut aligned = AlignedVec::new();
+        let archive = if archive.as_ptr().align_offset(16) == 0 {
+            archive
+        } else {
+            aligned.extend_from_slice(archive);
+            &aligned[..]
+        };
+        let mut inner = if version == PREVIOUS_BUFFER_FORMAT_VERSION {
+            buffer_legacy::read_previous(archive)?
+        } else {
+            buffer_check::check_archive(archive)?;
+            let archived = unsafe { archived_root::<SourceMapInner>(archive) };
+            // SourceMapView reads the archive directly, for maps that are only queried
+            buffer_check::deserialize_inner(archived)?
+        };
+        let mut reverse_index = inner.reverse_index.take();
+        // The checks only make sure it stays in bounds, lookups need it sorted
+        if let Some(reverse_index) = &mut reverse_index {
+            if !reverse_index.is_sorted() {
+                reverse_index.sort();
+            }
+        }
+        let mut sourcemap = SourceMap {
+            project_root: String::from(project_root),
+            inner,
+            reverse_index,
+            lookup_index: None,
+            vlq_cache: None,
+            raw_input: None,
+            #[cfg(feature = "json")]
+            parse_diagnostics: Vec::new(),
+            name_normalizer: None,
+            case_insensitive_sources: false,
+            add_source_mode: AddSourceMode::default(),
+            repair_missing_sources: false,
+            merge_equivalent_sources: true,
+            utf8_original_columns: false,
+            generated_offset: Position::default(),
+            name_without_source_policy: NameWithoutSourcePolicy::default(),
+            cancellation_token: None,
+            progress_reporter: None,
+            mutation_observer: None,
+            pending_capacity: CapacityHints::default(),
+            memory_budget: MemoryBudget::global().map(BudgetCharge::new),
+            #[cfg(feature = "compressed_sources_content")]
+            compressed_sources_content: None,
+        };
+        sourcemap.check_memory_budget()?;
+        Ok(sourcemap)
+    }
+
+    pub fn add_sourcemap(
+        &mut self,
+        sourcemap: &mut SourceMap,
+        line_offset: i64,
+    ) -> Result<(), SourceMapError> {
+        // The content gets counted by set_source_content
+        let added = sourcemap.added_memory_size();
+        self.ensure_memory(added + sourcemap.content_memory_size())?;
+        let (source_indexes, names_indexes) = self.merge_tables(sourcemap, None)?;
+        let mapping_lines = std::mem::take(&mut sourcemap.inner.mapping_lines);
+        let line_count = mapping_lines.len();
+        self.insert_map_lines(
+            mapping_lines.into_lines(),
+            line_count,
+            line_offset,
+            &source_indexes,
+            &names_indexes,
+        )?;
+        self.charge_memory(added);
         Ok(())
     }
 
     pub fn extends(&mut self, original_sourcemap: &mut SourceMap) -> Result<(), SourceMapError> {
+        // Only sources, names and content get added, the content is counted by set_source_content
+        let added = strings_size(&original_sourcemap.inner.sources)
+            + strings_size(&original_sourcemap.inner.names);
+        self.ensure_memory(added + original_sourcemap.content_memory_size())?;
         self.inner
             .sources
             .reserve(original_sourcemap.inner.sources.len());
         let mut source_indexes = Vec::with_capacity(original_sourcemap.inner.sources.len());
-        for s in original_sourcemap.inner.sources.iter() {
-            source_indexes.push(self.add_source(s));
+        for (i, s) in original_sourcemap.inner.sources.iter().enumerate() {
+            let source_index = self.add_source(s);
+            self.copy_ignored_source(source_index, original_sourcemap, i as u32);
+            source_indexes.push(source_index);
         }
 
         self.inner
@@ -442,16 +1460,20 @@ impl SourceMap {
             names_indexes.push(self.add_name(n));
         }
 
-        self.inner
-            .sources_content
-            .reserve(original_sourcemap.inner.sources_content.len());
-        for (i, source_content_str) in original_sourcemap.inner.sources_content.iter().enumerate() {
+        let original_sources_content = original_sourcemap.try_get_sources_content()?;
+        self.sources_content_mut()?
+            .reserve(original_sources_content.len());
+        for (i, source_content_str) in original_sources_content.iter().enumerate() {
             if let Some(source_index) = source_indexes.get(i) {
                 self.set_source_content(*source_index as usize, source_content_str)?;
             }
         }
 
-        for (_generated_line, line_content) in self.inner.mapping_lines.iter_mut().enumerate() {
+        self.reverse_index = None;
+        self.lookup_index = None;
+        self.invalidate_vlq_cache();
+        self.notify(MutationEvent::Reset);
+        for line_content in self.inner.mapping_lines.lines_mut() {
             for mapping in line_content.mappings.iter_mut() {
                 let original_location_option = &mut mapping.original;
                 if let Some(original_location) = original_location_option {
@@ -502,6 +1524,7 @@ impl SourceMap {
             }
         }
 
+        self.charge_memory(added);
         Ok(())
     }
 
@@ -514,6 +1537,112 @@ impl SourceMap {
         line_offset: i64,
         column_offset: i64,
     ) -> Result<(), SourceMapError> {
+        // Every segment is followed by a `,` or `;`, except the last one. The content is
+        // counted by set_source_content. Counting takes a pass over the input, so it's only
+        // done when there is a budget to check.
+        let added = if self.memory_budget.is_some() {
+            let segments = input.iter().filter(|b| **b == b',' || **b == b';').count() + 1;
+            let added = mappings_size(segments) + strings_size(&sources) + strings_size(&names);
+            self.ensure_memory(added + strings_size(&sources_content))?;
+            added
+        } else {
+            0
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            let chunk_count = self.parallel_chunk_count(input.len());
+            if chunk_count > 1 {
+                let (source_indexes, name_indexes) =
+                    self.add_vlq_sources(&sources, &sources_content, names)?;
+                self.add_vlq_chunks(
+                    input,
+                    &source_indexes,
+                    &name_indexes,
+                    line_offset,
+                    column_offset,
+                    chunk_count,
+                )?;
+                self.charge_memory(added);
+                return Ok(());
+            }
+        }
+
+        self.add_vlq_input(
+            &mut MappingBytes::new(input),
+            sources,
+            sources_content,
+            names,
+            line_offset,
+            column_offset,
+        )?;
+        self.charge_memory(added);
+        Ok(())
+    }
+
+    // Same as add_vlq_map, but the mappings are read and decoded in chunks, so huge maps
+    // don't have to be held in memory as a string next to the parsed mappings
+    pub fn add_vlq_map_from_reader<R: io::Read>(
+        &mut self,
+        reader: R,
+        sources: Vec<&str>,
+        sources_content: Vec<&str>,
+        names: Vec<&str>,
+        line_offset: i64,
+        column_offset: i64,
+    ) -> Result<(), SourceMapError> {
+        // The size of the input isn't known up front, so the mappings are counted after they
+        // were added: a map that goes over the memory budget keeps them
+        let added = strings_size(&sources) + strings_size(&names);
+        self.reserve_memory(added)?;
+        let mut input = ReaderBytes::new(reader);
+        let result = self.add_vlq_input(
+            &mut input,
+            sources,
+            sources_content,
+            names,
+            line_offset,
+            column_offset,
+        );
+        // A failed read looks like the end of the input to the decoder
+        match input.take_error() {
+            Some(error) => Err(error.into()),
+            None => self.reserve_mapping_memory(result?),
+        }
+    }
+
+    // Adds the sources and names of a map that is being parsed, returns the indexes in this
+    // map that the indexes in its mappings refer to
+    fn add_vlq_sources(
+        &mut self,
+        sources: &[&str],
+        sources_content: &[&str],
+        names: Vec<&str>,
+    ) -> Result<(Vec<u32>, Vec<u32>), SourceMapError> {
+        self.inner.sources.reserve(sources.len());
+        self.sources_content_mut()?.reserve(sources_content.len());
+        let mut source_indexes: Vec<u32> = Vec::with_capacity(sources.len());
+        for (i, source) in sources.iter().enumerate() {
+            let source_index = match sources_content.get(i) {
+                Some(source_content) => self.add_source_with_content(source, source_content)?,
+                None => self.add_source(source),
+            };
+            self.merge_source_provenance(source_index, SourceProvenance::Declared);
+            source_indexes.push(source_index);
+        }
+        let name_indexes: Vec<u32> = self.add_names(names);
+        Ok((source_indexes, name_indexes))
+    }
+
+    fn add_vlq_input<I: MappingInput>(
+        &mut self,
+        input: &mut I,
+        sources: Vec<&str>,
+        sources_content: Vec<&str>,
+        names: Vec<&str>,
+        line_offset: i64,
+        column_offset: i64,
+    ) -> Result<usize, SourceMapError> {
         let mut generated_line: i64 = line_offset;
         let mut generated_column: i64 = column_offset;
         let mut original_line = 0;
@@ -521,84 +1650,172 @@ impl SourceMap {
         let mut source = 0;
         let mut name = 0;
 
-        let source_indexes: Vec<u32> = self.add_sources(sources);
-        let name_indexes: Vec<u32> = self.add_names(names);
+        let (mut source_indexes, name_indexes) =
+            self.add_vlq_sources(&sources, &sources_content, names)?;
 
-        self.inner.sources_content.reserve(sources_content.len());
-        for (i, source_content) in sources_content.iter().enumerate() {
-            if let Some(source_index) = source_indexes.get(i) {
-                self.set_source_content(*source_index as usize, source_content)?;
-            }
-        }
-
-        let mut input = input.iter().cloned().peekable();
-        while let Some(byte) = input.peek().cloned() {
-            match byte {
-                b';' => {
-                    generated_line += 1;
-                    generated_column = column_offset;
-                    input.next().unwrap();
-                }
-                b',' => {
-                    input.next().unwrap();
-                }
-                _ => {
-                    // First is a generated column that is always present.
-                    read_relative_vlq(&mut generated_column, &mut input)?;
-
-                    // Read source, original line, and original column if the
-                    // mapping has them.
-                    let original = if input.peek().cloned().map_or(true, is_mapping_separator) {
-                        None
-                    } else {
-                        read_relative_vlq(&mut source, &mut input)?;
-                        read_relative_vlq(&mut original_line, &mut input)?;
-                        read_relative_vlq(&mut original_column, &mut input)?;
-                        Some(OriginalLocation::new(
-                            original_line as u32,
-                            original_column as u32,
-                            match source_indexes.get(source as usize) {
-                                Some(v) => *v,
-                                None => {
-                                    return Err(SourceMapError::new(
-                                        SourceMapErrorType::SourceOutOfRange,
-                                    ));
-                                }
-                            },
-                            if input.peek().cloned().map_or(true, is_mapping_separator) {
-                                None
-                            } else {
-                                read_relative_vlq(&mut name, &mut input)?;
-                                Some(match name_indexes.get(name as usize) {
-                                    Some(v) => *v,
-                                    None => {
-                                        return Err(SourceMapError::new(
-                                            SourceMapErrorType::NameOutOfRange,
-                                        ));
+        let mut column_converter = if self.utf8_original_columns {
+            Some(Utf8ColumnConverter::new(&sources_content))
+        } else {
+            None
+        };
+        let mut progress = self.progress_tracker(ProgressStage::Parse, None);
+        // Where the segment that is being decoded starts, attached to errors
+        let mut location = ErrorLocation::default();
+        // Mappings added, for add_vlq_map_from_reader to count against the memory budget
+        let mut added = 0;
+        let mut decode = || -> Result<(), SourceMapError> {
+            while let Some(byte) = input.peek() {
+                match byte {
+                    b';' => {
+                        self.check_cancelled()?;
+                        generated_line += 1;
+                        generated_column = column_offset;
+                        location.generated_line += 1;
+                        location.segment_index = 0;
+                        input.next().unwrap();
+                    }
+                    b',' => {
+                        location.segment_index += 1;
+                        input.next().unwrap();
+                    }
+                    _ => {
+                        location.byte_offset = input.byte_offset();
+                        let mut fields = [0; 5];
+                        let count = input.read_segment(&mut fields)?;
+                        // First is a generated column that is always present.
+                        apply_relative_vlq(&mut generated_column, fields[0])?;
+
+                        // Followed by source, original line, and original column if the
+                        // mapping has them.
+                        let original = match count {
+                            1 => None,
+                            // Only two fields, so the second one is a name rather than a source
+                            2 => {
+                                apply_relative_vlq(&mut name, fields[1])?;
+                                self.name_without_source(name, &name_indexes)?
+                            }
+                            _ => {
+                                apply_relative_vlq(&mut source, fields[1])?;
+                                apply_relative_vlq(&mut original_line, fields[2])?;
+                                apply_relative_vlq(&mut original_column, fields[3])?;
+                                if self.repair_missing_sources && source >= 0 {
+                                    while source_indexes.len() <= source as usize {
+                                        let index = source_indexes.len() as i64;
+                                        source_indexes.push(self.add_placeholder_source(index));
                                     }
-                                })
-                            },
-                        ))
-                    };
-
-                    if generated_line >= 0 {
-                        self.add_mapping(generated_line as u32, generated_column as u32, original);
+                                }
+                                let column = match &mut column_converter {
+                                    Some(converter) => converter.convert(
+                                        source as usize,
+                                        original_line as u32,
+                                        original_column as u32,
+                                    ),
+                                    None => original_column as u32,
+                                };
+                                Some(OriginalLocation::new(
+                                    original_line as u32,
+                                    column,
+                                    match source_indexes.get(source as usize) {
+                                        Some(v) => *v,
+                                        None => {
+                                            return Err(SourceMapError::new(
+                                                SourceMapErrorType::SourceOutOfRange,
+                                            ));
+                                        }
+                                    },
+                                    if count < 5 {
+                                        None
+                                    } else {
+                                        apply_relative_vlq(&mut name, fields[4])?;
+                                        Some(match name_indexes.get(name as usize) {
+                                            Some(v) => *v,
+                                            None => {
+                                                return Err(SourceMapError::new(
+                                                    SourceMapErrorType::NameOutOfRange,
+                                                ));
+                                            }
+                                        })
+                                    },
+                                ))
+                            }
+                        };
+
+                        if generated_line >= 0 {
+                            self.add_mapping(
+                                generated_line as u32,
+                                generated_column as u32,
+                                original,
+                            );
+                            added += 1;
+                        }
+                        progress.advance(1);
                     }
                 }
             }
+            Ok(())
+        };
+        decode().map_err(|error| error.with_location(location))?;
+
+        // Keep trailing empty lines, so the map still covers the same amount of generated lines
+        if generated_line > line_offset && generated_line >= 0 {
+            self.ensure_lines(generated_line as usize)?;
+        }
+
+        progress.finish();
+        Ok(added)
+    }
+
+    // Removes empty lines at the end of the map, these are emitted as trailing semicolons
+    pub fn trim_trailing_lines(&mut self) -> Result<(), SourceMapError> {
+        let line_count = self.inner.mapping_lines.len();
+        self.inner.mapping_lines.trim_end()?;
+        if self.inner.mapping_lines.len() == line_count {
+            return Ok(());
         }
 
+        if let Some(vlq_cache) = &mut self.vlq_cache {
+            vlq_cache.truncate(self.inner.mapping_lines.len());
+        }
+        self.notify(MutationEvent::LinesTruncated {
+            line_count: self.inner.mapping_lines.len() as u32,
+        });
         Ok(())
     }
 
+    // Releases the spare capacity of every line and table, worth calling once a map is done
+    // being built and will be kept around for lookups. Lines are stored the same way after
+    // this, every line with mappings still has its own Vec (see MappingLine::shrink_to_fit).
+    pub fn shrink_to_fit(&mut self) {
+        for line in self.inner.mapping_lines.lines_mut() {
+            line.shrink_to_fit();
+        }
+        self.inner.mapping_lines.shrink_to_fit();
+        self.inner.sources.shrink_to_fit();
+        self.inner.sources_content.shrink_to_fit();
+        self.inner.names.shrink_to_fit();
+    }
+
     pub fn offset_columns(
         &mut self,
         generated_line: u32,
         generated_column: u32,
         generated_column_offset: i64,
     ) -> Result<(), SourceMapError> {
+        self.reverse_index = None;
+        self.lookup_index = None;
+        self.invalidate_vlq_line(generated_line as usize);
+        let policy = self.inner.duplicate_mapping_policy;
         match self.inner.mapping_lines.get_mut(generated_line as usize) {
-            Some(line) => line.offset_columns(generated_column, generated_column_offset),
+            Some(line) => {
+                line.ensure_sorted_with(policy);
+                line.offset_columns(generated_column, generated_column_offset)?;
+                self.notify(MutationEvent::ColumnsShifted {
+                    generated_line,
+                    generated_column,
+                    offset: generated_column_offset,
+                });
+                Ok(())
+            }
             None => Ok(()),
         }
     }
@@ -614,27 +1831,47 @@ impl SourceMap {
 
         let (start_line, overflowed) =
             (generated_line as i64).overflowing_add(generated_line_offset);
-        if overflowed || start_line > (u32::MAX as i64) {
+        if overflowed || start_line < 0 {
             return Err(SourceMapError::new_with_reason(
                 SourceMapErrorType::UnexpectedNegativeNumber,
-                "column + column_offset cannot be negative",
+                "line + line_offset cannot be negative",
+            ));
+        }
+        // The last stored line moves along too, checked before anything moves
+        let last_line = (self.inner.mapping_lines.len() as i64 - 1).max(generated_line as i64);
+        if start_line > u32::MAX as i64 || last_line + generated_line_offset >= u32::MAX as i64 {
+            return Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::UnexpectedlyBigNumber,
+                "line + line_offset does not fit in a line",
             ));
         }
 
+        self.reverse_index = None;
+        self.lookup_index = None;
         let line = generated_line as usize;
-        let abs_offset = generated_line_offset.abs() as usize;
+        let abs_offset = generated_line_offset.unsigned_abs() as usize;
         if generated_line_offset > 0 {
             if line > self.inner.mapping_lines.len() {
-                self.ensure_lines(line + abs_offset);
+                self.ensure_lines(line + abs_offset)?;
             } else {
-                self.inner
-                    .mapping_lines
-                    .splice(line..line, (0..abs_offset).map(|_| MappingLine::new()));
+                self.inner.mapping_lines.insert_lines(line, abs_offset);
+                if let Some(vlq_cache) = &mut self.vlq_cache {
+                    vlq_cache.insert_lines(line, abs_offset);
+                }
             }
         } else {
-            self.inner.mapping_lines.drain(line - abs_offset..line);
+            self.inner
+                .mapping_lines
+                .remove_lines(line - abs_offset..line);
+            if let Some(vlq_cache) = &mut self.vlq_cache {
+                vlq_cache.remove_lines(line - abs_offset..line);
+            }
         }
 
+        self.notify(MutationEvent::LinesShifted {
+            generated_line,
+            offset: generated_line_offset,
+        });
         Ok(())
     }
 
@@ -683,3 +1920,690 @@ fn test_buffers() {
         Err(err) => panic!("{:?}", err),
     }
 }
+
+#[test]
+fn test_case_insensitive_sources() {
+    let mut map = SourceMap::new("C:\\project");
+    assert_eq!(map.add_source("C:\\project\\Foo.js"), 0);
+    assert_eq!(map.add_source("c:\\project\\foo.js"), 1);
+
+    let mut map = SourceMap::new("C:\\project");
+    map.set_case_insensitive_sources(true);
+    assert_eq!(map.add_source("C:\\project\\Foo.js"), 0);
+    assert_eq!(map.add_source("c:\\project\\foo.js"), 0);
+    assert_eq!(map.get_source_index("FOO.JS").unwrap(), Some(0));
+    assert_eq!(map.get_sources(), &vec!["Foo.js"]);
+}
+
+#[test]
+fn test_vlq_cache() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(
+        b"AAAA,EAAE;AACA;AACA,KAAK",
+        vec!["a.js", "b.js"],
+        vec![],
+        vec![],
+        0,
+        0,
+    )
+    .unwrap();
+    map.enable_vlq_cache();
+
+    let mut cached_output = vec![];
+    map.write_vlq(&mut cached_output).unwrap();
+
+    map.add_mapping(1, 8, Some(OriginalLocation::new(7, 2, 1, None)));
+    map.offset_lines(1, 2).unwrap();
+    cached_output.clear();
+    map.write_vlq(&mut cached_output).unwrap();
+
+    let mut output = vec![];
+    map.disable_vlq_cache();
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(cached_output, output);
+}
+
+#[test]
+fn test_repair_missing_sources() {
+    let mut map = SourceMap::new("/");
+    assert!(map
+        .add_vlq_map(b"AAAA,CEAA", vec!["a.js"], vec![], vec![], 0, 0)
+        .is_err());
+
+    let mut map = SourceMap::new("/");
+    map.set_repair_missing_sources(true);
+    map.add_vlq_map(b"AAAA,CEAA", vec!["a.js"], vec![], vec![], 0, 0)
+        .unwrap();
+    assert_eq!(
+        map.get_sources(),
+        &vec!["a.js", "<unknown:1>", "<unknown:2>"]
+    );
+    assert_eq!(map.get_mappings()[1].original.unwrap().source, 2);
+}
+
+#[test]
+fn test_generated_offset() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(b"CAAA;AACA", vec!["a.js"], vec![], vec![], 0, 0)
+        .unwrap();
+    map.set_generated_offset(2, 5);
+
+    assert!(map.find_closest_mapping(1, 3).is_none());
+    assert!(map.find_closest_mapping(2, 4).is_none());
+    let found = map.find_closest_mapping(2, 6).unwrap();
+    assert_eq!((found.generated_line, found.generated_column), (2, 6));
+    let found = map.find_closest_mapping(3, 0).unwrap();
+    assert_eq!(found.original.unwrap().original_line, 1);
+
+    let mut output = Vec::new();
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(output, b";;MAAA;AACA");
+
+    let positions = |mappings: Vec<Mapping>| -> Vec<(u32, u32)> {
+        mappings
+            .iter()
+            .map(|m| (m.generated_line, m.generated_column))
+            .collect()
+    };
+    assert_eq!(positions(map.get_mappings()), vec![(2, 6), (3, 0)]);
+    let flat_mappings = map.to_flat_arrays().unwrap();
+    assert_eq!(flat_mappings.generated_lines, vec![2, 3]);
+    assert_eq!(flat_mappings.generated_columns, vec![6, 0]);
+
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    let mut from_buffer = SourceMap::from_buffer("/", &buffer).unwrap();
+    assert_eq!(from_buffer.get_generated_offset(), Position::default());
+    output.clear();
+    from_buffer.write_vlq(&mut output).unwrap();
+    assert_eq!(output, b";;MAAA;AACA");
+
+    map.set_generated_offset(u32::MAX, 0);
+    assert_eq!(positions(map.get_mappings()), vec![(u32::MAX, 1)]);
+    assert!(map.to_flat_arrays().is_err());
+    assert!(matches!(
+        map.to_buffer(&mut buffer).unwrap_err().error_type,
+        SourceMapErrorType::UnexpectedlyBigNumber
+    ));
+    map.set_generated_offset(0, u32::MAX);
+    assert!(matches!(
+        map.write_vlq(&mut output).unwrap_err().error_type,
+        SourceMapErrorType::UnexpectedlyBigNumber
+    ));
+}
+
+#[test]
+fn test_resolved_sources() {
+    let mut map = SourceMap::new("/project/src");
+    map.add_source("/project/src/a.js");
+    map.add_source("/project/lib/b.js");
+    map.add_source("webpack://app/c.js");
+    assert_eq!(
+        map.get_resolved_sources(),
+        vec![
+            PathBuf::from("/project/src/a.js"),
+            PathBuf::from("/project/lib/b.js"),
+            PathBuf::from("webpack://app/c.js")
+        ]
+    );
+
+    map.set_source_root(Some("../generated"));
+    assert_eq!(
+        map.get_resolved_sources(),
+        vec![
+            PathBuf::from("/project/generated/a.js"),
+            PathBuf::from("/project/lib/b.js"),
+            PathBuf::from("webpack://app/c.js")
+        ]
+    );
+    map.set_source_root(Some("webpack://app/"));
+    assert_eq!(
+        map.get_resolved_sources()[0],
+        PathBuf::from("webpack://app/a.js")
+    );
+}
+
+#[test]
+fn test_name_without_source() {
+    let mut map = SourceMap::new("/");
+    let error = map
+        .add_vlq_map(b"AAAA,CC", vec!["a.js"], vec![], vec!["foo", "bar"], 0, 0)
+        .unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::NameWithoutSource
+    ));
+
+    let mut map = SourceMap::new("/");
+    map.set_name_without_source_policy(NameWithoutSourcePolicy::DropName);
+    map.add_vlq_map(
+        b"AAAAA,CC,CAAAA",
+        vec!["a.js"],
+        vec![],
+        vec!["foo", "bar"],
+        0,
+        0,
+    )
+    .unwrap();
+    let mappings = map.get_mappings();
+    assert!(mappings[1].original.is_none());
+    // The name of the two-field segment still counts for the next relative name
+    assert_eq!(mappings[2].original.unwrap().name, Some(1));
+
+    let mut map = SourceMap::new("/");
+    map.set_name_without_source_policy(NameWithoutSourcePolicy::SyntheticSource);
+    map.add_vlq_map(b"AC", vec!["a.js"], vec![], vec!["foo", "bar"], 0, 0)
+        .unwrap();
+    assert_eq!(map.get_sources(), &vec!["a.js", "<unknown>"]);
+    let original = map.get_mappings()[0].original.unwrap();
+    assert_eq!((original.source, original.name), (1, Some(1)));
+}
+
+#[test]
+fn test_trailing_lines() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(b"AAAA;;AACA;;;", vec!["a.js"], vec![], vec![], 0, 0)
+        .unwrap();
+    let mut output = vec![];
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(output, b"AAAA;;AACA;;;");
+
+    map.trim_trailing_lines().unwrap();
+    output.clear();
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(output, b"AAAA;;AACA");
+}
+
+#[test]
+fn test_large_line_offsets() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(b"AAAA,CAAC", vec!["a.js"], vec![], vec![], 0, 0)
+        .unwrap();
+    map.offset_lines(0, 10_000_000).unwrap();
+    map.add_mapping(10_000_005, 4, None);
+    assert_eq!(map.inner.mapping_lines.len(), 10_000_006);
+    assert_eq!(map.inner.mapping_lines.stored_len(), 2);
+    assert_eq!(
+        map.find_closest_mapping(10_000_000, 1)
+            .unwrap()
+            .original
+            .unwrap()
+            .original_column,
+        1
+    );
+
+    let mut output = vec![];
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(output.len(), 10_000_005 + "AAAA,CAAC".len() + "I".len());
+    assert!(output.starts_with(b";;;"));
+
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    let mut map = SourceMap::from_buffer("/", &buffer).unwrap();
+    map.offset_lines(10_000_000, -10_000_000).unwrap();
+    output.clear();
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(output, b"AAAA,CAAC;;;;;I");
+
+    // Lines past the shift can't be pushed out of the u32 range, nor lines before 0
+    let error = map.offset_lines(1, u32::MAX as i64 - 3).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::UnexpectedlyBigNumber
+    ));
+    let error = map.offset_lines(1, -2).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::UnexpectedNegativeNumber
+    ));
+    output.clear();
+    map.write_vlq(&mut output).unwrap();
+    assert_eq!(output, b"AAAA,CAAC;;;;;I");
+}
+
+#[test]
+fn test_buffer_format_version() {
+    let map = SourceMap::new("/");
+    let mut output = AlignedVec::new();
+    map.to_buffer(&mut output).unwrap();
+    assert_eq!(
+        SourceMap::get_buffer_format_version(&output),
+        Some(SourceMap::buffer_format_version())
+    );
+    assert!(SourceMap::is_buffer_compatible(&output));
+
+    output.as_mut_slice()[8] = 0xff;
+    assert_eq!(SourceMap::get_buffer_format_version(&output), Some(0xff));
+    assert!(!SourceMap::is_buffer_compatible(&output));
+    let error = SourceMap::from_buffer("/", &output).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::BufferVersionMismatch
+    ));
+    assert!(matches!(
+        SourceMap::from_buffer("/", b"garbage")
+            .unwrap_err()
+            .error_type,
+        SourceMapErrorType::BufferVersionMismatch
+    ));
+
+    // Buffers from before the header, with the version after the archive
+    let mut map = SourceMap::new("/");
+    map.add_source("a.js");
+    map.add_mapping(2, 3, Some(OriginalLocation::new(0, 1, 0, None)));
+    let mut legacy = AlignedVec::new();
+    let mut serializer = AlignedSerializer::new(&mut legacy);
+    serializer.serialize_value(&map.inner).unwrap();
+    legacy.extend_from_slice(&LEGACY_BUFFER_FORMAT_VERSION.to_le_bytes());
+    assert_eq!(
+        SourceMap::get_buffer_format_version(&legacy),
+        Some(LEGACY_BUFFER_FORMAT_VERSION)
+    );
+    // Their archive is older than the current one
+    assert!(!SourceMap::is_buffer_compatible(&legacy));
+    assert!(matches!(
+        SourceMap::from_buffer("/", &legacy).unwrap_err().error_type,
+        SourceMapErrorType::BufferVersionMismatch
+    ));
+}
+
+#[test]
+fn test_duplicate_mapping_policy() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 5, Some(OriginalLocation::new(1, 0, source, None)));
+    map.add_mapping(0, 5, Some(OriginalLocation::new(2, 0, source, None)));
+    map.add_mapping(0, 5, Some(OriginalLocation::new(3, 0, source, None)));
+
+    let found = map.find_closest_mapping(0, 5).unwrap();
+    assert_eq!(found.original.unwrap().original_line, 1);
+    assert_eq!(map.get_mappings().len(), 3);
+
+    map.set_duplicate_mapping_policy(DuplicateMappingPolicy::KeepLast);
+    let mut output = AlignedVec::new();
+    map.to_buffer(&mut output).unwrap();
+    let mut map = SourceMap::from_buffer("/", &output).unwrap();
+    assert_eq!(
+        map.get_duplicate_mapping_policy(),
+        DuplicateMappingPolicy::KeepLast
+    );
+    let found = map.find_closest_mapping(0, 5).unwrap();
+    assert_eq!(found.original.unwrap().original_line, 3);
+    assert_eq!(map.get_mappings().len(), 1);
+
+    // Readers resolve duplicates without a lookup first
+    let mut map = SourceMap::new("/");
+    map.set_duplicate_mapping_policy(DuplicateMappingPolicy::KeepFirst);
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 5, Some(OriginalLocation::new(1, 0, source, None)));
+    map.add_mapping(0, 2, Some(OriginalLocation::new(2, 0, source, None)));
+    map.add_mapping(0, 5, Some(OriginalLocation::new(3, 0, source, None)));
+    let mappings = map.get_mappings();
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[1].original.unwrap().original_line, 1);
+    assert_eq!(map.to_flat_arrays().unwrap().generated_columns, vec![2, 5]);
+}
+
+#[test]
+fn test_flat_arrays() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    let name = map.add_name("foo");
+    map.add_mapping(0, 2, Some(OriginalLocation::new(1, 4, source, Some(name))));
+    map.add_mapping(2, 0, None);
+
+    let flat_mappings = map.to_flat_arrays().unwrap();
+    assert_eq!(flat_mappings.names, vec![0, -1]);
+
+    let mut combined = SourceMap::new("/");
+    combined.add_sources(vec!["b.js", "a.js"]);
+    combined.add_names(vec!["bar", "foo"]);
+    combined
+        .add_flat_arrays(&flat_mappings, 10, 1, 1, 1)
+        .unwrap();
+    let mappings = combined.get_mappings();
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[0].generated_line, 10);
+    assert_eq!(mappings[0].generated_column, 3);
+    let original = mappings[0].original.unwrap();
+    assert_eq!(original.source, 1);
+    assert_eq!(original.name, Some(1));
+    assert!(mappings[1].original.is_none());
+}
+
+#[test]
+fn test_flat_arrays_round_trip_edge_values() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+    map.add_mapping(0, 1, None);
+    map.add_mapping(1, 0, Some(OriginalLocation::new(0, 0, source, Some(0))));
+
+    let flat_mappings = map.to_flat_arrays().unwrap();
+    assert_eq!(
+        flat_mappings.original(0).unwrap(),
+        Some(OriginalLocation::new(0, 0, source, None))
+    );
+    assert_eq!(flat_mappings.original(1).unwrap(), None);
+    assert_eq!(
+        flat_mappings.mapping(2).unwrap().original.unwrap().name,
+        Some(0)
+    );
+    assert!(flat_mappings.original(3).is_err());
+
+    // Original values past i32::MAX can't be stored next to the -1 for missing ones
+    let mut big = SourceMap::new("/");
+    let source = big.add_source("a.js");
+    big.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+    big.add_mapping(0, 4, Some(OriginalLocation::new(1 << 31, 0, source, None)));
+    assert!(matches!(
+        big.to_flat_arrays().unwrap_err().error_type,
+        SourceMapErrorType::UnexpectedlyBigNumber
+    ));
+    let mut batch = FlatMappings::default();
+    assert!(batch
+        .push(
+            0,
+            0,
+            Some(OriginalLocation::new(0, 0, source, Some(1 << 31)))
+        )
+        .is_err());
+    assert!(batch.is_empty() && batch.names.is_empty());
+
+    let mut round_tripped = SourceMap::new("/");
+    round_tripped.add_source("a.js");
+    round_tripped
+        .add_flat_arrays(&flat_mappings, 0, 0, 0, 0)
+        .unwrap();
+    assert_eq!(
+        format!("{:?}", round_tripped.get_mappings()),
+        format!("{:?}", map.get_mappings())
+    );
+
+    // Partial sentinels used to be read as unmapped
+    for (source, original_line, original_column, name) in [
+        (0, -1, 0, -1),
+        (-1, 0, 0, -1),
+        (-1, -1, -1, 0),
+        (0, 0, 0, -2),
+    ] {
+        let flat_mappings = FlatMappings {
+            generated_lines: vec![0],
+            generated_columns: vec![0],
+            sources: vec![source],
+            original_lines: vec![original_line],
+            original_columns: vec![original_column],
+            names: vec![name],
+        };
+        let error = SourceMap::new("/")
+            .add_flat_arrays(&flat_mappings, 0, 0, 0, 0)
+            .unwrap_err();
+        assert!(matches!(
+            error.error_type,
+            SourceMapErrorType::InvalidFlatMapping
+        ));
+    }
+}
+
+#[test]
+fn test_find_unordered_mappings() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(4, 0, source, None)));
+    map.add_mapping(0, 10, Some(OriginalLocation::new(1, 0, source, None)));
+    map.add_mapping(1, 0, Some(OriginalLocation::new(5, 2, source, None)));
+
+    let suspects = map.repair();
+    assert_eq!(suspects.len(), 1);
+    assert_eq!(suspects[0].generated_line, 0);
+    assert_eq!(suspects[0].generated_column, 10);
+}
+
+#[test]
+fn test_find_generated_locations() {
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    let b = map.add_source("b.js");
+    map.add_mapping(3, 2, Some(OriginalLocation::new(1, 4, a, None)));
+    map.add_mapping(0, 8, Some(OriginalLocation::new(1, 4, a, None)));
+    map.add_mapping(0, 0, Some(OriginalLocation::new(1, 4, b, None)));
+    map.add_mapping(1, 0, Some(OriginalLocation::new(1, 5, a, None)));
+
+    assert_eq!(
+        map.find_generated_locations(a, 1, 4),
+        vec![Position::new(0, 8), Position::new(3, 2)]
+    );
+    assert!(map.get_reverse_index().is_some());
+    assert!(map.find_generated_locations(a, 2, 0).is_empty());
+    assert!(map.find_generated_locations(7, 1, 4).is_empty());
+
+    map.add_mapping(2, 0, Some(OriginalLocation::new(1, 4, a, None)));
+    assert_eq!(map.find_generated_locations(a, 1, 4).len(), 3);
+}
+
+#[test]
+fn test_to_buffer_without_names() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    let name = map.add_name("foo");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, Some(name))));
+
+    let mut output = AlignedVec::new();
+    let options = ToBufferOptions {
+        include_names: false,
+        ..ToBufferOptions::default()
+    };
+    map.to_buffer_with_options(&mut output, &options).unwrap();
+    let buffer_map = SourceMap::from_buffer("/", &output).unwrap();
+    assert!(buffer_map.get_names().is_empty());
+    assert_eq!(buffer_map.get_mappings()[0].original.unwrap().name, None);
+    assert_eq!(map.get_names(), &vec!["foo"]);
+}
+
+#[test]
+fn test_vlq_error_location() {
+    let mut map = SourceMap::new("/");
+    let error = map
+        .add_vlq_map(b"AAAA;AACA,A!AA;", vec!["a.js"], vec![], vec![], 0, 0)
+        .unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::VlqInvalidBase64
+    ));
+    assert_eq!(
+        error.location,
+        Some(ErrorLocation {
+            byte_offset: 10,
+            generated_line: 1,
+            segment_index: 1,
+        })
+    );
+
+    let error = map
+        .add_vlq_map(b"AAAA;;AAAAA", vec!["a.js"], vec![], vec![], 3, 0)
+        .unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::NameOutOfRange
+    ));
+    assert_eq!(error.location.unwrap().generated_line, 2);
+}
+
+#[test]
+fn test_buffer_reverse_index() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 4, Some(OriginalLocation::new(2, 1, source, None)));
+    map.add_mapping(3, 0, Some(OriginalLocation::new(2, 1, source, None)));
+    map.build_reverse_index();
+
+    let mut output = AlignedVec::new();
+    map.to_buffer(&mut output).unwrap();
+    let mut without_index = AlignedVec::new();
+    map.clear_reverse_index();
+    map.to_buffer(&mut without_index).unwrap();
+    // By default the index is a cache, buffers are the same with or without it
+    assert_eq!(&output[..], &without_index[..]);
+    let map = SourceMap::from_buffer("/", &output).unwrap();
+    assert!(map.get_reverse_index().is_none());
+
+    // It gets built for the buffer when it is asked for, even if the map has none
+    let options = ToBufferOptions {
+        include_reverse_index: true,
+        ..ToBufferOptions::default()
+    };
+    let mut map = map;
+    map.set_generated_offset(1, 0);
+    map.to_buffer_with_options(&mut output, &options).unwrap();
+    assert!(output.len() > without_index.len());
+    let map = SourceMap::from_buffer("/", &output).unwrap();
+    let found = map
+        .get_reverse_index()
+        .unwrap()
+        .generated_locations(source, 2, 1);
+    assert_eq!(found.len(), 2);
+    assert_eq!((found[0].generated_line, found[0].generated_column), (1, 4));
+    assert_eq!((found[1].generated_line, found[1].generated_column), (4, 0));
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    let mut map = SourceMap::new("/");
+    map.add_vlq_map(
+        b"AAAA;AAAA,CAAC;;AAAA,CAAC,CAAC,CAAC,CAAC",
+        vec!["a.js"],
+        vec![],
+        vec![],
+        0,
+        0,
+    )
+    .unwrap();
+    map.shrink_to_fit();
+    for (_, line) in map.inner.mapping_lines.iter() {
+        if line.mappings.spilled() {
+            assert_eq!(line.mappings.capacity(), line.mappings.len());
+        }
+    }
+    assert_eq!(map.get_mappings().len(), 8);
+
+    // Lines with one or two mappings don't allocate, lines that got shorter move back inline
+    let mut map = SourceMap::new("/");
+    for line in 0..100 {
+        map.add_mapping(line, 0, None);
+    }
+    let inline = map.memory_size();
+    for line in 0..100 {
+        map.add_mapping(line, 1, None);
+        map.add_mapping(line, 2, None);
+    }
+    assert!(map.memory_size() - inline >= 100 * 4 * std::mem::size_of::<LineMapping>());
+    for line in 0..100 {
+        map.offset_columns(line, 1, -1).unwrap();
+    }
+    map.shrink_to_fit();
+    assert!(map.memory_size() <= inline);
+    assert_eq!(map.get_mappings().len(), 200);
+}
+
+#[test]
+fn test_add_source_mode() {
+    let mut map = SourceMap::new("/");
+    map.set_add_source_mode(AddSourceMode::DedupeByPathAndContent);
+    let a = map.add_source_with_content("a.js", "one").unwrap();
+    assert_eq!(map.add_source_with_content("a.js", "one").unwrap(), a);
+    let b = map.add_source_with_content("a.js", "two").unwrap();
+    assert_ne!(a, b);
+    assert_eq!(map.get_source_content(a).unwrap(), "one");
+    assert_eq!(map.get_source_content(b).unwrap(), "two");
+
+    map.add_vlq_map(b"AAAA", vec!["a.js"], vec!["two"], vec![], 0, 0)
+        .unwrap();
+    assert_eq!(map.get_sources().len(), 2);
+    assert_eq!(map.get_mappings()[0].original.unwrap().source, b);
+
+    map.set_add_source_mode(AddSourceMode::AlwaysAppend);
+    assert_eq!(map.add_source("a.js"), 2);
+
+    map.set_add_source_mode(AddSourceMode::DedupeByPath);
+    assert_eq!(map.add_source_with_content("a.js", "three").unwrap(), a);
+    assert_eq!(map.get_source_content(a).unwrap(), "three");
+}
+
+#[test]
+fn test_add_vlq_map_from_reader() {
+    // Hands out a few bytes per read, so segments get split across reads
+    struct SlowReader<'a>(&'a [u8], bool);
+    impl io::Read for SlowReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() && self.1 {
+                return Err(io::Error::other("connection reset"));
+            }
+            let len = self.0.len().min(buf.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let mappings = b"AAAA,SAASA,GAAG;;AACA,eAAe;AAChB";
+    let mut expected = SourceMap::new("/");
+    expected
+        .add_vlq_map(mappings, vec!["a.js"], vec![], vec!["foo"], 0, 0)
+        .unwrap();
+    let mut streamed = SourceMap::new("/");
+    streamed
+        .add_vlq_map_from_reader(
+            SlowReader(mappings, false),
+            vec!["a.js"],
+            vec![],
+            vec!["foo"],
+            0,
+            0,
+        )
+        .unwrap();
+    assert_eq!(
+        format!("{:?}", streamed.get_mappings()),
+        format!("{:?}", expected.get_mappings())
+    );
+
+    let error = SourceMap::new("/")
+        .add_vlq_map_from_reader(
+            SlowReader(b"AAAA;AACA", true),
+            vec!["a.js"],
+            vec![],
+            vec![],
+            0,
+            0,
+        )
+        .unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::IOError));
+
+    let error = SourceMap::new("/")
+        .add_vlq_map_from_reader(
+            SlowReader(b"AAAA;AA!A", false),
+            vec!["a.js"],
+            vec![],
+            vec![],
+            0,
+            0,
+        )
+        .unwrap_err();
+    assert_eq!(error.location.unwrap().byte_offset, 5);
+}
+
+#[test]
+fn test_last_line() {
+    let mut map = SourceMap::new("/");
+    map.try_add_mapping(u32::MAX - 1, 0, None).unwrap();
+    assert_eq!(map.inner.mapping_lines.len(), u32::MAX as usize);
+    map.trim_trailing_lines().unwrap();
+    assert_eq!(map.inner.mapping_lines.len(), u32::MAX as usize);
+
+    let error = map.try_add_mapping(u32::MAX, 0, None).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::UnexpectedlyBigNumber
+    ));
+    assert!(map.split_line(u32::MAX - 1, 0).is_err());
+    assert_eq!(map.inner.mapping_lines.len(), u32::MAX as usize);
+}
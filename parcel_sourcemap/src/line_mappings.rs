@@ -0,0 +1,384 @@
+use crate::mapping_line::{ArchivedLineMapping, LineMapping};
+use rkyv::ser::Serializer;
+use rkyv::std_impl::ArchivedVec;
+use rkyv::{Archive, ArchiveUnsized, Deserialize, Fallible, RelPtr, Serialize, SerializeUnsized};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut, Range};
+
+// Lines with at most this many mappings keep them in the MappingLine itself. Unminified
+// output has one or two mappings on a lot of its lines, a Vec would allocate room for 4.
+pub const INLINE_MAPPINGS: usize = 2;
+
+// The mappings of a line. Short lines are stored inline, longer ones move to a Vec, which
+// stays even if the line gets short again (until shrink_to_fit). Archived the same way as a
+// Vec<LineMapping>, so buffers don't depend on it.
+#[derive(Clone)]
+pub struct LineMappings(Storage);
+
+#[derive(Clone)]
+enum Storage {
+    Inline {
+        len: u8,
+        mappings: [LineMapping; INLINE_MAPPINGS],
+    },
+    Heap(Vec<LineMapping>),
+}
+
+impl LineMappings {
+    pub const fn new() -> Self {
+        LineMappings(Storage::Inline {
+            len: 0,
+            mappings: [LineMapping {
+                generated_column: 0,
+                original: None,
+            }; INLINE_MAPPINGS],
+        })
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity <= INLINE_MAPPINGS {
+            LineMappings::new()
+        } else {
+            LineMappings(Storage::Heap(Vec::with_capacity(capacity)))
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        match &self.0 {
+            Storage::Inline { .. } => INLINE_MAPPINGS,
+            Storage::Heap(mappings) => mappings.capacity(),
+        }
+    }
+
+    // Whether the mappings live in their own allocation
+    pub fn spilled(&self) -> bool {
+        matches!(self.0, Storage::Heap(_))
+    }
+
+    // Bytes allocated outside of the line
+    pub fn heap_size(&self) -> usize {
+        match &self.0 {
+            Storage::Inline { .. } => 0,
+            Storage::Heap(mappings) => mappings.capacity() * std::mem::size_of::<LineMapping>(),
+        }
+    }
+
+    pub fn as_slice(&self) -> &[LineMapping] {
+        match &self.0 {
+            Storage::Inline { len, mappings } => &mappings[..*len as usize],
+            Storage::Heap(mappings) => mappings,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [LineMapping] {
+        match &mut self.0 {
+            Storage::Inline { len, mappings } => &mut mappings[..*len as usize],
+            Storage::Heap(mappings) => mappings,
+        }
+    }
+
+    pub fn push(&mut self, mapping: LineMapping) {
+        match &mut self.0 {
+            Storage::Inline { len, mappings } if (*len as usize) < INLINE_MAPPINGS => {
+                mappings[*len as usize] = mapping;
+                *len += 1;
+            }
+            Storage::Inline { mappings, .. } => {
+                let mut spilled = Vec::with_capacity(INLINE_MAPPINGS * 2);
+                spilled.extend_from_slice(mappings);
+                spilled.push(mapping);
+                self.0 = Storage::Heap(spilled);
+            }
+            Storage::Heap(mappings) => mappings.push(mapping),
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        match &mut self.0 {
+            Storage::Inline { len, mappings } => {
+                let required = *len as usize + additional;
+                if required > INLINE_MAPPINGS {
+                    let mut spilled = Vec::with_capacity(required);
+                    spilled.extend_from_slice(&mappings[..*len as usize]);
+                    self.0 = Storage::Heap(spilled);
+                }
+            }
+            Storage::Heap(mappings) => mappings.reserve(additional),
+        }
+    }
+
+    // Short lines move back inline
+    pub fn shrink_to_fit(&mut self) {
+        if let Storage::Heap(mappings) = &mut self.0 {
+            if mappings.len() <= INLINE_MAPPINGS {
+                *self = self.as_slice().iter().copied().collect();
+            } else {
+                mappings.shrink_to_fit();
+            }
+        }
+    }
+
+    pub fn truncate(&mut self, new_len: usize) {
+        match &mut self.0 {
+            Storage::Inline { len, .. } => *len = (*len).min(new_len as u8),
+            Storage::Heap(mappings) => mappings.truncate(new_len),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        let len = self.len();
+        assert!(range.start <= range.end && range.end <= len);
+        self.as_mut_slice().copy_within(range.end.., range.start);
+        self.truncate(len - range.len());
+    }
+
+    // Moves the mappings from at on into new storage
+    pub fn split_off(&mut self, at: usize) -> LineMappings {
+        let tail = self[at..].iter().copied().collect();
+        self.truncate(at);
+        tail
+    }
+
+    // Same as Vec::dedup_by: same_bucket gets the current mapping and the last one that
+    // was kept, the current one is removed when it returns true
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where
+        F: FnMut(&mut LineMapping, &mut LineMapping) -> bool,
+    {
+        if let Storage::Heap(mappings) = &mut self.0 {
+            mappings.dedup_by(same_bucket);
+            return;
+        }
+
+        let mappings = self.as_mut_slice();
+        let mut kept = 1;
+        for i in 1..mappings.len() {
+            let (previous, current) = mappings.split_at_mut(i);
+            if !same_bucket(&mut current[0], &mut previous[kept - 1]) {
+                mappings[kept] = mappings[i];
+                kept += 1;
+            }
+        }
+        if !mappings.is_empty() {
+            self.truncate(kept);
+        }
+    }
+
+    pub fn dedup_by_key<K, F>(&mut self, mut key: F)
+    where
+        K: PartialEq,
+        F: FnMut(&mut LineMapping) -> K,
+    {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    pub fn into_vec(self) -> Vec<LineMapping> {
+        match self.0 {
+            Storage::Inline { len, mappings } => mappings[..len as usize].to_vec(),
+            Storage::Heap(mappings) => mappings,
+        }
+    }
+}
+
+impl Default for LineMappings {
+    fn default() -> Self {
+        LineMappings::new()
+    }
+}
+
+impl Deref for LineMappings {
+    type Target = [LineMapping];
+
+    fn deref(&self) -> &[LineMapping] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for LineMappings {
+    fn deref_mut(&mut self) -> &mut [LineMapping] {
+        self.as_mut_slice()
+    }
+}
+
+impl fmt::Debug for LineMappings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl From<Vec<LineMapping>> for LineMappings {
+    fn from(mappings: Vec<LineMapping>) -> Self {
+        if mappings.len() <= INLINE_MAPPINGS {
+            mappings.into_iter().collect()
+        } else {
+            LineMappings(Storage::Heap(mappings))
+        }
+    }
+}
+
+impl FromIterator<LineMapping> for LineMappings {
+    fn from_iter<I: IntoIterator<Item = LineMapping>>(iter: I) -> Self {
+        let mut mappings = LineMappings::new();
+        mappings.extend(iter);
+        mappings
+    }
+}
+
+impl Extend<LineMapping> for LineMappings {
+    fn extend<I: IntoIterator<Item = LineMapping>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for mapping in iter {
+            self.push(mapping);
+        }
+    }
+}
+
+impl IntoIterator for LineMappings {
+    type Item = LineMapping;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        match self.0 {
+            Storage::Inline { len, mappings } => {
+                IntoIter::Inline(mappings.into_iter().take(len as usize))
+            }
+            Storage::Heap(mappings) => IntoIter::Heap(mappings.into_iter()),
+        }
+    }
+}
+
+pub enum IntoIter {
+    Inline(std::iter::Take<std::array::IntoIter<LineMapping, INLINE_MAPPINGS>>),
+    Heap(std::vec::IntoIter<LineMapping>),
+}
+
+impl Iterator for IntoIter {
+    type Item = LineMapping;
+
+    fn next(&mut self) -> Option<LineMapping> {
+        match self {
+            IntoIter::Inline(iter) => iter.next(),
+            IntoIter::Heap(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IntoIter::Inline(iter) => iter.size_hint(),
+            IntoIter::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a LineMappings {
+    type Item = &'a LineMapping;
+    type IntoIter = std::slice::Iter<'a, LineMapping>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut LineMappings {
+    type Item = &'a mut LineMapping;
+    type IntoIter = std::slice::IterMut<'a, LineMapping>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+pub struct LineMappingsResolver {
+    pos: usize,
+    metadata_resolver: <[LineMapping] as ArchiveUnsized>::MetadataResolver,
+}
+
+// The same as the impls of Vec<LineMapping>, see there
+impl Archive for LineMappings {
+    type Archived = ArchivedVec<ArchivedLineMapping>;
+    type Resolver = LineMappingsResolver;
+
+    fn resolve(&self, pos: usize, resolver: Self::Resolver, out: &mut MaybeUninit<Self::Archived>) {
+        // ArchivedVec is a transparent wrapper around the RelPtr
+        let out = unsafe {
+            &mut *(out as *mut MaybeUninit<Self::Archived>
+                as *mut MaybeUninit<RelPtr<[ArchivedLineMapping]>>)
+        };
+        // The metadata resolver of a slice is ()
+        #[allow(clippy::unit_arg)]
+        self.as_slice()
+            .resolve_unsized(pos, resolver.pos, resolver.metadata_resolver, out);
+    }
+}
+
+impl<S: Serializer + ?Sized> Serialize<S> for LineMappings {
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(LineMappingsResolver {
+            pos: self.as_slice().serialize_unsized(serializer)?,
+            metadata_resolver: self.as_slice().serialize_metadata(serializer)?,
+        })
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<LineMappings, D> for ArchivedVec<ArchivedLineMapping>
+where
+    ArchivedVec<ArchivedLineMapping>: Deserialize<Vec<LineMapping>, D>,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<LineMappings, D::Error> {
+        let mappings: Vec<LineMapping> = self.deserialize(deserializer)?;
+        Ok(LineMappings::from(mappings))
+    }
+}
+
+#[test]
+fn test_line_mappings() {
+    let mapping = |generated_column| LineMapping {
+        generated_column,
+        original: None,
+    };
+    let columns = |mappings: &LineMappings| -> Vec<u32> {
+        mappings.iter().map(|m| m.generated_column).collect()
+    };
+
+    let mut mappings = LineMappings::new();
+    mappings.push(mapping(4));
+    mappings.push(mapping(4));
+    assert!(!mappings.spilled());
+    assert_eq!(mappings.heap_size(), 0);
+    mappings.dedup_by_key(|m| m.generated_column);
+    assert_eq!(columns(&mappings), vec![4]);
+
+    for column in [6, 8, 10] {
+        mappings.push(mapping(column));
+    }
+    assert!(mappings.spilled());
+    assert_eq!(columns(&mappings), vec![4, 6, 8, 10]);
+    mappings.remove_range(1..3);
+    assert_eq!(columns(&mappings), vec![4, 10]);
+    // Short lines go back inline
+    mappings.shrink_to_fit();
+    assert!(!mappings.spilled());
+    assert_eq!(columns(&mappings), vec![4, 10]);
+
+    let tail = mappings.split_off(1);
+    assert_eq!(columns(&tail), vec![10]);
+    assert_eq!(columns(&mappings), vec![4]);
+    assert_eq!(
+        mappings
+            .into_iter()
+            .chain(tail)
+            .map(|m| m.generated_column)
+            .collect::<Vec<u32>>(),
+        vec![4, 10]
+    );
+
+    let mappings: LineMappings = (0..10).map(mapping).collect();
+    assert_eq!(mappings.len(), 10);
+    assert_eq!(mappings.into_vec().len(), 10);
+}
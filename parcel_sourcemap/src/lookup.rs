@@ -0,0 +1,379 @@
+use crate::mapping::{LookupBias, LookupPosition, Mapping, OriginalLocation};
+use crate::reverse_index::ReverseIndex;
+use crate::SourceMap;
+
+// Lookup result without any strings, for hot loops that only need indices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawMapping {
+    pub generated_line: u32,
+    pub generated_column: u32,
+    pub source: u32,
+    pub original_line: u32,
+    pub original_column: u32,
+    pub name: Option<u32>,
+}
+
+// Lookup result with the source and name resolved to owned strings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedLocation {
+    pub source: String,
+    pub original_line: u32,
+    pub original_column: u32,
+    pub name: Option<String>,
+}
+
+// Same as ResolvedLocation, borrowing the source and name from the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedLocationRef<'a> {
+    pub source: &'a str,
+    pub original_line: u32,
+    pub original_column: u32,
+    pub name: Option<&'a str>,
+}
+
+impl ResolvedLocationRef<'_> {
+    pub fn into_owned(self) -> ResolvedLocation {
+        ResolvedLocation {
+            source: String::from(self.source),
+            original_line: self.original_line,
+            original_column: self.original_column,
+            name: self.name.map(String::from),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct LookupOptions {
+    // If the found mapping has no name, use the name of the closest preceding mapping on the
+    // same original line. This usually resolves anonymous functions to the name they got
+    // assigned to, the same heuristic Sentry uses. Builds the reverse index if the map doesn't
+    // have one yet.
+    pub infer_names: bool,
+    pub bias: LookupBias,
+}
+
+impl SourceMap {
+    pub fn find_closest_mapping_with_options(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        options: &LookupOptions,
+    ) -> Option<Mapping> {
+        let mut mapping =
+            self.find_closest_mapping_with_bias(generated_line, generated_column, options.bias)?;
+        if options.infer_names {
+            if let Some(original) = &mut mapping.original {
+                if original.name.is_none() {
+                    original.name = self.infer_name(original);
+                }
+            }
+        }
+        Some(mapping)
+    }
+
+    // Same results as calling find_closest_mapping for every (line, column) position, in the
+    // same order. The positions get sorted so every line is only visited once and walked
+    // forward, instead of a binary search per position. Pays off when remapping coverage
+    // data or large amounts of stack frames.
+    pub fn find_closest_mappings(&mut self, positions: &[(u32, u32)]) -> Vec<Option<Mapping>> {
+        let offset = self.generated_offset;
+        let mut results: Vec<Option<Mapping>> = positions.iter().map(|_| None).collect();
+        // (line index, column without the offset, position index) of positions inside the map
+        let mut queries: Vec<(u32, u32, usize)> = positions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (line, column))| {
+                let line_index = line.checked_sub(offset.line)?;
+                let column_offset = if line_index == 0 { offset.column } else { 0 };
+                Some((line_index, column.checked_sub(column_offset)?, i))
+            })
+            .collect();
+        queries.sort_unstable();
+
+        let policy = self.inner.duplicate_mapping_policy;
+        let mut columns = Vec::new();
+        for line_queries in queries.chunk_by(|a, b| a.0 == b.0) {
+            let line_index = line_queries[0].0;
+            let line = match self.inner.mapping_lines.get_mut(line_index as usize) {
+                Some(line) => line,
+                None => continue,
+            };
+            line.ensure_sorted_with(policy);
+
+            columns.clear();
+            columns.extend(line_queries.iter().map(|(_, column, i)| (*column, *i)));
+            let column_offset = if line_index == 0 { offset.column } else { 0 };
+            line.find_closest_mappings_sorted(&columns, |i, line_mapping| {
+                results[i] = line_mapping.map(|line_mapping| Mapping {
+                    generated_line: positions[i].0,
+                    generated_column: line_mapping.generated_column + column_offset,
+                    original: line_mapping.original,
+                });
+            });
+        }
+        results
+    }
+
+    // The returned mapping is always zero-based, invalid one-based positions give None
+    pub fn find_closest_mapping_at<P: Into<LookupPosition>>(
+        &mut self,
+        position: P,
+    ) -> Option<Mapping> {
+        let position = position.into().to_zero_based()?;
+        self.find_closest_mapping(position.line, position.column)
+    }
+
+    // Same as find_closest_mapping, but mappings without an original location give None
+    pub fn find_closest_mapping_raw(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Option<RawMapping> {
+        let mapping = self.find_closest_mapping(generated_line, generated_column)?;
+        let original = mapping.original?;
+        Some(RawMapping {
+            generated_line: mapping.generated_line,
+            generated_column: mapping.generated_column,
+            source: original.source,
+            original_line: original.original_line,
+            original_column: original.original_column,
+            name: original.name,
+        })
+    }
+
+    // Convenience wrapper around find_closest_mapping_raw, allocates the source and name
+    pub fn find_closest_location(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Option<ResolvedLocation> {
+        self.find_closest_location_ref(generated_line, generated_column)
+            .map(ResolvedLocationRef::into_owned)
+    }
+
+    // Same as find_closest_location without allocating, for symbolicating many frames
+    pub fn find_closest_location_ref(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Option<ResolvedLocationRef<'_>> {
+        let raw = self.find_closest_mapping_raw(generated_line, generated_column)?;
+        let source = self.get_source(raw.source).ok()?;
+        let name = raw.name.and_then(|name| self.get_name(name).ok());
+        Some(ResolvedLocationRef {
+            source,
+            original_line: raw.original_line,
+            original_column: raw.original_column,
+            name,
+        })
+    }
+
+    // Uses the reverse index, which is built on the first call and reused until the mappings
+    // change (see find_generated_locations)
+    fn infer_name(&mut self, original: &OriginalLocation) -> Option<u32> {
+        let reverse_index = match self.reverse_index.take() {
+            Some(reverse_index) => reverse_index,
+            None => ReverseIndex::build(&self.inner.mapping_lines, self.inner.sources.len()),
+        };
+        let name = self.infer_name_from_index(&reverse_index, original);
+        self.reverse_index = Some(reverse_index);
+        name
+    }
+
+    fn infer_name_from_index(
+        &mut self,
+        reverse_index: &ReverseIndex,
+        original: &OriginalLocation,
+    ) -> Option<u32> {
+        let source_mappings = reverse_index.sources.get(original.source as usize)?;
+        let start = source_mappings.partition_point(|m| m.original_line < original.original_line);
+        let end = source_mappings.partition_point(|m| {
+            (m.original_line, m.original_column)
+                < (original.original_line, original.original_column)
+        });
+        let policy = self.inner.duplicate_mapping_policy;
+        source_mappings[start..end].iter().rev().find_map(|m| {
+            let mapping_line = self
+                .inner
+                .mapping_lines
+                .get_mut(m.generated_line as usize)?;
+            mapping_line.ensure_sorted_with(policy);
+            let mappings = &mapping_line.mappings;
+            let first = mappings.partition_point(|l| l.generated_column < m.generated_column);
+            mappings[first..]
+                .iter()
+                .take_while(|l| l.generated_column == m.generated_column)
+                .filter_map(|l| l.original)
+                .find(|o| {
+                    o.source == original.source
+                        && (o.original_line, o.original_column)
+                            == (m.original_line, m.original_column)
+                })
+                .and_then(|o| o.name)
+        })
+    }
+}
+
+#[test]
+fn test_infer_names() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    let name = map.add_name("handler");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(4, 6, source, Some(name))));
+    map.add_mapping(0, 20, Some(OriginalLocation::new(4, 16, source, None)));
+    map.add_mapping(0, 40, None);
+
+    let found = map.find_closest_mapping(0, 22).unwrap();
+    assert_eq!(found.original.unwrap().name, None);
+
+    let options = LookupOptions {
+        infer_names: true,
+        ..Default::default()
+    };
+    let found = map
+        .find_closest_mapping_with_options(0, 22, &options)
+        .unwrap();
+    assert_eq!(found.original.unwrap().name, Some(name));
+
+    // The index gets built once and reused for the next lookups
+    assert!(map.get_reverse_index().is_some());
+    let found = map
+        .find_closest_mapping_with_options(0, 22, &options)
+        .unwrap();
+    assert_eq!(found.original.unwrap().name, Some(name));
+
+    // Unsorted lines, and a mapping on another line that is closer in the original
+    let other = map.add_name("other");
+    map.add_mapping(
+        1,
+        30,
+        Some(OriginalLocation::new(4, 30, source, Some(other))),
+    );
+    map.add_mapping(
+        1,
+        10,
+        Some(OriginalLocation::new(4, 10, source, Some(other))),
+    );
+    map.add_mapping(1, 0, Some(OriginalLocation::new(4, 12, source, None)));
+    assert!(map.get_reverse_index().is_none());
+    let found = map
+        .find_closest_mapping_with_options(0, 22, &options)
+        .unwrap();
+    assert_eq!(found.original.unwrap().name, Some(other));
+    let found = map
+        .find_closest_mapping_with_options(1, 0, &options)
+        .unwrap();
+    assert_eq!(found.original.unwrap().name, Some(other));
+}
+
+#[test]
+fn test_find_closest_mapping_raw() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    let name = map.add_name("main");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(2, 4, source, Some(name))));
+    map.add_mapping(1, 0, None);
+
+    let raw = map.find_closest_mapping_raw(0, 8).unwrap();
+    assert_eq!(raw.source, source);
+    assert_eq!(raw.name, Some(name));
+    assert_eq!((raw.original_line, raw.original_column), (2, 4));
+    assert!(map.find_closest_mapping_raw(1, 0).is_none());
+
+    let location = map.find_closest_location(0, 8).unwrap();
+    assert_eq!(location.source, "a.js");
+    assert_eq!(location.name.as_deref(), Some("main"));
+    let location_ref = map.find_closest_location_ref(0, 8).unwrap();
+    assert_eq!(location_ref.source, "a.js");
+    assert_eq!(location_ref.name, Some("main"));
+    assert_eq!(location_ref.into_owned(), location);
+}
+
+#[test]
+fn test_find_closest_mapping_at() {
+    use crate::mapping::{OneBasedPosition, Position};
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+    map.add_mapping(1, 4, Some(OriginalLocation::new(7, 0, source, None)));
+
+    let zero_based = map.find_closest_mapping_at(Position::new(1, 4)).unwrap();
+    let one_based = map
+        .find_closest_mapping_at(OneBasedPosition::new(2, 5))
+        .unwrap();
+    assert_eq!(zero_based.original.unwrap().original_line, 7);
+    assert_eq!(one_based.original.unwrap().original_line, 7);
+    assert!(map
+        .find_closest_mapping_at(OneBasedPosition::new(0, 1))
+        .is_none());
+    assert_eq!(
+        Position::new(1, 4).to_one_based(),
+        OneBasedPosition::new(2, 5)
+    );
+}
+
+#[test]
+fn test_find_closest_mapping_with_bias() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 2, Some(OriginalLocation::new(1, 0, source, None)));
+    map.add_mapping(0, 8, Some(OriginalLocation::new(2, 0, source, None)));
+    map.set_generated_offset(0, 10);
+
+    let lower = map
+        .find_closest_mapping_with_bias(0, 15, LookupBias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(lower.generated_column, 12);
+    let upper = map
+        .find_closest_mapping_with_bias(0, 15, LookupBias::LeastUpperBound)
+        .unwrap();
+    assert_eq!(upper.generated_column, 18);
+    assert_eq!(upper.original.unwrap().original_line, 2);
+    assert!(map
+        .find_closest_mapping_with_bias(0, 19, LookupBias::LeastUpperBound)
+        .is_none());
+
+    let options = LookupOptions {
+        bias: LookupBias::LeastUpperBound,
+        ..Default::default()
+    };
+    let found = map
+        .find_closest_mapping_with_options(0, 11, &options)
+        .unwrap();
+    assert_eq!(found.generated_column, 12);
+    assert!(LookupBias::from_optional_name(Some("nearest")).is_err());
+}
+
+#[test]
+fn test_find_closest_mappings() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    for (line, column) in [(0, 3), (0, 3), (0, 10), (1, 0), (1, 7), (3, 2), (0, 1)] {
+        map.add_mapping(
+            line,
+            column,
+            Some(OriginalLocation::new(line * 10 + column, 0, source, None)),
+        );
+    }
+    map.set_generated_offset(1, 2);
+
+    let mut positions = Vec::new();
+    for line in 0..6 {
+        for column in (0..14).rev() {
+            positions.push((line, column));
+        }
+    }
+    let results = map.find_closest_mappings(&positions);
+    assert_eq!(results.len(), positions.len());
+    for ((line, column), result) in positions.iter().zip(results) {
+        assert_eq!(
+            format!("{:?}", result),
+            format!("{:?}", map.find_closest_mapping(*line, *column)),
+            "{}:{}",
+            line,
+            column
+        );
+    }
+}
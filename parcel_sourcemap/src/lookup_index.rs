@@ -0,0 +1,166 @@
+use crate::mapping::OriginalLocation;
+use crate::mapping_line::LineMapping;
+use crate::mapping_lines::MappingLines;
+
+fn key(generated_line: u32, generated_column: u32) -> u64 {
+    ((generated_line as u64) << 32) | generated_column as u64
+}
+
+// Generated -> original index for lookup heavy work like symbolicating stack traces: all
+// mappings in one array sorted by line << 32 | column, a lookup is a single binary search
+// over it instead of finding the line first and then searching its mappings
+#[derive(Debug, Default, Clone)]
+pub struct LookupIndex {
+    keys: Vec<u64>,
+    originals: Vec<Option<OriginalLocation>>,
+    has_duplicates: bool,
+}
+
+// Index of the first key at or after `key` in keys[..end], searching backwards from the end.
+// The keys looked for are close to the end (the start of a line or of a column), a full
+// binary search would mostly touch cache lines far away from it.
+fn search_back(keys: &[u64], end: usize, key: u64) -> usize {
+    let mut step = 1;
+    let mut start = end;
+    while start > 0 && keys[start - 1] >= key {
+        let next = start.saturating_sub(step);
+        if keys[next] < key {
+            return next + keys[next..start].partition_point(|k| *k < key);
+        }
+        start = next;
+        step *= 2;
+    }
+    start
+}
+
+impl LookupIndex {
+    pub fn heap_size(&self) -> usize {
+        self.keys.capacity() * std::mem::size_of::<u64>()
+            + self.originals.capacity() * std::mem::size_of::<Option<OriginalLocation>>()
+    }
+
+    // The lines have to be sorted with their duplicates resolved
+    pub(crate) fn build(mapping_lines: &MappingLines) -> Self {
+        let len = mapping_lines.mappings_len();
+        let mut keys = Vec::with_capacity(len);
+        let mut originals = Vec::with_capacity(len);
+        let mut has_duplicates = false;
+        for (generated_line, mapping_line) in mapping_lines.iter() {
+            has_duplicates |= mapping_line.may_have_duplicates;
+            for mapping in mapping_line.mappings.iter() {
+                keys.push(key(generated_line as u32, mapping.generated_column));
+                originals.push(mapping.original);
+            }
+        }
+
+        Self {
+            keys,
+            originals,
+            has_duplicates,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Same result as MappingLine::find_closest_mapping on the line
+    pub(crate) fn find_closest_mapping(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Option<LineMapping> {
+        let on_line = |index: usize| {
+            self.keys
+                .get(index)
+                .is_some_and(|k| k >> 32 == generated_line as u64)
+        };
+        let at = |index: usize| LineMapping {
+            generated_column: self.keys[index] as u32,
+            original: self.originals[index],
+        };
+
+        // Always resolve to the first mapping of a column, so duplicates give stable results
+        let target = key(generated_line, generated_column);
+        let index = self.keys.partition_point(|k| *k < target);
+        if self.keys.get(index) == Some(&target) {
+            return Some(at(index));
+        }
+
+        let before = index.checked_sub(1).filter(|before| on_line(*before));
+        match (before, on_line(index)) {
+            (Some(before), true) => {
+                if !self.has_duplicates {
+                    return Some(at(before));
+                }
+                Some(at(search_back(&self.keys, before, self.keys[before])))
+            }
+            // Outside of the mappings of the line, the first one is used at column 0
+            (Some(_), false) | (None, true) => {
+                let first = search_back(&self.keys, index, key(generated_line, 0));
+                Some(LineMapping {
+                    generated_column: 0,
+                    original: self.originals[first],
+                })
+            }
+            (None, false) => None,
+        }
+    }
+}
+
+#[test]
+fn test_lookup_index() {
+    use crate::SourceMap;
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    for (line, column) in [(0, 4), (0, 8), (0, 8), (2, 3), (2, 1), (5, u32::MAX)] {
+        map.add_mapping(
+            line,
+            column,
+            Some(OriginalLocation::new(line, column / 2, source, None)),
+        );
+    }
+    // The second mapping at 0:8
+    map.add_mapping(0, 8, None);
+
+    let positions = [
+        (0, 0),
+        (0, 5),
+        (0, 7),
+        (0, 8),
+        (0, 9),
+        (1, 0),
+        (2, 0),
+        (2, 2),
+        (2, 3),
+        (2, 100),
+        (3, 0),
+        (5, 0),
+        (5, u32::MAX),
+        (6, 0),
+    ];
+    let lookup = |map: &mut SourceMap| -> Vec<Option<(u32, Option<OriginalLocation>)>> {
+        positions
+            .iter()
+            .map(|(line, column)| {
+                let mapping = map.find_closest_mapping(*line, *column)?;
+                Some((mapping.generated_column, mapping.original))
+            })
+            .collect()
+    };
+    let expected = lookup(&mut map);
+
+    assert_eq!(map.build_lookup_index().len(), 7);
+    assert_eq!(lookup(&mut map), expected);
+    // The first of the two mappings at 0:8
+    let mapping = map.find_closest_mapping(0, 8).unwrap();
+    assert_eq!(mapping.original.unwrap().original_column, 4);
+
+    map.add_mapping(1, 0, None);
+    assert!(map.get_lookup_index().is_none());
+}
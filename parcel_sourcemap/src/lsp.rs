@@ -0,0 +1,158 @@
+use crate::mapping::{OriginalLocation, Position};
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// Same shape and JSON representation as the Position and Range of the Language Server
+// Protocol. Lines are 0-based and characters are UTF-16 code units, which matches source map
+// columns. to_lsp/from_lsp convert to and from lsp_types::Position and lsp_types::Range (or
+// any other type with the same JSON representation) through serde.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+fn convert<F: Serialize, T: DeserializeOwned>(value: &F) -> Result<T, SourceMapError> {
+    Ok(serde_json::from_value(serde_json::to_value(value)?)?)
+}
+
+impl LspPosition {
+    pub fn new(line: u32, character: u32) -> Self {
+        Self { line, character }
+    }
+
+    // e.g. `let position: lsp_types::Position = position.to_lsp()?;`
+    pub fn to_lsp<T: DeserializeOwned>(&self) -> Result<T, SourceMapError> {
+        convert(self)
+    }
+
+    pub fn from_lsp<T: Serialize>(position: &T) -> Result<Self, SourceMapError> {
+        convert(position)
+    }
+}
+
+impl LspRange {
+    pub fn new(start: LspPosition, end: LspPosition) -> Self {
+        Self { start, end }
+    }
+
+    pub fn to_lsp<T: DeserializeOwned>(&self) -> Result<T, SourceMapError> {
+        convert(self)
+    }
+
+    pub fn from_lsp<T: Serialize>(range: &T) -> Result<Self, SourceMapError> {
+        convert(range)
+    }
+}
+
+impl From<Position> for LspPosition {
+    fn from(position: Position) -> Self {
+        LspPosition::new(position.line, position.column)
+    }
+}
+
+impl From<LspPosition> for Position {
+    fn from(position: LspPosition) -> Self {
+        Position::new(position.line, position.character)
+    }
+}
+
+impl From<OriginalLocation> for LspPosition {
+    fn from(original: OriginalLocation) -> Self {
+        LspPosition::new(original.original_line, original.original_column)
+    }
+}
+
+impl SourceMap {
+    // Maps a range in the generated file back to its original source, e.g. for diagnostics.
+    // When the end resolves to another source (or before the start), the range is
+    // collapsed to the original start position.
+    pub fn original_range(&mut self, range: LspRange) -> Option<(u32, LspRange)> {
+        let start = self
+            .find_closest_mapping(range.start.line, range.start.character)?
+            .original?;
+        let start_position = LspPosition::from(start);
+        let end_position = self
+            .find_closest_mapping(range.end.line, range.end.character)
+            .and_then(|mapping| mapping.original)
+            .filter(|end| end.source == start.source)
+            .map(LspPosition::from)
+            .filter(|end| *end >= start_position)
+            .unwrap_or(start_position);
+        Some((start.source, LspRange::new(start_position, end_position)))
+    }
+}
+
+#[test]
+fn test_original_range() {
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.ts");
+    let b = map.add_source("b.ts");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(3, 2, a, None)));
+    map.add_mapping(0, 10, Some(OriginalLocation::new(3, 12, a, None)));
+    map.add_mapping(1, 0, Some(OriginalLocation::new(0, 0, b, None)));
+
+    let range = LspRange::new(LspPosition::new(0, 0), LspPosition::new(0, 10));
+    let (source, original) = map.original_range(range).unwrap();
+    assert_eq!(source, a);
+    assert_eq!(
+        original,
+        LspRange::new(LspPosition::new(3, 2), LspPosition::new(3, 12))
+    );
+
+    let range = LspRange::new(LspPosition::new(0, 10), LspPosition::new(1, 0));
+    let (_, original) = map.original_range(range).unwrap();
+    assert_eq!(original.start, original.end);
+
+    let json = serde_json::to_string(&range).unwrap();
+    assert_eq!(
+        json,
+        r#"{"start":{"line":0,"character":10},"end":{"line":1,"character":0}}"#
+    );
+}
+
+#[test]
+fn test_lsp_conversions() {
+    // Declared like lsp_types::Position and lsp_types::Range
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Position {
+        line: u32,
+        character: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Range {
+        start: Position,
+        end: Position,
+    }
+
+    let range = LspRange::new(LspPosition::new(1, 2), LspPosition::new(3, 4));
+    let lsp_range: Range = range.to_lsp().unwrap();
+    assert_eq!(
+        lsp_range,
+        Range {
+            start: Position {
+                line: 1,
+                character: 2
+            },
+            end: Position {
+                line: 3,
+                character: 4
+            },
+        }
+    );
+    assert_eq!(LspRange::from_lsp(&lsp_range).unwrap(), range);
+    let position: Position = range.end.to_lsp().unwrap();
+    assert_eq!(LspPosition::from_lsp(&position).unwrap(), range.end);
+
+    // Not an LSP position
+    assert!(LspPosition::from_lsp(&range).is_err());
+}
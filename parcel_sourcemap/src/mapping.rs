@@ -1,8 +1,14 @@
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
 use napi_derive::napi;
 use rkyv::{Archive, Deserialize, Serialize};
 
 #[napi(object)]
-#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct OriginalLocation {
     pub original_line: u32,
     pub original_column: u32,
@@ -23,8 +29,348 @@ impl OriginalLocation {
 
 #[napi(object)]
 #[derive(Archive, Serialize, Deserialize, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct Mapping {
     pub generated_line: u32,
     pub generated_column: u32,
     pub original: Option<OriginalLocation>,
 }
+
+// What to do with segments that have a name but no source ([column, name]),
+// which the spec does not allow but some tools emit anyway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameWithoutSourcePolicy {
+    #[default]
+    Error,
+    // Keep the mapping, without an original position
+    DropName,
+    // Keep the name, pointing to line 0, column 0 of a placeholder source (`<unknown>`)
+    SyntheticSource,
+}
+
+impl NameWithoutSourcePolicy {
+    // Names as used in the JS bindings
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(NameWithoutSourcePolicy::Error),
+            "dropName" => Some(NameWithoutSourcePolicy::DropName),
+            "syntheticSource" => Some(NameWithoutSourcePolicy::SyntheticSource),
+            _ => None,
+        }
+    }
+}
+
+// What parsing does with the sourceRoot of a map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceRootHandling {
+    // Sources stay as they are, sourceRoot is kept on the map and written back out
+    #[default]
+    Keep,
+    // sourceRoot is prepended to every source, the map is left without one
+    Join,
+}
+
+impl SourceRootHandling {
+    // Names as used in the JS bindings
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(SourceRootHandling::Keep),
+            "join" => Some(SourceRootHandling::Join),
+            _ => None,
+        }
+    }
+}
+
+// How to_json writes the sourceRoot of a map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceRootOutput {
+    // sourceRoot gets written with the sources relative to it
+    #[default]
+    Emit,
+    // sourceRoot is prepended to the sources instead of being written
+    Resolve,
+}
+
+// How add_source treats a source that is already in the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddSourceMode {
+    // Reuse the existing source, content added later replaces its content
+    #[default]
+    DedupeByPath,
+    // Every add creates a new source, even for the same path
+    AlwaysAppend,
+    // Only reuse a source when the content matches as well, for plugins that register the
+    // same path with different content. Without content this behaves like DedupeByPath.
+    // Finding the source is a linear scan like DedupeByPath, not a hash lookup.
+    DedupeByPathAndContent,
+}
+
+impl AddSourceMode {
+    // Names as used in the JS bindings
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dedupeByPath" => Some(AddSourceMode::DedupeByPath),
+            "alwaysAppend" => Some(AddSourceMode::AlwaysAppend),
+            "dedupeByPathAndContent" => Some(AddSourceMode::DedupeByPathAndContent),
+            _ => None,
+        }
+    }
+}
+
+// What to do with multiple mappings on the same generated column
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateMappingPolicy {
+    // Keep all of them, lookups resolve to the one that got added first
+    #[default]
+    KeepAll,
+    KeepFirst,
+    KeepLast,
+}
+
+// Which mapping a lookup on a column without a mapping of its own resolves to, same as the
+// bias of Mozilla's source-map library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LookupBias {
+    // The closest mapping before the column
+    #[default]
+    GreatestLowerBound,
+    // The closest mapping after the column on the same line
+    LeastUpperBound,
+}
+
+impl LookupBias {
+    // Names as used in the JS bindings
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "greatestLowerBound" => Some(LookupBias::GreatestLowerBound),
+            "leastUpperBound" => Some(LookupBias::LeastUpperBound),
+            _ => None,
+        }
+    }
+
+    // For the optional bias argument of the bindings
+    pub fn from_optional_name(name: Option<&str>) -> Result<Self, SourceMapError> {
+        match name {
+            Some(name) => Self::from_name(name).ok_or_else(|| {
+                SourceMapError::new_with_reason(
+                    SourceMapErrorType::InvalidOptions,
+                    "bias must be one of greatestLowerBound, leastUpperBound",
+                )
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+// Zero-based position in the generated file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Position {
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+
+    // Position right after the given text, columns are counted in UTF-16 code units
+    pub fn end_of(text: &str) -> Self {
+        let line = text.matches('\n').count() as u32;
+        let last_line = text.rsplit('\n').next().unwrap_or("");
+        Self::new(line, last_line.encode_utf16().count() as u32)
+    }
+
+    // Where `other` ends up when it is measured from this position instead of the start
+    pub fn then(self, other: Position) -> Self {
+        if other.line == 0 {
+            Self::new(self.line, self.column + other.column)
+        } else {
+            Self::new(self.line + other.line, other.column)
+        }
+    }
+
+    pub fn to_one_based(self) -> OneBasedPosition {
+        OneBasedPosition::new(self.line + 1, self.column + 1)
+    }
+}
+
+// Position as it shows up in stack traces and editors, lines and columns start at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OneBasedPosition {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl OneBasedPosition {
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+
+    // None for a line or column of 0, which can't be one-based
+    pub fn to_zero_based(self) -> Option<Position> {
+        Some(Position::new(
+            self.line.checked_sub(1)?,
+            self.column.checked_sub(1)?,
+        ))
+    }
+}
+
+// Lookups accept either convention, so the caller has to say which one it is using
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookupPosition {
+    ZeroBased(Position),
+    OneBased(OneBasedPosition),
+}
+
+impl LookupPosition {
+    pub fn to_zero_based(self) -> Option<Position> {
+        match self {
+            LookupPosition::ZeroBased(position) => Some(position),
+            LookupPosition::OneBased(position) => position.to_zero_based(),
+        }
+    }
+}
+
+impl From<Position> for LookupPosition {
+    fn from(position: Position) -> Self {
+        LookupPosition::ZeroBased(position)
+    }
+}
+
+impl From<OneBasedPosition> for LookupPosition {
+    fn from(position: OneBasedPosition) -> Self {
+        LookupPosition::OneBased(position)
+    }
+}
+
+// Parallel arrays of all mappings. A mapping without original position has -1 as its source,
+// original line, original column and name, a mapping without name only has -1 as its name.
+// 0 is always a real value, use original() instead of checking the sentinels by hand.
+#[derive(Debug, Default, Clone)]
+pub struct FlatMappings {
+    pub generated_lines: Vec<u32>,
+    pub generated_columns: Vec<u32>,
+    pub sources: Vec<i32>,
+    pub original_lines: Vec<i32>,
+    pub original_columns: Vec<i32>,
+    pub names: Vec<i32>,
+}
+
+impl FlatMappings {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            generated_lines: Vec::with_capacity(capacity),
+            generated_columns: Vec::with_capacity(capacity),
+            sources: Vec::with_capacity(capacity),
+            original_lines: Vec::with_capacity(capacity),
+            original_columns: Vec::with_capacity(capacity),
+            names: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.generated_lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.generated_lines.is_empty()
+    }
+
+    // The original position of the mapping at index, None when it has none. Mappings that mix
+    // -1 with values, use other negative values or have a name without original position are
+    // rejected instead of being read as unmapped.
+    pub fn original(&self, index: usize) -> Result<Option<OriginalLocation>, SourceMapError> {
+        let value = |values: &[i32]| {
+            values
+                .get(index)
+                .copied()
+                .ok_or_else(|| SourceMapError::new(SourceMapErrorType::FlatArraysLengthMismatch))
+        };
+        let source = value(&self.sources)?;
+        let original_line = value(&self.original_lines)?;
+        let original_column = value(&self.original_columns)?;
+        let name = value(&self.names)?;
+
+        let name = match name {
+            -1 => None,
+            name if name >= 0 => Some(name as u32),
+            _ => {
+                return Err(SourceMapError::new_with_reason(
+                    SourceMapErrorType::InvalidFlatMapping,
+                    "name",
+                ))
+            }
+        };
+        match (source, original_line, original_column) {
+            (-1, -1, -1) => match name {
+                Some(_) => Err(SourceMapError::new(SourceMapErrorType::InvalidFlatMapping)),
+                None => Ok(None),
+            },
+            (source, original_line, original_column)
+                if source >= 0 && original_line >= 0 && original_column >= 0 =>
+            {
+                Ok(Some(OriginalLocation::new(
+                    original_line as u32,
+                    original_column as u32,
+                    source as u32,
+                    name,
+                )))
+            }
+            _ => Err(SourceMapError::new(SourceMapErrorType::InvalidFlatMapping)),
+        }
+    }
+
+    pub fn mapping(&self, index: usize) -> Result<Mapping, SourceMapError> {
+        let generated = self
+            .generated_lines
+            .get(index)
+            .zip(self.generated_columns.get(index))
+            .ok_or_else(|| SourceMapError::new(SourceMapErrorType::FlatArraysLengthMismatch))?;
+        Ok(Mapping {
+            generated_line: *generated.0,
+            generated_column: *generated.1,
+            original: self.original(index)?,
+        })
+    }
+
+    // The original values have to fit in an i32, nothing gets pushed if they don't
+    pub fn push(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        original: Option<OriginalLocation>,
+    ) -> Result<(), SourceMapError> {
+        let to_i32 = |value: u32, field: &str| {
+            i32::try_from(value).map_err(|_| {
+                SourceMapError::new_with_reason(
+                    SourceMapErrorType::UnexpectedlyBigNumber,
+                    &format!("{} does not fit in the flat arrays", field),
+                )
+            })
+        };
+        let (source, original_line, original_column, name) = match original {
+            Some(original) => (
+                to_i32(original.source, "source")?,
+                to_i32(original.original_line, "original line")?,
+                to_i32(original.original_column, "original column")?,
+                match original.name {
+                    Some(name) => to_i32(name, "name")?,
+                    None => -1,
+                },
+            ),
+            None => (-1, -1, -1, -1),
+        };
+        self.generated_lines.push(generated_line);
+        self.generated_columns.push(generated_column);
+        self.sources.push(source);
+        self.original_lines.push(original_line);
+        self.original_columns.push(original_column);
+        self.names.push(name);
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
-use crate::mapping::OriginalLocation;
+use crate::line_mappings::LineMappings;
+use crate::mapping::{DuplicateMappingPolicy, OriginalLocation};
 use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use rkyv::de::deserializers::AllocDeserializer;
 use rkyv::{Archive, Deserialize, Serialize};
+use std::borrow::Cow;
 
 #[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy, Default)]
 pub struct LineMapping {
@@ -8,25 +11,88 @@ pub struct LineMapping {
     pub original: Option<OriginalLocation>,
 }
 
+// Implemented by owned and archived mappings, so lookups work on both
+pub(crate) trait ColumnMapping {
+    fn generated_column(&self) -> u32;
+}
+
+impl ColumnMapping for LineMapping {
+    #[inline]
+    fn generated_column(&self) -> u32 {
+        self.generated_column
+    }
+}
+
+impl ColumnMapping for ArchivedLineMapping {
+    #[inline]
+    fn generated_column(&self) -> u32 {
+        self.generated_column
+    }
+}
+
+// Index of the first mapping at or after generated_column
+#[inline]
+fn lower_bound<T: ColumnMapping>(mappings: &[T], generated_column: u32) -> usize {
+    mappings.partition_point(|m| m.generated_column() < generated_column)
+}
+
+pub(crate) enum Closest {
+    At(usize),
+    // No mapping before the column, the first mapping of the line is used at column 0
+    First,
+}
+
+// The mappings have to be sorted
+#[inline]
+pub(crate) fn find_closest<T: ColumnMapping>(
+    mappings: &[T],
+    generated_column: u32,
+    may_have_duplicates: bool,
+) -> Option<Closest> {
+    if mappings.is_empty() {
+        return None;
+    }
+
+    // Always resolve to the first mapping of a column, so duplicates give stable results
+    let mut index = lower_bound(mappings, generated_column);
+    if index == mappings.len() || mappings[index].generated_column() != generated_column {
+        if index == 0 || index == mappings.len() {
+            return Some(Closest::First);
+        }
+
+        index -= 1;
+        // Only lines with duplicates need a second search for the first mapping of a column
+        if may_have_duplicates {
+            index = lower_bound(mappings, mappings[index].generated_column());
+        }
+    }
+    Some(Closest::At(index))
+}
+
 #[derive(Archive, Serialize, Deserialize, Debug, Default, Clone)]
 pub struct MappingLine {
-    pub mappings: Vec<LineMapping>,
+    pub mappings: LineMappings,
     pub last_column: u32,
     pub is_sorted: bool,
+    pub may_have_duplicates: bool,
 }
 
 impl MappingLine {
     pub fn new() -> Self {
         Self {
-            mappings: Vec::new(),
+            mappings: LineMappings::new(),
             last_column: 0,
             is_sorted: true,
+            may_have_duplicates: false,
         }
     }
 
     pub fn add_mapping(&mut self, generated_column: u32, original: Option<OriginalLocation>) {
-        if self.is_sorted && self.last_column > generated_column {
-            self.is_sorted = false;
+        if !self.mappings.is_empty() && self.last_column >= generated_column {
+            self.may_have_duplicates = true;
+            if self.last_column > generated_column {
+                self.is_sorted = false;
+            }
         }
 
         self.mappings.push(LineMapping {
@@ -39,36 +105,133 @@ impl MappingLine {
 
     pub fn ensure_sorted(&mut self) {
         if !self.is_sorted {
-            self.mappings
-                .sort_by(|a, b| a.generated_column.cmp(&b.generated_column));
+            // Stable sort, so duplicates keep their insertion order
+            self.mappings.sort_by_key(|m| m.generated_column);
             self.is_sorted = true
         }
     }
 
+    // Whether ensure_sorted_with would change anything
+    pub fn needs_sorting(&self, policy: DuplicateMappingPolicy) -> bool {
+        !self.is_sorted || (self.may_have_duplicates && policy != DuplicateMappingPolicy::KeepAll)
+    }
+
+    pub fn ensure_sorted_with(&mut self, policy: DuplicateMappingPolicy) {
+        self.ensure_sorted();
+        if !self.may_have_duplicates {
+            return;
+        }
+
+        match policy {
+            DuplicateMappingPolicy::KeepAll => return,
+            DuplicateMappingPolicy::KeepFirst => {
+                self.mappings.dedup_by_key(|m| m.generated_column);
+            }
+            DuplicateMappingPolicy::KeepLast => {
+                self.mappings.dedup_by(|current, previous| {
+                    if current.generated_column == previous.generated_column {
+                        *previous = *current;
+                        true
+                    } else {
+                        false
+                    }
+                });
+            }
+        }
+        self.may_have_duplicates = false;
+    }
+
+    // The mappings as ensure_sorted_with(policy) leaves them, for readers that can't sort the
+    // line itself. Only lines that need it get copied.
+    pub fn resolved_mappings(&self, policy: DuplicateMappingPolicy) -> Cow<'_, [LineMapping]> {
+        if !self.needs_sorting(policy) {
+            return Cow::Borrowed(self.mappings.as_slice());
+        }
+        let mut line = self.clone();
+        line.ensure_sorted_with(policy);
+        Cow::Owned(line.mappings.into_vec())
+    }
+
     pub fn find_closest_mapping(&mut self, generated_column: u32) -> Option<LineMapping> {
-        if self.mappings.is_empty() {
-            return None;
+        self.ensure_sorted();
+        match find_closest(&self.mappings, generated_column, self.may_have_duplicates)? {
+            Closest::At(index) => Some(self.mappings[index]),
+            Closest::First => Some(LineMapping {
+                generated_column: 0,
+                original: self.mappings[0].original,
+            }),
         }
+    }
 
+    // find_closest_mapping for many columns at once, the columns have to be sorted ascending.
+    // The line is walked forward once instead of searching it again for every column,
+    // `found` gets called with the id that was passed along with each column.
+    pub fn find_closest_mappings_sorted<F>(&mut self, columns: &[(u32, usize)], mut found: F)
+    where
+        F: FnMut(usize, Option<LineMapping>),
+    {
         self.ensure_sorted();
-        let index = match self
-            .mappings
-            .binary_search_by(|m| m.generated_column.cmp(&generated_column))
-        {
-            Ok(index) => index,
-            Err(index) => {
-                if index == 0 || index == self.mappings.len() {
-                    return Some(LineMapping {
-                        generated_column: 0,
-                        original: self.mappings[0].original,
-                    });
+        let mappings = &self.mappings;
+        let mut index = 0;
+        // Start of the group of mappings on the column before `index`
+        let mut previous_start = 0;
+        for (generated_column, id) in columns.iter().copied() {
+            while index < mappings.len() && mappings[index].generated_column < generated_column {
+                if index == 0
+                    || mappings[index].generated_column != mappings[index - 1].generated_column
+                {
+                    previous_start = index;
                 }
-
-                index - 1
+                index += 1;
             }
-        };
 
-        Some(self.mappings[index])
+            let mapping = if mappings.is_empty() {
+                None
+            } else if index < mappings.len() && mappings[index].generated_column == generated_column
+            {
+                Some(mappings[index])
+            } else if index == 0 || index == mappings.len() {
+                Some(LineMapping {
+                    generated_column: 0,
+                    original: mappings[0].original,
+                })
+            } else {
+                Some(mappings[previous_start])
+            };
+            found(id, mapping);
+        }
+    }
+
+    // The first mapping at or after generated_column, None if there is none on this line
+    pub fn find_least_upper_bound(&mut self, generated_column: u32) -> Option<LineMapping> {
+        self.ensure_sorted();
+        let index = lower_bound(&self.mappings, generated_column);
+        self.mappings.get(index).copied()
+    }
+
+    // Lines that grew past INLINE_MAPPINGS keep their allocation when they get shorter, this
+    // moves those back inline and releases the spare capacity of the others
+    pub fn shrink_to_fit(&mut self) {
+        self.mappings.shrink_to_fit();
+    }
+
+    // Moves all mappings at or after generated_column into a new line, rebased to column 0
+    pub fn split_off(&mut self, generated_column: u32) -> MappingLine {
+        self.ensure_sorted();
+        let index = self
+            .mappings
+            .partition_point(|m| m.generated_column < generated_column);
+
+        let mut new_line = MappingLine::new();
+        new_line.may_have_duplicates = self.may_have_duplicates;
+        for mapping in self.mappings.split_off(index) {
+            new_line.add_mapping(
+                mapping.generated_column - generated_column,
+                mapping.original,
+            );
+        }
+        self.last_column = self.mappings.last().map_or(0, |m| m.generated_column);
+        new_line
     }
 
     pub fn offset_columns(
@@ -78,7 +241,7 @@ impl MappingLine {
     ) -> Result<(), SourceMapError> {
         let (start_column, overflowed) =
             (generated_column as i64).overflowing_add(generated_column_offset);
-        if overflowed || start_column > (u32::MAX as i64) {
+        if overflowed || start_column < 0 {
             return Err(SourceMapError::new_with_reason(
                 SourceMapErrorType::UnexpectedNegativeNumber,
                 "column + column_offset cannot be negative",
@@ -86,6 +249,17 @@ impl MappingLine {
         }
 
         self.ensure_sorted();
+        // Checked before anything moves, so a failed shift leaves the line as it was
+        let last_column = self.mappings.last().map_or(generated_column, |m| {
+            m.generated_column.max(generated_column)
+        });
+        if last_column as i64 + generated_column_offset > u32::MAX as i64 {
+            return Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::UnexpectedlyBigNumber,
+                "column + column_offset does not fit in a column",
+            ));
+        }
+
         let mut index = match self
             .mappings
             .binary_search_by(|m| m.generated_column.cmp(&generated_column))
@@ -104,11 +278,11 @@ impl MappingLine {
                 Err(index) => index,
             };
 
-            self.mappings.drain(start_index..index);
+            self.mappings.remove_range(start_index..index);
             index = start_index;
         }
 
-        let abs_offset = generated_column_offset.abs() as u32;
+        let abs_offset = generated_column_offset.unsigned_abs() as u32;
         for i in index..self.mappings.len() {
             let mapping = &mut self.mappings[i];
             mapping.generated_column = if generated_column_offset < 0 {
@@ -117,7 +291,133 @@ impl MappingLine {
                 mapping.generated_column + abs_offset
             };
         }
+        // add_mapping compares against it to tell whether the line is still sorted
+        self.last_column = self.mappings.last().map_or(0, |m| m.generated_column);
 
         Ok(())
     }
 }
+
+impl ArchivedMappingLine {
+    // Same result as MappingLine::find_closest_mapping after ensure_sorted_with(policy)
+    pub fn find_closest_mapping(
+        &self,
+        generated_column: u32,
+        policy: DuplicateMappingPolicy,
+    ) -> Option<LineMapping> {
+        if !self.is_sorted
+            || (self.may_have_duplicates && policy != DuplicateMappingPolicy::KeepAll)
+        {
+            // Only lines that still need sorting get copied
+            let mut line: MappingLine = self.deserialize(&mut AllocDeserializer).ok()?;
+            line.ensure_sorted_with(policy);
+            return line.find_closest_mapping(generated_column);
+        }
+        self.find_sorted_closest_mapping(generated_column)
+    }
+
+    // find_closest_mapping for lines that are known to be sorted and resolved already
+    pub fn find_sorted_closest_mapping(&self, generated_column: u32) -> Option<LineMapping> {
+        let mappings: &[ArchivedLineMapping] = &self.mappings;
+        match find_closest(mappings, generated_column, self.may_have_duplicates)? {
+            Closest::At(index) => mappings[index].deserialize(&mut AllocDeserializer).ok(),
+            Closest::First => {
+                let first: LineMapping = mappings[0].deserialize(&mut AllocDeserializer).ok()?;
+                Some(LineMapping {
+                    generated_column: 0,
+                    original: first.original,
+                })
+            }
+        }
+    }
+}
+
+#[test]
+fn test_lower_bound() {
+    let mut line = MappingLine::new();
+    for column in [0, 4, 4, 9, 12] {
+        line.add_mapping(column, None);
+    }
+    for column in 0..14 {
+        assert_eq!(
+            lower_bound(&line.mappings, column),
+            line.mappings
+                .partition_point(|m| m.generated_column < column),
+            "column {}",
+            column
+        );
+    }
+    assert_eq!(lower_bound::<LineMapping>(&[], 3), 0);
+    assert_eq!(line.find_closest_mapping(6).unwrap().generated_column, 4);
+    assert_eq!(line.find_least_upper_bound(6).unwrap().generated_column, 9);
+    assert_eq!(line.find_least_upper_bound(9).unwrap().generated_column, 9);
+    assert!(line.find_least_upper_bound(13).is_none());
+}
+
+#[test]
+fn test_offset_columns() {
+    let columns = |line: &MappingLine| -> Vec<u32> {
+        line.mappings.iter().map(|m| m.generated_column).collect()
+    };
+    let mut line = MappingLine::new();
+    for column in [0, 4, 8, 12] {
+        line.add_mapping(column, None);
+    }
+
+    // A wrapper injected in front of column 4
+    line.offset_columns(4, 10).unwrap();
+    assert_eq!(columns(&line), vec![0, 14, 18, 22]);
+    // Moving left drops the mappings that get overwritten
+    line.offset_columns(18, -5).unwrap();
+    assert_eq!(columns(&line), vec![0, 13, 17]);
+
+    assert!(matches!(
+        line.offset_columns(2, -3).unwrap_err().error_type,
+        SourceMapErrorType::UnexpectedNegativeNumber
+    ));
+    assert!(matches!(
+        line.offset_columns(0, u32::MAX as i64)
+            .unwrap_err()
+            .error_type,
+        SourceMapErrorType::UnexpectedlyBigNumber
+    ));
+    assert_eq!(columns(&line), vec![0, 13, 17]);
+
+    // Mappings added after the shift still end up sorted
+    let mut line = MappingLine::new();
+    for column in [0, 4, 8] {
+        line.add_mapping(column, Some(OriginalLocation::new(0, column, 0, None)));
+    }
+    line.offset_columns(4, 10).unwrap();
+    assert_eq!(line.last_column, 18);
+    line.add_mapping(10, Some(OriginalLocation::new(1, 0, 0, None)));
+    assert!(!line.is_sorted);
+    let mapping = line.find_closest_mapping(12).unwrap();
+    assert_eq!(mapping.generated_column, 10);
+    assert_eq!(mapping.original.unwrap().original_line, 1);
+    assert_eq!(columns(&line), vec![0, 10, 14, 18]);
+}
+
+#[test]
+fn test_find_closest_mappings_sorted() {
+    let mut line = MappingLine::new();
+    for (column, original_line) in [(4, 0), (4, 1), (9, 2), (12, 3), (0, 4)] {
+        line.add_mapping(
+            column,
+            Some(OriginalLocation::new(original_line, 0, 0, None)),
+        );
+    }
+
+    let columns: Vec<(u32, usize)> = (0..15).map(|column| (column, column as usize)).collect();
+    let mut results = vec![None; columns.len()];
+    line.find_closest_mappings_sorted(&columns, |id, mapping| results[id] = mapping);
+    for (column, id) in columns {
+        let expected = line.find_closest_mapping(column);
+        assert_eq!(
+            format!("{:?}", results[id]),
+            format!("{:?}", expected),
+            "column {}",
+            column
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use crate::mapping_line::{ArchivedMappingLine, MappingLine};
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use rkyv::{Archive, Deserialize, Serialize};
+use std::ops::Range;
+
+// All generated lines of a map. Only lines that got mappings at some point are stored, so
+// banners, large line offsets and bundles that are one minified line don't allocate a
+// MappingLine for every empty line.
+#[derive(Archive, Serialize, Deserialize, Debug, Default, Clone)]
+pub struct MappingLines {
+    // Ascending, one for every stored line
+    line_numbers: Vec<u32>,
+    lines: Vec<MappingLine>,
+    // Number of lines including the empty lines that aren't stored
+    len: u32,
+}
+
+pub(crate) fn too_many_lines() -> SourceMapError {
+    SourceMapError::new_with_reason(
+        SourceMapErrorType::UnexpectedlyBigNumber,
+        "line count does not fit in a u32",
+    )
+}
+
+impl MappingLines {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Makes sure the map has at least line_count lines
+    pub fn ensure_len(&mut self, line_count: usize) -> Result<(), SourceMapError> {
+        let line_count = u32::try_from(line_count).map_err(|_| too_many_lines())?;
+        self.len = self.len.max(line_count);
+        Ok(())
+    }
+
+    // Number of lines that are stored, used for capacity hints
+    pub fn stored_len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn mappings_len(&self) -> usize {
+        self.lines.iter().map(|l| l.mappings.len()).sum()
+    }
+
+    fn position(&self, line: usize) -> Result<usize, usize> {
+        match u32::try_from(line) {
+            Ok(line) => self.line_numbers.binary_search(&line),
+            Err(_) => Err(self.lines.len()),
+        }
+    }
+
+    // None for lines without mappings
+    pub fn get(&self, line: usize) -> Option<&MappingLine> {
+        self.position(line).ok().map(|i| &self.lines[i])
+    }
+
+    pub fn get_mut(&mut self, line: usize) -> Option<&mut MappingLine> {
+        match self.position(line) {
+            Ok(i) => Some(&mut self.lines[i]),
+            Err(_) => None,
+        }
+    }
+
+    // Stores the line if it isn't yet, and extends the map up to it
+    pub fn get_or_insert(&mut self, line: usize) -> Result<&mut MappingLine, SourceMapError> {
+        self.ensure_len(line.checked_add(1).ok_or_else(too_many_lines)?)?;
+        // Lines are mostly added in order
+        let i = match self.line_numbers.last() {
+            Some(last) if (*last as usize) < line => Err(self.lines.len()),
+            None => Err(0),
+            _ => self.position(line),
+        };
+        let i = match i {
+            Ok(i) => i,
+            Err(i) => {
+                self.line_numbers.insert(i, line as u32);
+                self.lines.insert(i, MappingLine::new());
+                i
+            }
+        };
+        Ok(&mut self.lines[i])
+    }
+
+    // Bytes allocated for the lines and their mappings
+    pub fn heap_size(&self) -> usize {
+        self.line_numbers.capacity() * std::mem::size_of::<u32>()
+            + self.lines.capacity() * std::mem::size_of::<MappingLine>()
+            + self
+                .lines
+                .iter()
+                .map(|line| line.mappings.heap_size())
+                .sum::<usize>()
+    }
+
+    // Room for additional stored lines
+    pub fn reserve(&mut self, additional: usize) {
+        self.line_numbers.reserve(additional);
+        self.lines.reserve(additional);
+    }
+
+    pub fn replace(
+        &mut self,
+        line: usize,
+        mapping_line: MappingLine,
+    ) -> Result<(), SourceMapError> {
+        *self.get_or_insert(line)? = mapping_line;
+        Ok(())
+    }
+
+    // Stored lines with their line number, in order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (usize, &MappingLine)> {
+        self.line_numbers
+            .iter()
+            .map(|l| *l as usize)
+            .zip(self.lines.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (usize, &mut MappingLine)> {
+        self.line_numbers
+            .iter()
+            .map(|l| *l as usize)
+            .zip(self.lines.iter_mut())
+    }
+
+    pub fn into_lines(self) -> impl Iterator<Item = (usize, MappingLine)> {
+        self.line_numbers
+            .into_iter()
+            .map(|l| l as usize)
+            .zip(self.lines)
+    }
+
+    pub fn lines_mut(&mut self) -> impl Iterator<Item = &mut MappingLine> {
+        self.lines.iter_mut()
+    }
+
+    // Stored lines from the given line on
+    pub fn range_from(&self, line: usize) -> impl Iterator<Item = (usize, &MappingLine)> {
+        let start = match self.position(line) {
+            Ok(i) | Err(i) => i,
+        };
+        self.line_numbers[start..]
+            .iter()
+            .map(|l| *l as usize)
+            .zip(self.lines[start..].iter())
+    }
+
+    // Empties the lines in the range and returns the ones that had been stored
+    pub fn clear_lines(&mut self, lines: Range<usize>) -> Vec<usize> {
+        let start_index = match self.position(lines.start) {
+            Ok(i) | Err(i) => i,
+        };
+        let end_index = match self.position(lines.end) {
+            Ok(i) | Err(i) => i,
+        };
+        self.lines.drain(start_index..end_index);
+        self.line_numbers
+            .drain(start_index..end_index)
+            .map(|l| l as usize)
+            .collect()
+    }
+
+    // Inserts count empty lines at line, nothing happens past the end of the map
+    pub fn insert_lines(&mut self, line: usize, count: usize) {
+        if line > self.len() {
+            return;
+        }
+        let start = match self.position(line) {
+            Ok(i) | Err(i) => i,
+        };
+        // Callers check that the shifted lines still fit in a u32 (see SourceMap::offset_lines)
+        for line_number in self.line_numbers[start..].iter_mut() {
+            *line_number += count as u32;
+        }
+        self.len += count as u32;
+    }
+
+    pub fn remove_lines(&mut self, lines: Range<usize>) {
+        let end = lines.end.min(self.len());
+        if lines.start >= end {
+            return;
+        }
+        let start_index = match self.position(lines.start) {
+            Ok(i) | Err(i) => i,
+        };
+        let end_index = match self.position(end) {
+            Ok(i) | Err(i) => i,
+        };
+        self.line_numbers.drain(start_index..end_index);
+        self.lines.drain(start_index..end_index);
+        let count = (end - lines.start) as u32;
+        for line_number in self.line_numbers[start_index..].iter_mut() {
+            *line_number -= count;
+        }
+        self.len -= count;
+    }
+
+    // Drops the empty lines at the end
+    pub fn trim_end(&mut self) -> Result<(), SourceMapError> {
+        while let Some(line) = self.lines.last() {
+            if !line.mappings.is_empty() {
+                break;
+            }
+            self.lines.pop();
+            self.line_numbers.pop();
+        }
+        self.len = match self.line_numbers.last() {
+            Some(l) => l.checked_add(1).ok_or_else(too_many_lines)?,
+            None => 0,
+        };
+        Ok(())
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.shrink_to_fit();
+        }
+        self.lines.shrink_to_fit();
+        self.line_numbers.shrink_to_fit();
+    }
+}
+
+impl ArchivedMappingLines {
+    // Where the fields are, so buffer_check can validate them before they are read
+    pub(crate) const LINE_NUMBERS_OFFSET: usize =
+        std::mem::offset_of!(ArchivedMappingLines, line_numbers);
+    pub(crate) const LINES_OFFSET: usize = std::mem::offset_of!(ArchivedMappingLines, lines);
+    pub(crate) const LEN_OFFSET: usize = std::mem::offset_of!(ArchivedMappingLines, len);
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn get(&self, line: usize) -> Option<&ArchivedMappingLine> {
+        let line = u32::try_from(line).ok()?;
+        let index = self.line_numbers.binary_search(&line).ok()?;
+        Some(&self.lines[index])
+    }
+}
+
+#[test]
+fn test_sparse_lines() {
+    let mut lines = MappingLines::default();
+    lines.get_or_insert(1_000_000).unwrap().add_mapping(0, None);
+    assert_eq!(lines.len(), 1_000_001);
+    assert_eq!(lines.stored_len(), 1);
+    assert!(lines.get(5).is_none());
+
+    lines.get_or_insert(5).unwrap().add_mapping(2, None);
+    lines.insert_lines(0, 10);
+    assert_eq!(
+        lines.iter().map(|(l, _)| l).collect::<Vec<usize>>(),
+        vec![15, 1_000_010]
+    );
+
+    lines.remove_lines(10..20);
+    assert_eq!(
+        lines.iter().map(|(l, _)| l).collect::<Vec<usize>>(),
+        vec![1_000_000]
+    );
+    assert_eq!(lines.len(), 1_000_001);
+
+    lines.get_or_insert(2_000_000).unwrap();
+    lines.trim_end().unwrap();
+    assert_eq!(lines.len(), 1_000_001);
+}
+
+#[test]
+fn test_line_count_overflow() {
+    let mut lines = MappingLines::default();
+    let last = u32::MAX as usize - 1;
+    lines.get_or_insert(last).unwrap().add_mapping(0, None);
+    assert_eq!(lines.len(), u32::MAX as usize);
+    lines.trim_end().unwrap();
+    assert_eq!(lines.len(), u32::MAX as usize);
+
+    assert!(lines.get_or_insert(last + 1).is_err());
+    assert!(lines.ensure_len(u32::MAX as usize + 1).is_err());
+    assert!(lines.get(last + 1 + (1 << 32)).is_none());
+    assert_eq!(lines.len(), u32::MAX as usize);
+    assert_eq!(lines.stored_len(), 1);
+}
@@ -0,0 +1,353 @@
+use crate::mapping_line::LineMapping;
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug)]
+struct BudgetState {
+    limit: AtomicUsize,
+    used: AtomicUsize,
+}
+
+// Limit on the bytes held by all live maps that share it, e.g. the maps of one tenant of a
+// long running daemon. Maps created after set_global get the global one, a map can also be
+// given its own using SourceMap::set_memory_budget.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget(Arc<BudgetState>);
+
+// Process wide, see MemoryBudget::set_global
+static GLOBAL_BUDGET: RwLock<Option<MemoryBudget>> = RwLock::new(None);
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        Self(Arc::new(BudgetState {
+            limit: AtomicUsize::new(limit),
+            used: AtomicUsize::new(0),
+        }))
+    }
+
+    pub fn limit(&self) -> usize {
+        self.0.limit.load(Ordering::Relaxed)
+    }
+
+    // Lowering the limit doesn't free anything, maps over it fail the next time they grow
+    pub fn set_limit(&self, limit: usize) {
+        self.0.limit.store(limit, Ordering::Relaxed);
+    }
+
+    // Bytes counted for the maps that are still alive
+    pub fn used(&self) -> usize {
+        self.0.used.load(Ordering::Relaxed)
+    }
+
+    pub fn global() -> Option<MemoryBudget> {
+        GLOBAL_BUDGET.read().ok()?.clone()
+    }
+
+    // Existing maps keep the budget they had
+    pub fn set_global(budget: Option<MemoryBudget>) {
+        if let Ok(mut global) = GLOBAL_BUDGET.write() {
+            *global = budget;
+        }
+    }
+
+    fn exceeded(&self, needed: usize) -> SourceMapError {
+        SourceMapError::new_with_reason(
+            SourceMapErrorType::MemoryBudgetExceeded,
+            &format!(
+                "{} more bytes needed, {} in use, the limit is {}",
+                needed,
+                self.used(),
+                self.limit()
+            ),
+        )
+    }
+
+    fn check(&self, bytes: usize) -> Result<(), SourceMapError> {
+        match self.used().checked_add(bytes) {
+            Some(used) if used <= self.limit() => Ok(()),
+            _ => Err(self.exceeded(bytes)),
+        }
+    }
+
+    // Counts bytes against the budget, unless that would take it over the limit
+    fn try_reserve(&self, bytes: usize) -> Result<(), SourceMapError> {
+        let limit = self.limit();
+        self.0
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|used| *used <= limit)
+            })
+            .map(|_| ())
+            .map_err(|_| self.exceeded(bytes))
+    }
+}
+
+// The bytes a map counts against its budget, released when the map gets dropped
+#[derive(Debug)]
+pub(crate) struct BudgetCharge {
+    budget: MemoryBudget,
+    bytes: usize,
+}
+
+impl BudgetCharge {
+    pub fn new(budget: MemoryBudget) -> Self {
+        Self { budget, bytes: 0 }
+    }
+
+    fn reserve(&mut self, bytes: usize) -> Result<(), SourceMapError> {
+        self.budget.try_reserve(bytes)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    fn add(&mut self, bytes: usize) {
+        self.set_bytes(self.bytes + bytes);
+    }
+
+    fn set_bytes(&mut self, bytes: usize) {
+        let used = &self.budget.0.used;
+        if bytes > self.bytes {
+            used.fetch_add(bytes - self.bytes, Ordering::Relaxed);
+        } else {
+            used.fetch_sub(self.bytes - bytes, Ordering::Relaxed);
+        }
+        self.bytes = bytes;
+    }
+}
+
+// A clone of a map holds its own copy of everything
+impl Clone for BudgetCharge {
+    fn clone(&self) -> Self {
+        let mut charge = BudgetCharge::new(self.budget.clone());
+        charge.set_bytes(self.bytes);
+        charge
+    }
+}
+
+impl Drop for BudgetCharge {
+    fn drop(&mut self) {
+        self.set_bytes(0);
+    }
+}
+
+impl SourceMap {
+    // Approximate bytes allocated by the map: strings, mappings and caches
+    pub fn memory_size(&self) -> usize {
+        let strings = |strings: &Vec<String>| {
+            strings.capacity() * size_of::<String>()
+                + strings.iter().map(|s| s.capacity()).sum::<usize>()
+        };
+        size_of::<SourceMap>()
+            + strings(&self.inner.sources)
+            + strings(&self.inner.sources_content)
+            + strings(&self.inner.names)
+            + self.inner.mapping_lines.heap_size()
+            + self
+                .reverse_index
+                .as_ref()
+                .map_or(0, |index| index.heap_size())
+            + self
+                .lookup_index
+                .as_ref()
+                .map_or(0, |index| index.heap_size())
+            + self.inner.source_hashes.capacity() * size_of::<Option<u64>>()
+            + self.raw_input.as_ref().map_or(0, |input| input.capacity())
+            + self.vlq_cache.as_ref().map_or(0, |cache| cache.heap_size())
+            + self.compressed_content_heap_size()
+    }
+
+    #[cfg(feature = "compressed_sources_content")]
+    fn compressed_content_heap_size(&self) -> usize {
+        self.compressed_sources_content()
+            .map_or(0, |contents| contents.heap_size())
+    }
+
+    #[cfg(not(feature = "compressed_sources_content"))]
+    fn compressed_content_heap_size(&self) -> usize {
+        0
+    }
+
+    // Counts the map against budget instead of the one it had (by default the global one at
+    // the time it was created). Parsing, adding maps, mappings and content, composing and
+    // from_buffer fail with MemoryBudgetExceeded when what they add doesn't fit in what is
+    // left of the limit, before the map is changed.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        let bytes = self.memory_size();
+        self.memory_budget = budget.map(|budget| {
+            let mut charge = BudgetCharge::new(budget);
+            charge.set_bytes(bytes);
+            charge
+        });
+    }
+
+    pub fn get_memory_budget(&self) -> Option<&MemoryBudget> {
+        self.memory_budget.as_ref().map(|charge| &charge.budget)
+    }
+
+    // Counts count mappings that are about to be added against the budget, add_mapping itself
+    // isn't checked (see try_add_mapping)
+    pub fn reserve_mapping_memory(&mut self, count: usize) -> Result<(), SourceMapError> {
+        self.reserve_memory(mappings_size(count))
+    }
+
+    // Counts the whole map, for maps that were just built: they get dropped along with the error
+    pub(crate) fn check_memory_budget(&mut self) -> Result<(), SourceMapError> {
+        if self.memory_budget.is_none() {
+            return Ok(());
+        }
+        let bytes = self.memory_size();
+        match &mut self.memory_budget {
+            Some(charge) => {
+                charge.set_bytes(0);
+                charge.reserve(bytes)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Checks and counts bytes the map is about to grow by
+    pub(crate) fn reserve_memory(&mut self, bytes: usize) -> Result<(), SourceMapError> {
+        match &mut self.memory_budget {
+            Some(charge) => charge.reserve(bytes),
+            None => Ok(()),
+        }
+    }
+
+    // Checks that bytes fit without counting them yet, for operations that count what they
+    // add in steps (e.g. set_source_content and then the mappings)
+    pub(crate) fn ensure_memory(&self, bytes: usize) -> Result<(), SourceMapError> {
+        match &self.memory_budget {
+            Some(charge) => charge.budget.check(bytes),
+            None => Ok(()),
+        }
+    }
+
+    // Counts bytes that were checked by ensure_memory
+    pub(crate) fn charge_memory(&mut self, bytes: usize) {
+        if let Some(charge) = &mut self.memory_budget {
+            charge.add(bytes);
+        }
+    }
+
+    pub(crate) fn release_memory(&mut self, bytes: usize) {
+        if let Some(charge) = &mut self.memory_budget {
+            charge.set_bytes(charge.bytes.saturating_sub(bytes));
+        }
+    }
+
+    // Bytes the sources, names and mappings of this map take up once added to another map,
+    // sources content is counted separately (see content_memory_size)
+    pub(crate) fn added_memory_size(&self) -> usize {
+        strings_size(&self.inner.sources)
+            + strings_size(&self.inner.names)
+            + mappings_size(
+                self.inner
+                    .mapping_lines
+                    .iter()
+                    .map(|(_, line)| line.mappings.len())
+                    .sum(),
+            )
+    }
+
+    pub(crate) fn content_memory_size(&self) -> usize {
+        #[cfg(feature = "compressed_sources_content")]
+        if let Some(contents) = self.compressed_sources_content() {
+            return contents.uncompressed_size();
+        }
+        self.inner.sources_content.iter().map(|c| c.len()).sum()
+    }
+}
+
+pub(crate) fn mappings_size(count: usize) -> usize {
+    count * size_of::<LineMapping>()
+}
+
+pub(crate) fn strings_size<S: AsRef<str>>(strings: &[S]) -> usize {
+    strings
+        .iter()
+        .map(|s| size_of::<String>() + s.as_ref().len())
+        .sum()
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_memory_budget() {
+    use crate::json::ParseOptions;
+
+    let budget = MemoryBudget::new(1 << 20);
+    let input = r#"{"sources": ["a.js"], "sourcesContent": ["let a;"], "mappings": "AAAA;AACA"}"#;
+    let mut map = SourceMap::from_json("/", input).unwrap();
+    map.set_memory_budget(Some(budget.clone()));
+    let size = map.memory_size();
+    assert_eq!(budget.used(), size);
+
+    let copy = map.clone();
+    assert_eq!(budget.used(), 2 * size);
+    drop(copy);
+    assert_eq!(budget.used(), size);
+
+    budget.set_limit(size + 100);
+    let error = map.set_source_content(0, &"x".repeat(1000)).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::MemoryBudgetExceeded
+    ));
+    // Nothing changed
+    assert_eq!(map.get_source_content(0).unwrap(), "let a;");
+    assert_eq!(budget.used(), size);
+
+    let mut other = SourceMap::from_json("/", input).unwrap();
+    other.set_source_content(0, &"x".repeat(1000)).unwrap();
+    assert!(map.add_sourcemap(&mut other.clone(), 2).is_err());
+    let mappings = "AAAA,".repeat(100);
+    assert!(map
+        .add_vlq_map(mappings.as_bytes(), vec!["b.js"], vec![], vec![], 0, 0)
+        .is_err());
+    assert!(map.reserve_mapping_memory(100).is_err());
+    assert!(map.try_add_mapping(0, 0, None).is_ok());
+    assert_eq!(map.get_sources().len(), 1);
+    assert_eq!(map.get_mappings().len(), 3);
+    assert!(budget.used() < size + 100);
+
+    budget.set_limit(usize::MAX);
+    let used = budget.used();
+    map.add_sourcemap(&mut other, 2).unwrap();
+    assert!(budget.used() >= used + 1000);
+
+    drop(map);
+    assert_eq!(budget.used(), 0);
+
+    let options = ParseOptions {
+        memory_budget: Some(MemoryBudget::new(100)),
+        ..Default::default()
+    };
+    let error = SourceMap::from_json_with_options("/", input, &options).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::MemoryBudgetExceeded
+    ));
+    // The map that went over is dropped along with the error
+    assert_eq!(options.memory_budget.unwrap().used(), 0);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_global_memory_budget() {
+    use rkyv::AlignedVec;
+
+    let map = SourceMap::from_json("/", r#"{"sources": ["a.js"], "mappings": "AAAA"}"#).unwrap();
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+
+    // Other tests run in parallel and can create maps while it is set, so it is never lowered
+    let budget = MemoryBudget::new(usize::MAX);
+    MemoryBudget::set_global(Some(budget.clone()));
+    let from_buffer = SourceMap::from_buffer("/", &buffer).unwrap();
+    MemoryBudget::set_global(None);
+    assert!(from_buffer.get_memory_budget().is_some());
+    assert!(budget.used() >= from_buffer.memory_size());
+    assert!(map.get_memory_budget().is_none());
+}
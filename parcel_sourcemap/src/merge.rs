@@ -0,0 +1,280 @@
+use crate::mapping::AddSourceMode;
+use crate::mapping_line::MappingLine;
+use crate::observer::MutationEvent;
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::utils::case_key;
+use crate::SourceMap;
+use std::collections::HashMap;
+
+// Indexes of the sources and names of a map by their key, so extend_many doesn't have to
+// search the tables again for every part
+pub(crate) struct TableLookup {
+    sources: HashMap<String, u32>,
+    names: HashMap<String, u32>,
+}
+
+impl SourceMap {
+    // Same result as calling add_sourcemap for every (line offset, map) part in order, but
+    // sources and names are deduplicated through one lookup table for all parts instead of
+    // searching the tables again for every part. Bundles with hundreds of modules benefit most.
+    pub fn extend_many(&mut self, parts: &[(u32, &SourceMap)]) -> Result<(), SourceMapError> {
+        // The shared table only works when a path always resolves to the same source
+        let added: usize = parts.iter().map(|(_, part)| part.added_memory_size()).sum();
+        let content: usize = parts
+            .iter()
+            .map(|(_, part)| part.content_memory_size())
+            .sum();
+        self.ensure_memory(added + content)?;
+
+        let mut lookup = match self.add_source_mode {
+            AddSourceMode::DedupeByPath => Some(self.table_lookup()),
+            _ => None,
+        };
+        for (line_offset, part) in parts.iter() {
+            let (source_indexes, names_indexes) = self.merge_tables(part, lookup.as_mut())?;
+            self.insert_map_lines(
+                part.inner
+                    .mapping_lines
+                    .iter()
+                    .map(|(line, mapping_line)| (line, mapping_line.clone())),
+                part.inner.mapping_lines.len(),
+                *line_offset as i64,
+                &source_indexes,
+                &names_indexes,
+            )?;
+        }
+        self.charge_memory(added);
+        Ok(())
+    }
+
+    fn source_key(&self, normalized_source: &str) -> String {
+        if self.case_insensitive_sources {
+            case_key(normalized_source)
+        } else {
+            String::from(normalized_source)
+        }
+    }
+
+    fn table_lookup(&self) -> TableLookup {
+        let mut lookup = TableLookup {
+            sources: HashMap::new(),
+            names: HashMap::new(),
+        };
+        for (i, source) in self.inner.sources.iter().enumerate() {
+            lookup
+                .sources
+                .entry(self.source_key(source))
+                .or_insert(i as u32);
+        }
+        for (i, name) in self.inner.names.iter().enumerate() {
+            lookup.names.entry(name.clone()).or_insert(i as u32);
+        }
+        lookup
+    }
+
+    // Adds the sources (with their content, provenance and ignore list entry) and names of
+    // sourcemap, returns the indexes in this map that its indexes refer to. Sources are named
+    // the way this map would (see merged_source_name).
+    pub(crate) fn merge_tables(
+        &mut self,
+        sourcemap: &SourceMap,
+        mut lookup: Option<&mut TableLookup>,
+    ) -> Result<(Vec<u32>, Vec<u32>), SourceMapError> {
+        self.inner.sources.reserve(sourcemap.inner.sources.len());
+        let mut source_indexes = Vec::with_capacity(sourcemap.inner.sources.len());
+        for (i, source) in sourcemap.inner.sources.iter().enumerate() {
+            let source = self.merged_source_name(sourcemap, source);
+            let content = sourcemap.get_sources_content().get(i);
+            let source_index = match lookup.as_deref_mut() {
+                Some(lookup) => {
+                    let normalized = self.normalize_source(&source);
+                    let key = self.source_key(&normalized);
+                    let index = match lookup.sources.get(&key) {
+                        Some(index) => *index,
+                        None => {
+                            self.inner.sources.push(normalized);
+                            let index = (self.inner.sources.len() - 1) as u32;
+                            lookup.sources.insert(key, index);
+                            self.notify(MutationEvent::SourceAdded(index));
+                            index
+                        }
+                    };
+                    if let Some(content) = content {
+                        self.set_source_content(index as usize, content)?;
+                    }
+                    index
+                }
+                None => match content {
+                    Some(content) => self.add_source_with_content(&source, content)?,
+                    None => self.add_source(&source),
+                },
+            };
+            self.merge_source_provenance(source_index, sourcemap.get_source_provenance(i as u32));
+            self.copy_ignored_source(source_index, sourcemap, i as u32);
+            self.copy_source_hash(source_index, sourcemap, i as u32);
+            source_indexes.push(source_index);
+        }
+
+        self.inner.names.reserve(sourcemap.inner.names.len());
+        let mut names_indexes = Vec::with_capacity(sourcemap.inner.names.len());
+        for name in sourcemap.inner.names.iter() {
+            let index = match lookup.as_deref_mut() {
+                Some(lookup) => {
+                    let name = match &self.name_normalizer {
+                        Some(name_normalizer) => name_normalizer.normalize(name),
+                        None => name.clone(),
+                    };
+                    match lookup.names.get(&name) {
+                        Some(index) => *index,
+                        None => {
+                            self.inner.names.push(name.clone());
+                            let index = (self.inner.names.len() - 1) as u32;
+                            lookup.names.insert(name, index);
+                            self.notify(MutationEvent::NameAdded(index));
+                            index
+                        }
+                    }
+                }
+                None => self.add_name(name),
+            };
+            names_indexes.push(index);
+        }
+        Ok((source_indexes, names_indexes))
+    }
+
+    // Puts the lines of an added map (line_count lines, including its empty ones) at
+    // line_offset, replacing the lines that are there. Sources and names get remapped using
+    // the indexes merge_tables returned.
+    pub(crate) fn insert_map_lines<I>(
+        &mut self,
+        lines: I,
+        line_count: usize,
+        line_offset: i64,
+        source_indexes: &[u32],
+        names_indexes: &[u32],
+    ) -> Result<(), SourceMapError>
+    where
+        I: Iterator<Item = (usize, MappingLine)>,
+    {
+        self.reverse_index = None;
+        self.lookup_index = None;
+        let start = line_offset.max(0) as usize;
+        let end = (line_count as i64 + line_offset).max(0) as usize;
+        for line in self.inner.mapping_lines.clear_lines(start..end) {
+            self.invalidate_vlq_line(line);
+            self.notify(MutationEvent::LineCleared(line as u32));
+        }
+        if end > 0 {
+            self.ensure_lines(end - 1)?;
+        }
+        for (line, mut mapping_line) in lines {
+            let generated_line = (line as i64) + line_offset;
+            if generated_line < 0 {
+                continue;
+            }
+            for mapping in mapping_line.mappings.iter_mut() {
+                if let Some(original) = &mut mapping.original {
+                    original.source = *source_indexes
+                        .get(original.source as usize)
+                        .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))?;
+                    if let Some(name) = original.name {
+                        original.name =
+                            Some(*names_indexes.get(name as usize).ok_or_else(|| {
+                                SourceMapError::new(SourceMapErrorType::NameOutOfRange)
+                            })?);
+                    }
+                }
+            }
+
+            self.invalidate_vlq_line(generated_line as usize);
+            self.inner
+                .mapping_lines
+                .replace(generated_line as usize, mapping_line)?;
+            self.notify(MutationEvent::LineReplaced(generated_line as u32));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_extend_many() {
+    use crate::mapping::OriginalLocation;
+
+    let mut parts = Vec::new();
+    for i in 0..3 {
+        let mut part = SourceMap::new("/");
+        let shared = part.add_source("shared.js");
+        let own = part.add_source(&format!("module{}.js", i));
+        let name = part.add_name("render");
+        part.set_source_content(own as usize, &format!("// {}", i))
+            .unwrap();
+        part.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, shared, Some(name))));
+        part.add_mapping(1, 2, Some(OriginalLocation::new(i, 0, own, None)));
+        parts.push((i * 2, part));
+    }
+
+    let mut sequential = SourceMap::new("/");
+    for (line_offset, part) in parts.iter() {
+        sequential
+            .add_sourcemap(&mut part.clone(), *line_offset as i64)
+            .unwrap();
+    }
+
+    let mut batched = SourceMap::new("/");
+    let borrowed: Vec<(u32, &SourceMap)> = parts.iter().map(|(o, p)| (*o, p)).collect();
+    batched.extend_many(&borrowed).unwrap();
+
+    assert_eq!(batched.get_sources(), sequential.get_sources());
+    assert_eq!(
+        batched.get_sources_content(),
+        sequential.get_sources_content()
+    );
+    assert_eq!(batched.get_names(), sequential.get_names());
+    assert_eq!(
+        format!("{:?}", batched.get_mappings()),
+        format!("{:?}", sequential.get_mappings())
+    );
+    assert_eq!(batched.get_sources().len(), 4);
+}
+
+#[test]
+fn test_extend_many_overlapping() {
+    use crate::mapping::OriginalLocation;
+
+    let mut first = SourceMap::new("/");
+    let a = first.add_source("src/a.js");
+    for line in 0..4 {
+        first.add_mapping(line, 0, Some(OriginalLocation::new(line, 0, a, None)));
+    }
+    // Starts on the second line of the first part, its sourceRoot points to the same file
+    let mut second = SourceMap::new("/");
+    second.set_source_root(Some("src"));
+    let b = second.add_source("a.js");
+    let name = second.add_name("b");
+    second.add_mapping(1, 4, Some(OriginalLocation::new(7, 0, b, Some(name))));
+    let parts = [(0, &first), (1, &second)];
+
+    let mut sequential = SourceMap::new("/");
+    for (line_offset, part) in parts.iter() {
+        sequential
+            .add_sourcemap(&mut (*part).clone(), *line_offset as i64)
+            .unwrap();
+    }
+    let mut batched = SourceMap::new("/");
+    batched.extend_many(&parts).unwrap();
+
+    assert_eq!(batched.get_sources(), &vec![String::from("src/a.js")]);
+    assert_eq!(batched.get_sources(), sequential.get_sources());
+    let lines = |map: &SourceMap| -> Vec<(u32, u32, u32)> {
+        map.get_mappings()
+            .iter()
+            .map(|m| {
+                let original = m.original.unwrap();
+                (m.generated_line, m.generated_column, original.original_line)
+            })
+            .collect()
+    };
+    // Lines 1 and 2 of the first part are replaced by the second part
+    assert_eq!(lines(&batched), vec![(0, 0, 0), (2, 4, 7), (3, 0, 3)]);
+    assert_eq!(lines(&batched), lines(&sequential));
+}
@@ -0,0 +1,106 @@
+use crate::SourceMap;
+use std::fmt;
+use std::sync::Arc;
+
+// Cleans up names before they end up in the names table,
+// e.g. stripping `__WEBPACK_IMPORTED_MODULE_` prefixes from vendor maps
+#[derive(Clone)]
+pub struct NameNormalizer(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl NameNormalizer {
+    pub fn new<F>(normalizer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Self(Arc::new(normalizer))
+    }
+
+    pub fn normalize(&self, name: &str) -> String {
+        (self.0)(name)
+    }
+}
+
+impl fmt::Debug for NameNormalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NameNormalizer")
+    }
+}
+
+impl SourceMap {
+    // Applied to every name added from now on, including names of merged maps
+    pub fn set_name_normalizer(&mut self, normalizer: Option<NameNormalizer>) {
+        self.name_normalizer = normalizer;
+    }
+
+    // Runs the normalizer over the existing names table, names that end up being equal
+    // get merged and all mappings are updated to point to the merged name.
+    pub fn normalize_names(&mut self, normalizer: &NameNormalizer) {
+        let names_indexes = self.normalize_names_table(normalizer);
+        self.remap_originals(None, Some(&names_indexes));
+    }
+
+    // Only replaces the names table, returns the new index of every old name
+    pub(crate) fn normalize_names_table(&mut self, normalizer: &NameNormalizer) -> Vec<u32> {
+        let names = std::mem::take(&mut self.inner.names);
+        let mut names_indexes = Vec::with_capacity(names.len());
+        for name in names.iter() {
+            let normalized = normalizer.normalize(name);
+            let index = match self.inner.names.iter().position(|n| normalized.eq(n)) {
+                Some(i) => i as u32,
+                None => {
+                    self.inner.names.push(normalized);
+                    (self.inner.names.len() - 1) as u32
+                }
+            };
+            names_indexes.push(index);
+        }
+        names_indexes
+    }
+
+    // Orders the names table, returns the new index of every old name (see sort_sources)
+    pub(crate) fn sort_names_table(&mut self) -> Vec<u32> {
+        let mut order: Vec<usize> = (0..self.inner.names.len()).collect();
+        order.sort_by(|a, b| self.inner.names[*a].cmp(&self.inner.names[*b]));
+        let mut names_indexes = vec![0; order.len()];
+        let mut names: Vec<String> = std::mem::take(&mut self.inner.names);
+        for (index, old) in order.into_iter().enumerate() {
+            names_indexes[old] = index as u32;
+            self.inner.names.push(std::mem::take(&mut names[old]));
+        }
+        names_indexes
+    }
+}
+
+#[test]
+fn test_normalize_names() {
+    use crate::mapping::OriginalLocation;
+
+    let strip_prefix = NameNormalizer::new(|name| {
+        String::from(name.trim_start_matches("_react__WEBPACK_IMPORTED_MODULE_0__."))
+    });
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    let name = map.add_name("_react__WEBPACK_IMPORTED_MODULE_0__.useState");
+    let other_name = map.add_name("useState");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, Some(name))));
+    map.add_mapping(
+        0,
+        4,
+        Some(OriginalLocation::new(0, 4, source, Some(other_name))),
+    );
+
+    map.normalize_names(&strip_prefix);
+    assert_eq!(map.get_names(), &vec!["useState"]);
+    assert!(map
+        .get_mappings()
+        .iter()
+        .all(|m| m.original.unwrap().name == Some(0)));
+
+    map.set_name_normalizer(Some(strip_prefix));
+    assert_eq!(
+        map.add_name("_react__WEBPACK_IMPORTED_MODULE_0__.useEffect"),
+        1
+    );
+    assert_eq!(map.get_name(1).unwrap(), "useEffect");
+}
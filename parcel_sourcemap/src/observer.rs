@@ -0,0 +1,137 @@
+use crate::mapping::OriginalLocation;
+use crate::SourceMap;
+use std::fmt;
+use std::sync::Arc;
+
+// What changed in a map, so wrappers can keep derived indexes up to date incrementally
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationEvent {
+    // Only for sources and names that were not in the map yet
+    SourceAdded(u32),
+    NameAdded(u32),
+    MappingAdded {
+        generated_line: u32,
+        generated_column: u32,
+        original: Option<OriginalLocation>,
+    },
+    // All mappings of the line were replaced, e.g. by add_sourcemap
+    LineReplaced(u32),
+    // All mappings of the line were removed, e.g. add_sourcemap clears the lines the added
+    // map covers before it puts its own in
+    LineCleared(u32),
+    // Lines at and after line_count were removed, trim_trailing_lines only removes empty ones
+    LinesTruncated {
+        line_count: u32,
+    },
+    // Empty lines were inserted at generated_line (positive offset) or the lines right
+    // before it were removed (negative offset), later lines moved by offset
+    LinesShifted {
+        generated_line: u32,
+        offset: i64,
+    },
+    // Mappings of the line at or after generated_column moved by offset
+    ColumnsShifted {
+        generated_line: u32,
+        generated_column: u32,
+        offset: i64,
+    },
+    // Sources, names or mappings changed in a way the other events don't describe (sources
+    // got sorted, names normalized, the map got flattened, ...), everything derived from
+    // the map has to be rebuilt
+    Reset,
+}
+
+// Gets called synchronously for every mutation, so it should be cheap
+#[derive(Clone)]
+pub struct MutationObserver {
+    callback: Arc<dyn Fn(&MutationEvent) + Send + Sync>,
+}
+
+impl MutationObserver {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&MutationEvent) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+impl fmt::Debug for MutationObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MutationObserver").finish()
+    }
+}
+
+impl SourceMap {
+    pub fn set_mutation_observer(&mut self, observer: Option<MutationObserver>) {
+        self.mutation_observer = observer;
+    }
+
+    #[inline]
+    pub(crate) fn notify(&self, event: MutationEvent) {
+        if let Some(observer) = &self.mutation_observer {
+            (observer.callback)(&event);
+        }
+    }
+}
+
+#[test]
+fn test_mutation_observer() {
+    use std::sync::Mutex;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut map = SourceMap::new("/");
+    let events_clone = events.clone();
+    map.set_mutation_observer(Some(MutationObserver::new(move |event| {
+        events_clone.lock().unwrap().push(*event);
+    })));
+
+    let a = map.add_source("a.js");
+    map.add_source("a.js");
+    let name = map.add_name("foo");
+    map.add_mapping(1, 2, Some(OriginalLocation::new(0, 0, a, Some(name))));
+    map.offset_lines(1, 2).unwrap();
+    map.offset_columns(3, 0, 4).unwrap();
+    map.sort_sources().unwrap();
+
+    let mut other = SourceMap::new("/");
+    other.add_source("a.js");
+    other.add_mapping(0, 0, None);
+    map.add_sourcemap(&mut other, 5).unwrap();
+    // Covers line 5, which has its mappings replaced again
+    let mut other = SourceMap::new("/");
+    other.add_mapping(0, 4, None);
+    map.add_sourcemap(&mut other, 5).unwrap();
+    map.ensure_lines(11).unwrap();
+    map.trim_trailing_lines().unwrap();
+    map.trim_trailing_lines().unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            MutationEvent::SourceAdded(a),
+            MutationEvent::NameAdded(name),
+            MutationEvent::MappingAdded {
+                generated_line: 1,
+                generated_column: 2,
+                original: Some(OriginalLocation::new(0, 0, a, Some(name))),
+            },
+            MutationEvent::LinesShifted {
+                generated_line: 1,
+                offset: 2,
+            },
+            MutationEvent::ColumnsShifted {
+                generated_line: 3,
+                generated_column: 0,
+                offset: 4,
+            },
+            MutationEvent::Reset,
+            MutationEvent::LineReplaced(5),
+            MutationEvent::LineCleared(5),
+            MutationEvent::LineReplaced(5),
+            MutationEvent::LinesTruncated { line_count: 6 },
+        ]
+    );
+}
@@ -0,0 +1,364 @@
+use crate::capacity::CapacityHints;
+use crate::json::ParseOptions;
+use crate::mapping::{NameWithoutSourcePolicy, SourceRootHandling};
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use serde_json::{Map, Value};
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn invalid_options(reason: String) -> SourceMapError {
+    SourceMapError::new_with_reason(SourceMapErrorType::InvalidOptions, &reason)
+}
+
+// Reads the options object the bindings receive from JS (converted to JSON), so that
+// mistakes are reported with the offending key rather than as a generic error
+pub struct OptionsReader<'a> {
+    fields: Option<&'a Map<String, Value>>,
+}
+
+impl<'a> OptionsReader<'a> {
+    // null (or undefined) is the same as an empty object
+    pub fn new(value: &'a Value, allowed_keys: &[&str]) -> Result<Self, SourceMapError> {
+        let fields = match value {
+            Value::Null => return Ok(Self { fields: None }),
+            Value::Object(fields) => fields,
+            _ => {
+                return Err(invalid_options(format!(
+                    "options must be an object, got {}",
+                    type_name(value)
+                )))
+            }
+        };
+
+        if let Some(key) = fields.keys().find(|k| !allowed_keys.contains(&k.as_str())) {
+            return Err(invalid_options(format!(
+                "unknown option {}, expected one of {}",
+                key,
+                allowed_keys.join(", ")
+            )));
+        }
+
+        Ok(Self {
+            fields: Some(fields),
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.fields
+            .and_then(|fields| fields.get(key))
+            .filter(|value| !value.is_null())
+    }
+
+    pub fn integer(&self, key: &str) -> Result<Option<i64>, SourceMapError> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        // JS numbers are doubles, so 1.0 is a valid integer as well
+        let integer = match value {
+            Value::Number(number) => number.as_i64().or_else(|| {
+                number
+                    .as_f64()
+                    .filter(|n| n.fract() == 0.0 && n.abs() <= i64::MAX as f64)
+                    .map(|n| n as i64)
+            }),
+            _ => None,
+        };
+
+        match integer {
+            Some(integer) => Ok(Some(integer)),
+            None => Err(invalid_options(format!(
+                "{} must be an integer, got {}",
+                key,
+                match value {
+                    Value::Number(number) => number.to_string(),
+                    _ => String::from(type_name(value)),
+                }
+            ))),
+        }
+    }
+
+    pub fn unsigned(&self, key: &str) -> Result<Option<u32>, SourceMapError> {
+        match self.integer(key)? {
+            Some(integer) => u32::try_from(integer).map(Some).map_err(|_| {
+                invalid_options(format!(
+                    "{} must be a non-negative integer below 2^32, got {}",
+                    key, integer
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn string(&self, key: &str) -> Result<Option<&'a str>, SourceMapError> {
+        match self.get(key) {
+            Some(Value::String(value)) => Ok(Some(value.as_str())),
+            Some(value) => Err(invalid_options(format!(
+                "{} must be a string, got {}",
+                key,
+                type_name(value)
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn boolean(&self, key: &str) -> Result<Option<bool>, SourceMapError> {
+        match self.get(key) {
+            Some(Value::Bool(value)) => Ok(Some(*value)),
+            Some(value) => Err(invalid_options(format!(
+                "{} must be a boolean, got {}",
+                key,
+                type_name(value)
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+
+// Options structs that can be read from a JS options object
+pub trait FromOptions: Sized {
+    const KEYS: &'static [&'static str];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError>;
+
+    fn from_options(value: &Value) -> Result<Self, SourceMapError> {
+        Self::read(&OptionsReader::new(value, Self::KEYS)?)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OffsetOptions {
+    pub line_offset: i64,
+    pub column_offset: i64,
+}
+
+impl FromOptions for OffsetOptions {
+    const KEYS: &'static [&'static str] = &["lineOffset", "columnOffset"];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        Ok(Self {
+            line_offset: reader.integer("lineOffset")?.unwrap_or(0),
+            column_offset: reader.integer("columnOffset")?.unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LineOffsetOptions {
+    pub line_offset: i64,
+}
+
+impl FromOptions for LineOffsetOptions {
+    const KEYS: &'static [&'static str] = &["lineOffset"];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        Ok(Self {
+            line_offset: reader.integer("lineOffset")?.unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlatArraysOptions {
+    pub line_offset: i64,
+    pub column_offset: i64,
+    pub source_index_offset: u32,
+    pub name_index_offset: u32,
+}
+
+impl FromOptions for FlatArraysOptions {
+    const KEYS: &'static [&'static str] = &[
+        "lineOffset",
+        "columnOffset",
+        "sourceIndexOffset",
+        "nameIndexOffset",
+    ];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        Ok(Self {
+            line_offset: reader.integer("lineOffset")?.unwrap_or(0),
+            column_offset: reader.integer("columnOffset")?.unwrap_or(0),
+            source_index_offset: reader.unsigned("sourceIndexOffset")?.unwrap_or(0),
+            name_index_offset: reader.unsigned("nameIndexOffset")?.unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IterateOptions {
+    pub batch_size: u32,
+}
+
+impl Default for IterateOptions {
+    fn default() -> Self {
+        Self { batch_size: 10000 }
+    }
+}
+
+impl FromOptions for IterateOptions {
+    const KEYS: &'static [&'static str] = &["batchSize"];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        let batch_size = reader
+            .unsigned("batchSize")?
+            .unwrap_or(IterateOptions::default().batch_size);
+        if batch_size == 0 {
+            return Err(invalid_options(String::from(
+                "batchSize must be at least 1",
+            )));
+        }
+        Ok(Self { batch_size })
+    }
+}
+
+// Options of setProgressCallback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProgressOptions {
+    // Number of mappings between two callbacks
+    pub every: u32,
+}
+
+impl Default for ProgressOptions {
+    fn default() -> Self {
+        Self { every: 10000 }
+    }
+}
+
+impl FromOptions for ProgressOptions {
+    const KEYS: &'static [&'static str] = &["every"];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        let every = reader
+            .unsigned("every")?
+            .unwrap_or(ProgressOptions::default().every);
+        if every == 0 {
+            return Err(invalid_options(String::from("every must be at least 1")));
+        }
+        Ok(Self { every })
+    }
+}
+
+// Also accepted by the SourceMap constructors of the bindings
+impl FromOptions for CapacityHints {
+    const KEYS: &'static [&'static str] = &[
+        "reserveSources",
+        "reserveNames",
+        "reserveLines",
+        "reserveMappings",
+    ];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        Ok(Self {
+            sources: reader.unsigned("reserveSources")?.unwrap_or(0) as usize,
+            names: reader.unsigned("reserveNames")?.unwrap_or(0) as usize,
+            lines: reader.unsigned("reserveLines")?.unwrap_or(0) as usize,
+            mappings: reader.unsigned("reserveMappings")?.unwrap_or(0) as usize,
+        })
+    }
+}
+
+impl FromOptions for ParseOptions {
+    const KEYS: &'static [&'static str] = &[
+        "keepRawInput",
+        "repairMissingSources",
+        "nameWithoutSource",
+        "utf8OriginalColumns",
+        "reserveSources",
+        "reserveNames",
+        "reserveLines",
+        "reserveMappings",
+        "sourceRoot",
+        "lenientNumbers",
+    ];
+
+    fn read(reader: &OptionsReader) -> Result<Self, SourceMapError> {
+        let name_without_source = match reader.string("nameWithoutSource")? {
+            Some(name) => NameWithoutSourcePolicy::from_name(name).ok_or_else(|| {
+                invalid_options(format!(
+                    "nameWithoutSource must be one of error, dropName, syntheticSource, got {}",
+                    name
+                ))
+            })?,
+            None => NameWithoutSourcePolicy::default(),
+        };
+        let source_root = match reader.string("sourceRoot")? {
+            Some(name) => SourceRootHandling::from_name(name).ok_or_else(|| {
+                invalid_options(format!(
+                    "sourceRoot must be one of keep, join, got {}",
+                    name
+                ))
+            })?,
+            None => SourceRootHandling::default(),
+        };
+
+        Ok(Self {
+            keep_raw_input: reader.boolean("keepRawInput")?.unwrap_or(false),
+            repair_missing_sources: reader.boolean("repairMissingSources")?.unwrap_or(false),
+            name_without_source,
+            utf8_original_columns: reader.boolean("utf8OriginalColumns")?.unwrap_or(false),
+            // Tokens and budgets can't be passed as plain options
+            cancellation: None,
+            memory_budget: None,
+            capacity: CapacityHints::read(reader)?,
+            source_root,
+            lenient_numbers: reader.boolean("lenientNumbers")?.unwrap_or(false),
+        })
+    }
+}
+
+#[test]
+fn test_from_options() {
+    use serde_json::json;
+
+    let options = OffsetOptions::from_options(&json!({ "lineOffset": 2.0 })).unwrap();
+    assert_eq!(
+        options,
+        OffsetOptions {
+            line_offset: 2,
+            column_offset: 0
+        }
+    );
+    assert_eq!(
+        OffsetOptions::from_options(&Value::Null).unwrap(),
+        OffsetOptions::default()
+    );
+
+    let error = OffsetOptions::from_options(&json!({ "lineOffset": "2" })).unwrap_err();
+    assert_eq!(
+        error.reason.unwrap(),
+        "lineOffset must be an integer, got string"
+    );
+    let error = OffsetOptions::from_options(&json!({ "lineOfset": 2 })).unwrap_err();
+    assert_eq!(
+        error.reason.unwrap(),
+        "unknown option lineOfset, expected one of lineOffset, columnOffset"
+    );
+    let error = FlatArraysOptions::from_options(&json!({ "nameIndexOffset": -1 })).unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::InvalidOptions
+    ));
+    assert!(OffsetOptions::from_options(&json!(3)).is_err());
+
+    let hints =
+        CapacityHints::from_options(&json!({ "reserveSources": 2, "reserveMappings": 100 }))
+            .unwrap();
+    assert_eq!((hints.sources, hints.mappings), (2, 100));
+    let options = ParseOptions::from_options(&json!({ "reserveLines": 3 })).unwrap();
+    assert_eq!(options.capacity.lines, 3);
+}
@@ -0,0 +1,146 @@
+use crate::code_frame::{render_lines, DEFAULT_CONTEXT_LINES};
+#[cfg(feature = "json")]
+use crate::sourcemap_error::SourceMapError;
+use crate::stack_trace::{parse_stack_trace, remap_frames, StackFrame, StackLine};
+use crate::SourceMap;
+
+// A frame as the dev server error overlay shows it, lines and columns are 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct OverlayFrame {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub function_name: Option<String>,
+    // Whether the frame points to the original source or (if it isn't mapped) the bundle
+    pub original: bool,
+    pub code_frame: Option<String>,
+}
+
+// Lines and columns are 1-based, like in the frames
+fn code_frame(code: &str, line: u32, column: u32) -> Option<String> {
+    let lines: Vec<&str> = code.lines().collect();
+    let frame = render_lines(
+        &lines,
+        line.checked_sub(1)?,
+        column.saturating_sub(1),
+        DEFAULT_CONTEXT_LINES,
+    )?;
+    Some(frame.join("\n"))
+}
+
+impl SourceMap {
+    // Resolves the frames of an error thrown by the generated `code`. Mapped frames get a code
+    // frame of the original source if the map has its content, unmapped frames one of `code`.
+    pub fn overlay_frames(&mut self, error_stack: &str, code: &str) -> Vec<OverlayFrame> {
+        let frames: Vec<StackFrame> = parse_stack_trace(error_stack)
+            .into_iter()
+            .filter_map(|line| match line {
+                StackLine::Frame(frame) => Some(frame),
+                StackLine::Text(_) => None,
+            })
+            .collect();
+        let remapped = remap_frames(&frames, |frame| frame.resolve(self));
+        frames
+            .into_iter()
+            .zip(remapped)
+            .map(|(frame, remapped)| match remapped {
+                Some(remapped) => {
+                    let content = self
+                        .get_source_index(&remapped.file)
+                        .ok()
+                        .flatten()
+                        .and_then(|index| self.get_source_content(index).ok());
+                    OverlayFrame {
+                        code_frame: content
+                            .and_then(|c| code_frame(c, remapped.line, remapped.column)),
+                        file: remapped.file,
+                        line: remapped.line,
+                        column: remapped.column,
+                        function_name: remapped.function.filter(|f| !f.is_empty()),
+                        original: true,
+                    }
+                }
+                None => OverlayFrame {
+                    code_frame: code_frame(code, frame.line, frame.column),
+                    file: frame.file,
+                    line: frame.line,
+                    column: frame.column,
+                    function_name: frame.function.filter(|f| !f.is_empty()),
+                    original: false,
+                },
+            })
+            .collect()
+    }
+
+    // overlay_frames as a JSON array, for the dev server to pass along as is
+    #[cfg(feature = "json")]
+    pub fn to_overlay_frames(
+        &mut self,
+        error_stack: &str,
+        code: &str,
+    ) -> Result<String, SourceMapError> {
+        Ok(serde_json::to_string(
+            &self.overlay_frames(error_stack, code),
+        )?)
+    }
+}
+
+#[test]
+fn test_code_frame() {
+    let code = "let a = 1;\nlet b = 2;\n\ta();\nlet c = 3;\nlet d = 4;\nlet e = 5;";
+    assert_eq!(
+        code_frame(code, 3, 3).unwrap(),
+        "  1 | let a = 1;\n  2 | let b = 2;\n> 3 | \ta();\n    | \t ^\n  4 | let c = 3;\n  5 | let d = 4;"
+    );
+    assert_eq!(
+        code_frame(code, 1, 1).unwrap(),
+        "> 1 | let a = 1;\n    | ^\n  2 | let b = 2;\n  3 | \ta();"
+    );
+    assert!(code_frame(code, 7, 1).is_none());
+    assert!(code_frame(code, 0, 1).is_none());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_overlay_frames() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("src/app.js");
+    map.set_source_content(source as usize, "function app() {\n  crash();\n}")
+        .unwrap();
+    let name = map.add_name("crash");
+    map.add_mapping(0, 10, Some(OriginalLocation::new(1, 2, source, Some(name))));
+
+    let stack = "Error: boom\n    at a (http://localhost/index.js:1:11)\n    at http://localhost/index.js:2:3";
+    let frames = map.overlay_frames(stack, "function a(){c()}\nb();");
+    assert_eq!(frames.len(), 2);
+    assert_eq!(
+        frames[0],
+        OverlayFrame {
+            file: String::from("src/app.js"),
+            line: 2,
+            column: 3,
+            // The caller isn't mapped, so there is no original name for it
+            function_name: Some(String::from("a")),
+            original: true,
+            code_frame: Some(String::from(
+                "  1 | function app() {\n> 2 |   crash();\n    |   ^\n  3 | }"
+            )),
+        }
+    );
+    assert!(!frames[1].original);
+    assert_eq!(frames[1].function_name, None);
+    assert_eq!(
+        frames[1].code_frame.as_deref(),
+        Some("  1 | function a(){c()}\n> 2 | b();\n    |   ^")
+    );
+
+    let json = map.to_overlay_frames(stack, "").unwrap();
+    assert!(json.starts_with(r#"[{"file":"src/app.js","line":2,"column":3,"functionName":"a","original":true,"codeFrame":"#));
+}
@@ -0,0 +1,431 @@
+use crate::cancel::CancellationToken;
+use crate::capacity::CapacityHints;
+use crate::mapping::{NameWithoutSourcePolicy, OriginalLocation};
+use crate::mapping_line::MappingLine;
+use crate::sourcemap_error::{panic_error, ErrorLocation, SourceMapError, SourceMapErrorType};
+use crate::vlq_utils::{apply_relative_vlq, MappingBytes, MappingInput};
+use crate::SourceMap;
+use std::ops::Range;
+use std::thread;
+
+// Plain scoped threads rather than a pool like rayon: the input is split into one chunk per
+// thread up front, so there is nothing to balance, and it keeps the crate free of a global
+// pool the bindings would have to configure
+
+// Threads get at least this much of the mappings string, smaller inputs aren't worth it
+const MIN_CHUNK_SIZE: usize = 256 * 1024;
+
+// Source, original line, original column and name of the last segment. Everything but the
+// generated column is relative to the previous segment, also across lines, so a chunk can
+// only be decoded once the state at its start is known.
+#[derive(Debug, Default, Clone, Copy)]
+struct SegmentState {
+    source: i64,
+    original_line: i64,
+    original_column: i64,
+    name: i64,
+}
+
+impl SegmentState {
+    fn add(&self, other: &SegmentState) -> SegmentState {
+        SegmentState {
+            source: self.source.wrapping_add(other.source),
+            original_line: self.original_line.wrapping_add(other.original_line),
+            original_column: self.original_column.wrapping_add(other.original_column),
+            name: self.name.wrapping_add(other.name),
+        }
+    }
+}
+
+struct ChunkContext<'a> {
+    source_indexes: &'a [u32],
+    name_indexes: &'a [u32],
+    name_without_source: NameWithoutSourcePolicy,
+    cancellation: Option<&'a CancellationToken>,
+    line_offset: i64,
+    column_offset: i64,
+}
+
+// Splits the mappings into about count chunks that each start at the beginning of a line
+fn split_chunks(input: &[u8], count: usize) -> Vec<Range<usize>> {
+    let target = input.len().div_ceil(count);
+    let mut chunks = Vec::with_capacity(count);
+    let mut start = 0;
+    while start < input.len() {
+        let mut end = (start + target).min(input.len());
+        if let Some(separator) = input[end..].iter().position(|b| *b == b';') {
+            end += separator + 1;
+        } else {
+            end = input.len();
+        }
+        chunks.push(start..end);
+        start = end;
+    }
+    chunks
+}
+
+// Sum of the relative fields of a chunk and the number of lines it ends. Invalid input is
+// left for decode_chunk to report, the sums after it don't matter anymore.
+fn sum_chunk(chunk: &[u8]) -> (SegmentState, u32) {
+    let mut input = MappingBytes::new(chunk);
+    let mut state = SegmentState::default();
+    let mut lines = 0;
+    let mut fields = [0; 5];
+    while let Some(byte) = input.peek() {
+        match byte {
+            b';' => {
+                lines += 1;
+                input.next();
+            }
+            b',' => {
+                input.next();
+            }
+            _ => match input.read_segment(&mut fields) {
+                Ok(1) => {}
+                Ok(2) => state.name = state.name.wrapping_add(fields[1]),
+                Ok(count) => {
+                    state.source = state.source.wrapping_add(fields[1]);
+                    state.original_line = state.original_line.wrapping_add(fields[2]);
+                    state.original_column = state.original_column.wrapping_add(fields[3]);
+                    if count == 5 {
+                        state.name = state.name.wrapping_add(fields[4]);
+                    }
+                }
+                Err(_) => break,
+            },
+        }
+    }
+    (state, lines)
+}
+
+// Decodes a chunk the same way add_vlq_input does, starting from the state at its start
+fn decode_chunk(
+    chunk: &[u8],
+    byte_offset: usize,
+    first_line: u32,
+    mut state: SegmentState,
+    mut capacity: CapacityHints,
+    context: &ChunkContext,
+) -> Result<Vec<(u32, MappingLine)>, SourceMapError> {
+    let mut input = MappingBytes::new(chunk);
+    let mut generated_line = context.line_offset + first_line as i64;
+    let mut generated_column = context.column_offset;
+    let mut lines: Vec<(u32, MappingLine)> = Vec::new();
+    let mut location = ErrorLocation {
+        generated_line: first_line,
+        ..ErrorLocation::default()
+    };
+    let mut decode = || -> Result<(), SourceMapError> {
+        while let Some(byte) = input.peek() {
+            match byte {
+                b';' => {
+                    if let Some(token) = context.cancellation {
+                        token.check()?;
+                    }
+                    generated_line += 1;
+                    generated_column = context.column_offset;
+                    location.generated_line += 1;
+                    location.segment_index = 0;
+                    input.next();
+                }
+                b',' => {
+                    location.segment_index += 1;
+                    input.next();
+                }
+                _ => {
+                    location.byte_offset = byte_offset + input.byte_offset();
+                    let mut fields = [0; 5];
+                    let count = input.read_segment(&mut fields)?;
+                    apply_relative_vlq(&mut generated_column, fields[0])?;
+
+                    let original = match count {
+                        1 => None,
+                        2 => {
+                            apply_relative_vlq(&mut state.name, fields[1])?;
+                            if context.name_indexes.get(state.name as usize).is_none() {
+                                return Err(SourceMapError::new(
+                                    SourceMapErrorType::NameOutOfRange,
+                                ));
+                            }
+                            match context.name_without_source {
+                                NameWithoutSourcePolicy::DropName => None,
+                                // Adding the synthetic source needs the map, see parallel_chunk_count
+                                _ => {
+                                    return Err(SourceMapError::new(
+                                        SourceMapErrorType::NameWithoutSource,
+                                    ));
+                                }
+                            }
+                        }
+                        _ => {
+                            apply_relative_vlq(&mut state.source, fields[1])?;
+                            apply_relative_vlq(&mut state.original_line, fields[2])?;
+                            apply_relative_vlq(&mut state.original_column, fields[3])?;
+                            let source = *context
+                                .source_indexes
+                                .get(state.source as usize)
+                                .ok_or_else(|| {
+                                    SourceMapError::new(SourceMapErrorType::SourceOutOfRange)
+                                })?;
+                            let name = if count < 5 {
+                                None
+                            } else {
+                                apply_relative_vlq(&mut state.name, fields[4])?;
+                                Some(*context.name_indexes.get(state.name as usize).ok_or_else(
+                                    || SourceMapError::new(SourceMapErrorType::NameOutOfRange),
+                                )?)
+                            };
+                            Some(OriginalLocation::new(
+                                state.original_line as u32,
+                                state.original_column as u32,
+                                source,
+                                name,
+                            ))
+                        }
+                    };
+
+                    if generated_line >= 0 {
+                        let line = generated_line as u32;
+                        if lines.last().is_none_or(|(last, _)| *last != line) {
+                            let mut mapping_line = MappingLine::new();
+                            capacity.reserve_line(&mut mapping_line.mappings);
+                            lines.push((line, mapping_line));
+                        }
+                        if let Some((_, mapping_line)) = lines.last_mut() {
+                            mapping_line.add_mapping(generated_column as u32, original);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    };
+    decode().map_err(|error| error.with_location(location))?;
+    Ok(lines)
+}
+
+impl SourceMap {
+    // How many threads add_vlq_map should decode input_len bytes of mappings on. Options that
+    // need to update the map while decoding (adding sources, progress, observers, ...) keep
+    // it on the calling thread.
+    pub(crate) fn parallel_chunk_count(&self, input_len: usize) -> usize {
+        if self.repair_missing_sources
+            || self.utf8_original_columns
+            || self.name_without_source_policy == NameWithoutSourcePolicy::SyntheticSource
+            || self.progress_reporter.is_some()
+            || self.mutation_observer.is_some()
+        {
+            return 1;
+        }
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        threads.min(input_len / MIN_CHUNK_SIZE).max(1)
+    }
+
+    // Decodes the mappings in chunks on separate threads, the result is the same as decoding
+    // them in one go. Every chunk is read twice: once to sum up its relative fields, so each
+    // chunk knows the state it starts with, and once to decode it.
+    pub(crate) fn add_vlq_chunks(
+        &mut self,
+        input: &[u8],
+        source_indexes: &[u32],
+        name_indexes: &[u32],
+        line_offset: i64,
+        column_offset: i64,
+        chunk_count: usize,
+    ) -> Result<(), SourceMapError> {
+        self.check_cancelled()?;
+        let chunks = split_chunks(input, chunk_count);
+        let sums: Vec<(SegmentState, u32)> = thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .iter()
+                .map(|chunk| scope.spawn(|| sum_chunk(&input[chunk.clone()])))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().map_err(panic_error))
+                .collect::<Result<_, _>>()
+        })?;
+
+        let context = ChunkContext {
+            source_indexes,
+            name_indexes,
+            name_without_source: self.name_without_source_policy,
+            cancellation: self.cancellation_token.as_ref(),
+            line_offset,
+            column_offset,
+        };
+        // The reserved mappings are handed to the chunks by the lines they have, add_mapping
+        // would hand them to the same lines one at a time
+        let total_lines: usize = sums.iter().map(|(_, lines)| *lines as usize + 1).sum();
+        let pending = self.pending_capacity;
+        let mut allotted = 0;
+        let mut state = SegmentState::default();
+        let mut line_count: u32 = 0;
+        let decoded: Vec<Result<Vec<(u32, MappingLine)>, SourceMapError>> =
+            thread::scope(|scope| {
+                let mut handles = Vec::with_capacity(chunks.len());
+                for (chunk, (sum, lines)) in chunks.iter().zip(sums.iter()) {
+                    let start_state = state;
+                    let first_line = line_count;
+                    let capacity = CapacityHints {
+                        mappings: pending.mappings * (*lines as usize + 1) / total_lines,
+                        lines: *lines as usize + 1,
+                        ..CapacityHints::default()
+                    };
+                    allotted += capacity.mappings;
+                    let context = &context;
+                    handles.push(scope.spawn(move || {
+                        decode_chunk(
+                            &input[chunk.clone()],
+                            chunk.start,
+                            first_line,
+                            start_state,
+                            capacity,
+                            context,
+                        )
+                    }));
+                    state = state.add(sum);
+                    line_count = line_count.wrapping_add(*lines);
+                }
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().map_err(panic_error).and_then(|lines| lines))
+                    .collect()
+            });
+
+        self.pending_capacity.mappings -= allotted;
+        self.pending_capacity.lines = pending.lines.saturating_sub(line_count as usize + 1);
+
+        // The first error in the mappings is the one a sequential decode reports, chunks
+        // after it may have started from a wrong state
+        self.reverse_index = None;
+        self.lookup_index = None;
+        for lines in decoded {
+            for (line, mapping_line) in lines? {
+                let line = line as usize;
+                self.invalidate_vlq_line(line);
+                match self.inner.mapping_lines.get_mut(line) {
+                    Some(existing) => {
+                        for mapping in mapping_line.mappings {
+                            existing.add_mapping(mapping.generated_column, mapping.original);
+                        }
+                    }
+                    None => self.inner.mapping_lines.replace(line, mapping_line)?,
+                }
+            }
+        }
+
+        // Keep trailing empty lines, like add_vlq_input
+        let generated_line = line_offset + line_count as i64;
+        if generated_line > line_offset && generated_line >= 0 {
+            self.ensure_lines(generated_line as usize)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_parallel_decode() {
+    use crate::json::ToJsonOptions;
+
+    let mut mappings = String::new();
+    for line in 0..50 {
+        if line % 7 == 3 {
+            mappings.push(';');
+            continue;
+        }
+        // Relative fields keep going across lines, names and sources jump back and forth
+        mappings.push_str(if line % 2 == 0 {
+            "AAAAC,CAACD,EAAC,CACEA"
+        } else {
+            "GCEDC,ADAA,IAACD"
+        });
+        mappings.push(';');
+    }
+    mappings.push_str(";;");
+    let sources = vec!["a.js", "b.js"];
+    let names = vec!["x", "y"];
+
+    for (line_offset, column_offset) in [(0, 0), (3, 2), (-5, 0)] {
+        let mut sequential = SourceMap::new("/");
+        sequential
+            .add_vlq_input(
+                &mut MappingBytes::new(mappings.as_bytes()),
+                sources.clone(),
+                vec![],
+                names.clone(),
+                line_offset,
+                column_offset,
+            )
+            .unwrap();
+
+        for chunk_count in [2, 3, 16] {
+            let mut parallel = SourceMap::new("/");
+            let (source_indexes, name_indexes) = parallel
+                .add_vlq_sources(&sources, &[], names.clone())
+                .unwrap();
+            parallel
+                .add_vlq_chunks(
+                    mappings.as_bytes(),
+                    &source_indexes,
+                    &name_indexes,
+                    line_offset,
+                    column_offset,
+                    chunk_count,
+                )
+                .unwrap();
+            assert_eq!(
+                parallel.to_json(&ToJsonOptions::default()).unwrap(),
+                sequential.to_json(&ToJsonOptions::default()).unwrap()
+            );
+        }
+    }
+
+    // Errors point to the same place as a sequential decode
+    let invalid = format!("{}AAAA,AYAA;AAAA", mappings);
+    let sequential_error = SourceMap::new("/")
+        .add_vlq_input(
+            &mut MappingBytes::new(invalid.as_bytes()),
+            sources.clone(),
+            vec![],
+            names.clone(),
+            0,
+            0,
+        )
+        .unwrap_err();
+    let mut parallel = SourceMap::new("/");
+    let (source_indexes, name_indexes) = parallel
+        .add_vlq_sources(&sources, &[], names.clone())
+        .unwrap();
+    let parallel_error = parallel
+        .add_vlq_chunks(invalid.as_bytes(), &source_indexes, &name_indexes, 0, 0, 4)
+        .unwrap_err();
+    assert!(matches!(
+        parallel_error.error_type,
+        SourceMapErrorType::SourceOutOfRange
+    ));
+    assert_eq!(parallel_error.location, sequential_error.location);
+
+    // Reserved mappings are spread over the decoded lines
+    let mut reserved = SourceMap::new("/");
+    reserved.reserve_mappings(10_000);
+    let (source_indexes, name_indexes) = reserved
+        .add_vlq_sources(&sources, &[], names.clone())
+        .unwrap();
+    reserved
+        .add_vlq_chunks(mappings.as_bytes(), &source_indexes, &name_indexes, 0, 0, 4)
+        .unwrap();
+    assert!(
+        reserved
+            .inner
+            .mapping_lines
+            .get(0)
+            .unwrap()
+            .mappings
+            .capacity()
+            >= 100
+    );
+    assert!(reserved.pending_capacity.mappings < 10_000);
+}
@@ -0,0 +1,500 @@
+#[cfg(feature = "json")]
+use crate::json::ParseOptions;
+use crate::mapping::DuplicateMappingPolicy;
+use crate::names::NameNormalizer;
+use crate::observer::MutationEvent;
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+#[cfg(feature = "json")]
+use rkyv::AlignedVec;
+use std::fmt;
+use std::sync::Arc;
+
+// Decides which sources to keep, e.g. to hide everything in node_modules
+#[derive(Clone)]
+pub struct SourceFilter(Arc<dyn Fn(&str) -> bool + Send + Sync>);
+
+impl SourceFilter {
+    pub fn new<F>(keep: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(keep))
+    }
+
+    pub fn keep(&self, source: &str) -> bool {
+        (self.0)(source)
+    }
+}
+
+impl fmt::Debug for SourceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SourceFilter")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PipelineStep {
+    RetainSources(SourceFilter),
+    // Sorts the names as well, see SourceMap::sort_sources
+    SortSources,
+    NormalizeNames(NameNormalizer),
+    DedupeMappings(DuplicateMappingPolicy),
+    TrimTrailingLines,
+    ShrinkToFit,
+    // See SourceMap::set_debug_id, None removes the one the input had
+    SetDebugId(Option<String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PipelineDescription {
+    // Used by run_json
+    #[cfg(feature = "json")]
+    pub parse: ParseOptions,
+    pub steps: Vec<PipelineStep>,
+}
+
+// Runs a list of steps over a map. Consecutive steps that rewrite mappings only change
+// the sources and names tables first, the mappings themselves are then updated in one pass.
+#[derive(Debug, Clone, Default)]
+pub struct MapPipeline {
+    description: PipelineDescription,
+}
+
+// Index changes collected from consecutive steps that haven't been applied to the mappings yet
+#[derive(Default)]
+struct PendingRemap {
+    sources: Option<Vec<Option<u32>>>,
+    names: Option<Vec<u32>>,
+    dedupe: bool,
+}
+
+impl PendingRemap {
+    fn is_empty(&self) -> bool {
+        self.sources.is_none() && self.names.is_none() && !self.dedupe
+    }
+}
+
+// Chains two index tables, old -> intermediate -> new
+fn chain<T: Copy>(first: Option<Vec<T>>, second: Vec<T>, get: impl Fn(&[T], T) -> T) -> Vec<T> {
+    match first {
+        Some(first) => first.into_iter().map(|i| get(&second, i)).collect(),
+        None => second,
+    }
+}
+
+impl MapPipeline {
+    pub fn new(description: PipelineDescription) -> Self {
+        Self { description }
+    }
+
+    pub fn description(&self) -> &PipelineDescription {
+        &self.description
+    }
+
+    pub fn run(&self, sourcemap: &mut SourceMap) -> Result<(), SourceMapError> {
+        let mut pending = PendingRemap::default();
+        for step in self.description.steps.iter() {
+            match step {
+                PipelineStep::RetainSources(filter) => {
+                    let sources = sourcemap.retain_sources_table(filter)?;
+                    pending.sources = Some(chain(pending.sources, sources, |second, i| {
+                        i.and_then(|i| second[i as usize])
+                    }));
+                }
+                PipelineStep::SortSources => {
+                    let sources = sourcemap.sort_sources_table()?;
+                    pending.sources = Some(chain(pending.sources, sources, |second, i| {
+                        i.and_then(|i| second[i as usize])
+                    }));
+                    let names = sourcemap.sort_names_table();
+                    pending.names =
+                        Some(chain(pending.names, names, |second, i| second[i as usize]));
+                }
+                PipelineStep::NormalizeNames(normalizer) => {
+                    let names = sourcemap.normalize_names_table(normalizer);
+                    pending.names =
+                        Some(chain(pending.names, names, |second, i| second[i as usize]));
+                }
+                PipelineStep::DedupeMappings(policy) => {
+                    sourcemap.set_duplicate_mapping_policy(*policy);
+                    pending.dedupe = true;
+                }
+                PipelineStep::TrimTrailingLines => {
+                    apply(sourcemap, &mut pending);
+                    sourcemap.trim_trailing_lines()?;
+                }
+                PipelineStep::ShrinkToFit => {
+                    apply(sourcemap, &mut pending);
+                    sourcemap.shrink_to_fit();
+                }
+                PipelineStep::SetDebugId(debug_id) => {
+                    sourcemap.set_debug_id(debug_id.as_deref());
+                }
+            }
+            sourcemap.check_cancelled()?;
+        }
+        apply(sourcemap, &mut pending);
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn run_json(&self, project_root: &str, input: &str) -> Result<SourceMap, SourceMapError> {
+        let mut sourcemap =
+            SourceMap::from_json_with_options(project_root, input, &self.description.parse)?;
+        self.run(&mut sourcemap)?;
+        Ok(sourcemap)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn run_json_to_buffer(
+        &self,
+        project_root: &str,
+        input: &str,
+        output: &mut AlignedVec,
+    ) -> Result<(), SourceMapError> {
+        self.run_json(project_root, input)?.to_buffer(output)
+    }
+}
+
+fn apply(sourcemap: &mut SourceMap, pending: &mut PendingRemap) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let pending = std::mem::take(pending);
+    if pending.dedupe {
+        let policy = sourcemap.inner.duplicate_mapping_policy;
+        for mapping_line in sourcemap.inner.mapping_lines.lines_mut() {
+            mapping_line.ensure_sorted_with(policy);
+        }
+    }
+    sourcemap.remap_originals(pending.sources.as_deref(), pending.names.as_deref());
+}
+
+impl SourceMap {
+    // Drops the sources the filter rejects, mappings pointing to them lose their original
+    // location but stay in the map so lookups don't fall through to the previous mapping
+    pub fn retain_sources(&mut self, filter: &SourceFilter) -> Result<(), SourceMapError> {
+        let sources_indexes = self.retain_sources_table(filter)?;
+        self.remap_originals(Some(&sources_indexes), None);
+        Ok(())
+    }
+
+    // Only updates the sources and sourcesContent tables, returns the new index of every
+    // old source or None if it got dropped
+    pub(crate) fn retain_sources_table(
+        &mut self,
+        filter: &SourceFilter,
+    ) -> Result<Vec<Option<u32>>, SourceMapError> {
+        let mut sources_content = std::mem::take(self.sources_content_mut()?).into_iter();
+        let sources = std::mem::take(&mut self.inner.sources);
+        let provenance = std::mem::take(&mut self.inner.source_provenance);
+        let hashes = std::mem::take(&mut self.inner.source_hashes);
+        let mut sources_indexes = Vec::with_capacity(sources.len());
+        for (i, source) in sources.into_iter().enumerate() {
+            let content = sources_content.next();
+            if !filter.keep(&source) {
+                sources_indexes.push(None);
+                continue;
+            }
+
+            let index = self.inner.sources.len() as u32;
+            sources_indexes.push(Some(index));
+            self.inner.sources.push(source);
+            self.set_source_provenance(index, provenance.get(i).copied().unwrap_or_default());
+            if let Some(hash) = hashes.get(i).copied().flatten() {
+                self.inner.source_hashes.resize(index as usize, None);
+                self.inner.source_hashes.push(Some(hash));
+            }
+            if let Some(content) = content {
+                self.inner
+                    .sources_content
+                    .resize(self.inner.sources.len() - 1, String::new());
+                self.inner.sources_content.push(content);
+            }
+        }
+        self.remap_ignore_list(&sources_indexes);
+        Ok(sources_indexes)
+    }
+
+    // Orders sources by path and names alphabetically with the mappings reindexed, so the
+    // output doesn't depend on the order they were added in (e.g. by multiple workers)
+    pub fn sort_sources(&mut self) -> Result<(), SourceMapError> {
+        let sources_indexes = self.sort_sources_table()?;
+        let names_indexes = self.sort_names_table();
+        self.remap_originals(Some(&sources_indexes), Some(&names_indexes));
+        Ok(())
+    }
+
+    pub(crate) fn sort_sources_table(&mut self) -> Result<Vec<Option<u32>>, SourceMapError> {
+        let mut order: Vec<usize> = (0..self.inner.sources.len()).collect();
+        order.sort_by(|a, b| self.inner.sources[*a].cmp(&self.inner.sources[*b]));
+        let mut sources_indexes = vec![None; order.len()];
+        if order.iter().enumerate().all(|(new, old)| new == *old) {
+            for (i, index) in sources_indexes.iter_mut().enumerate() {
+                *index = Some(i as u32);
+            }
+            return Ok(sources_indexes);
+        }
+
+        let mut sources_content = std::mem::take(self.sources_content_mut()?);
+        let mut sources: Vec<Option<String>> = std::mem::take(&mut self.inner.sources)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let provenance = std::mem::take(&mut self.inner.source_provenance);
+        let hashes = std::mem::take(&mut self.inner.source_hashes);
+        for (index, old) in order.into_iter().enumerate() {
+            sources_indexes[old] = Some(index as u32);
+            self.inner
+                .sources
+                .push(sources[old].take().unwrap_or_default());
+            self.set_source_provenance(
+                index as u32,
+                provenance.get(old).copied().unwrap_or_default(),
+            );
+            if let Some(hash) = hashes.get(old).copied().flatten() {
+                self.inner.source_hashes.resize(index, None);
+                self.inner.source_hashes.push(Some(hash));
+            }
+            if let Some(content) = sources_content.get_mut(old) {
+                if !content.is_empty() {
+                    self.inner.sources_content.resize(index, String::new());
+                    self.inner.sources_content.push(std::mem::take(content));
+                }
+            }
+        }
+        self.remap_ignore_list(&sources_indexes);
+        Ok(sources_indexes)
+    }
+
+    // Replaces every source with what rename returns for it. Sources that end up with the same
+    // name are merged, keeping the first content that isn't empty.
+    pub fn rename_sources<F>(&mut self, rename: F) -> Result<(), SourceMapError>
+    where
+        F: FnMut(&str) -> String,
+    {
+        let sources_indexes = self.rename_sources_table(rename)?;
+        self.remap_originals(Some(&sources_indexes), None);
+        Ok(())
+    }
+
+    pub(crate) fn rename_sources_table<F>(
+        &mut self,
+        mut rename: F,
+    ) -> Result<Vec<Option<u32>>, SourceMapError>
+    where
+        F: FnMut(&str) -> String,
+    {
+        let mut sources_content = std::mem::take(self.sources_content_mut()?);
+        let sources = std::mem::take(&mut self.inner.sources);
+        let provenance = std::mem::take(&mut self.inner.source_provenance);
+        let hashes = std::mem::take(&mut self.inner.source_hashes);
+        let mut sources_indexes = Vec::with_capacity(sources.len());
+        for (i, source) in sources.iter().enumerate() {
+            let renamed = rename(source);
+            let index = match self.find_source(&renamed) {
+                Some(index) => index,
+                None => {
+                    self.inner.sources.push(renamed);
+                    self.inner.sources.len() - 1
+                }
+            };
+            sources_indexes.push(Some(index as u32));
+            self.merge_source_provenance(
+                index as u32,
+                provenance.get(i).copied().unwrap_or_default(),
+            );
+
+            let has_content = self
+                .inner
+                .sources_content
+                .get(index)
+                .is_some_and(|c| !c.is_empty());
+            if let Some(content) = sources_content.get_mut(i).filter(|c| !c.is_empty()) {
+                if !has_content {
+                    // Merged sources point back, so this might not be the last one
+                    if self.inner.sources_content.len() <= index {
+                        self.inner.sources_content.resize(index + 1, String::new());
+                    }
+                    self.inner.sources_content[index] = std::mem::take(content);
+                    if let Some(hash) = hashes.get(i).copied().flatten() {
+                        if self.inner.source_hashes.len() <= index {
+                            self.inner.source_hashes.resize(index + 1, None);
+                        }
+                        self.inner.source_hashes[index] = Some(hash);
+                    }
+                }
+            }
+        }
+        self.remap_ignore_list(&sources_indexes);
+        Ok(sources_indexes)
+    }
+
+    pub(crate) fn remap_originals(
+        &mut self,
+        sources: Option<&[Option<u32>]>,
+        names: Option<&[u32]>,
+    ) {
+        if sources.is_none() && names.is_none() {
+            return;
+        }
+
+        if sources.is_some() {
+            self.reverse_index = None;
+            self.lookup_index = None;
+        }
+        self.invalidate_vlq_cache();
+        self.notify(MutationEvent::Reset);
+        for mapping_line in self.inner.mapping_lines.lines_mut() {
+            for mapping in mapping_line.mappings.iter_mut() {
+                if let Some(original) = &mut mapping.original {
+                    if let Some(names) = names {
+                        original.name = original
+                            .name
+                            .and_then(|name| names.get(name as usize).copied());
+                    }
+                    if let Some(sources) = sources {
+                        match sources.get(original.source as usize).copied().flatten() {
+                            Some(source) => original.source = source,
+                            None => mapping.original = None,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_map_pipeline() {
+    let input = r#"{
+        "sources": ["src/a.js", "node_modules/b.js", "src/c.js"],
+        "sourcesContent": ["a", "b", "c"],
+        "names": ["_a", "a"],
+        "mappings": "AAAAA,AAAA,ECAA,ECAAC;;"
+    }"#;
+    let pipeline = MapPipeline::new(PipelineDescription {
+        parse: ParseOptions::default(),
+        steps: vec![
+            PipelineStep::RetainSources(SourceFilter::new(|s| !s.contains("node_modules"))),
+            PipelineStep::NormalizeNames(NameNormalizer::new(|n| {
+                String::from(n.trim_start_matches('_'))
+            })),
+            PipelineStep::DedupeMappings(DuplicateMappingPolicy::KeepFirst),
+            PipelineStep::TrimTrailingLines,
+            PipelineStep::SetDebugId(Some(String::from("85314830-023f-4cf1-a267-535f4e37bb17"))),
+        ],
+    });
+
+    let map = pipeline.run_json("/", input).unwrap();
+    assert_eq!(
+        map.get_debug_id(),
+        Some("85314830-023f-4cf1-a267-535f4e37bb17")
+    );
+    assert_eq!(map.get_sources(), &vec!["src/a.js", "src/c.js"]);
+    assert_eq!(map.get_sources_content(), &vec!["a", "c"]);
+    assert_eq!(map.get_names(), &vec!["a"]);
+    assert_eq!(map.inner.mapping_lines.len(), 1);
+
+    let mappings = map.get_mappings();
+    assert_eq!(mappings.len(), 3);
+    let original = mappings[0].original.unwrap();
+    assert_eq!((original.source, original.name), (0, Some(0)));
+    assert!(mappings[1].original.is_none());
+    let original = mappings[2].original.unwrap();
+    assert_eq!((original.source, original.name), (1, Some(0)));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_pipeline_sort_sources() {
+    let input = r#"{
+        "sources": ["b.js", "a.js"],
+        "names": ["_render", "app"],
+        "mappings": "AAAAA,IAAAC,ICAAD"
+    }"#;
+    let pipeline = MapPipeline::new(PipelineDescription {
+        parse: ParseOptions::default(),
+        steps: vec![
+            PipelineStep::NormalizeNames(NameNormalizer::new(|n| {
+                String::from(n.trim_start_matches('_'))
+            })),
+            PipelineStep::SortSources,
+        ],
+    });
+
+    let map = pipeline.run_json("/", input).unwrap();
+    assert_eq!(map.get_sources(), &vec!["a.js", "b.js"]);
+    assert_eq!(map.get_names(), &vec!["app", "render"]);
+    let originals: Vec<(u32, Option<u32>)> = map
+        .get_mappings()
+        .iter()
+        .map(|m| (m.original.unwrap().source, m.original.unwrap().name))
+        .collect();
+    assert_eq!(originals, vec![(1, Some(1)), (1, Some(0)), (0, Some(1))]);
+}
+
+#[test]
+fn test_sort_sources() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let c = map.add_source("c.js");
+    let a = map.add_source("a.js");
+    let b = map.add_source("b.js");
+    let render = map.add_name("render");
+    let app = map.add_name("app");
+    map.set_source_content(a as usize, "a").unwrap();
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, c, Some(render))));
+    map.add_mapping(0, 4, Some(OriginalLocation::new(0, 0, a, Some(app))));
+    map.add_mapping(0, 8, Some(OriginalLocation::new(0, 0, b, None)));
+    map.sort_sources().unwrap();
+
+    assert_eq!(map.get_sources(), &vec!["a.js", "b.js", "c.js"]);
+    assert_eq!(map.get_names(), &vec!["app", "render"]);
+    let names: Vec<Option<u32>> = map
+        .get_mappings()
+        .iter()
+        .map(|m| m.original.unwrap().name)
+        .collect();
+    assert_eq!(names, vec![Some(1), Some(0), None]);
+    assert_eq!(map.get_source_content(0).unwrap(), "a");
+    assert_eq!(map.get_sources_content().len(), 1);
+    let sources: Vec<u32> = map
+        .get_mappings()
+        .iter()
+        .map(|m| m.original.unwrap().source)
+        .collect();
+    assert_eq!(sources, vec![2, 0, 1]);
+}
+
+#[test]
+fn test_rename_sources() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("lib/a.js");
+    let b = map.add_source("lib/./b.js");
+    let c = map.add_source("lib/c.js");
+    map.set_source_content(c as usize, "c").unwrap();
+    map.add_ignored_source(c).unwrap();
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, a, None)));
+    map.add_mapping(0, 4, Some(OriginalLocation::new(0, 0, b, None)));
+    map.add_mapping(0, 8, Some(OriginalLocation::new(0, 0, c, None)));
+    // b and c end up as the same source
+    map.rename_sources(|source| source.replace("lib/", "src/").replace("b.js", "c.js"))
+        .unwrap();
+
+    assert_eq!(map.get_sources(), &vec!["src/a.js", "src/c.js"]);
+    assert_eq!(map.get_source_content(1).unwrap(), "c");
+    assert_eq!(map.get_ignore_list(), &[1]);
+    let sources: Vec<u32> = map
+        .get_mappings()
+        .iter()
+        .map(|m| m.original.unwrap().source)
+        .collect();
+    assert_eq!(sources, vec![0, 1, 1]);
+}
@@ -0,0 +1,11 @@
+// The types most code needs, `use speedy_parcel_sourcemap::prelude::*;`.
+// Everything in here is part of the stable API, removing or renaming any of it is a breaking
+// change. tests/public_api.rs lists the full public surface.
+#[cfg(feature = "json")]
+pub use crate::json::ParseOptions;
+pub use crate::lookup::LookupOptions;
+pub use crate::mapping::{
+    DuplicateMappingPolicy, LookupPosition, Mapping, OneBasedPosition, OriginalLocation, Position,
+};
+pub use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+pub use crate::SourceMap;
@@ -0,0 +1,148 @@
+#[cfg(feature = "json")]
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+use std::env;
+#[cfg(feature = "json")]
+use std::io;
+use std::time::{Duration, Instant};
+
+// Setting this env var to anything but "0" or "false" enables profiling
+pub const PROFILE_ENV_VAR: &str = "PARCEL_SOURCEMAP_PROFILE";
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct StageProfile {
+    pub name: String,
+    pub duration_ms: f64,
+    // Size of the map after this stage
+    pub mappings: usize,
+    pub sources: usize,
+    pub names: usize,
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileReport<'a> {
+    total_ms: f64,
+    stages: &'a [StageProfile],
+}
+
+// Times a sequence of operations on a map (parse -> extend -> optimize -> emit, ...)
+// and reports the duration and map size of every stage as JSON
+#[derive(Debug, Default, Clone)]
+pub struct PipelineProfiler {
+    enabled: bool,
+    stages: Vec<StageProfile>,
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl PipelineProfiler {
+    // Enabled based on PROFILE_ENV_VAR
+    pub fn new() -> Self {
+        let enabled = env::var(PROFILE_ENV_VAR).is_ok_and(|v| v != "0" && v != "false");
+        Self::with_enabled(enabled)
+    }
+
+    pub fn with_enabled(enabled: bool) -> Self {
+        Self {
+            enabled,
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn stage<T, F>(&mut self, name: &str, sourcemap: &mut SourceMap, f: F) -> T
+    where
+        F: FnOnce(&mut SourceMap) -> T,
+    {
+        if !self.enabled {
+            return f(sourcemap);
+        }
+
+        let start = Instant::now();
+        let result = f(sourcemap);
+        let duration = start.elapsed();
+        self.stages.push(StageProfile {
+            name: String::from(name),
+            duration_ms: duration_ms(duration),
+            mappings: sourcemap.inner.mapping_lines.mappings_len(),
+            sources: sourcemap.inner.sources.len(),
+            names: sourcemap.inner.names.len(),
+        });
+        result
+    }
+
+    pub fn stages(&self) -> &[StageProfile] {
+        &self.stages
+    }
+
+    // None when profiling is disabled
+    #[cfg(feature = "json")]
+    pub fn report_json(&self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        let report = ProfileReport {
+            total_ms: self.stages.iter().map(|s| s.duration_ms).sum(),
+            stages: &self.stages,
+        };
+        serde_json::to_string(&report).ok()
+    }
+
+    // Writes the report to the writer (a log file, ...) when profiling is enabled, nothing
+    // otherwise
+    #[cfg(feature = "json")]
+    pub fn write_report<W: io::Write>(&self, output: &mut W) -> Result<(), SourceMapError> {
+        if let Some(report) = self.report_json() {
+            output.write_all(report.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_pipeline_profiler() {
+    let mut map = SourceMap::new("/");
+    let mut profiler = PipelineProfiler::with_enabled(true);
+    profiler
+        .stage("parse", &mut map, |map| {
+            map.add_vlq_map(b"AAAA;AACA", vec!["a.js"], vec![], vec!["foo"], 0, 0)
+        })
+        .unwrap();
+    let mut output = Vec::new();
+    profiler
+        .stage("emit", &mut map, |map| map.write_vlq(&mut output))
+        .unwrap();
+
+    let stages = profiler.stages();
+    assert_eq!(stages.len(), 2);
+    assert_eq!(stages[0].name, "parse");
+    assert_eq!(stages[0].mappings, 2);
+    assert_eq!(stages[0].names, 1);
+    let report = profiler.report_json().unwrap();
+    assert!(report.contains("\"stages\":[{\"name\":\"parse\""));
+    let mut written = Vec::new();
+    profiler.write_report(&mut written).unwrap();
+    assert_eq!(written, report.as_bytes());
+
+    let mut disabled = PipelineProfiler::with_enabled(false);
+    disabled.stage("parse", &mut map, |_| ());
+    assert!(disabled.stages().is_empty());
+    assert!(disabled.report_json().is_none());
+    let mut written = Vec::new();
+    disabled.write_report(&mut written).unwrap();
+    assert!(written.is_empty());
+}
@@ -0,0 +1,160 @@
+use crate::SourceMap;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum ProgressStage {
+    Parse,
+    Serialize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct Progress {
+    pub stage: ProgressStage,
+    pub processed: usize,
+    // Not known upfront while parsing
+    pub total: Option<usize>,
+}
+
+// Gets called every `every` mappings while parsing or serializing, and once more when done
+#[derive(Clone)]
+pub struct ProgressReporter {
+    callback: Arc<dyn Fn(Progress) + Send + Sync>,
+    every: usize,
+}
+
+impl ProgressReporter {
+    pub fn new<F>(every: usize, callback: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+            every: every.max(1),
+        }
+    }
+
+    pub fn every(&self) -> usize {
+        self.every
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("every", &self.every)
+            .finish()
+    }
+}
+
+// Counts processed mappings for a single operation and throttles the callbacks
+pub(crate) struct ProgressTracker {
+    reporter: Option<ProgressReporter>,
+    stage: ProgressStage,
+    total: Option<usize>,
+    processed: usize,
+    next_report: usize,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(
+        reporter: Option<ProgressReporter>,
+        stage: ProgressStage,
+        total: Option<usize>,
+    ) -> Self {
+        let next_report = reporter.as_ref().map_or(usize::MAX, |r| r.every);
+        Self {
+            reporter,
+            stage,
+            total,
+            processed: 0,
+            next_report,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn advance(&mut self, count: usize) {
+        self.processed += count;
+        if self.processed >= self.next_report {
+            self.report();
+        }
+    }
+
+    pub(crate) fn finish(mut self) {
+        self.total = Some(self.processed);
+        self.report();
+    }
+
+    fn report(&mut self) {
+        if let Some(reporter) = &self.reporter {
+            (reporter.callback)(Progress {
+                stage: self.stage,
+                processed: self.processed,
+                total: self.total,
+            });
+            self.next_report = self.processed - self.processed % reporter.every + reporter.every;
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn set_progress_reporter(&mut self, reporter: Option<ProgressReporter>) {
+        self.progress_reporter = reporter;
+    }
+
+    pub(crate) fn progress_tracker(
+        &self,
+        stage: ProgressStage,
+        total: Option<usize>,
+    ) -> ProgressTracker {
+        ProgressTracker::new(self.progress_reporter.clone(), stage, total)
+    }
+}
+
+#[test]
+fn test_progress_reporter() {
+    use std::sync::Mutex;
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let mut map = SourceMap::new("/");
+    let reports_clone = reports.clone();
+    map.set_progress_reporter(Some(ProgressReporter::new(2, move |progress| {
+        reports_clone.lock().unwrap().push(progress);
+    })));
+
+    map.add_vlq_map(
+        b"AAAA,CAAC,CAAC;AAAA,CAAC",
+        vec!["a.js"],
+        vec![],
+        vec![],
+        0,
+        0,
+    )
+    .unwrap();
+    let parsed: Vec<usize> = reports
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|p| p.processed)
+        .collect();
+    assert_eq!(parsed, vec![2, 4, 5]);
+
+    reports.lock().unwrap().clear();
+    let mut output = Vec::new();
+    map.write_vlq(&mut output).unwrap();
+    let serialized = reports.lock().unwrap().clone();
+    assert_eq!(serialized.len(), 3);
+    assert!(serialized
+        .iter()
+        .all(|p| p.stage == ProgressStage::Serialize && p.total == Some(5)));
+}
@@ -0,0 +1,84 @@
+use crate::SourceMap;
+use rkyv::{Archive, Deserialize, Serialize};
+
+// Where a source came from, so reporting can tell project files apart from placeholders
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceProvenance {
+    // Added directly using add_source (or add_mapping and friends)
+    #[default]
+    Added,
+    // Listed in the sources of a parsed map
+    Declared,
+    // Made up while repairing a map, see set_repair_missing_sources and
+    // NameWithoutSourcePolicy::SyntheticSource
+    Synthesized,
+}
+
+impl SourceProvenance {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceProvenance::Added => "added",
+            SourceProvenance::Declared => "declared",
+            SourceProvenance::Synthesized => "synthesized",
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn get_source_provenance(&self, index: u32) -> SourceProvenance {
+        // Only sources that aren't Added get an entry, so the list can be shorter
+        self.inner
+            .source_provenance
+            .get(index as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_source_provenance(&mut self, index: u32, provenance: SourceProvenance) {
+        let provenance_list = &mut self.inner.source_provenance;
+        if provenance_list.len() <= index as usize {
+            if provenance == SourceProvenance::Added {
+                return;
+            }
+            provenance_list.resize(index as usize + 1, SourceProvenance::Added);
+        }
+        provenance_list[index as usize] = provenance;
+    }
+
+    // A source that is known for a more specific reason keeps it when it is added again
+    pub(crate) fn merge_source_provenance(&mut self, index: u32, provenance: SourceProvenance) {
+        if self.get_source_provenance(index) == SourceProvenance::Added {
+            self.set_source_provenance(index, provenance);
+        }
+    }
+}
+
+#[test]
+fn test_source_provenance() {
+    let mut map = SourceMap::new("/");
+    let added = map.add_source("added.js");
+    map.set_repair_missing_sources(true);
+    map.add_vlq_map(b"AAAA,CCAA", vec!["a.js"], vec![], vec![], 0, 0)
+        .unwrap();
+
+    assert_eq!(map.get_source_provenance(added), SourceProvenance::Added);
+    let declared = map.get_source_index("a.js").unwrap().unwrap();
+    assert_eq!(
+        map.get_source_provenance(declared),
+        SourceProvenance::Declared
+    );
+    assert_eq!(map.get_source(2).unwrap(), "<unknown:1>");
+    assert_eq!(map.get_source_provenance(2), SourceProvenance::Synthesized);
+
+    let mut merged = SourceMap::new("/");
+    merged.add_sourcemap(&mut map.clone(), 0).unwrap();
+    assert_eq!(
+        merged.get_source_provenance(2),
+        SourceProvenance::Synthesized
+    );
+
+    let mut buffer = rkyv::AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    let map = SourceMap::from_buffer("/", &buffer).unwrap();
+    assert_eq!(map.get_source_provenance(2), SourceProvenance::Synthesized);
+}
@@ -0,0 +1,391 @@
+use crate::mapping::{Mapping, Position};
+use crate::sourcemap_error::SourceMapError;
+use crate::SourceMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub type FileNormalizer = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+// Default normalizer, drops the query string and hash of a generated file url
+pub fn strip_query_and_hash(file: &str) -> String {
+    let end = file.find(['?', '#']).unwrap_or(file.len());
+    String::from(&file[..end])
+}
+
+// Holds the maps of multiple generated files and routes lookups to the right one
+pub struct MapRegistry {
+    maps: HashMap<String, SourceMap>,
+    normalizer: FileNormalizer,
+}
+
+impl MapRegistry {
+    pub fn new() -> Self {
+        Self::with_normalizer(Box::new(strip_query_and_hash))
+    }
+
+    // The normalizer is applied to every file url before it is used as a key,
+    // e.g. to map hashed bundle names back to a stable name
+    pub fn with_normalizer(normalizer: FileNormalizer) -> Self {
+        Self {
+            maps: HashMap::new(),
+            normalizer,
+        }
+    }
+
+    pub fn normalize(&self, file: &str) -> String {
+        (self.normalizer)(file)
+    }
+
+    pub fn insert(&mut self, file: &str, sourcemap: SourceMap) -> Option<SourceMap> {
+        let key = self.normalize(file);
+        self.maps.insert(key, sourcemap)
+    }
+
+    // For code evaluated with `//# sourceURL=`, e.g. HMR updates. The wrapper around the
+    // module (`prologue`, use Position::end_of on the wrapper code) shifts every position,
+    // it gets applied on top of the generated offset the map already has.
+    pub fn insert_eval(
+        &mut self,
+        source_url: &str,
+        mut sourcemap: SourceMap,
+        prologue: Position,
+    ) -> Option<SourceMap> {
+        let offset = prologue.then(sourcemap.get_generated_offset());
+        sourcemap.set_generated_offset(offset.line, offset.column);
+        self.insert(source_url, sourcemap)
+    }
+
+    pub fn remove(&mut self, file: &str) -> Option<SourceMap> {
+        let key = self.normalize(file);
+        self.maps.remove(&key)
+    }
+
+    pub fn get(&self, file: &str) -> Option<&SourceMap> {
+        self.maps.get(&self.normalize(file))
+    }
+
+    pub fn get_mut(&mut self, file: &str) -> Option<&mut SourceMap> {
+        let key = self.normalize(file);
+        self.maps.get_mut(&key)
+    }
+
+    pub fn contains(&self, file: &str) -> bool {
+        self.maps.contains_key(&self.normalize(file))
+    }
+
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    pub fn lookup(
+        &mut self,
+        file: &str,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Option<Mapping> {
+        self.get_mut(file)?
+            .find_closest_mapping(generated_line, generated_column)
+    }
+}
+
+impl Default for MapRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MapRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapRegistry")
+            .field("maps", &self.maps)
+            .finish()
+    }
+}
+
+// Fetches maps for a LoadingMapRegistry (from disk, S3, http, ...) on a cache miss
+pub trait MapLoader {
+    // Ok(None) means there is no map for this file, which is cached as well
+    fn load(&self, release: &str, file: &str) -> Result<Option<SourceMap>, SourceMapError>;
+}
+
+struct CacheEntry {
+    // None if the loader had no map for the file
+    sourcemap: Option<Box<SourceMap>>,
+    cached_at: Instant,
+    // Value of LoadingMapRegistry::clock when the entry was last looked up
+    last_used: u64,
+}
+
+// Two-level (release -> generated file) registry that loads maps lazily using a MapLoader
+pub struct LoadingMapRegistry<L: MapLoader> {
+    loader: L,
+    releases: HashMap<String, HashMap<String, CacheEntry>>,
+    normalizer: FileNormalizer,
+    // How long loaded maps and misses stay cached, None means forever
+    pub ttl: Option<Duration>,
+    pub negative_ttl: Option<Duration>,
+    // Entries (maps and misses) kept at most, the least recently used ones are evicted first
+    pub max_entries: Option<usize>,
+    clock: u64,
+}
+
+impl<L: MapLoader> LoadingMapRegistry<L> {
+    pub fn new(loader: L) -> Self {
+        Self::with_normalizer(loader, Box::new(strip_query_and_hash))
+    }
+
+    pub fn with_normalizer(loader: L, normalizer: FileNormalizer) -> Self {
+        Self {
+            loader,
+            releases: HashMap::new(),
+            normalizer,
+            ttl: None,
+            negative_ttl: None,
+            max_entries: None,
+            clock: 0,
+        }
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        let ttl = match entry.sourcemap {
+            Some(_) => self.ttl,
+            None => self.negative_ttl,
+        };
+        ttl.is_some_and(|ttl| entry.cached_at.elapsed() >= ttl)
+    }
+
+    // Number of cached maps and misses, expired ones included until they are evicted
+    pub fn len(&self) -> usize {
+        self.releases.values().map(|files| files.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Drops the entries that outlived their ttl, they would be loaded again on the next lookup
+    pub fn evict_expired(&mut self) {
+        let mut releases = std::mem::take(&mut self.releases);
+        for files in releases.values_mut() {
+            files.retain(|_, entry| !self.is_expired(entry));
+        }
+        releases.retain(|_, files| !files.is_empty());
+        self.releases = releases;
+    }
+
+    // Makes room for one more entry within max_entries
+    fn evict_for_insert(&mut self) {
+        let max_entries = match self.max_entries {
+            Some(max_entries) => max_entries,
+            None => return,
+        };
+        if self.len() < max_entries {
+            return;
+        }
+
+        self.evict_expired();
+        while self.len() >= max_entries.max(1) {
+            let oldest = self
+                .releases
+                .iter()
+                .flat_map(|(release, files)| {
+                    files
+                        .iter()
+                        .map(move |(file, entry)| (entry.last_used, release, file))
+                })
+                .min()
+                .map(|(_, release, file)| (release.clone(), file.clone()));
+            match oldest {
+                Some((release, file)) => self.remove_entry(&release, &file),
+                None => break,
+            }
+        }
+    }
+
+    fn remove_entry(&mut self, release: &str, key: &str) {
+        if let Some(files) = self.releases.get_mut(release) {
+            files.remove(key);
+            if files.is_empty() {
+                self.releases.remove(release);
+            }
+        }
+    }
+
+    pub fn get_or_load(
+        &mut self,
+        release: &str,
+        file: &str,
+    ) -> Result<Option<&mut SourceMap>, SourceMapError> {
+        let key = (self.normalizer)(file);
+        let cached = self
+            .releases
+            .get(release)
+            .and_then(|files| files.get(&key))
+            .is_some_and(|entry| !self.is_expired(entry));
+
+        self.clock += 1;
+        if !cached {
+            let sourcemap = self.loader.load(release, &key)?;
+            self.remove_entry(release, &key);
+            self.evict_for_insert();
+            let entry = CacheEntry {
+                sourcemap: sourcemap.map(Box::new),
+                cached_at: Instant::now(),
+                last_used: self.clock,
+            };
+            self.releases
+                .entry(String::from(release))
+                .or_default()
+                .insert(key.clone(), entry);
+        }
+
+        let clock = self.clock;
+        Ok(
+            match self.releases.get_mut(release).and_then(|f| f.get_mut(&key)) {
+                Some(entry) => {
+                    entry.last_used = clock;
+                    entry.sourcemap.as_deref_mut()
+                }
+                None => None,
+            },
+        )
+    }
+
+    pub fn lookup(
+        &mut self,
+        release: &str,
+        file: &str,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Result<Option<Mapping>, SourceMapError> {
+        let sourcemap = self.get_or_load(release, file)?;
+        Ok(sourcemap.and_then(|s| s.find_closest_mapping(generated_line, generated_column)))
+    }
+
+    pub fn invalidate(&mut self, release: &str, file: &str) {
+        let key = (self.normalizer)(file);
+        self.remove_entry(release, &key);
+    }
+
+    pub fn invalidate_release(&mut self, release: &str) {
+        self.releases.remove(release);
+    }
+}
+
+#[test]
+fn test_registry_lookup() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(3, 2, source, None)));
+
+    let mut registry = MapRegistry::new();
+    registry.insert("http://localhost/main.js", map);
+    let found = registry
+        .lookup("http://localhost/main.js?v=3#top", 0, 10)
+        .unwrap();
+    assert_eq!(found.original.unwrap().original_line, 3);
+    assert!(registry.lookup("http://localhost/other.js", 0, 0).is_none());
+}
+
+#[test]
+fn test_registry_eval() {
+    use crate::mapping::OriginalLocation;
+
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, source, None)));
+    map.add_mapping(1, 2, Some(OriginalLocation::new(1, 4, source, None)));
+
+    let prologue = Position::end_of("(function (module, exports) {\n  ");
+    assert_eq!(prologue, Position::new(1, 2));
+
+    let mut registry = MapRegistry::new();
+    registry.insert_eval("webpack-internal:///./a.js", map, prologue);
+    let found = registry.lookup("webpack-internal:///./a.js", 1, 2).unwrap();
+    assert_eq!(found.original.unwrap().original_line, 0);
+    let found = registry.lookup("webpack-internal:///./a.js", 2, 2).unwrap();
+    assert_eq!(found.original.unwrap().original_line, 1);
+}
+
+#[test]
+fn test_loading_registry_negative_cache() {
+    use crate::mapping::OriginalLocation;
+    use std::cell::Cell;
+
+    struct CountingLoader {
+        loads: Cell<u32>,
+    }
+
+    impl MapLoader for CountingLoader {
+        fn load(&self, release: &str, file: &str) -> Result<Option<SourceMap>, SourceMapError> {
+            self.loads.set(self.loads.get() + 1);
+            if release != "v1" || file != "main.js" {
+                return Ok(None);
+            }
+
+            let mut map = SourceMap::new("/");
+            let source = map.add_source("a.js");
+            map.add_mapping(0, 0, Some(OriginalLocation::new(1, 0, source, None)));
+            Ok(Some(map))
+        }
+    }
+
+    let mut registry = LoadingMapRegistry::new(CountingLoader {
+        loads: Cell::new(0),
+    });
+    assert!(registry.lookup("v1", "main.js?x", 0, 0).unwrap().is_some());
+    assert!(registry.lookup("v1", "main.js", 0, 4).unwrap().is_some());
+    assert!(registry.lookup("v1", "missing.js", 0, 0).unwrap().is_none());
+    assert!(registry.lookup("v1", "missing.js", 0, 0).unwrap().is_none());
+    assert_eq!(registry.loader.loads.get(), 2);
+
+    registry.negative_ttl = Some(Duration::from_secs(0));
+    assert!(registry.lookup("v1", "missing.js", 0, 0).unwrap().is_none());
+    assert_eq!(registry.loader.loads.get(), 3);
+}
+
+#[test]
+fn test_loading_registry_eviction() {
+    use std::cell::RefCell;
+
+    struct RecordingLoader {
+        loads: RefCell<Vec<String>>,
+    }
+
+    impl MapLoader for RecordingLoader {
+        fn load(&self, release: &str, file: &str) -> Result<Option<SourceMap>, SourceMapError> {
+            self.loads
+                .borrow_mut()
+                .push(format!("{}/{}", release, file));
+            Ok(Some(SourceMap::new("/")))
+        }
+    }
+
+    let mut registry = LoadingMapRegistry::new(RecordingLoader {
+        loads: RefCell::new(Vec::new()),
+    });
+    registry.max_entries = Some(2);
+    registry.get_or_load("v1", "a.js").unwrap();
+    registry.get_or_load("v1", "b.js").unwrap();
+    // a.js is used more recently than b.js, so b.js goes when c.js gets loaded
+    registry.get_or_load("v1", "a.js").unwrap();
+    registry.get_or_load("v2", "c.js").unwrap();
+    assert_eq!(registry.len(), 2);
+    registry.get_or_load("v1", "a.js").unwrap();
+    registry.get_or_load("v1", "b.js").unwrap();
+    assert_eq!(
+        *registry.loader.loads.borrow(),
+        vec!["v1/a.js", "v1/b.js", "v2/c.js", "v1/b.js"]
+    );
+
+    registry.ttl = Some(Duration::from_secs(0));
+    registry.evict_expired();
+    assert!(registry.is_empty());
+}
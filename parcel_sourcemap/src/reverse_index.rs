@@ -0,0 +1,93 @@
+use crate::mapping_lines::MappingLines;
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ReverseMapping {
+    pub original_line: u32,
+    pub original_column: u32,
+    pub generated_line: u32,
+    pub generated_column: u32,
+}
+
+// Original -> generated index, one list per source sorted by original position
+#[derive(Archive, Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ReverseIndex {
+    pub sources: Vec<Vec<ReverseMapping>>,
+}
+
+impl ReverseIndex {
+    pub fn heap_size(&self) -> usize {
+        self.sources.capacity() * std::mem::size_of::<Vec<ReverseMapping>>()
+            + self
+                .sources
+                .iter()
+                .map(|mappings| mappings.capacity() * std::mem::size_of::<ReverseMapping>())
+                .sum::<usize>()
+    }
+
+    pub fn build(mapping_lines: &MappingLines, sources_len: usize) -> Self {
+        let mut sources: Vec<Vec<ReverseMapping>> = vec![Vec::new(); sources_len];
+        for (generated_line, mapping_line) in mapping_lines.iter() {
+            for mapping in mapping_line.mappings.iter() {
+                if let Some(original) = mapping.original {
+                    let source = original.source as usize;
+                    if source >= sources.len() {
+                        sources.resize(source + 1, Vec::new());
+                    }
+                    sources[source].push(ReverseMapping {
+                        original_line: original.original_line,
+                        original_column: original.original_column,
+                        generated_line: generated_line as u32,
+                        generated_column: mapping.generated_column,
+                    });
+                }
+            }
+        }
+
+        let mut reverse_index = Self { sources };
+        reverse_index.sort();
+        reverse_index
+    }
+
+    // Indexes read from a buffer are not guaranteed to be sorted
+    pub fn is_sorted(&self) -> bool {
+        self.sources.iter().all(|source_mappings| {
+            source_mappings
+                .windows(2)
+                .all(|w| sort_key(&w[0]) <= sort_key(&w[1]))
+        })
+    }
+
+    pub fn sort(&mut self) {
+        for source_mappings in self.sources.iter_mut() {
+            source_mappings.sort_by_key(sort_key);
+        }
+    }
+
+    // All entries of a source that map to exactly this original position
+    pub fn generated_locations(
+        &self,
+        source: u32,
+        original_line: u32,
+        original_column: u32,
+    ) -> &[ReverseMapping] {
+        let source_mappings = match self.sources.get(source as usize) {
+            Some(source_mappings) => source_mappings,
+            None => return &[],
+        };
+
+        let key = (original_line, original_column);
+        let start = source_mappings.partition_point(|m| (m.original_line, m.original_column) < key);
+        let end = source_mappings.partition_point(|m| (m.original_line, m.original_column) <= key);
+        &source_mappings[start..end]
+    }
+}
+
+fn sort_key(m: &ReverseMapping) -> (u32, u32, u32, u32) {
+    (
+        m.original_line,
+        m.original_column,
+        m.generated_line,
+        m.generated_column,
+    )
+}
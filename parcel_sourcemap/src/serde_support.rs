@@ -0,0 +1,107 @@
+use crate::json::{JsonSourceMap, ParseOptions, ToJsonOptions};
+use crate::SourceMap;
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+
+// The fields write_json writes, in the same order, borrowed from its output
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WrittenMap<'a> {
+    version: u32,
+    #[serde(borrow, skip_serializing_if = "Option::is_none")]
+    source_root: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    sources: Vec<Cow<'a, str>>,
+    #[serde(borrow)]
+    sources_content: Vec<Option<Cow<'a, str>>>,
+    #[serde(borrow)]
+    names: Vec<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ignore_list: Option<Vec<u32>>,
+    #[serde(borrow, skip_serializing_if = "Option::is_none")]
+    debug_id: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    mappings: Cow<'a, str>,
+}
+
+// A map is serialized as the regular v3 source map write_json writes
+impl Serialize for SourceMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // write_json sorts the lines of the map it writes, serializing can't change this one
+        let mut map = self.clone();
+        let mut output = Vec::new();
+        map.write_json(&mut output, &ToJsonOptions::default())
+            .map_err(S::Error::custom)?;
+        let written: WrittenMap = serde_json::from_slice(&output).map_err(S::Error::custom)?;
+        written.serialize(serializer)
+    }
+}
+
+// Reads a map like from_json does, sources are kept as they are written (relative to "/")
+impl<'de> Deserialize<'de> for SourceMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = JsonSourceMap::deserialize(deserializer)?;
+        SourceMap::from_json_source_map("/", &json, &ParseOptions::default())
+            .map_err(D::Error::custom)
+    }
+}
+
+#[test]
+fn test_serde_round_trip() {
+    use crate::mapping::{Mapping, OriginalLocation};
+
+    #[derive(Serialize, Deserialize)]
+    struct CacheEntry {
+        hash: String,
+        map: SourceMap,
+    }
+
+    let input = r#"{"version":3,"sourceRoot":"src","sources":["a.js","b.js"],"sourcesContent":["let a\n\"a\"",null],"names":["a"],"ignoreList":[1],"debugId":"85314830-023f-4cf1-a267-535f4e37bb17","mappings":"AAAAA,EACA;;ACAA"}"#;
+    let mut map = SourceMap::from_json("/", input).unwrap();
+    map.add_mapping(0, 1, Some(OriginalLocation::new(0, 1, 0, None)));
+
+    let json = serde_json::to_string(&CacheEntry {
+        hash: String::from("abc"),
+        map: map.clone(),
+    })
+    .unwrap();
+    let expected = map.to_json(&ToJsonOptions::default()).unwrap();
+    assert_eq!(json, format!(r#"{{"hash":"abc","map":{}}}"#, expected));
+
+    let mut entry: CacheEntry = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        entry.map.to_json(&ToJsonOptions::default()).unwrap(),
+        expected
+    );
+    assert_eq!(entry.map.get_source_content(0).unwrap(), "let a\n\"a\"");
+    assert_eq!(entry.map.get_source_root(), Some("src"));
+    assert_eq!(entry.map.get_ignore_list(), &[1]);
+
+    assert!(serde_json::from_str::<SourceMap>(r#"{"mappings":"AAAA"}"#).is_err());
+
+    // Index maps are read like from_json reads them
+    let index_map = r#"{"version":3,"sections":[{"offset":{"line":1,"column":0},"map":{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}}]}"#;
+    let mut map: SourceMap = serde_json::from_str(index_map).unwrap();
+    assert_eq!(
+        map.to_json(&ToJsonOptions::default()).unwrap(),
+        SourceMap::from_json("/", index_map)
+            .unwrap()
+            .to_json(&ToJsonOptions::default())
+            .unwrap()
+    );
+
+    let mapping = Mapping {
+        generated_line: 1,
+        generated_column: 2,
+        original: Some(OriginalLocation::new(3, 4, 0, None)),
+    };
+    let json = serde_json::to_string(&mapping).unwrap();
+    assert_eq!(
+        json,
+        r#"{"generatedLine":1,"generatedColumn":2,"original":{"originalLine":3,"originalColumn":4,"source":0,"name":null}}"#
+    );
+    let mapping: Mapping = serde_json::from_str(&json).unwrap();
+    assert_eq!(mapping.original.unwrap().original_line, 3);
+}
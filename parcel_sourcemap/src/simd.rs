@@ -0,0 +1,265 @@
+// Decodes a segment of a mappings string 16 bytes at a time: turning base64 characters into
+// sextets and finding the separator that ends the segment is done with SSE2 on x86_64 and
+// NEON on aarch64, both are always available there. Other targets use the same code with a
+// scalar block. Anything out of the ordinary (invalid characters, huge values, too many or
+// too few fields) returns None, so the regular decoder handles it and reports the error.
+
+const BLOCK_SIZE: usize = 16;
+// Values that need more sextets are left to the regular decoder, which checks for overflow
+const MAX_SHIFT: u32 = 30;
+
+struct Block {
+    sextets: [u8; BLOCK_SIZE],
+    // Index of the first ',' or ';', BLOCK_SIZE if there is none
+    separator: usize,
+    // Index of the first byte that is neither base64 nor a separator
+    invalid: usize,
+}
+
+// Reads a segment from the start of bytes, returns the number of fields and the length
+pub(crate) fn read_segment(bytes: &[u8], fields: &mut [i64; 5]) -> Option<(usize, usize)> {
+    let mut count = 0;
+    let mut accum: u64 = 0;
+    let mut shift = 0;
+    let mut position = 0;
+    loop {
+        let block = load(&bytes[position..]);
+        let end = block.separator;
+        if block.invalid < end {
+            return None;
+        }
+
+        for sextet in &block.sextets[..end] {
+            accum |= ((sextet & 31) as u64) << shift;
+            if sextet & 32 != 0 {
+                shift += 5;
+                if shift > MAX_SHIFT {
+                    return None;
+                }
+            } else {
+                if count == fields.len() {
+                    return None;
+                }
+                // The low bit holds the sign
+                let value = (accum >> 1) as i64;
+                fields[count] = if accum & 1 != 0 { -value } else { value };
+                count += 1;
+                accum = 0;
+                shift = 0;
+            }
+        }
+
+        position += end;
+        if end < BLOCK_SIZE {
+            break;
+        }
+    }
+
+    // A value that isn't finished or a segment with 3 fields is invalid
+    if shift != 0 || count == 0 || count == 3 {
+        return None;
+    }
+    Some((count, position))
+}
+
+// The end of the input is padded with separators, so it ends the segment like one
+fn load(bytes: &[u8]) -> Block {
+    let mut chunk = [b';'; BLOCK_SIZE];
+    let len = bytes.len().min(BLOCK_SIZE);
+    chunk[..len].copy_from_slice(&bytes[..len]);
+    classify(&chunk)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn classify(chunk: &[u8; BLOCK_SIZE]) -> Block {
+    unsafe { classify_sse2(chunk) }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline]
+fn classify(chunk: &[u8; BLOCK_SIZE]) -> Block {
+    unsafe { classify_neon(chunk) }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[inline]
+fn classify(chunk: &[u8; BLOCK_SIZE]) -> Block {
+    classify_scalar(chunk)
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn classify_sse2(chunk: &[u8; BLOCK_SIZE]) -> Block {
+    use std::arch::x86_64::*;
+
+    let input = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+    // Bytes above 127 are negative, so they are never in range
+    let in_range = |low: u8, high: u8| {
+        _mm_and_si128(
+            _mm_cmpgt_epi8(input, _mm_set1_epi8(low as i8 - 1)),
+            _mm_cmplt_epi8(input, _mm_set1_epi8(high as i8 + 1)),
+        )
+    };
+    let equals = |byte: u8| _mm_cmpeq_epi8(input, _mm_set1_epi8(byte as i8));
+
+    let upper = in_range(b'A', b'Z');
+    let lower = in_range(b'a', b'z');
+    let digit = in_range(b'0', b'9');
+    let plus = equals(b'+');
+    let slash = equals(b'/');
+    let offset = |mask: __m128i, offset: i8| _mm_and_si128(mask, _mm_set1_epi8(offset));
+    let offsets = _mm_or_si128(
+        _mm_or_si128(offset(upper, -65), offset(lower, -71)),
+        _mm_or_si128(
+            offset(digit, 4),
+            _mm_or_si128(offset(plus, 19), offset(slash, 16)),
+        ),
+    );
+    let valid = _mm_or_si128(
+        _mm_or_si128(upper, lower),
+        _mm_or_si128(digit, _mm_or_si128(plus, slash)),
+    );
+    let separator = _mm_or_si128(equals(b','), equals(b';'));
+
+    let mut sextets = [0; BLOCK_SIZE];
+    _mm_storeu_si128(
+        sextets.as_mut_ptr() as *mut __m128i,
+        _mm_add_epi8(input, offsets),
+    );
+    let separators = _mm_movemask_epi8(separator) as u32;
+    let known = _mm_movemask_epi8(_mm_or_si128(valid, separator)) as u32;
+    Block {
+        sextets,
+        separator: (separators | 1 << BLOCK_SIZE).trailing_zeros() as usize,
+        invalid: ((!known & 0xffff) | 1 << BLOCK_SIZE).trailing_zeros() as usize,
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn classify_neon(chunk: &[u8; BLOCK_SIZE]) -> Block {
+    use std::arch::aarch64::*;
+
+    let input = vld1q_u8(chunk.as_ptr());
+    let in_range = |low: u8, high: u8| {
+        vandq_u8(
+            vcgeq_u8(input, vdupq_n_u8(low)),
+            vcleq_u8(input, vdupq_n_u8(high)),
+        )
+    };
+    let equals = |byte: u8| vceqq_u8(input, vdupq_n_u8(byte));
+
+    let upper = in_range(b'A', b'Z');
+    let lower = in_range(b'a', b'z');
+    let digit = in_range(b'0', b'9');
+    let plus = equals(b'+');
+    let slash = equals(b'/');
+    let offset = |mask: uint8x16_t, offset: i8| vandq_u8(mask, vdupq_n_u8(offset as u8));
+    let offsets = vorrq_u8(
+        vorrq_u8(offset(upper, -65), offset(lower, -71)),
+        vorrq_u8(
+            offset(digit, 4),
+            vorrq_u8(offset(plus, 19), offset(slash, 16)),
+        ),
+    );
+    let valid = vorrq_u8(
+        vorrq_u8(upper, lower),
+        vorrq_u8(digit, vorrq_u8(plus, slash)),
+    );
+    let separator = vorrq_u8(equals(b','), equals(b';'));
+
+    let mut sextets = [0; BLOCK_SIZE];
+    vst1q_u8(sextets.as_mut_ptr(), vaddq_u8(input, offsets));
+    // Narrows the 0x00/0xff lanes to 4 bits each, as there is no movemask
+    let mask = |v: uint8x16_t| {
+        vget_lane_u64::<0>(vreinterpret_u64_u8(vshrn_n_u16::<4>(vreinterpretq_u16_u8(
+            v,
+        ))))
+    };
+    let separators = mask(separator);
+    let known = mask(vorrq_u8(valid, separator));
+    Block {
+        sextets,
+        separator: (separators.trailing_zeros() / 4) as usize,
+        invalid: ((!known).trailing_zeros() / 4) as usize,
+    }
+}
+
+#[cfg(any(test, not(any(target_arch = "x86_64", target_arch = "aarch64"))))]
+fn classify_scalar(chunk: &[u8; BLOCK_SIZE]) -> Block {
+    let mut block = Block {
+        sextets: [0; BLOCK_SIZE],
+        separator: BLOCK_SIZE,
+        invalid: BLOCK_SIZE,
+    };
+    for (i, byte) in chunk.iter().enumerate() {
+        let sextet = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b',' | b';' => {
+                block.separator = block.separator.min(i);
+                continue;
+            }
+            _ => {
+                block.invalid = block.invalid.min(i);
+                continue;
+            }
+        };
+        block.sextets[i] = sextet;
+    }
+    block
+}
+
+#[test]
+fn test_classify() {
+    // Every byte value in every position of the block
+    for byte in 0..=255u8 {
+        for i in 0..BLOCK_SIZE {
+            let mut chunk = *b"AZaz09+/gKkPvE4e";
+            chunk[i] = byte;
+            let expected = classify_scalar(&chunk);
+            let block = classify(&chunk);
+            assert_eq!(block.separator, expected.separator);
+            assert_eq!(block.invalid, expected.invalid);
+            if expected.invalid == BLOCK_SIZE {
+                for j in 0..BLOCK_SIZE {
+                    if j != expected.separator {
+                        assert_eq!(block.sextets[j], expected.sextets[j]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_read_segment() {
+    let mut fields = [0; 5];
+    assert_eq!(read_segment(b"AAAA", &mut fields), Some((4, 4)));
+    assert_eq!(fields[..4], [0, 0, 0, 0]);
+    assert_eq!(read_segment(b"gCACkBM,AAAA", &mut fields), Some((5, 7)));
+    assert_eq!(fields, [32, 0, 1, 18, 6]);
+    assert_eq!(read_segment(b"CC;", &mut fields), Some((2, 2)));
+    assert_eq!(fields[..2], [1, 1]);
+    // Segments that go on past the first block
+    assert_eq!(
+        read_segment(b"ggggggCggggggCggggggCggggggCD", &mut fields),
+        Some((5, 29))
+    );
+    assert_eq!(fields, [1 << 30, 1 << 30, 1 << 30, 1 << 30, -1]);
+
+    // Left to the regular decoder
+    for segment in [
+        &b"AA A"[..],
+        b"AAA",
+        b"AAAAAA",
+        b"AAAg",
+        b"ggggggggC",
+        b"\xffAAA",
+        b"",
+    ] {
+        assert_eq!(read_segment(segment, &mut fields), None);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::mapping_line::{ArchivedLineMapping, ArchivedMappingLine};
+use crate::provenance::SourceProvenance;
+use crate::{ArchivedSourceMapInner, SourceMap, BUFFER_HEADER_SIZE};
+use rkyv::std_impl::ArchivedString;
+use rkyv::Archived;
+use std::mem::size_of;
+
+// {"mappings":"","sources":[],"sourcesContent":[],"names":[],"version":3}, the fields
+// stringify writes in the JS package
+const JSON_FRAME_SIZE: usize = 71;
+// VLQ fields of minified code mostly take one or two characters, so a segment with an
+// original position and a name takes about this many
+const ESTIMATED_SEGMENT_SIZE: usize = 6;
+// Arrays in the archive are aligned, which takes at most this many bytes of padding each
+const MAX_PADDING: usize = 7;
+
+// Every string quoted, with commas in between
+fn json_strings_size<I: Iterator<Item = usize>>(lengths: I) -> usize {
+    let mut size = 0;
+    let mut count: usize = 0;
+    for length in lengths {
+        size += length + 2;
+        count += 1;
+    }
+    size + count.saturating_sub(1)
+}
+
+fn archived_strings_size(strings: &[String]) -> usize {
+    strings.iter().map(|s| s.len()).sum::<usize>()
+        + strings.len() * size_of::<ArchivedString>()
+        + MAX_PADDING
+}
+
+impl SourceMap {
+    // Size in bytes of the JSON map, without file and sourceRoot which are only known when
+    // stringifying. Computed from the number and length of the strings and mappings without
+    // encoding anything, so it is cheap enough to decide between an inline (base64 adds a
+    // third) and an external map upfront. Strings are counted as if nothing needs escaping,
+    // mappings at ESTIMATED_SEGMENT_SIZE bytes each.
+    pub fn estimate_json_size(&self) -> usize {
+        let lines = &self.inner.mapping_lines;
+        let mappings_len = lines.mappings_len();
+        let sources_content = self.get_sources_content();
+        let mappings_size = mappings_len * ESTIMATED_SEGMENT_SIZE
+            // Commas between the segments of a line and semicolons between lines
+            + mappings_len.saturating_sub(lines.stored_len())
+            + lines.len().saturating_sub(1);
+
+        JSON_FRAME_SIZE
+            + mappings_size
+            + json_strings_size(self.inner.sources.iter().map(|s| s.len()))
+            + json_strings_size((0..self.inner.sources.len()).map(|i| {
+                match sources_content.get(i).filter(|c| !c.is_empty()) {
+                    Some(content) => content.len(),
+                    // null, without the quotes that get added
+                    None => 2,
+                }
+            }))
+            + json_strings_size(self.inner.names.iter().map(|n| n.len()))
+    }
+
+    // Upper bound of the size in bytes of the output of to_buffer, computed from the number
+    // and length of the strings, lines and mappings. The buffer is smaller by the alignment
+    // padding that isn't needed, which is at most a few bytes per line.
+    pub fn estimate_buffer_size(&self) -> usize {
+        let inner = &self.inner;
+        let lines = &inner.mapping_lines;
+        let optional_len = |s: &Option<String>| s.as_ref().map_or(0, |s| s.len());
+
+        BUFFER_HEADER_SIZE
+            + size_of::<ArchivedSourceMapInner>()
+            + MAX_PADDING
+            + archived_strings_size(&inner.sources)
+            + archived_strings_size(self.get_sources_content())
+            + archived_strings_size(&inner.names)
+            // Line numbers and lines are two arrays, every line has an array of mappings
+            + lines.stored_len()
+                * (size_of::<u32>() + size_of::<ArchivedMappingLine>() + MAX_PADDING)
+            + 2 * MAX_PADDING
+            + lines.mappings_len() * size_of::<ArchivedLineMapping>()
+            + inner.source_provenance.len() * size_of::<Archived<SourceProvenance>>()
+            + inner.source_hashes.len() * size_of::<Archived<Option<u64>>>()
+            + inner.ignore_list.len() * size_of::<u32>()
+            + 3 * MAX_PADDING
+            + optional_len(&inner.debug_id)
+            + optional_len(&inner.source_root)
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_estimate_sizes() {
+    use crate::json::ToJsonOptions;
+    use crate::mapping::OriginalLocation;
+    use rkyv::AlignedVec;
+
+    assert_eq!(
+        r#"{"mappings":"","sources":[],"sourcesContent":[],"names":[],"version":3}"#.len(),
+        JSON_FRAME_SIZE
+    );
+
+    // Strings without anything to escape are counted exactly
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    map.add_source("b.js");
+    map.set_source_content(a as usize, "const a = '\u{e9}';")
+        .unwrap();
+    map.add_name("a");
+    map.add_name("b");
+    let json = map.to_json(&ToJsonOptions::default()).unwrap();
+    assert_eq!(map.estimate_json_size(), json.len());
+
+    // Mappings of a minified bundle
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("src/app.js");
+    let names: Vec<u32> = (0..20).map(|i| map.add_name(&format!("n{}", i))).collect();
+    for line in 0..50 {
+        for i in 0..40 {
+            map.add_mapping(
+                line,
+                i * 9,
+                Some(OriginalLocation::new(
+                    line * 3 + i / 10,
+                    (i % 10) * 4,
+                    source,
+                    Some(names[(i % 20) as usize]),
+                )),
+            );
+        }
+    }
+    let json_size = map.to_json(&ToJsonOptions::default()).unwrap().len();
+    let estimate = map.estimate_json_size();
+    assert!(estimate * 10 > json_size * 8 && estimate * 10 < json_size * 12);
+
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    let estimate = map.estimate_buffer_size();
+    assert!(estimate >= buffer.len());
+    // Only padding that wasn't needed
+    assert!((estimate - buffer.len()) * 100 < buffer.len());
+}
@@ -0,0 +1,369 @@
+use std::ops::Range;
+
+// The language of the generated code, which decides the comment syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Js,
+    Css,
+}
+
+// Where a sourceMappingURL comment points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceMapRef {
+    // Inline map, see SourceMap::from_data_url
+    DataUrl(String),
+    // Path or url of a separate map, relative to the generated file
+    Path(String),
+}
+
+impl SourceMapRef {
+    fn new(url: &str) -> Self {
+        let is_data_url = url
+            .get(..5)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"));
+        if is_data_url {
+            SourceMapRef::DataUrl(String::from(url))
+        } else {
+            SourceMapRef::Path(String::from(url))
+        }
+    }
+}
+
+// The url of a `//# sourceMappingURL=` comment (or `//@`, or a /* */ comment), None when it
+// isn't a sourceMappingURL comment. comment includes the delimiters.
+fn parse_comment(comment: &str) -> Option<&str> {
+    let body = match comment.strip_prefix("/*") {
+        Some(body) => body.strip_suffix("*/")?,
+        None => comment.strip_prefix("//")?,
+    };
+    let body = body.strip_prefix(['#', '@'])?.trim_start();
+    let url = body.strip_prefix("sourceMappingURL=")?.trim();
+    if url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(url)
+}
+
+// A / at this point starts a regular expression instead of being a division
+fn starts_regex(previous: Option<u8>) -> bool {
+    match previous {
+        None => true,
+        Some(byte) => b"(,=:[!&|?{};+-*%<>~^".contains(&byte),
+    }
+}
+
+// Index after the closing quote, or the end of the line for unterminated strings
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            byte if byte == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// Index after the closing / of a regular expression, ignoring slashes in character classes
+fn skip_regex(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    let mut in_class = false;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            b'[' => {
+                in_class = true;
+                i += 1;
+            }
+            b']' => {
+                in_class = false;
+                i += 1;
+            }
+            b'/' if !in_class => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// Scans the rest of a template literal starting at start (after ` or }), returns the index
+// after it and whether it stopped at a ${ instead of the closing `
+fn skip_template(bytes: &[u8], start: usize) -> (usize, bool) {
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => return (i + 1, false),
+            b'$' if bytes.get(i + 1) == Some(&b'{') => return (i + 2, true),
+            _ => i += 1,
+        }
+    }
+    (bytes.len(), false)
+}
+
+// The range of the sourceMappingURL comment at the end of the code (only whitespace and other
+// comments may follow it) and its url. Comment-like text in strings, template literals and
+// regular expressions is skipped, so code that generates these comments isn't mistaken for one.
+pub(crate) fn find_source_map_comment(code: &str, syntax: Syntax) -> Option<(Range<usize>, &str)> {
+    let bytes = code.as_bytes();
+    let js = syntax == Syntax::Js;
+    let mut found = None;
+    let mut previous: Option<u8> = None;
+    // Open braces in every ${} of the template literals we are in
+    let mut templates: Vec<u32> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        let comment_end = match (byte, next) {
+            (b'/', Some(b'/')) if js => Some(
+                code[i..]
+                    .find('\n')
+                    .map_or(bytes.len(), |offset| i + offset),
+            ),
+            (b'/', Some(b'*')) => Some(
+                code[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |offset| i + 2 + offset + 2),
+            ),
+            _ => None,
+        };
+        if let Some(end) = comment_end {
+            if let Some(url) = parse_comment(&code[i..end]) {
+                found = Some((i..end, url));
+            }
+            i = end;
+            continue;
+        }
+        if byte.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Anything else is code, so an earlier comment isn't at the end
+        found = None;
+        i = match byte {
+            b'"' | b'\'' => skip_string(bytes, i),
+            b'`' if js => {
+                let (end, expression) = skip_template(bytes, i + 1);
+                if expression {
+                    templates.push(0);
+                }
+                end
+            }
+            b'/' if js && starts_regex(previous) => skip_regex(bytes, i),
+            b'{' => {
+                if let Some(depth) = templates.last_mut() {
+                    *depth += 1;
+                }
+                i + 1
+            }
+            b'}' if templates.last() == Some(&0) => {
+                templates.pop();
+                let (end, expression) = skip_template(bytes, i + 1);
+                if expression {
+                    templates.push(0);
+                }
+                end
+            }
+            b'}' => {
+                if let Some(depth) = templates.last_mut() {
+                    *depth -= 1;
+                }
+                i + 1
+            }
+            _ => i + 1,
+        };
+        previous = Some(bytes[i - 1]);
+    }
+    found
+}
+
+// The map the trailing sourceMappingURL comment of generated code points to, if it has one
+pub fn extract_source_map_url(code: &str, syntax: Syntax) -> Option<SourceMapRef> {
+    find_source_map_comment(code, syntax).map(|(_, url)| SourceMapRef::new(url))
+}
+
+// The code without its trailing sourceMappingURL comment. A comment on a line of its own is
+// removed along with the line, so stripping what append_source_map_comment added gives back
+// the original code. Other trailing comments are kept.
+pub fn strip_source_map_comment(code: &str, syntax: Syntax) -> String {
+    let range = match find_source_map_comment(code, syntax) {
+        Some((range, _)) => range,
+        None => return String::from(code),
+    };
+    let line_start = code[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = code[range.end..]
+        .find('\n')
+        .map_or(code.len(), |i| range.end + i);
+    let own_line = code[line_start..range.start].trim().is_empty()
+        && code[range.end..line_end].trim().is_empty();
+
+    let (start, end) = if !own_line {
+        // foo(); //# sourceMappingURL=a.js.map
+        (
+            code[..range.start].trim_end_matches([' ', '\t']).len(),
+            range.end,
+        )
+    } else if line_end < code.len() {
+        (line_start, line_end + 1)
+    } else {
+        // The last line, so the newline before it goes instead
+        let start = code[..line_start]
+            .strip_suffix('\n')
+            .map_or(line_start, |before| before.trim_end_matches('\r').len());
+        (start, code.len())
+    };
+    let mut stripped = String::with_capacity(code.len() - (end - start));
+    stripped.push_str(&code[..start]);
+    stripped.push_str(&code[end..]);
+    stripped
+}
+
+// Adds a sourceMappingURL comment for url to the end of the code, on a line of its own.
+// An existing one is removed, and a final newline is kept.
+pub fn append_source_map_comment(code: &str, url: &str, syntax: Syntax) -> String {
+    let mut code = strip_source_map_comment(code, syntax);
+    let final_newline = code.ends_with('\n');
+    if !code.is_empty() && !final_newline {
+        code.push('\n');
+    }
+    match syntax {
+        Syntax::Js => code.push_str("//# sourceMappingURL="),
+        Syntax::Css => code.push_str("/*# sourceMappingURL="),
+    }
+    code.push_str(url);
+    if syntax == Syntax::Css {
+        code.push_str(" */");
+    }
+    if final_newline {
+        code.push('\n');
+    }
+    code
+}
+
+#[test]
+fn test_extract_source_map_url() {
+    let path = |path: &str| Some(SourceMapRef::Path(String::from(path)));
+    for (code, expected) in [
+        ("foo();\n//# sourceMappingURL=a.js.map", path("a.js.map")),
+        ("foo();//# sourceMappingURL=a.js.map\n\n", path("a.js.map")),
+        (
+            "foo();\n//@ sourceMappingURL=a.js.map\r\n",
+            path("a.js.map"),
+        ),
+        ("foo();\n/*# sourceMappingURL=a.js.map */", path("a.js.map")),
+        (
+            "foo();\n//# sourceMappingURL=a.js.map\n// end of bundle\n/* */",
+            path("a.js.map"),
+        ),
+        (
+            "//# sourceMappingURL=old.map\nfoo();\n//# sourceMappingURL=new.map",
+            path("new.map"),
+        ),
+        (
+            "foo();\n//# sourceMappingURL=data:application/json;base64,e30=",
+            Some(SourceMapRef::DataUrl(String::from(
+                "data:application/json;base64,e30=",
+            ))),
+        ),
+        ("//# sourceMappingURL=a.js.map\nfoo();", None),
+        ("foo();\n// sourceMappingURL=a.js.map", None),
+        ("foo();\n//# sourceMappingURL=", None),
+        // Code that writes these comments
+        ("let comment = \"//# sourceMappingURL=a.js.map\";", None),
+        ("let comment = '\\'//# sourceMappingURL=a.js.map';", None),
+        ("let comment = `\n//# sourceMappingURL=${url}\n`;", None),
+        (
+            "let comment = `${`//# sourceMappingURL=`}\n//# sourceMappingURL=${url}`",
+            None,
+        ),
+        ("let comment = /\\/\\/# sourceMappingURL=[/]a/", None),
+        (
+            "let a = b / 2;\n//# sourceMappingURL=a.js.map",
+            path("a.js.map"),
+        ),
+        (
+            "let a = `${{ a: 1 }.a}`;\n//# sourceMappingURL=a.js.map",
+            path("a.js.map"),
+        ),
+    ] {
+        assert_eq!(
+            extract_source_map_url(code, Syntax::Js),
+            expected,
+            "{}",
+            code
+        );
+    }
+
+    assert_eq!(
+        extract_source_map_url(
+            "a { content: \"/*# sourceMappingURL=b.css.map */\" }\n/*# sourceMappingURL=a.css.map */",
+            Syntax::Css
+        ),
+        path("a.css.map")
+    );
+    // No line comments in CSS
+    assert_eq!(
+        extract_source_map_url("a {}\n//# sourceMappingURL=a.css.map", Syntax::Css),
+        None
+    );
+}
+
+#[test]
+fn test_source_map_comments() {
+    for (code, stripped) in [
+        ("foo();\n//# sourceMappingURL=a.js.map", "foo();"),
+        ("foo();\n//# sourceMappingURL=a.js.map\n", "foo();\n"),
+        ("foo();\r\n//# sourceMappingURL=a.js.map", "foo();"),
+        ("foo(); //# sourceMappingURL=a.js.map\n", "foo();\n"),
+        (
+            "foo();\n//# sourceMappingURL=a.js.map\n// license\n",
+            "foo();\n// license\n",
+        ),
+        ("//# sourceMappingURL=a.js.map", ""),
+        ("foo();\n", "foo();\n"),
+    ] {
+        assert_eq!(
+            strip_source_map_comment(code, Syntax::Js),
+            stripped,
+            "{}",
+            code
+        );
+    }
+
+    for (code, appended) in [
+        ("foo();", "foo();\n//# sourceMappingURL=b.js.map"),
+        ("foo();\n", "foo();\n//# sourceMappingURL=b.js.map\n"),
+        ("", "//# sourceMappingURL=b.js.map"),
+        (
+            "foo();\n//# sourceMappingURL=a.js.map\n",
+            "foo();\n//# sourceMappingURL=b.js.map\n",
+        ),
+        (
+            "foo();\n//# sourceMappingURL=a.js.map\n// license",
+            "foo();\n// license\n//# sourceMappingURL=b.js.map",
+        ),
+    ] {
+        let result = append_source_map_comment(code, "b.js.map", Syntax::Js);
+        assert_eq!(result, appended, "{}", code);
+        assert_eq!(
+            extract_source_map_url(&result, Syntax::Js),
+            Some(SourceMapRef::Path(String::from("b.js.map")))
+        );
+        if !code.contains("sourceMappingURL") {
+            assert_eq!(strip_source_map_comment(&result, Syntax::Js), code);
+        }
+    }
+
+    let css = append_source_map_comment(
+        "a {}\n/*# sourceMappingURL=a.css.map */\n",
+        "b.css.map",
+        Syntax::Css,
+    );
+    assert_eq!(css, "a {}\n/*# sourceMappingURL=b.css.map */\n");
+}
@@ -0,0 +1,145 @@
+use crate::url::{normalize_url, resolve_source_url};
+use crate::SourceMap;
+use std::borrow::Cow;
+
+impl SourceMap {
+    // Prepended to every source by consumers (sourceRoot in JSON). Maps that get combined
+    // with this one are expected to use the same sourceRoot or none, only add_sourcemap
+    // accounts for a different one (see merged_source_name).
+    pub fn get_source_root(&self) -> Option<&str> {
+        self.inner.source_root.as_deref()
+    }
+
+    // An empty sourceRoot is the same as none
+    pub fn set_source_root(&mut self, source_root: Option<&str>) {
+        self.inner.source_root = source_root.filter(|r| !r.is_empty()).map(String::from);
+    }
+
+    // The source with sourceRoot prepended, the way a consumer would see it
+    pub fn get_source_with_root(&self, source: &str) -> String {
+        join_source_root(self.get_source_root(), source)
+    }
+
+    // What a source of other is called once it is added to this map: with the sourceRoot of
+    // other applied when it differs from ours (relative to our sourceRoot where it can be),
+    // and without . and .. segments
+    pub(crate) fn merged_source_name<'a>(
+        &self,
+        other: &SourceMap,
+        source: &'a str,
+    ) -> Cow<'a, str> {
+        if !self.merge_equivalent_sources || source.is_empty() {
+            return Cow::Borrowed(source);
+        }
+
+        let own_root = self.get_source_root();
+        let other_root = other.get_source_root();
+        if own_root == other_root {
+            return Cow::Owned(normalize_url(source));
+        }
+        let joined = normalize_url(&join_source_root(other_root, source));
+        let relative = own_root.and_then(|own_root| {
+            let own_root = normalize_url(own_root);
+            let own_root = own_root.trim_end_matches('/');
+            joined.strip_prefix(own_root)?.strip_prefix('/')
+        });
+        Cow::Owned(relative.map_or_else(|| joined.clone(), String::from))
+    }
+}
+
+// Null sources (empty strings) don't get a sourceRoot
+pub(crate) fn join_source_root(source_root: Option<&str>, source: &str) -> String {
+    match source_root {
+        Some(source_root) if !source.is_empty() => {
+            resolve_source_url(Some(source_root), source, None)
+        }
+        _ => String::from(source),
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_source_root() {
+    use crate::json::{ParseOptions, ToJsonOptions};
+    use crate::mapping::{SourceRootHandling, SourceRootOutput};
+    use rkyv::AlignedVec;
+
+    let input = r#"{"version":3,"sourceRoot":"webpack://app/","sources":["src/a.js",""],"sourcesContent":[null,null],"names":[],"mappings":"AAAA"}"#;
+    let mut map = SourceMap::from_json("/", input).unwrap();
+    assert_eq!(map.get_source_root(), Some("webpack://app/"));
+    assert_eq!(map.get_sources(), &vec!["src/a.js", ""]);
+    assert_eq!(
+        map.get_source_with_root("src/a.js"),
+        "webpack://app/src/a.js"
+    );
+    assert_eq!(map.to_json(&ToJsonOptions::default()).unwrap(), input);
+    assert_eq!(
+        map.to_json(&ToJsonOptions {
+            source_root: SourceRootOutput::Resolve,
+            ..Default::default()
+        })
+        .unwrap(),
+        r#"{"version":3,"sources":["webpack://app/src/a.js",""],"sourcesContent":[null,null],"names":[],"mappings":"AAAA"}"#
+    );
+
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+    assert_eq!(
+        SourceMap::from_buffer("/", &buffer)
+            .unwrap()
+            .get_source_root(),
+        Some("webpack://app/")
+    );
+
+    let options = ParseOptions {
+        source_root: SourceRootHandling::Join,
+        ..Default::default()
+    };
+    let map = SourceMap::from_json_with_options("/", input, &options).unwrap();
+    assert_eq!(map.get_source_root(), None);
+    assert_eq!(map.get_sources(), &vec!["webpack://app/src/a.js", ""]);
+
+    // Sections can't share a sourceRoot, theirs always gets joined
+    let index_map = r#"{"version":3,"sections":[{"offset":{"line":0,"column":0},"map":{"version":3,"sourceRoot":"lib","sources":["a.js"],"names":[],"mappings":"AAAA"}}]}"#;
+    let map = SourceMap::from_json("/", index_map).unwrap();
+    assert_eq!(map.get_sources(), &vec!["lib/a.js"]);
+
+    let mut map = SourceMap::new("/");
+    map.set_source_root(Some(""));
+    assert_eq!(map.get_source_root(), None);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_merge_equivalent_sources() {
+    let mut map = SourceMap::new("/");
+    map.add_source("src/a.js");
+    map.set_source_root(Some("lib/"));
+
+    let mut other = SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sources":["./src/a.js","src/../src/a.js","lib/b.js","c.js"],"names":[],"mappings":"AAAA,CCAA,CCAA,CCAA"}"#,
+    )
+    .unwrap();
+    map.add_sourcemap(&mut other.clone(), 0).unwrap();
+    // b.js gets rewritten relative to our sourceRoot, c.js can't be
+    assert_eq!(map.get_sources(), &vec!["src/a.js", "b.js", "c.js"]);
+
+    let mut rooted = SourceMap::from_json(
+        "/",
+        r#"{"version":3,"sourceRoot":"lib/src","sources":["a.js"],"names":[],"mappings":"AAAA"}"#,
+    )
+    .unwrap();
+    map.add_sourcemap(&mut rooted, 1).unwrap();
+    assert_eq!(map.get_sources(), &vec!["src/a.js", "b.js", "c.js"]);
+    let mapping = map.find_closest_mapping(1, 0).unwrap();
+    assert_eq!(mapping.original.unwrap().source, 0);
+
+    let mut map = SourceMap::new("/");
+    map.set_merge_equivalent_sources(false);
+    map.add_sourcemap(&mut other, 0).unwrap();
+    assert_eq!(
+        map.get_sources(),
+        &vec!["src/a.js", "src/../src/a.js", "lib/b.js", "c.js"]
+    );
+}
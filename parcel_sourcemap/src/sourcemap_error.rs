@@ -1,4 +1,10 @@
+#[cfg(feature = "json")]
+use serde::Serialize;
+use std::any::Any;
+use std::fmt;
 use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Errors that can occur during processing/modifying source map
 #[derive(Copy, Clone, Debug)]
@@ -37,12 +43,204 @@ pub enum SourceMapErrorType {
 
     // Failed to convert utf-8 to array
     FromUtf8Error = 11,
+
+    // Flat mapping arrays are not all the same length
+    FlatArraysLengthMismatch = 12,
+
+    // Input is not a valid JSON source map
+    InvalidJson = 13,
+
+    // A map (indirectly) references itself while flattening
+    CompositionCycle = 14,
+
+    // Flattening exceeded the maximum depth
+    CompositionDepthExceeded = 15,
+
+    // Split offsets have to be in ascending order
+    UnsortedSplitOffsets = 16,
+
+    // Input is not valid UTF-8 or UTF-16
+    InvalidEncoding = 17,
+
+    // Options object contains unknown keys or values of the wrong type
+    InvalidOptions = 18,
+
+    // Chunk id is unknown or already in use
+    InvalidChunkId = 19,
+
+    // Mapping has a name but no source, which the spec does not allow
+    NameWithoutSource = 20,
+
+    // An operation was aborted through its cancellation token
+    Cancelled = 21,
+
+    // A flat mapping mixes -1 sentinels with values, or has a name without an original position
+    InvalidFlatMapping = 22,
+
+    // Buffer was written with a different buffer format version, or isn't a buffer at all
+    BufferVersionMismatch = 23,
+
+    // Not a data: url containing a JSON source map
+    InvalidDataUrl = 24,
+
+    // A panic inside the library, caught at the bindings instead of aborting the process
+    InternalError = 25,
+
+    // Allocating more would go over the memory budget of the map
+    MemoryBudgetExceeded = 26,
+}
+
+impl SourceMapErrorType {
+    pub fn message(&self) -> &'static str {
+        match self {
+            SourceMapErrorType::UnexpectedNegativeNumber => "Unexpected Negative Number",
+            SourceMapErrorType::UnexpectedlyBigNumber => "Unexpected Big Number",
+            SourceMapErrorType::VlqUnexpectedEof => "VLQ Unexpected end of file",
+            SourceMapErrorType::VlqInvalidBase64 => "VLQ Invalid Base 64 value",
+            SourceMapErrorType::VlqOverflow => "VLQ Value overflowed, does not fit in u32",
+            SourceMapErrorType::IOError => "IO Error",
+            SourceMapErrorType::NameOutOfRange => "Name out of range",
+            SourceMapErrorType::SourceOutOfRange => "Source out of range",
+            SourceMapErrorType::InvalidFilePath => "Invalid FilePath",
+            SourceMapErrorType::BufferError => {
+                "Something went wrong while writing/reading a sourcemap buffer"
+            }
+            SourceMapErrorType::FromUtf8Error => "Could not convert utf-8 array to string",
+            SourceMapErrorType::FlatArraysLengthMismatch => {
+                "Flat mapping arrays have different lengths"
+            }
+            SourceMapErrorType::InvalidJson => "Invalid JSON source map",
+            SourceMapErrorType::CompositionCycle => "Source map composition contains a cycle",
+            SourceMapErrorType::CompositionDepthExceeded => {
+                "Source map composition exceeded the maximum depth"
+            }
+            SourceMapErrorType::UnsortedSplitOffsets => "Split offsets are not in ascending order",
+            SourceMapErrorType::InvalidEncoding => "Input is not valid UTF-8 or UTF-16",
+            SourceMapErrorType::InvalidOptions => "Invalid options",
+            SourceMapErrorType::InvalidChunkId => "Invalid chunk id",
+            SourceMapErrorType::NameWithoutSource => "Mapping has a name but no source",
+            SourceMapErrorType::Cancelled => "Operation was cancelled",
+            SourceMapErrorType::InvalidFlatMapping => {
+                "Flat mapping has an incomplete original position"
+            }
+            SourceMapErrorType::BufferVersionMismatch => {
+                "The sourcemap buffer was written with an unsupported format version"
+            }
+            SourceMapErrorType::InvalidDataUrl => "Invalid source map data url",
+            SourceMapErrorType::InternalError => "Internal error",
+            SourceMapErrorType::MemoryBudgetExceeded => "Memory budget exceeded",
+        }
+    }
+}
+
+// SourceMapError grouped by the kind of operation that failed, with the details that are known
+// as typed fields. Every SourceMapErrorType belongs to exactly one variant, so Rust consumers
+// can match exhaustively instead of inspecting reasons. The bindings keep using the numeric
+// codes, see SourceMapError::code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceMapErrorKind<'a> {
+    Parse(ParseError<'a>),
+    Lookup(LookupError<'a>),
+    Merge(MergeError<'a>),
+    Serialize(SerializeError<'a>),
+    // Not specific to an operation
+    Runtime(RuntimeError<'a>),
+}
+
+// Reading a map from JSON, VLQ mappings, flat arrays or a data url
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError<'a> {
+    // A number (or an offset applied to it) that doesn't fit in a u32
+    NumberOutOfRange {
+        negative: bool,
+        reason: Option<&'a str>,
+        location: Option<ErrorLocation>,
+    },
+    Vlq {
+        error: VlqError,
+        location: Option<ErrorLocation>,
+    },
+    InvalidJson {
+        reason: Option<&'a str>,
+    },
+    // Not valid UTF-8 or UTF-16
+    InvalidEncoding,
+    NameWithoutSource {
+        location: Option<ErrorLocation>,
+    },
+    InvalidDataUrl {
+        reason: Option<&'a str>,
+    },
+    FlatArraysLengthMismatch,
+    InvalidFlatMapping {
+        reason: Option<&'a str>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VlqError {
+    UnexpectedEof,
+    InvalidBase64,
+    Overflow,
+}
+
+// A source or name index that the map doesn't have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookupError<'a> {
+    SourceOutOfRange {
+        reason: Option<&'a str>,
+        location: Option<ErrorLocation>,
+    },
+    NameOutOfRange {
+        reason: Option<&'a str>,
+        location: Option<ErrorLocation>,
+    },
+}
+
+// Combining maps (composing, flattening, concatenating) or splitting them up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError<'a> {
+    // The sources that lead back to the first one
+    CompositionCycle { chain: Vec<&'a str> },
+    CompositionDepthExceeded { chain: Vec<&'a str> },
+    UnsortedSplitOffsets,
+    InvalidChunkId { reason: Option<&'a str> },
+}
+
+// Writing or reading buffers and other output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializeError<'a> {
+    Buffer { reason: Option<&'a str> },
+    BufferVersionMismatch { reason: Option<&'a str> },
+    Io,
+    InvalidFilePath,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError<'a> {
+    InvalidOptions { reason: Option<&'a str> },
+    Cancelled,
+    MemoryBudgetExceeded { reason: Option<&'a str> },
+    // A caught panic, with its message as reason
+    Internal { reason: Option<&'a str> },
+}
+
+// Where in a mappings string decoding failed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorLocation {
+    // Start of the segment that failed to decode
+    pub byte_offset: usize,
+    // Line within the mappings string, without any line offset
+    pub generated_line: u32,
+    // Index of the segment within its line
+    pub segment_index: u32,
 }
 
 #[derive(Debug)]
 pub struct SourceMapError {
     pub error_type: SourceMapErrorType,
     pub reason: Option<String>,
+    pub location: Option<ErrorLocation>,
 }
 
 impl SourceMapError {
@@ -50,6 +248,7 @@ impl SourceMapError {
         Self {
             error_type,
             reason: None,
+            location: None,
         }
     }
 
@@ -57,8 +256,221 @@ impl SourceMapError {
         Self {
             error_type,
             reason: Some(String::from(reason)),
+            location: None,
+        }
+    }
+
+    // Keeps the innermost location if the error already has one
+    pub fn with_location(mut self, location: ErrorLocation) -> Self {
+        self.location.get_or_insert(location);
+        self
+    }
+
+    // The numeric code of error_type, stable across versions
+    pub fn code(&self) -> u32 {
+        self.error_type as u32
+    }
+
+    pub fn kind(&self) -> SourceMapErrorKind<'_> {
+        use SourceMapErrorType as Type;
+
+        let reason = self.reason.as_deref();
+        let location = self.location;
+        let chain = || reason.map_or_else(Vec::new, |r| r.split(" -> ").collect());
+        match self.error_type {
+            Type::UnexpectedNegativeNumber | Type::UnexpectedlyBigNumber => {
+                SourceMapErrorKind::Parse(ParseError::NumberOutOfRange {
+                    negative: matches!(self.error_type, Type::UnexpectedNegativeNumber),
+                    reason,
+                    location,
+                })
+            }
+            Type::VlqUnexpectedEof | Type::VlqInvalidBase64 | Type::VlqOverflow => {
+                let error = match self.error_type {
+                    Type::VlqUnexpectedEof => VlqError::UnexpectedEof,
+                    Type::VlqInvalidBase64 => VlqError::InvalidBase64,
+                    _ => VlqError::Overflow,
+                };
+                SourceMapErrorKind::Parse(ParseError::Vlq { error, location })
+            }
+            Type::InvalidJson => SourceMapErrorKind::Parse(ParseError::InvalidJson { reason }),
+            Type::InvalidEncoding | Type::FromUtf8Error => {
+                SourceMapErrorKind::Parse(ParseError::InvalidEncoding)
+            }
+            Type::NameWithoutSource => {
+                SourceMapErrorKind::Parse(ParseError::NameWithoutSource { location })
+            }
+            Type::InvalidDataUrl => {
+                SourceMapErrorKind::Parse(ParseError::InvalidDataUrl { reason })
+            }
+            Type::FlatArraysLengthMismatch => {
+                SourceMapErrorKind::Parse(ParseError::FlatArraysLengthMismatch)
+            }
+            Type::InvalidFlatMapping => {
+                SourceMapErrorKind::Parse(ParseError::InvalidFlatMapping { reason })
+            }
+            Type::SourceOutOfRange => {
+                SourceMapErrorKind::Lookup(LookupError::SourceOutOfRange { reason, location })
+            }
+            Type::NameOutOfRange => {
+                SourceMapErrorKind::Lookup(LookupError::NameOutOfRange { reason, location })
+            }
+            Type::CompositionCycle => {
+                SourceMapErrorKind::Merge(MergeError::CompositionCycle { chain: chain() })
+            }
+            Type::CompositionDepthExceeded => {
+                SourceMapErrorKind::Merge(MergeError::CompositionDepthExceeded { chain: chain() })
+            }
+            Type::UnsortedSplitOffsets => {
+                SourceMapErrorKind::Merge(MergeError::UnsortedSplitOffsets)
+            }
+            Type::InvalidChunkId => {
+                SourceMapErrorKind::Merge(MergeError::InvalidChunkId { reason })
+            }
+            Type::BufferError => SourceMapErrorKind::Serialize(SerializeError::Buffer { reason }),
+            Type::BufferVersionMismatch => {
+                SourceMapErrorKind::Serialize(SerializeError::BufferVersionMismatch { reason })
+            }
+            Type::IOError => SourceMapErrorKind::Serialize(SerializeError::Io),
+            Type::InvalidFilePath => SourceMapErrorKind::Serialize(SerializeError::InvalidFilePath),
+            Type::InvalidOptions => {
+                SourceMapErrorKind::Runtime(RuntimeError::InvalidOptions { reason })
+            }
+            Type::Cancelled => SourceMapErrorKind::Runtime(RuntimeError::Cancelled),
+            Type::MemoryBudgetExceeded => {
+                SourceMapErrorKind::Runtime(RuntimeError::MemoryBudgetExceeded { reason })
+            }
+            Type::InternalError => SourceMapErrorKind::Runtime(RuntimeError::Internal { reason }),
+        }
+    }
+
+    // The message the bindings throw, in the format set using set_error_format
+    pub fn render(&self) -> String {
+        match error_format() {
+            ErrorFormat::Text => self.to_text(),
+            #[cfg(feature = "json")]
+            ErrorFormat::Json => self.to_json(),
         }
     }
+
+    pub fn to_text(&self) -> String {
+        // Prefix all errors, so it's obvious they originate from this library
+        let mut text = String::from("[parcel-sourcemap] ");
+        text.push_str(self.error_type.message());
+        if let Some(reason) = &self.reason {
+            text.push_str(", ");
+            text.push_str(reason);
+        }
+        if let Some(location) = &self.location {
+            text.push_str(&format!(
+                " (at byte {}, line {}, segment {})",
+                location.byte_offset, location.generated_line, location.segment_index
+            ));
+        }
+        text
+    }
+
+    // e.g. {"code":"SourceOutOfRange","message":"...","reason":null,"position":{...}}
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        let json = JsonError {
+            code: format!("{:?}", self.error_type),
+            message: self.error_type.message(),
+            reason: self.reason.as_deref(),
+            position: self.location.map(|location| JsonErrorPosition {
+                byte_offset: location.byte_offset,
+                generated_line: location.generated_line,
+                segment_index: location.segment_index,
+            }),
+        };
+        // Only strings and numbers, this can't fail
+        serde_json::to_string(&json).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+impl std::error::Error for SourceMapError {}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonError<'a> {
+    code: String,
+    message: &'static str,
+    reason: Option<&'a str>,
+    position: Option<JsonErrorPosition>,
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonErrorPosition {
+    byte_offset: usize,
+    generated_line: u32,
+    segment_index: u32,
+}
+
+// How the bindings render errors, tools that wrap them can switch to JSON to check for
+// specific failures instead of matching on messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(ErrorFormat::Text),
+            #[cfg(feature = "json")]
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Process wide, errors are converted to binding errors without access to the map
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_error_format(format: ErrorFormat) {
+    JSON_ERRORS.store(format != ErrorFormat::Text, Ordering::Relaxed);
+}
+
+pub fn error_format() -> ErrorFormat {
+    #[cfg(feature = "json")]
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        return ErrorFormat::Json;
+    }
+    ErrorFormat::Text
+}
+
+// Runs f with panics turned into an InternalError (with the panic message as reason), the
+// map f was working on can be left half-updated. The bindings call into the library through
+// this, a panic unwinding into Node would abort the whole process. wasm builds abort on panic,
+// which traps and shows up in JS as a RuntimeError instead.
+pub fn catch_panic<T, F>(f: F) -> Result<T, SourceMapError>
+where
+    F: FnOnce() -> Result<T, SourceMapError>,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| Err(panic_error(payload)))
+}
+
+// An InternalError for the payload of a caught panic
+pub(crate) fn panic_error(payload: Box<dyn Any + Send>) -> SourceMapError {
+    let reason = match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown panic", |message| message.as_str()),
+    };
+    SourceMapError::new_with_reason(SourceMapErrorType::InternalError, reason)
 }
 
 impl From<vlq::Error> for SourceMapError {
@@ -85,55 +497,8 @@ impl From<io::Error> for SourceMapError {
 impl From<SourceMapError> for napi::Error {
     #[inline]
     fn from(err: SourceMapError) -> napi::Error {
-        // Prefix all errors, so it's obvious they originate from this library
-        let mut reason = String::from("[parcel-sourcemap] ");
-
-        // Convert error type into an error message...
-        match err.error_type {
-            SourceMapErrorType::UnexpectedNegativeNumber => {
-                reason.push_str("Unexpected Negative Number");
-            }
-            SourceMapErrorType::UnexpectedlyBigNumber => {
-                reason.push_str("Unexpected Big Number");
-            }
-            SourceMapErrorType::VlqUnexpectedEof => {
-                reason.push_str("VLQ Unexpected end of file");
-            }
-            SourceMapErrorType::VlqInvalidBase64 => {
-                reason.push_str("VLQ Invalid Base 64 value");
-            }
-            SourceMapErrorType::VlqOverflow => {
-                reason.push_str("VLQ Value overflowed, does not fit in u32");
-            }
-            SourceMapErrorType::IOError => {
-                reason.push_str("IO Error");
-            }
-            SourceMapErrorType::NameOutOfRange => {
-                reason.push_str("Name out of range");
-            }
-            SourceMapErrorType::SourceOutOfRange => {
-                reason.push_str("Source out of range");
-            }
-            SourceMapErrorType::InvalidFilePath => {
-                reason.push_str("Invalid FilePath");
-            }
-            SourceMapErrorType::BufferError => {
-                reason.push_str("Something went wrong while writing/reading a sourcemap buffer");
-            }
-            SourceMapErrorType::FromUtf8Error => {
-                reason.push_str("Could not convert utf-8 array to string");
-            }
-        }
-
-        // Add reason to error string if there is one
-
-        if let Some(r) = err.reason {
-            reason.push_str(", ");
-            reason.push_str(&r[..]);
-        }
-
         // Return a napi error :)
-        napi::Error::new(napi::Status::GenericFailure, reason)
+        napi::Error::new(napi::Status::GenericFailure, err.render())
     }
 }
 
@@ -141,54 +506,8 @@ impl From<SourceMapError> for napi::Error {
 impl From<SourceMapError> for wasm_bindgen::JsValue {
     #[inline]
     fn from(err: SourceMapError) -> wasm_bindgen::JsValue {
-        // Prefix all errors, so it's obvious they originate from this library
-        let mut reason = String::from("[parcel-sourcemap] ");
-
-        // Convert error type into an error message...
-        match err.error_type {
-            SourceMapErrorType::UnexpectedNegativeNumber => {
-                reason.push_str("Unexpected Negative Number");
-            }
-            SourceMapErrorType::UnexpectedlyBigNumber => {
-                reason.push_str("Unexpected Big Number");
-            }
-            SourceMapErrorType::VlqUnexpectedEof => {
-                reason.push_str("VLQ Unexpected end of file");
-            }
-            SourceMapErrorType::VlqInvalidBase64 => {
-                reason.push_str("VLQ Invalid Base 64 value");
-            }
-            SourceMapErrorType::VlqOverflow => {
-                reason.push_str("VLQ Value overflowed, does not fit in u32");
-            }
-            SourceMapErrorType::IOError => {
-                reason.push_str("IO Error");
-            }
-            SourceMapErrorType::NameOutOfRange => {
-                reason.push_str("Name out of range");
-            }
-            SourceMapErrorType::SourceOutOfRange => {
-                reason.push_str("Source out of range");
-            }
-            SourceMapErrorType::InvalidFilePath => {
-                reason.push_str("Invalid FilePath");
-            }
-            SourceMapErrorType::BufferError => {
-                reason.push_str("Something went wrong while writing/reading a sourcemap buffer");
-            }
-            SourceMapErrorType::FromUtf8Error => {
-                reason.push_str("Could not convert utf-8 array to string");
-            }
-        }
-
-        // Add reason to error string if there is one
-        if let Some(r) = err.reason {
-            reason.push_str(", ");
-            reason.push_str(&r[..]);
-        }
-
         // Return a JavaScript error :)
-        js_sys::Error::new(&reason).into()
+        js_sys::Error::new(&err.render()).into()
     }
 }
 
@@ -205,3 +524,97 @@ impl From<std::string::FromUtf8Error> for SourceMapError {
         SourceMapError::new(SourceMapErrorType::FromUtf8Error)
     }
 }
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for SourceMapError {
+    #[inline]
+    fn from(err: serde_json::Error) -> SourceMapError {
+        SourceMapError::new_with_reason(SourceMapErrorType::InvalidJson, &err.to_string())
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_error_formats() {
+    let error = SourceMapError::new_with_reason(SourceMapErrorType::SourceOutOfRange, "index 3")
+        .with_location(ErrorLocation {
+            byte_offset: 10,
+            generated_line: 1,
+            segment_index: 2,
+        });
+    assert_eq!(
+        error.to_text(),
+        "[parcel-sourcemap] Source out of range, index 3 (at byte 10, line 1, segment 2)"
+    );
+    assert_eq!(
+        error.to_json(),
+        r#"{"code":"SourceOutOfRange","message":"Source out of range","reason":"index 3","position":{"byteOffset":10,"generatedLine":1,"segmentIndex":2}}"#
+    );
+
+    set_error_format(ErrorFormat::from_name("json").unwrap());
+    assert_eq!(error.render(), error.to_json());
+    set_error_format(ErrorFormat::Text);
+    assert_eq!(error.render(), error.to_text());
+    assert!(ErrorFormat::from_name("xml").is_none());
+}
+
+#[test]
+fn test_catch_panic() {
+    assert_eq!(catch_panic(|| Ok(1)).unwrap(), 1);
+    let error = catch_panic(|| -> Result<(), SourceMapError> {
+        Err(SourceMapError::new(SourceMapErrorType::Cancelled))
+    })
+    .unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::Cancelled));
+
+    let error = catch_panic(|| -> Result<(), SourceMapError> {
+        let indexes: Vec<u32> = Vec::new();
+        panic!("index {} out of range", indexes.len() + 1)
+    })
+    .unwrap_err();
+    assert!(matches!(
+        error.error_type,
+        SourceMapErrorType::InternalError
+    ));
+    assert_eq!(error.reason.as_deref(), Some("index 1 out of range"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_error_kinds() {
+    use crate::SourceMap;
+
+    let error =
+        SourceMap::from_json("/", r#"{"sources": ["a.js"], "mappings": "AAAA;g"}"#).unwrap_err();
+    assert_eq!(error.code(), 3);
+    assert_eq!(
+        error.kind(),
+        SourceMapErrorKind::Parse(ParseError::Vlq {
+            error: VlqError::UnexpectedEof,
+            location: error.location,
+        })
+    );
+    assert!(error.location.is_some());
+
+    let error = SourceMapError::new_with_reason(
+        SourceMapErrorType::CompositionCycle,
+        "a.js -> b.js -> a.js",
+    );
+    match error.kind() {
+        SourceMapErrorKind::Merge(MergeError::CompositionCycle { chain }) => {
+            assert_eq!(chain, vec!["a.js", "b.js", "a.js"])
+        }
+        kind => panic!("unexpected {:?}", kind),
+    }
+
+    let error = SourceMap::from_buffer("/", b"not a buffer").unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        SourceMapErrorKind::Serialize(SerializeError::BufferVersionMismatch { .. })
+    ));
+    assert_eq!(
+        SourceMapError::new(SourceMapErrorType::Cancelled).kind(),
+        SourceMapErrorKind::Runtime(RuntimeError::Cancelled)
+    );
+    assert_eq!(error.to_string(), error.to_text());
+}
@@ -0,0 +1,272 @@
+use crate::mapping::{DuplicateMappingPolicy, OriginalLocation, Position};
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::SourceMap;
+use std::collections::HashMap;
+
+// Copies sources and names into a split map on first use, so every chunk only
+// contains the sources and names it actually references
+struct ChunkBuilder {
+    sourcemap: SourceMap,
+    sources: HashMap<u32, u32>,
+    names: HashMap<u32, u32>,
+}
+
+impl ChunkBuilder {
+    fn new(project_root: &str, policy: DuplicateMappingPolicy) -> Self {
+        let mut sourcemap = SourceMap::new(project_root);
+        sourcemap.set_duplicate_mapping_policy(policy);
+        Self {
+            sourcemap,
+            sources: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    fn copy_original(
+        &mut self,
+        from: &SourceMap,
+        original: OriginalLocation,
+    ) -> Result<OriginalLocation, SourceMapError> {
+        let source = match self.sources.get(&original.source) {
+            Some(source) => *source,
+            None => {
+                let source = self.sourcemap.inner.sources.len() as u32;
+                self.sourcemap
+                    .inner
+                    .sources
+                    .push(String::from(from.get_source(original.source)?));
+                self.sourcemap
+                    .set_source_provenance(source, from.get_source_provenance(original.source));
+                self.sourcemap
+                    .copy_ignored_source(source, from, original.source);
+                if let Some(content) = from
+                    .try_get_sources_content()?
+                    .get(original.source as usize)
+                {
+                    self.sourcemap
+                        .set_source_content(source as usize, content)?;
+                }
+                self.sourcemap
+                    .copy_source_hash(source, from, original.source);
+                self.sources.insert(original.source, source);
+                source
+            }
+        };
+
+        let name = match original.name {
+            Some(name) => Some(match self.names.get(&name) {
+                Some(name) => *name,
+                None => {
+                    let new_name = self.sourcemap.add_name(from.get_name(name)?);
+                    self.names.insert(name, new_name);
+                    new_name
+                }
+            }),
+            None => None,
+        };
+
+        Ok(OriginalLocation::new(
+            original.original_line,
+            original.original_column,
+            source,
+            name,
+        ))
+    }
+}
+
+impl SourceMap {
+    // One map per source (in the same order as get_sources), each only containing the
+    // mappings that point to that source, at their original generated positions
+    pub fn split_by_source(&self) -> Result<Vec<SourceMap>, SourceMapError> {
+        let policy = self.inner.duplicate_mapping_policy;
+        let mut split_maps = Vec::with_capacity(self.inner.sources.len());
+        for (source_index, source) in self.inner.sources.iter().enumerate() {
+            let mut split_map = SourceMap::new(self.project_root.as_str());
+            split_map.set_duplicate_mapping_policy(policy);
+            split_map.inner.sources.push(source.clone());
+            split_map.set_source_provenance(0, self.get_source_provenance(source_index as u32));
+            split_map.copy_ignored_source(0, self, source_index as u32);
+            if let Some(source_content) = self.try_get_sources_content()?.get(source_index) {
+                split_map.set_source_content(0, source_content)?;
+            }
+            split_map.copy_source_hash(0, self, source_index as u32);
+            split_maps.push(split_map);
+        }
+
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            for mapping in mapping_line.resolved_mappings(policy).iter() {
+                let original = match mapping.original {
+                    Some(original) => original,
+                    None => continue,
+                };
+
+                let split_map = match split_maps.get_mut(original.source as usize) {
+                    Some(split_map) => split_map,
+                    None => continue,
+                };
+                let name = match original.name {
+                    Some(name) => Some(split_map.add_name(self.get_name(name)?)),
+                    None => None,
+                };
+                split_map.add_mapping(
+                    generated_line as u32,
+                    mapping.generated_column,
+                    Some(OriginalLocation::new(
+                        original.original_line,
+                        original.original_column,
+                        0,
+                        name,
+                    )),
+                );
+            }
+        }
+
+        Ok(split_maps)
+    }
+
+    // Splits the generated file into chunks starting at each offset, the inverse of
+    // concatenating maps. Every chunk is rebased so its offset becomes line 0, column 0,
+    // mappings before the first offset are dropped.
+    pub fn split_at(&mut self, offsets: &[Position]) -> Result<Vec<SourceMap>, SourceMapError> {
+        if offsets.windows(2).any(|w| w[0] > w[1]) {
+            return Err(SourceMapError::new(
+                SourceMapErrorType::UnsortedSplitOffsets,
+            ));
+        }
+
+        let policy = self.inner.duplicate_mapping_policy;
+        let mut chunks: Vec<ChunkBuilder> = offsets
+            .iter()
+            .map(|_| ChunkBuilder::new(self.project_root.as_str(), policy))
+            .collect();
+        if chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        for line in self.inner.mapping_lines.lines_mut() {
+            line.ensure_sorted_with(policy);
+        }
+
+        let mut chunk_index = 0;
+        for (generated_line, mapping_line) in self.inner.mapping_lines.iter() {
+            for mapping in mapping_line.mappings.iter() {
+                let position = Position::new(generated_line as u32, mapping.generated_column);
+                while chunk_index + 1 < offsets.len() && offsets[chunk_index + 1] <= position {
+                    chunk_index += 1;
+                }
+
+                let start = offsets[chunk_index];
+                if position < start {
+                    continue;
+                }
+
+                let chunk = &mut chunks[chunk_index];
+                let original = match mapping.original {
+                    Some(original) => Some(chunk.copy_original(self, original)?),
+                    None => None,
+                };
+                let generated_column = if position.line == start.line {
+                    position.column - start.column
+                } else {
+                    position.column
+                };
+                chunk
+                    .sourcemap
+                    .add_mapping(position.line - start.line, generated_column, original);
+            }
+        }
+
+        Ok(chunks.into_iter().map(|chunk| chunk.sourcemap).collect())
+    }
+}
+
+#[test]
+fn test_split_at() {
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    let b = map.add_source("b.js");
+    let name = map.add_name("foo");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, a, None)));
+    map.add_mapping(0, 20, Some(OriginalLocation::new(1, 0, b, Some(name))));
+    map.add_mapping(1, 4, Some(OriginalLocation::new(2, 0, b, None)));
+    map.add_mapping(1, 8, Some(OriginalLocation::new(5, 0, a, None)));
+
+    let chunks = map
+        .split_at(&[
+            Position::new(0, 0),
+            Position::new(0, 20),
+            Position::new(1, 8),
+        ])
+        .unwrap();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0].get_sources(), &vec!["a.js"]);
+
+    let second = &chunks[1];
+    assert_eq!(second.get_sources(), &vec!["b.js"]);
+    assert_eq!(second.get_names(), &vec!["foo"]);
+    let mappings = second.get_mappings();
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(
+        (mappings[0].generated_line, mappings[0].generated_column),
+        (0, 0)
+    );
+    assert_eq!(
+        (mappings[1].generated_line, mappings[1].generated_column),
+        (1, 4)
+    );
+
+    let third = chunks[2].get_mappings();
+    assert_eq!((third[0].generated_line, third[0].generated_column), (0, 0));
+    assert_eq!(third[0].original.unwrap().source, 0);
+
+    assert!(map
+        .split_at(&[Position::new(1, 0), Position::new(0, 0)])
+        .is_err());
+}
+
+#[test]
+fn test_split_duplicate_mappings() {
+    let mut map = SourceMap::new("/");
+    map.set_duplicate_mapping_policy(DuplicateMappingPolicy::KeepLast);
+    let a = map.add_source("a.js");
+    map.add_mapping(0, 5, Some(OriginalLocation::new(1, 0, a, None)));
+    map.add_mapping(0, 5, Some(OriginalLocation::new(2, 0, a, None)));
+
+    let chunks = map.split_at(&[Position::new(0, 0)]).unwrap();
+    let mappings = chunks[0].get_mappings();
+    assert_eq!(mappings.len(), 1);
+    assert_eq!(mappings[0].original.unwrap().original_line, 2);
+    assert_eq!(
+        chunks[0].get_duplicate_mapping_policy(),
+        DuplicateMappingPolicy::KeepLast
+    );
+
+    let split_maps = map.split_by_source().unwrap();
+    let mappings = split_maps[0].get_mappings();
+    assert_eq!(mappings.len(), 1);
+    assert_eq!(mappings[0].original.unwrap().original_line, 2);
+}
+
+#[test]
+fn test_split_by_source() {
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    let b = map.add_source("b.js");
+    map.set_source_content(b as usize, "b content").unwrap();
+    let name = map.add_name("foo");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(0, 0, a, None)));
+    map.add_mapping(0, 10, Some(OriginalLocation::new(3, 1, b, Some(name))));
+    map.add_mapping(1, 0, None);
+
+    let split_maps = map.split_by_source().unwrap();
+    assert_eq!(split_maps.len(), 2);
+    assert_eq!(split_maps[0].get_mappings().len(), 1);
+
+    let b_map = &split_maps[1];
+    assert_eq!(b_map.get_sources(), &vec!["b.js"]);
+    assert_eq!(b_map.get_source_content(0).unwrap(), "b content");
+    assert_eq!(b_map.get_names(), &vec!["foo"]);
+    let mappings = b_map.get_mappings();
+    assert_eq!(mappings.len(), 1);
+    assert_eq!(mappings[0].generated_column, 10);
+}
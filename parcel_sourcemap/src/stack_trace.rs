@@ -0,0 +1,309 @@
+use crate::registry::MapRegistry;
+use crate::SourceMap;
+use std::fmt;
+
+// The style a frame was written in, so it can be rendered the same way again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackFormat {
+    // `    at fn (file:1:2)` and `    at file:1:2` (Chrome, Node, Edge)
+    V8,
+    // `    at fn (address at file:1:2)` (React Native)
+    Hermes,
+    // `fn@file:1:2`, `@file:1:2` and `file:1:2` (Safari, Firefox uses the same shape)
+    JavaScriptCore,
+}
+
+// Lines and columns are kept as they appear in the trace, which means 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub format: StackFormat,
+    // Whitespace before the frame
+    pub indent: String,
+    // None when the frame has no function at all, Some("") for JSC frames like `@file:1:2`
+    pub function: Option<String>,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackLine {
+    Frame(StackFrame),
+    // The message and frames that can't be remapped (`at native`, `[native code]`, ...)
+    Text(String),
+}
+
+// `file:line:column`, the file can contain colons itself (urls, windows paths)
+fn parse_location(location: &str) -> Option<(&str, u32, u32)> {
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?;
+    if file.is_empty() {
+        return None;
+    }
+    Some((file, line, column))
+}
+
+impl StackFrame {
+    // Detects the format of a single line, None if it isn't a frame with a location
+    pub fn parse(line: &str) -> Option<StackFrame> {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let trimmed = trimmed.trim_end();
+
+        let (format, function, location) = if let Some(rest) = trimmed.strip_prefix("at ") {
+            match rest.strip_suffix(')').and_then(|r| r.rsplit_once(" (")) {
+                Some((function, location)) => match location.strip_prefix("address at ") {
+                    Some(location) => (StackFormat::Hermes, Some(function), location),
+                    None => (StackFormat::V8, Some(function), location),
+                },
+                None => (StackFormat::V8, None, rest),
+            }
+        } else {
+            match trimmed.rsplit_once('@') {
+                Some((function, location)) => {
+                    (StackFormat::JavaScriptCore, Some(function), location)
+                }
+                None => (StackFormat::JavaScriptCore, None, trimmed),
+            }
+        };
+
+        let (file, line, column) = parse_location(location)?;
+        Some(StackFrame {
+            format,
+            indent: String::from(indent),
+            function: function.map(String::from),
+            file: String::from(file),
+            line,
+            column,
+        })
+    }
+}
+
+impl StackFrame {
+    // The same frame pointing to the original source, with its function name as it is, and
+    // the name of the mapping at its position. None if the position isn't mapped.
+    pub fn resolve(&self, sourcemap: &mut SourceMap) -> Option<(StackFrame, Option<String>)> {
+        let mapping = sourcemap
+            .find_closest_mapping(self.line.checked_sub(1)?, self.column.checked_sub(1)?)?;
+        let original = mapping.original?;
+        let name = match original.name {
+            Some(name) => Some(String::from(sourcemap.get_name(name).ok()?)),
+            None => None,
+        };
+        let frame = StackFrame {
+            format: self.format,
+            indent: self.indent.clone(),
+            function: self.function.clone(),
+            file: String::from(sourcemap.get_source(original.source).ok()?),
+            line: original.original_line + 1,
+            column: original.original_column + 1,
+        };
+        Some((frame, name))
+    }
+
+    // resolve without the name, a single frame can't tell what its function was called
+    // (see remap_frames)
+    pub fn remap(&self, sourcemap: &mut SourceMap) -> Option<StackFrame> {
+        self.resolve(sourcemap).map(|(frame, _)| frame)
+    }
+}
+
+// Remaps the frames of a trace, innermost first, using resolve (see StackFrame::resolve).
+// The position of a frame is where it called the frame above it, so the name mapped there is
+// the original name of that function: a remapped frame takes its function name from the frame
+// below it and keeps its own when that one isn't mapped or has no name.
+pub fn remap_frames<F>(frames: &[StackFrame], resolve: F) -> Vec<Option<StackFrame>>
+where
+    F: FnMut(&StackFrame) -> Option<(StackFrame, Option<String>)>,
+{
+    let mut resolved: Vec<Option<(StackFrame, Option<String>)>> =
+        frames.iter().map(resolve).collect();
+    for i in 0..resolved.len() {
+        let caller_name = resolved
+            .get(i + 1)
+            .and_then(|caller| caller.as_ref())
+            .and_then(|(_, name)| name.clone());
+        if let (Some((frame, _)), Some(name)) = (&mut resolved[i], caller_name) {
+            frame.function = Some(name);
+        }
+    }
+    resolved
+        .into_iter()
+        .map(|resolved| resolved.map(|(frame, _)| frame))
+        .collect()
+}
+
+fn remap_lines<F>(stack: &str, resolve: F) -> String
+where
+    F: FnMut(&StackFrame) -> Option<(StackFrame, Option<String>)>,
+{
+    let mut lines = parse_stack_trace(stack);
+    let frames: Vec<StackFrame> = lines
+        .iter()
+        .filter_map(|line| match line {
+            StackLine::Frame(frame) => Some(frame.clone()),
+            StackLine::Text(_) => None,
+        })
+        .collect();
+    let mut remapped = remap_frames(&frames, resolve).into_iter();
+    for line in lines.iter_mut() {
+        if let StackLine::Frame(frame) = line {
+            if let Some(Some(remapped)) = remapped.next() {
+                *frame = remapped;
+            }
+        }
+    }
+    render_stack_trace(&lines)
+}
+
+impl SourceMap {
+    // Rewrites every frame of the trace through this map, so it should only contain frames
+    // of the generated file (use MapRegistry::remap_stack_trace otherwise). Frames that
+    // aren't mapped and other lines stay as they are.
+    pub fn remap_stack_trace(&mut self, stack: &str) -> String {
+        remap_lines(stack, |frame| frame.resolve(self))
+    }
+}
+
+impl MapRegistry {
+    // Rewrites the frames of all files that have a map in the registry
+    pub fn remap_stack_trace(&mut self, stack: &str) -> String {
+        remap_lines(stack, |frame| frame.resolve(self.get_mut(&frame.file)?))
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = format!("{}:{}:{}", self.file, self.line, self.column);
+        match (self.format, &self.function) {
+            (StackFormat::V8, Some(function)) => {
+                write!(f, "{}at {} ({})", self.indent, function, location)
+            }
+            (StackFormat::Hermes, Some(function)) => {
+                write!(
+                    f,
+                    "{}at {} (address at {})",
+                    self.indent, function, location
+                )
+            }
+            (StackFormat::V8 | StackFormat::Hermes, None) => {
+                write!(f, "{}at {}", self.indent, location)
+            }
+            (StackFormat::JavaScriptCore, Some(function)) => {
+                write!(f, "{}{}@{}", self.indent, function, location)
+            }
+            (StackFormat::JavaScriptCore, None) => write!(f, "{}{}", self.indent, location),
+        }
+    }
+}
+
+impl fmt::Display for StackLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackLine::Frame(frame) => frame.fmt(f),
+            StackLine::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+// Every line of the trace, the format is detected per line so traces that mix formats
+// (e.g. a native module in between) still parse
+pub fn parse_stack_trace(stack: &str) -> Vec<StackLine> {
+    stack
+        .lines()
+        .map(|line| match StackFrame::parse(line) {
+            Some(frame) => StackLine::Frame(frame),
+            None => StackLine::Text(String::from(line)),
+        })
+        .collect()
+}
+
+pub fn render_stack_trace(lines: &[StackLine]) -> String {
+    lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[test]
+fn test_parse_frames() {
+    let frame = StackFrame::parse("    at Object.<anonymous> (/app/dist/index.js:10:15)").unwrap();
+    assert_eq!(frame.format, StackFormat::V8);
+    assert_eq!(frame.function.as_deref(), Some("Object.<anonymous>"));
+    assert_eq!(
+        (frame.file.as_str(), frame.line, frame.column),
+        ("/app/dist/index.js", 10, 15)
+    );
+
+    let frame = StackFrame::parse("    at http://localhost:1234/index.js:1:200").unwrap();
+    assert_eq!(frame.function, None);
+    assert_eq!(frame.file, "http://localhost:1234/index.js");
+
+    let frame =
+        StackFrame::parse("    at anonymous (address at index.android.bundle:1:2345)").unwrap();
+    assert_eq!(frame.format, StackFormat::Hermes);
+    assert_eq!(frame.file, "index.android.bundle");
+
+    let frame = StackFrame::parse("render@http://localhost:1234/index.js:3:40").unwrap();
+    assert_eq!(frame.format, StackFormat::JavaScriptCore);
+    assert_eq!(frame.function.as_deref(), Some("render"));
+
+    let frame = StackFrame::parse("@http://localhost:1234/index.js:3:40").unwrap();
+    assert_eq!(frame.function.as_deref(), Some(""));
+
+    assert!(StackFrame::parse("Error: something went wrong").is_none());
+    assert!(StackFrame::parse("    at new Promise (<anonymous>)").is_none());
+    assert!(StackFrame::parse("[native code]").is_none());
+}
+
+#[test]
+fn test_stack_trace_round_trip() {
+    let stacks = [
+        "TypeError: x is undefined\n    at render (http://localhost/index.js:1:20)\n    at new Promise (<anonymous>)\n    at http://localhost/index.js:5:1",
+        "render@http://localhost/index.js:1:20\n@http://localhost/index.js:5:1\nglobal code@http://localhost/index.js:9:3\n[native code]",
+        "Error: boom\n    at anonymous (address at index.android.bundle:1:2345)\n    at apply (native)",
+    ];
+    for stack in stacks {
+        assert_eq!(render_stack_trace(&parse_stack_trace(stack)), stack);
+    }
+}
+
+#[test]
+fn test_remap_stack_trace() {
+    use crate::mapping::OriginalLocation;
+
+    // function a(){x.y}function b(){a()}b()
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("src/app.js");
+    let render = map.add_name("renderApp");
+    let start = map.add_name("start");
+    map.add_mapping(0, 13, Some(OriginalLocation::new(9, 4, source, None)));
+    map.add_mapping(
+        0,
+        30,
+        Some(OriginalLocation::new(14, 2, source, Some(render))),
+    );
+    map.add_mapping(
+        0,
+        35,
+        Some(OriginalLocation::new(18, 0, source, Some(start))),
+    );
+
+    let stack = "TypeError: x is undefined\n    at a (http://localhost/index.js:1:14)\n    at b (http://localhost/index.js:1:31)\n    at new Promise (<anonymous>)\n    at http://localhost/index.js:1:36";
+    assert_eq!(
+        map.remap_stack_trace(stack),
+        "TypeError: x is undefined\n    at renderApp (src/app.js:10:5)\n    at start (src/app.js:15:3)\n    at new Promise (<anonymous>)\n    at src/app.js:19:1"
+    );
+
+    let mut registry = MapRegistry::new();
+    registry.insert("http://localhost/index.js", map);
+    assert_eq!(
+        registry.remap_stack_trace(
+            "a@http://localhost/index.js?v=1:1:14\nb@http://localhost/vendor.js:1:1"
+        ),
+        "a@src/app.js:10:5\nb@http://localhost/vendor.js:1:1"
+    );
+}
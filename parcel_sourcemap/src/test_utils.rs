@@ -0,0 +1,227 @@
+use crate::mapping::OriginalLocation;
+use crate::SourceMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// Fluent builder for maps in tests, e.g.
+// map().source("a.js").mapping((0, 0), (1, 4)).named((0, 6), (1, 8), "foo").build()
+// Mappings use the last source that was added, positions are (line, column) and 0-based.
+pub fn map() -> MapBuilder {
+    MapBuilder::new("/")
+}
+
+#[derive(Debug, Clone)]
+pub struct MapBuilder {
+    sourcemap: SourceMap,
+    source: Option<u32>,
+}
+
+impl MapBuilder {
+    pub fn new(project_root: &str) -> Self {
+        Self {
+            sourcemap: SourceMap::new(project_root),
+            source: None,
+        }
+    }
+
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = Some(self.sourcemap.add_source(source));
+        self
+    }
+
+    pub fn content(mut self, content: &str) -> Self {
+        let source = self.current_source();
+        self.sourcemap
+            .set_source_content(source as usize, content)
+            .unwrap();
+        self
+    }
+
+    pub fn mapping(self, generated: (u32, u32), original: (u32, u32)) -> Self {
+        self.add(generated, original, None)
+    }
+
+    pub fn named(mut self, generated: (u32, u32), original: (u32, u32), name: &str) -> Self {
+        let name = self.sourcemap.add_name(name);
+        self.add(generated, original, Some(name))
+    }
+
+    pub fn unmapped(mut self, generated: (u32, u32)) -> Self {
+        self.sourcemap.add_mapping(generated.0, generated.1, None);
+        self
+    }
+
+    pub fn build(self) -> SourceMap {
+        self.sourcemap
+    }
+
+    fn current_source(&self) -> u32 {
+        self.source
+            .expect("call source() before adding content or mappings")
+    }
+
+    fn add(mut self, generated: (u32, u32), original: (u32, u32), name: Option<u32>) -> Self {
+        let source = self.current_source();
+        self.sourcemap.add_mapping(
+            generated.0,
+            generated.1,
+            Some(OriginalLocation::new(original.0, original.1, source, name)),
+        );
+        self
+    }
+}
+
+// One line per mapping like `0:4 -> a.js:1:2 foo`, sources and names are resolved so maps
+// with differently ordered tables still compare equal
+pub fn describe_mappings(sourcemap: &SourceMap) -> Vec<String> {
+    sourcemap
+        .get_mappings()
+        .iter()
+        .map(|mapping| {
+            let mut line = format!("{}:{}", mapping.generated_line, mapping.generated_column);
+            if let Some(original) = mapping.original {
+                let source = sourcemap.get_source(original.source).unwrap_or("<missing>");
+                write!(
+                    line,
+                    " -> {}:{}:{}",
+                    source, original.original_line, original.original_column
+                )
+                .unwrap();
+                if let Some(name) = original.name {
+                    let name = sourcemap.get_name(name).unwrap_or("<missing>");
+                    write!(line, " {}", name).unwrap();
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+// Line diff of two descriptions, unchanged lines are prefixed with two spaces
+fn diff_lines(left: &[String], right: &[String]) -> String {
+    // Longest common subsequence, maps in tests are small enough for the quadratic table
+    let mut table = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            table[i][j] = if left[i] == right[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            writeln!(diff, "  {}", left[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if j < right.len() && (i == left.len() || table[i][j + 1] >= table[i + 1][j]) {
+            writeln!(diff, "+ {}", right[j]).unwrap();
+            j += 1;
+        } else {
+            writeln!(diff, "- {}", left[i]).unwrap();
+            i += 1;
+        }
+    }
+    diff
+}
+
+// Panics with a diff of the mappings (- left, + right) when the maps are not equivalent
+#[track_caller]
+pub fn assert_maps_eq(left: &SourceMap, right: &SourceMap) {
+    let left = describe_mappings(left);
+    let right = describe_mappings(right);
+    if left != right {
+        panic!(
+            "source maps are not equal (- left, + right):\n{}",
+            diff_lines(&left, &right)
+        );
+    }
+}
+
+// Asserts the original position a generated position resolves to, None means unmapped
+#[track_caller]
+pub fn assert_lookup(
+    sourcemap: &mut SourceMap,
+    generated: (u32, u32),
+    expected: Option<(&str, u32, u32)>,
+) {
+    let found = sourcemap
+        .find_closest_mapping(generated.0, generated.1)
+        .and_then(|mapping| mapping.original)
+        .map(|original| {
+            (
+                sourcemap.get_source(original.source).unwrap_or("<missing>"),
+                original.original_line,
+                original.original_column,
+            )
+        });
+    assert_eq!(found, expected, "lookup of {}:{}", generated.0, generated.1);
+}
+
+// Reads a JSON map from disk, panics with the path when it is missing or invalid
+#[track_caller]
+pub fn load_fixture<P: AsRef<Path>>(path: P) -> SourceMap {
+    let path = path.as_ref();
+    let input = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("could not read fixture {}: {}", path.display(), e));
+    SourceMap::from_json("/", &input)
+        .unwrap_or_else(|e| panic!("could not parse fixture {}: {:?}", path.display(), e))
+}
+
+// Reads a generated file together with the `.map` next to it
+#[track_caller]
+pub fn load_fixture_with_code<P: AsRef<Path>>(path: P) -> (String, SourceMap) {
+    let path = path.as_ref();
+    let code = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("could not read fixture {}: {}", path.display(), e));
+    let mut map_path = path.as_os_str().to_owned();
+    map_path.push(".map");
+    (code, load_fixture(map_path))
+}
+
+#[test]
+fn test_builder_and_asserts() {
+    let mut built = map()
+        .source("a.js")
+        .content("let foo")
+        .mapping((0, 0), (1, 0))
+        .named((0, 4), (1, 4), "foo")
+        .source("b.js")
+        .mapping((1, 0), (0, 0))
+        .unmapped((1, 6))
+        .build();
+    assert_eq!(
+        describe_mappings(&built),
+        vec![
+            "0:0 -> a.js:1:0",
+            "0:4 -> a.js:1:4 foo",
+            "1:0 -> b.js:0:0",
+            "1:6"
+        ]
+    );
+    assert_lookup(&mut built, (0, 2), Some(("a.js", 1, 0)));
+    assert_lookup(&mut built, (0, 4), Some(("a.js", 1, 4)));
+    assert_lookup(&mut built, (1, 6), None);
+
+    let same = map()
+        .source("b.js")
+        .mapping((1, 0), (0, 0))
+        .unmapped((1, 6))
+        .source("a.js")
+        .mapping((0, 0), (1, 0))
+        .named((0, 4), (1, 4), "foo")
+        .build();
+    assert_maps_eq(&built, &same);
+}
+
+#[test]
+fn test_diff_lines() {
+    let left: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+    let right: Vec<String> = ["a", "x", "c"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(diff_lines(&left, &right), "  a\n+ x\n- b\n  c\n");
+}
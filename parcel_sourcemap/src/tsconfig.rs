@@ -0,0 +1,160 @@
+use crate::integrity::SourceProvider;
+#[cfg(feature = "json")]
+use crate::json::ParseOptions;
+use crate::sourcemap_error::SourceMapError;
+use crate::url::{is_absolute_url, normalize_url, resolve_url};
+use crate::utils::{is_abs_path, resolve_path};
+use crate::SourceMap;
+use std::path::PathBuf;
+
+// The rootDir and outDir of a tsconfig.json, relative to the project root. tsc writes every
+// file of rootDir to the same place in outDir, src/a/b.ts ends up as dist/a/b.js.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TsConfigLayout {
+    pub root_dir: String,
+    pub out_dir: String,
+}
+
+fn trim_dir(dir: &str) -> String {
+    let dir = normalize_url(dir);
+    let dir = dir.trim_end_matches('/');
+    String::from(dir.strip_prefix("./").unwrap_or(dir))
+}
+
+// The part of path inside dir, if it is inside
+fn strip_dir<'a>(path: &'a str, dir: &str) -> Option<&'a str> {
+    if dir.is_empty() || dir == "." {
+        return Some(path);
+    }
+    path.strip_prefix(dir)?.strip_prefix('/')
+}
+
+impl TsConfigLayout {
+    pub fn new(root_dir: &str, out_dir: &str) -> Self {
+        Self {
+            root_dir: trim_dir(root_dir),
+            out_dir: trim_dir(out_dir),
+        }
+    }
+
+    // Where a source of the map of emitted_file (both as tsc wrote them) is relative to the
+    // project root. tsc writes sources relative to the map, e.g. ../../src/a/b.ts for
+    // dist/a/b.js.map. Sources that don't resolve to somewhere in rootDir (maps that were
+    // moved, or written with a sourceRoot that doesn't apply anymore) are put where tsc takes
+    // them from: the same place in rootDir as the emitted file in outDir.
+    pub fn resolve_source(&self, emitted_file: &str, source: &str) -> String {
+        if is_absolute_url(source) || is_abs_path(source) {
+            return normalize_url(source);
+        }
+
+        let emitted_file = trim_dir(emitted_file);
+        let resolved = resolve_url(&emitted_file, source);
+        if strip_dir(&resolved, &self.root_dir).is_some() {
+            return resolved;
+        }
+
+        let emitted_dir = match strip_dir(&emitted_file, &self.out_dir) {
+            Some(emitted) => emitted.rsplit_once('/').map_or("", |(dir, _)| dir),
+            None => return resolved,
+        };
+        let file_name = resolved.rsplit('/').next().unwrap_or(&resolved);
+        [self.root_dir.as_str(), emitted_dir, file_name]
+            .iter()
+            .filter(|part| !part.is_empty() && **part != ".")
+            .copied()
+            .collect::<Vec<&str>>()
+            .join("/")
+    }
+}
+
+impl SourceMap {
+    // Parses the map tsc wrote for emitted_file (relative to the project root), with the
+    // sources relative to the project root instead of to the map, see
+    // TsConfigLayout::resolve_source
+    #[cfg(feature = "json")]
+    pub fn from_tsc_json(
+        project_root: &str,
+        input: &str,
+        layout: &TsConfigLayout,
+        emitted_file: &str,
+    ) -> Result<SourceMap, SourceMapError> {
+        // Parsed relative to the map, so absolute sources get relative to it like the rest
+        let emitted_file = trim_dir(emitted_file);
+        let emitted_dir = emitted_file.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let mut sourcemap = SourceMap::from_json_with_options(
+            &resolve_path(project_root, emitted_dir),
+            input,
+            &ParseOptions::default(),
+        )?;
+        sourcemap.project_root = String::from(project_root);
+        sourcemap.rename_sources(|source| layout.resolve_source(&emitted_file, source))?;
+        Ok(sourcemap)
+    }
+
+    // Fills in the sourcesContent of sources without any, using the provider to read them
+    // (resolved against the project root). Returns how many were filled in.
+    pub fn load_missing_sources_content<P: SourceProvider>(
+        &mut self,
+        provider: &P,
+    ) -> Result<usize, SourceMapError> {
+        let mut loaded = 0;
+        for index in 0..self.inner.sources.len() {
+            let has_content = self
+                .try_get_sources_content()?
+                .get(index)
+                .is_some_and(|c| !c.is_empty());
+            if has_content {
+                continue;
+            }
+
+            let path = PathBuf::from(resolve_path(&self.project_root, &self.inner.sources[index]));
+            if let Some(content) = provider.read(&path) {
+                self.set_source_content(index, &content)?;
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+#[test]
+fn test_resolve_source() {
+    let layout = TsConfigLayout::new("./src/", "dist");
+    assert_eq!(
+        layout.resolve_source("dist/a/b.js", "../../src/a/b.ts"),
+        "src/a/b.ts"
+    );
+    // Written with a sourceRoot that pointed somewhere else
+    assert_eq!(layout.resolve_source("dist/a/b.js", "b.ts"), "src/a/b.ts");
+    assert_eq!(
+        layout.resolve_source("./dist/a/b.js", "../../../other/a/b.ts"),
+        "src/a/b.ts"
+    );
+    assert_eq!(layout.resolve_source("lib/c.js", "c.ts"), "lib/c.ts");
+    assert_eq!(
+        layout.resolve_source("dist/a/b.js", "webpack://app/b.ts"),
+        "webpack://app/b.ts"
+    );
+
+    let layout = TsConfigLayout::new(".", "dist");
+    assert_eq!(layout.resolve_source("dist/b.js", "../b.ts"), "b.ts");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_from_tsc_json() {
+    use std::path::Path;
+
+    let input = r#"{"version":3,"file":"b.js","sourceRoot":"","sources":["../../src/a/b.ts","/project/src/a/c.ts"],"names":[],"mappings":"AAAA,ECAA"}"#;
+    let layout = TsConfigLayout::new("src", "dist");
+    let mut map = SourceMap::from_tsc_json("/project", input, &layout, "dist/a/b.js").unwrap();
+    assert_eq!(map.project_root, "/project");
+    assert_eq!(map.get_sources(), &vec!["src/a/b.ts", "src/a/c.ts"]);
+
+    let provider = |path: &Path| {
+        (path == Path::new("/project/src/a/b.ts")).then(|| String::from("let b: number;"))
+    };
+    assert_eq!(map.load_missing_sources_content(&provider).unwrap(), 1);
+    assert_eq!(map.get_source_content(0).unwrap(), "let b: number;");
+    assert_eq!(map.load_missing_sources_content(&provider).unwrap(), 0);
+}
@@ -0,0 +1,257 @@
+// Sources are URLs according to the spec, a source is resolved by prepending sourceRoot and
+// resolving the result against the url of the map (RFC 3986, section 5.2). Paths are treated
+// as URLs without a scheme, with backslashes as separators and drive letters (C:/) as part of
+// the path rather than a scheme.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct UrlParts<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+fn is_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    // Single letters are drive letters
+    scheme.len() > 1
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+}
+
+fn split_url(url: &str) -> UrlParts<'_> {
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url, None),
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let (scheme, rest) = match rest.split_once(':') {
+        Some((scheme, rest)) if is_scheme(scheme) => (Some(scheme), rest),
+        _ => (None, rest),
+    };
+    let (authority, path) = match rest.strip_prefix("//") {
+        Some(rest) => {
+            let end = rest.find('/').unwrap_or(rest.len());
+            (Some(&rest[..end]), &rest[end..])
+        }
+        None => (None, rest),
+    };
+    UrlParts {
+        scheme,
+        authority,
+        path,
+        query,
+        fragment,
+    }
+}
+
+fn join_url(parts: &UrlParts, path: &str) -> String {
+    let mut url = String::new();
+    if let Some(scheme) = parts.scheme {
+        url.push_str(scheme);
+        url.push(':');
+    }
+    if let Some(authority) = parts.authority {
+        url.push_str("//");
+        url.push_str(authority);
+    }
+    url.push_str(path);
+    if let Some(query) = parts.query {
+        url.push('?');
+        url.push_str(query);
+    }
+    if let Some(fragment) = parts.fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+// Resolves `.` and `..` segments. Relative paths keep the `..` segments that go above them,
+// absolute ones drop them.
+fn remove_dot_segments(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let absolute = path.starts_with('/');
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/') {
+        trailing_slash = false;
+        match segment {
+            "" => trailing_slash = true,
+            "." => trailing_slash = true,
+            ".." => {
+                trailing_slash = true;
+                if segments.last().is_some_and(|s| *s != "..") {
+                    segments.pop();
+                } else if !absolute {
+                    segments.push("..");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let mut result = String::new();
+    if absolute {
+        result.push('/');
+    }
+    result.push_str(&segments.join("/"));
+    if trailing_slash && !segments.is_empty() {
+        result.push('/');
+    }
+    result
+}
+
+// A URL with a scheme, like https://example.com/a.js or webpack://app/a.js
+pub fn is_absolute_url(url: &str) -> bool {
+    split_url(url).scheme.is_some()
+}
+
+// Resolves the `.` and `..` segments of a URL or path
+pub fn normalize_url(url: &str) -> String {
+    let parts = split_url(url);
+    join_url(&parts, &remove_dot_segments(parts.path))
+}
+
+// Resolves reference against base, the same way a browser resolves a relative URL
+pub fn resolve_url(base: &str, reference: &str) -> String {
+    let reference_parts = split_url(reference);
+    if reference_parts.scheme.is_some() {
+        return normalize_url(reference);
+    }
+
+    let base_parts = split_url(base);
+    let mut target = UrlParts {
+        scheme: base_parts.scheme,
+        authority: base_parts.authority,
+        path: "",
+        query: reference_parts.query,
+        fragment: reference_parts.fragment,
+    };
+    let path = if reference_parts.authority.is_some() {
+        target.authority = reference_parts.authority;
+        remove_dot_segments(reference_parts.path)
+    } else if reference_parts.path.is_empty() {
+        if reference_parts.query.is_none() {
+            target.query = base_parts.query;
+        }
+        String::from(base_parts.path)
+    } else if reference_parts.path.starts_with(['/', '\\']) {
+        remove_dot_segments(reference_parts.path)
+    } else if base_parts.authority.is_some() && base_parts.path.is_empty() {
+        remove_dot_segments(&format!("/{}", reference_parts.path))
+    } else {
+        // Everything of the base up to its last segment
+        let base_path = base_parts.path.replace('\\', "/");
+        let directory = match base_path.rfind('/') {
+            Some(index) => &base_path[..=index],
+            None => "",
+        };
+        remove_dot_segments(&format!("{}{}", directory, reference_parts.path))
+    };
+    join_url(&target, &path)
+}
+
+// The URL of a source: sourceRoot is prepended (with a slash in between, sources that are
+// absolute URLs already don't get it) and the result is resolved against the url of the map
+// if there is one
+pub fn resolve_source_url(
+    source_root: Option<&str>,
+    source: &str,
+    map_url: Option<&str>,
+) -> String {
+    let mut url = String::new();
+    if let Some(source_root) = source_root.filter(|r| !r.is_empty() && !is_absolute_url(source)) {
+        url.push_str(source_root);
+        if !source_root.ends_with('/') {
+            url.push('/');
+        }
+    }
+    url.push_str(source);
+    match map_url {
+        Some(map_url) => resolve_url(map_url, &url),
+        None => normalize_url(&url),
+    }
+}
+
+#[test]
+fn test_resolve_url() {
+    // From RFC 3986, section 5.4 (except g:h, single letters are drive letters here)
+    let base = "http://a/b/c/d;p?q";
+    for (reference, expected) in [
+        ("g", "http://a/b/c/g"),
+        ("./g", "http://a/b/c/g"),
+        ("g/", "http://a/b/c/g/"),
+        ("/g", "http://a/g"),
+        ("//g", "http://g"),
+        ("?y", "http://a/b/c/d;p?y"),
+        ("g?y", "http://a/b/c/g?y"),
+        ("#s", "http://a/b/c/d;p?q#s"),
+        ("g#s", "http://a/b/c/g#s"),
+        ("", "http://a/b/c/d;p?q"),
+        (".", "http://a/b/c/"),
+        ("./", "http://a/b/c/"),
+        ("..", "http://a/b/"),
+        ("../g", "http://a/b/g"),
+        ("../..", "http://a/"),
+        ("../../g", "http://a/g"),
+        ("../../../g", "http://a/g"),
+        ("/./g", "http://a/g"),
+        ("g.", "http://a/b/c/g."),
+        ("..g", "http://a/b/c/..g"),
+        ("./../g", "http://a/b/g"),
+        ("g/./h", "http://a/b/c/g/h"),
+        ("g/../h", "http://a/b/c/h"),
+    ] {
+        assert_eq!(resolve_url(base, reference), expected, "{}", reference);
+    }
+
+    assert_eq!(resolve_url("/dist/app.js.map", "../src/a.js"), "/src/a.js");
+    assert_eq!(resolve_url("dist/app.js.map", "../../a.js"), "../a.js");
+    assert_eq!(
+        resolve_url("C:\\dist\\app.js.map", "src\\a.js"),
+        "C:/dist/src/a.js"
+    );
+    assert_eq!(
+        resolve_url("file:///dist/app.js.map", "./a.js"),
+        "file:///dist/a.js"
+    );
+    assert_eq!(
+        normalize_url("webpack://app/./src/../a.js"),
+        "webpack://app/a.js"
+    );
+    assert!(is_absolute_url("webpack://app/a.js"));
+    assert!(!is_absolute_url("C:/a.js"));
+}
+
+#[test]
+fn test_resolve_source_url() {
+    assert_eq!(resolve_source_url(None, "a.js", None), "a.js");
+    assert_eq!(resolve_source_url(Some("src"), "a.js", None), "src/a.js");
+    assert_eq!(resolve_source_url(Some("src/"), "./a.js", None), "src/a.js");
+    assert_eq!(resolve_source_url(Some(""), "a.js", None), "a.js");
+    assert_eq!(
+        resolve_source_url(
+            Some("../src"),
+            "lib/a.js",
+            Some("https://example.com/dist/app.js.map")
+        ),
+        "https://example.com/src/lib/a.js"
+    );
+    assert_eq!(
+        resolve_source_url(None, "/a.js", Some("https://example.com/dist/app.js.map")),
+        "https://example.com/a.js"
+    );
+    assert_eq!(
+        resolve_source_url(Some("webpack://"), "app/a.js", None),
+        "webpack://app/a.js"
+    );
+    assert_eq!(
+        resolve_source_url(Some("src"), "https://example.com/a.js", None),
+        "https://example.com/a.js"
+    );
+}
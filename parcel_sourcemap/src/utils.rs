@@ -1,6 +1,7 @@
 // Based on https://github.com/getsentry/rust-sourcemap/blob/master/src/utils.rs
+use crate::url::normalize_url;
 use std::borrow::Cow;
-use std::iter::repeat;
+use std::iter::repeat_n;
 
 pub fn is_abs_path(s: &str) -> bool {
     if s.starts_with('/') || s.starts_with('\\') {
@@ -19,7 +20,20 @@ pub fn is_abs_path(s: &str) -> bool {
     false
 }
 
-fn get_common_prefix_len<'a>(items: &'a [Cow<'a, [&'a str]>]) -> usize {
+fn fold_case(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().flat_map(char::to_lowercase)
+}
+
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+    fold_case(a).eq(fold_case(b))
+}
+
+// Equal for exactly the strings eq_ignore_case considers equal, to use as a lookup key
+pub(crate) fn case_key(s: &str) -> String {
+    fold_case(s).collect()
+}
+
+fn get_common_prefix_len<'a>(items: &'a [Cow<'a, [&'a str]>], ignore_case: bool) -> usize {
     if items.is_empty() {
         return 0;
     }
@@ -29,7 +43,12 @@ fn get_common_prefix_len<'a>(items: &'a [Cow<'a, [&'a str]>]) -> usize {
     for seq in items.iter() {
         let mut seq_max_idx = None;
         for (idx, &comp) in shortest.iter().enumerate() {
-            if seq.get(idx) != Some(&comp) {
+            let is_equal = match seq.get(idx) {
+                Some(other) if ignore_case => eq_ignore_case(other, comp),
+                Some(other) => *other == comp,
+                None => false,
+            };
+            if !is_equal {
                 break;
             }
             seq_max_idx = Some(idx);
@@ -47,14 +66,18 @@ fn get_common_prefix_len<'a>(items: &'a [Cow<'a, [&'a str]>]) -> usize {
 }
 
 fn chunk_path(p: &str) -> Vec<&str> {
-    return p
-        .split(&['/', '\\'][..])
+    p.split(&['/', '\\'][..])
         .filter(|x| !x.is_empty() && *x != ".")
-        .collect();
+        .collect()
 }
 
 // Helper function to calculate the path from a base file to a target file.
 pub fn make_relative_path(base: &str, target: &str) -> String {
+    make_relative_path_with_case(base, target, false)
+}
+
+// Same as make_relative_path, but optionally compares path components case-insensitively
+pub fn make_relative_path_with_case(base: &str, target: &str, ignore_case: bool) -> String {
     let mut target_str = target;
     {
         let target_lower = target.to_ascii_lowercase();
@@ -65,9 +88,10 @@ pub fn make_relative_path(base: &str, target: &str) -> String {
 
     if !is_abs_path(target_str) {
         if target_str.contains(':') {
-            String::from(target_str)
+            // Urls like webpack://app/a.js only get their dot segments resolved
+            normalize_url(target_str)
         } else {
-            return chunk_path(target_str).join("/");
+            chunk_path(target_str).join("/")
         }
     } else {
         let target_path: Vec<&str> = chunk_path(target_str);
@@ -76,13 +100,67 @@ pub fn make_relative_path(base: &str, target: &str) -> String {
             Cow::Borrowed(base_dir.as_slice()),
             Cow::Borrowed(target_path.as_slice()),
         ];
-        let prefix_len = get_common_prefix_len(&items);
-        let mut rel_list: Vec<&str> = repeat("..").take(base_dir.len() - prefix_len).collect();
+        let prefix_len = get_common_prefix_len(&items, ignore_case);
+        let mut rel_list: Vec<&str> = repeat_n("..", base_dir.len() - prefix_len).collect();
         rel_list.extend_from_slice(&target_path[prefix_len..]);
         rel_list.join("/")
     }
 }
 
+// Splits an absolute path into its root ("/" or a drive like "C:/") and the rest
+fn split_root(path: &str) -> (String, &str) {
+    if path.starts_with('/') || path.starts_with('\\') {
+        (String::from("/"), &path[1..])
+    } else if is_abs_path(path) {
+        (format!("{}/", &path[..2]), &path[3..])
+    } else {
+        (String::new(), path)
+    }
+}
+
+// Joins path onto base and resolves `.` and `..` components, absolute paths are only
+// normalized and urls (e.g. webpack://) are returned as is
+pub fn resolve_path(base: &str, path: &str) -> String {
+    if !is_abs_path(path) && path.contains(':') {
+        return String::from(path);
+    }
+
+    let (root, base_rest, path_rest) = if is_abs_path(path) {
+        let (root, rest) = split_root(path);
+        (root, "", rest)
+    } else {
+        let (root, rest) = split_root(base);
+        (root, rest, path)
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in chunk_path(base_rest)
+        .into_iter()
+        .chain(chunk_path(path_rest))
+    {
+        if component == ".." && components.last().is_some_and(|c| *c != "..") {
+            components.pop();
+        } else if component != ".." || root.is_empty() {
+            components.push(component);
+        }
+    }
+
+    root + &components.join("/")
+}
+
+#[test]
+fn test_resolve_path() {
+    assert_eq!(&resolve_path("/foo/bar", "baz/../a.js"), "/foo/bar/a.js");
+    assert_eq!(&resolve_path("/foo/bar", "../../../a.js"), "/a.js");
+    assert_eq!(&resolve_path("/foo", "/other/./a.js"), "/other/a.js");
+    assert_eq!(&resolve_path("C:\\foo", "..\\a.js"), "C:/a.js");
+    assert_eq!(&resolve_path("foo", "../../a.js"), "../a.js");
+    assert_eq!(
+        &resolve_path("/foo", "webpack://app/a.js"),
+        "webpack://app/a.js"
+    );
+}
+
 #[test]
 fn test_make_relative_path() {
     assert_eq!(
@@ -0,0 +1,154 @@
+use crate::buffer_check::check_archive;
+use crate::mapping::{DuplicateMappingPolicy, Mapping};
+use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use crate::{ArchivedSourceMapInner, SourceMap, SourceMapInner, BUFFER_FLAG_SORTED};
+use rkyv::{archived_root, de::deserializers::AllocDeserializer, Deserialize};
+
+// Read-only map that looks things up in a buffer written by to_buffer, without deserializing
+// it. Cheaper than from_buffer for cached maps that only get queried a few times.
+pub struct SourceMapView<'a> {
+    inner: &'a ArchivedSourceMapInner,
+    duplicate_mapping_policy: DuplicateMappingPolicy,
+    // The buffer has BUFFER_FLAG_SORTED, lines don't have to be checked
+    sorted: bool,
+}
+
+impl<'a> SourceMapView<'a> {
+    // The buffer has to be aligned to 16 bytes, like the AlignedVec that to_buffer writes to
+    pub fn from_buffer(buf: &'a [u8]) -> Result<Self, SourceMapError> {
+        let archive = SourceMap::buffer_archive(buf)?;
+        if archive.as_ptr().align_offset(16) != 0 {
+            return Err(SourceMapError::new_with_reason(
+                SourceMapErrorType::BufferError,
+                "buffer is not aligned to 16 bytes",
+            ));
+        }
+
+        check_archive(archive)?;
+        let inner = unsafe { archived_root::<SourceMapInner>(archive) };
+        let duplicate_mapping_policy = inner
+            .duplicate_mapping_policy
+            .deserialize(&mut AllocDeserializer)?;
+        Ok(Self {
+            inner,
+            duplicate_mapping_policy,
+            sorted: SourceMap::buffer_flags(buf) & BUFFER_FLAG_SORTED != 0,
+        })
+    }
+
+    pub fn find_closest_mapping(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> Option<Mapping> {
+        let line = self.inner.mapping_lines.get(generated_line as usize)?;
+        let line_mapping = if self.sorted {
+            line.find_sorted_closest_mapping(generated_column)?
+        } else {
+            line.find_closest_mapping(generated_column, self.duplicate_mapping_policy)?
+        };
+        Some(Mapping {
+            generated_line,
+            generated_column: line_mapping.generated_column,
+            original: line_mapping.original,
+        })
+    }
+
+    pub fn get_source(&self, index: u32) -> Result<&'a str, SourceMapError> {
+        self.inner
+            .sources
+            .get(index as usize)
+            .map(|v| v.as_str())
+            .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))
+    }
+
+    pub fn get_sources_count(&self) -> usize {
+        self.inner.sources.len()
+    }
+
+    pub fn get_source_content(&self, index: u32) -> Result<&'a str, SourceMapError> {
+        self.inner
+            .sources_content
+            .get(index as usize)
+            .map(|v| v.as_str())
+            .ok_or_else(|| SourceMapError::new(SourceMapErrorType::SourceOutOfRange))
+    }
+
+    pub fn get_name(&self, index: u32) -> Result<&'a str, SourceMapError> {
+        self.inner
+            .names
+            .get(index as usize)
+            .map(|v| v.as_str())
+            .ok_or_else(|| SourceMapError::new(SourceMapErrorType::NameOutOfRange))
+    }
+
+    pub fn get_line_count(&self) -> usize {
+        self.inner.mapping_lines.len()
+    }
+}
+
+#[test]
+fn test_source_map_view() {
+    use crate::mapping::OriginalLocation;
+    use rkyv::AlignedVec;
+
+    let mut map = SourceMap::new("/");
+    let a = map.add_source("a.js");
+    map.set_source_content(a as usize, "let a").unwrap();
+    let name = map.add_name("main");
+    map.add_mapping(0, 10, Some(OriginalLocation::new(3, 0, a, None)));
+    map.add_mapping(0, 2, Some(OriginalLocation::new(1, 4, a, Some(name))));
+    map.add_mapping(2, 4, Some(OriginalLocation::new(5, 0, a, None)));
+    map.add_mapping(2, 4, Some(OriginalLocation::new(6, 0, a, None)));
+    map.add_mapping(2, 8, None);
+    map.set_duplicate_mapping_policy(DuplicateMappingPolicy::KeepLast);
+    let mut buffer = AlignedVec::new();
+    map.to_buffer(&mut buffer).unwrap();
+
+    let view = SourceMapView::from_buffer(&buffer).unwrap();
+    assert!(view.sorted);
+    // The lines were sorted and deduplicated while writing, the map itself wasn't changed
+    for line in [0, 2] {
+        let archived = view.inner.mapping_lines.get(line).unwrap();
+        assert!(archived.is_sorted && !archived.may_have_duplicates);
+    }
+    assert_eq!(view.inner.mapping_lines.get(2).unwrap().mappings.len(), 2);
+    assert_eq!(map.inner.mapping_lines.get(2).unwrap().mappings.len(), 3);
+    assert_eq!(view.get_source(a).unwrap(), "a.js");
+    assert_eq!(view.get_source_content(a).unwrap(), "let a");
+    assert_eq!(view.get_name(name).unwrap(), "main");
+    assert!(view.get_name(1).is_err());
+    assert_eq!(view.get_line_count(), 3);
+    for (line, column) in [
+        (0, 0),
+        (0, 5),
+        (0, 12),
+        (1, 0),
+        (2, 4),
+        (2, 6),
+        (2, 9),
+        (3, 0),
+    ] {
+        assert_eq!(
+            view.find_closest_mapping(line, column)
+                .map(|m| (m.generated_column, m.original)),
+            map.find_closest_mapping(line, column)
+                .map(|m| (m.generated_column, m.original))
+        );
+    }
+
+    let mut unaligned = AlignedVec::new();
+    unaligned.push(0);
+    unaligned.extend_from_slice(&buffer);
+    assert!(SourceMapView::from_buffer(&unaligned[1..]).is_err());
+
+    // Buffers without the flag still get their lines checked
+    let mut unflagged = buffer.clone();
+    unflagged[12] = 0;
+    let view = SourceMapView::from_buffer(&unflagged).unwrap();
+    assert!(!view.sorted);
+    assert_eq!(
+        view.find_closest_mapping(2, 4).unwrap().original,
+        map.find_closest_mapping(2, 4).unwrap().original
+    );
+}
@@ -0,0 +1,100 @@
+use crate::mapping_line::LineMapping;
+use crate::sourcemap_error::SourceMapError;
+use crate::vlq_utils::{write_vlq_line, VlqState};
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+struct CachedLine {
+    start_state: VlqState,
+    end_state: VlqState,
+    encoded: Vec<u8>,
+}
+
+// Encoded mappings per generated line, a line only gets re-encoded if it has been
+// invalidated or if the relative state it starts from has changed. Keyed by line, as maps
+// can have millions of empty lines that never get cached.
+#[derive(Debug, Default, Clone)]
+pub struct VlqLineCache {
+    lines: BTreeMap<usize, CachedLine>,
+}
+
+impl VlqLineCache {
+    pub fn invalidate_line(&mut self, line: usize) {
+        self.lines.remove(&line);
+    }
+
+    // Bytes of the encoded lines, leaving out the tree itself
+    pub fn heap_size(&self) -> usize {
+        self.lines
+            .values()
+            .map(|line| std::mem::size_of::<CachedLine>() + line.encoded.capacity())
+            .sum()
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn insert_lines(&mut self, line: usize, count: usize) {
+        let moved = self.lines.split_off(&line);
+        self.lines.extend(
+            moved
+                .into_iter()
+                .map(|(l, cached_line)| (l + count, cached_line)),
+        );
+    }
+
+    pub fn remove_lines(&mut self, lines: Range<usize>) {
+        if lines.start >= lines.end {
+            return;
+        }
+        let mut removed = self.lines.split_off(&lines.start);
+        let moved = removed.split_off(&lines.end);
+        let count = lines.end - lines.start;
+        self.lines.extend(
+            moved
+                .into_iter()
+                .map(|(l, cached_line)| (l - count, cached_line)),
+        );
+    }
+
+    pub fn truncate(&mut self, line_count: usize) {
+        self.lines.split_off(&line_count);
+    }
+
+    pub fn write_line<W>(
+        &mut self,
+        line: usize,
+        mappings: &[LineMapping],
+        state: &mut VlqState,
+        output: &mut W,
+    ) -> Result<(), SourceMapError>
+    where
+        W: io::Write,
+    {
+        if let Some(cached_line) = self.lines.get(&line) {
+            if cached_line.start_state == *state {
+                output.write_all(&cached_line.encoded)?;
+                *state = cached_line.end_state;
+                return Ok(());
+            }
+        }
+
+        let start_state = *state;
+        let mut encoded = Vec::new();
+        write_vlq_line(mappings, state, &mut encoded)?;
+        output.write_all(&encoded)?;
+
+        self.lines.insert(
+            line,
+            CachedLine {
+                start_state,
+                end_state: *state,
+                encoded,
+            },
+        );
+        Ok(())
+    }
+}
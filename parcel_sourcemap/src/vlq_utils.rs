@@ -1,14 +1,12 @@
 // Based on https://github.com/fitzgen/source-map-mappings
+use crate::mapping_line::LineMapping;
 use crate::sourcemap_error::{SourceMapError, SourceMapErrorType};
+use std::io;
 use vlq::decode;
 
 #[inline]
-pub fn read_relative_vlq<B>(previous: &mut i64, input: &mut B) -> Result<(), SourceMapError>
-where
-    B: Iterator<Item = u8>,
-{
-    let decoded = decode(input)?;
-    let (new, overflowed) = (*previous as i64).overflowing_add(decoded);
+pub fn apply_relative_vlq(previous: &mut i64, decoded: i64) -> Result<(), SourceMapError> {
+    let (new, overflowed) = previous.overflowing_add(decoded);
     if overflowed || new > (u32::MAX as i64) {
         return Err(SourceMapError::new(
             SourceMapErrorType::UnexpectedlyBigNumber,
@@ -26,7 +24,250 @@ where
     Ok(())
 }
 
+// Bytes of a mappings string that can be peeked and know their position, so errors
+// can point to where decoding failed
+pub trait MappingInput: Iterator<Item = u8> {
+    fn peek(&mut self) -> Option<u8>;
+    fn byte_offset(&self) -> usize;
+
+    // Reads the fields of the segment at the current position, returns how many there are
+    fn read_segment(&mut self, fields: &mut [i64; 5]) -> Result<usize, SourceMapError>
+    where
+        Self: Sized,
+    {
+        read_segment(self, fields)
+    }
+}
+
+// A generated column, optionally followed by a source, original line and original column
+// (and a name), or by only a name
+pub fn read_segment<I: MappingInput>(
+    input: &mut I,
+    fields: &mut [i64; 5],
+) -> Result<usize, SourceMapError> {
+    fields[0] = decode(input)?;
+    if input.peek().is_none_or(is_mapping_separator) {
+        return Ok(1);
+    }
+    fields[1] = decode(input)?;
+    if input.peek().is_none_or(is_mapping_separator) {
+        return Ok(2);
+    }
+    fields[2] = decode(input)?;
+    fields[3] = decode(input)?;
+    if input.peek().is_none_or(is_mapping_separator) {
+        return Ok(4);
+    }
+    fields[4] = decode(input)?;
+    Ok(5)
+}
+
+pub struct MappingBytes<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> MappingBytes<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+}
+
+impl Iterator for MappingBytes<'_> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+}
+
+impl MappingInput for MappingBytes<'_> {
+    #[inline]
+    fn peek(&mut self) -> Option<u8> {
+        self.bytes.get(self.position).cloned()
+    }
+
+    #[inline]
+    fn byte_offset(&self) -> usize {
+        self.position
+    }
+
+    #[inline]
+    fn read_segment(&mut self, fields: &mut [i64; 5]) -> Result<usize, SourceMapError> {
+        #[cfg(feature = "simd")]
+        if let Some((count, len)) = crate::simd::read_segment(&self.bytes[self.position..], fields)
+        {
+            self.position += len;
+            return Ok(count);
+        }
+        read_segment(self, fields)
+    }
+}
+
+const READER_CHUNK_SIZE: usize = 64 * 1024;
+
+// Reads the mappings in fixed-size chunks, so only one chunk is in memory at a time.
+// Read errors end the input, take_error returns them afterwards.
+pub struct ReaderBytes<R> {
+    reader: R,
+    chunk: Box<[u8]>,
+    start: usize,
+    end: usize,
+    // Bytes of the chunks before the current one
+    consumed: usize,
+    error: Option<io::Error>,
+}
+
+impl<R: io::Read> ReaderBytes<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            chunk: vec![0; READER_CHUNK_SIZE].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            consumed: 0,
+            error: None,
+        }
+    }
+
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    // Returns false at the end of the input
+    fn fill(&mut self) -> bool {
+        if self.start < self.end {
+            return true;
+        }
+        if self.error.is_some() {
+            return false;
+        }
+
+        self.consumed += self.end;
+        self.start = 0;
+        self.end = 0;
+        loop {
+            match self.reader.read(&mut self.chunk) {
+                Ok(read) => {
+                    self.end = read;
+                    return read > 0;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.error = Some(e);
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for ReaderBytes<R> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        if !self.fill() {
+            return None;
+        }
+        let byte = self.chunk[self.start];
+        self.start += 1;
+        Some(byte)
+    }
+}
+
+impl<R: io::Read> MappingInput for ReaderBytes<R> {
+    #[inline]
+    fn peek(&mut self) -> Option<u8> {
+        if !self.fill() {
+            return None;
+        }
+        Some(self.chunk[self.start])
+    }
+
+    #[inline]
+    fn byte_offset(&self) -> usize {
+        self.consumed + self.start
+    }
+}
+
 #[inline]
 pub fn is_mapping_separator(byte: u8) -> bool {
     byte == b';' || byte == b','
 }
+
+// Semicolons for lines without mappings, written in blocks as there can be millions
+pub fn write_line_separators<W>(output: &mut W, count: usize) -> Result<(), SourceMapError>
+where
+    W: io::Write,
+{
+    const SEPARATORS: [u8; 256] = [b';'; 256];
+    let mut remaining = count;
+    while remaining > 0 {
+        let len = remaining.min(SEPARATORS.len());
+        output.write_all(&SEPARATORS[..len])?;
+        remaining -= len;
+    }
+    Ok(())
+}
+
+// Values that are encoded relative to the previous mapping, carried over between lines
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VlqState {
+    pub source: i64,
+    pub original_line: i64,
+    pub original_column: i64,
+    pub name: i64,
+}
+
+pub fn write_vlq_line<W>(
+    mappings: &[LineMapping],
+    state: &mut VlqState,
+    output: &mut W,
+) -> Result<(), SourceMapError>
+where
+    W: io::Write,
+{
+    let mut previous_generated_column: u32 = 0;
+    let mut is_first_mapping: bool = true;
+    for mapping in mappings {
+        let generated_column = mapping.generated_column;
+        if !is_first_mapping {
+            output.write_all(b",")?;
+        }
+
+        vlq::encode(
+            (generated_column - previous_generated_column) as i64,
+            output,
+        )?;
+        previous_generated_column = generated_column;
+
+        // Source should only be written if there is any
+        if let Some(original) = &mapping.original {
+            let original_source = original.source as i64;
+            vlq::encode(original_source - state.source, output)?;
+            state.source = original_source;
+
+            let original_line = original.original_line as i64;
+            vlq::encode(original_line - state.original_line, output)?;
+            state.original_line = original_line;
+
+            let original_column = original.original_column as i64;
+            vlq::encode(original_column - state.original_column, output)?;
+            state.original_column = original_column;
+
+            if let Some(name) = original.name {
+                let original_name = name as i64;
+                vlq::encode(original_name - state.name, output)?;
+                state.name = original_name;
+            }
+        }
+
+        is_first_mapping = false;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+// Runs a fixture through Mozilla's source-map, used by compare_source_map.rs
+// Usage: node run.js <fixture.json> <queries as JSON [[line, column], ...]>
+const fs = require('fs');
+
+let SourceMapConsumer;
+try {
+  ({ SourceMapConsumer } = require('source-map'));
+} catch (e) {
+  // Tells the harness that source-map is not installed
+  process.exit(2);
+}
+
+async function run() {
+  const rawMap = JSON.parse(fs.readFileSync(process.argv[2], 'utf8'));
+  const queries = JSON.parse(process.argv[3]);
+  const consumer = await new SourceMapConsumer(rawMap);
+
+  const mappings = [];
+  consumer.eachMapping((m) => {
+    mappings.push([
+      m.generatedLine,
+      m.generatedColumn,
+      m.source,
+      m.originalLine,
+      m.originalColumn,
+      m.name,
+    ]);
+  }, null, SourceMapConsumer.GENERATED_ORDER);
+
+  const lookups = queries.map(([line, column]) => {
+    const position = consumer.originalPositionFor({ line, column });
+    return [position.line, position.column];
+  });
+
+  if (consumer.destroy) {
+    consumer.destroy();
+  }
+  process.stdout.write(JSON.stringify({ mappings, lookups }));
+}
+
+run().catch((e) => {
+  console.error(e);
+  process.exit(1);
+});
@@ -0,0 +1,122 @@
+// Compares decoding and lookups against Mozilla's source-map (through node) on the
+// fixtures in tests/compare/fixtures. Needs node and the source-map dev dependency (yarn
+// install), the test fails if they are not available.
+// Run using: cargo test --features compare_js --test compare_source_map
+#![cfg(feature = "compare_js")]
+
+use serde::Deserialize;
+use speedy_parcel_sourcemap::SourceMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Sources (with the sourceRoot applied) and names are compared as strings, index maps
+// don't have a single table to index into
+type JsMapping = (
+    u32,
+    u32,
+    Option<String>,
+    Option<u32>,
+    Option<u32>,
+    Option<String>,
+);
+
+#[derive(Deserialize)]
+struct JsResult {
+    mappings: Vec<JsMapping>,
+    lookups: Vec<(Option<u32>, Option<u32>)>,
+}
+
+fn run_source_map(fixture: &Path, queries: &[(u32, u32)]) -> JsResult {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/compare/run.js");
+    let output = Command::new("node")
+        .arg(script)
+        .arg(fixture)
+        .arg(serde_json::to_string(queries).unwrap())
+        .output()
+        .unwrap_or_else(|e| panic!("compare_js needs node to run source-map: {}", e));
+
+    match output.status.code() {
+        Some(0) => serde_json::from_slice(&output.stdout).unwrap(),
+        Some(2) => panic!(
+            "compare_js needs the source-map dev dependency, run yarn install first \
+             (or run the tests without the compare_js feature)"
+        ),
+        _ => panic!(
+            "source-map failed on {}: {}",
+            fixture.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    }
+}
+
+#[test]
+fn compare_with_source_map() {
+    let fixtures_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/compare/fixtures");
+    let mut fixtures: Vec<_> = fs::read_dir(fixtures_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty());
+    for fixture in fixtures {
+        let input = fs::read_to_string(&fixture).unwrap();
+        let mut map = SourceMap::from_json("/", &input).unwrap();
+
+        let mappings = map.get_mappings();
+        let mapped: Vec<JsMapping> = mappings
+            .iter()
+            .map(|m| {
+                (
+                    m.generated_line + 1,
+                    m.generated_column,
+                    m.original
+                        .map(|o| map.get_source_with_root(map.get_source(o.source).unwrap())),
+                    m.original.map(|o| o.original_line + 1),
+                    m.original.map(|o| o.original_column),
+                    m.original
+                        .and_then(|o| o.name)
+                        .map(|name| String::from(map.get_name(name).unwrap())),
+                )
+            })
+            .collect();
+
+        // Query every mapping and the column right after it, as long as that is not
+        // past the last mapping of the line
+        let mut queries = Vec::new();
+        for (i, m) in mappings.iter().enumerate() {
+            queries.push((m.generated_line + 1, m.generated_column));
+            if let Some(next) = mappings.get(i + 1) {
+                if next.generated_line == m.generated_line
+                    && next.generated_column > m.generated_column + 1
+                {
+                    queries.push((m.generated_line + 1, m.generated_column + 1));
+                }
+            }
+        }
+
+        let expected = run_source_map(&fixture, &queries);
+
+        assert_eq!(
+            mapped,
+            expected.mappings,
+            "mappings of {}",
+            fixture.display()
+        );
+        for ((line, column), expected) in queries.iter().zip(expected.lookups) {
+            let found = map
+                .find_closest_mapping(line - 1, *column)
+                .and_then(|m| m.original)
+                .map(|o| (Some(o.original_line + 1), Some(o.original_column)))
+                .unwrap_or((None, None));
+            assert_eq!(
+                found,
+                expected,
+                "lookup of {}:{} in {}",
+                line,
+                column,
+                fixture.display()
+            );
+        }
+    }
+}
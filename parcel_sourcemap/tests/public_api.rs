@@ -0,0 +1,114 @@
+// Every item of the public API gets imported here and the signatures of the free functions
+// and core methods are pinned below, so removing, moving or changing one fails to compile on
+// stable. The full listing, which also catches added items, is checked by
+// tests/public_api_snapshot.rs. Update this file deliberately when the API changes.
+#![allow(unused_imports)]
+
+use speedy_parcel_sourcemap::prelude::*;
+
+use speedy_parcel_sourcemap::{
+    AddSourceMode, CancellationToken, CapacityHints, ClosureMapExtras, Concatenator,
+    DuplicateMappingPolicy, ErrorLocation, ExtendsOptions, Features, FlatArraysOptions,
+    FlatMappings, FlattenOptions, FromOptions, InputEncoding, IterateOptions, LazySourceMap,
+    LineOffsetOptions, LoadingMapRegistry, LookupBias, LookupIndex, LookupOptions, LookupPosition,
+    MapLoader, MapPipeline, MapRegistry, Mapping, MappingCursor, MutationEvent, MutationObserver,
+    NameNormalizer, NameWithoutSourcePolicy, OffsetOptions, OneBasedPosition, OriginalLocation,
+    OverlayFrame, ParseOptions, PipelineDescription, PipelineProfiler, PipelineStep, Position,
+    Progress, ProgressOptions, ProgressReporter, RawMapping, ResolvedLocation, ResolvedLocationRef,
+    ReverseIndex, ReverseMapping, SourceFilter, SourceMap, SourceMapError, SourceMapErrorType,
+    SourceMapView, SourceMismatch, SourceMismatchKind, SourceProvenance, SourceProvider,
+    SourceRootHandling, SourceRootOutput, StackFormat, StackFrame, StackLine, TsConfigLayout,
+    BUFFER_FORMAT_VERSION, DEFAULT_MAX_COLUMN, PROFILE_ENV_VAR,
+};
+
+use speedy_parcel_sourcemap::{
+    DensityHistogram, FileNormalizer, HistogramBucket, MappingBatches, OptionsReader,
+    ProgressStage, SourceMapRef, StageProfile, Syntax, ToBufferOptions, ToJsonOptions,
+};
+
+use speedy_parcel_sourcemap::{
+    append_source_map_comment, catch_panic, decode_data_url, decode_mappings, detect_encoding,
+    encode_mappings, extract_source_map_url, hash_source_content, is_declaration_file,
+    parse_stack_trace, remap_frames, render_stack_trace, set_error_format,
+    strip_source_map_comment, ErrorFormat,
+};
+
+use speedy_parcel_sourcemap::{mapping, prelude, sourcemap_error, utils};
+
+use rkyv::AlignedVec;
+
+#[test]
+fn test_signatures() {
+    let _: fn(&[Mapping]) -> String = encode_mappings;
+    let _: fn(&str) -> Result<Vec<Mapping>, SourceMapError> = decode_mappings;
+    let _: fn(ErrorFormat) = set_error_format;
+    let _: fn(&str) -> Result<Vec<u8>, SourceMapError> = decode_data_url;
+    let _: fn(&[u8]) -> InputEncoding = detect_encoding;
+    let _: fn(&str) -> u64 = hash_source_content;
+    let _: fn(&str) -> bool = is_declaration_file;
+    let _: fn(&str) -> Vec<StackLine> = parse_stack_trace;
+    let _: fn(&[StackLine]) -> String = render_stack_trace;
+    let _: fn(&str, Syntax) -> Option<SourceMapRef> = extract_source_map_url;
+    let _: fn(&str, Syntax) -> String = strip_source_map_comment;
+    let _: fn(&str, &str, Syntax) -> String = append_source_map_comment;
+    let _: u32 = BUFFER_FORMAT_VERSION;
+    let _: u32 = DEFAULT_MAX_COLUMN;
+    let _: &str = PROFILE_ENV_VAR;
+
+    let _: fn(&str) -> SourceMap = SourceMap::new;
+    let _: fn(&str, &str) -> Result<SourceMap, SourceMapError> = SourceMap::from_json;
+    let _: fn(&str, &str, &ParseOptions) -> Result<SourceMap, SourceMapError> =
+        SourceMap::from_json_with_options;
+    let _: fn(&mut SourceMap, &ToJsonOptions) -> Result<String, SourceMapError> =
+        SourceMap::to_json;
+    let _: fn(&str, &[u8]) -> Result<SourceMap, SourceMapError> = SourceMap::from_buffer;
+    let _: fn(&SourceMap, &mut AlignedVec) -> Result<(), SourceMapError> = SourceMap::to_buffer;
+    let _: fn(&SourceMap, &mut AlignedVec, &ToBufferOptions) -> Result<(), SourceMapError> =
+        SourceMap::to_buffer_with_options;
+    let _: fn(&mut SourceMap, u32, u32, Option<OriginalLocation>) = SourceMap::add_mapping;
+    let _: fn(&mut SourceMap, u32, u32) -> Option<Mapping> = SourceMap::find_closest_mapping;
+    let _: fn(&SourceMap) -> Vec<Mapping> = SourceMap::get_mappings;
+    let _: fn(&mut SourceMap, &str) -> u32 = SourceMap::add_source;
+    let _: fn(&SourceMap, u32) -> Result<&str, SourceMapError> = SourceMap::get_source;
+    let _: fn(&SourceMap) -> &Vec<String> = SourceMap::get_sources;
+    let _: fn(&mut SourceMap, &str) -> u32 = SourceMap::add_name;
+    let _: fn(&SourceMap, u32) -> Result<&str, SourceMapError> = SourceMap::get_name;
+    let _: fn(&SourceMap) -> &Vec<String> = SourceMap::get_names;
+    let _: fn(&mut SourceMap, usize, &str) -> Result<(), SourceMapError> =
+        SourceMap::set_source_content;
+    let _: fn(&SourceMap, u32) -> Result<&str, SourceMapError> = SourceMap::get_source_content;
+    let _: fn(&mut SourceMap, &mut SourceMap, i64) -> Result<(), SourceMapError> =
+        SourceMap::add_sourcemap;
+    let _: fn(&mut SourceMap, &mut SourceMap) -> Result<(), SourceMapError> = SourceMap::extends;
+    let _: fn(&mut SourceMap, u32, i64) -> Result<(), SourceMapError> = SourceMap::offset_lines;
+}
+
+#[test]
+fn test_options_are_non_exhaustive() {
+    // Options can only be built from their defaults outside of the crate, so adding a field
+    // isn't a breaking change
+    let mut options = ParseOptions::default();
+    options.keep_raw_input = true;
+    let mut lookup = LookupOptions::default();
+    lookup.bias = LookupBias::LeastUpperBound;
+    let _ = (
+        options,
+        lookup,
+        ToJsonOptions::default(),
+        CapacityHints::default(),
+    );
+}
+
+#[test]
+fn test_prelude() {
+    let mut map = SourceMap::new("/");
+    let source = map.add_source("a.js");
+    map.add_mapping(0, 0, Some(OriginalLocation::new(1, 2, source, None)));
+    let mapping: Mapping = map
+        .find_closest_mapping_at(OneBasedPosition::new(1, 1))
+        .unwrap();
+    assert_eq!(mapping.original.unwrap().original_line, 1);
+
+    let error: SourceMapError = SourceMap::from_json("/", "{").unwrap_err();
+    assert!(matches!(error.error_type, SourceMapErrorType::InvalidJson));
+}
@@ -0,0 +1,544 @@
+// Snapshot of the whole public API in tests/public-api.txt, listed from the rustdoc JSON of
+// the crate: every public item, field, variant, method and trait impl with its signature, so
+// adding an item shows up in the diff just like removing or changing one. Needs a nightly
+// toolchain (rustup toolchain install nightly) for the JSON output of rustdoc.
+// Run using: cargo test --features public_api --test public_api_snapshot
+// and with UPDATE_PUBLIC_API=1 to write the new snapshot after a deliberate API change.
+#![cfg(feature = "public_api")]
+
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Every optional part of the API, the bindings only features are left out
+const FEATURES: &str =
+    "json serde diagnostics lsp-types test_utils parallel simd compressed_sources_content mmap";
+
+fn rustdoc_json() -> Value {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("public-api");
+    let output = Command::new("cargo")
+        .args(["+nightly", "rustdoc", "--lib", "--features", FEATURES])
+        .arg("--manifest-path")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .args(["--", "-Z", "unstable-options", "--output-format", "json"])
+        .output()
+        .unwrap_or_else(|e| panic!("public_api needs cargo to run rustdoc: {}", e));
+    if !output.status.success() {
+        panic!(
+            "rustdoc failed, public_api needs a nightly toolchain: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let json = fs::read_to_string(target_dir.join("doc/speedy_parcel_sourcemap.json")).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+// Types are written the way they are used in the source, io::Error and serde_json::Error
+// would both be Error. Gives them the full path of their definition instead.
+fn qualify_paths(value: &mut Value, paths: &Value) {
+    match value {
+        Value::Object(object) => {
+            // Types, and the traits of bounds and impls
+            for key in ["resolved_path", "trait"] {
+                if let Some(Value::Object(resolved)) = object.get_mut(key) {
+                    let id = resolved.get("id").map(|id| id.to_string());
+                    if let Some(path) = id.and_then(|id| paths[id]["path"].as_array()) {
+                        let path: Vec<&str> = path.iter().filter_map(|p| p.as_str()).collect();
+                        resolved.insert(String::from("path"), Value::from(path.join("::")));
+                    }
+                }
+            }
+            for value in object.values_mut() {
+                qualify_paths(value, paths);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                qualify_paths(value, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Listing<'a> {
+    index: &'a Value,
+    lines: Vec<String>,
+    visited: HashSet<(String, String)>,
+}
+
+fn join(items: impl Iterator<Item = String>, separator: &str) -> String {
+    items.collect::<Vec<_>>().join(separator)
+}
+
+fn generic_args(args: &Value) -> String {
+    if let Some(angle) = args.get("angle_bracketed") {
+        let mut rendered: Vec<String> = angle["args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|arg| match arg {
+                Value::Object(arg) => match arg.iter().next() {
+                    Some((kind, value)) if kind == "type" => ty(value),
+                    Some((kind, value)) if kind == "lifetime" => value.as_str().unwrap().into(),
+                    Some((_, value)) => value.to_string(),
+                    None => String::new(),
+                },
+                arg => arg.as_str().unwrap_or_default().into(),
+            })
+            .collect();
+        for constraint in angle["constraints"].as_array().into_iter().flatten() {
+            if let Some(equality) = constraint["binding"].get("equality") {
+                let value = equality
+                    .get("type")
+                    .map_or_else(|| equality.to_string(), ty);
+                rendered.push(format!(
+                    "{} = {}",
+                    constraint["name"].as_str().unwrap(),
+                    value
+                ));
+            }
+        }
+        if rendered.is_empty() {
+            return String::new();
+        }
+        return format!("<{}>", rendered.join(", "));
+    }
+    let parenthesized = &args["parenthesized"];
+    let inputs = join(
+        parenthesized["inputs"].as_array().unwrap().iter().map(ty),
+        ", ",
+    );
+    match parenthesized.get("output").filter(|o| !o.is_null()) {
+        Some(output) => format!("({}) -> {}", inputs, ty(output)),
+        None => format!("({})", inputs),
+    }
+}
+
+fn path(path: &Value) -> String {
+    let args = match path.get("args").filter(|a| !a.is_null()) {
+        Some(args) => generic_args(args),
+        None => String::new(),
+    };
+    format!("{}{}", path["path"].as_str().unwrap(), args)
+}
+
+fn bound(bound: &Value) -> String {
+    if let Some(trait_bound) = bound.get("trait_bound") {
+        let modifier = match trait_bound["modifier"].as_str() {
+            Some("maybe") => "?",
+            _ => "",
+        };
+        return format!("{}{}", modifier, path(&trait_bound["trait"]));
+    }
+    match bound.get("outlives") {
+        Some(lifetime) => lifetime.as_str().unwrap().into(),
+        None => bound.to_string(),
+    }
+}
+
+fn bounds(bounds: &Value) -> String {
+    join(bounds.as_array().unwrap().iter().map(bound), " + ")
+}
+
+fn ty(ty: &Value) -> String {
+    let (kind, value) = match ty.as_object().and_then(|t| t.iter().next()) {
+        Some(kind) => kind,
+        None => return ty.to_string(),
+    };
+    match kind.as_str() {
+        "primitive" | "generic" => value.as_str().unwrap().into(),
+        "resolved_path" => path(value),
+        "borrowed_ref" => {
+            let lifetime = match value["lifetime"].as_str() {
+                Some(lifetime) => format!("{} ", lifetime),
+                None => String::new(),
+            };
+            let mutability = if value["is_mutable"] == true {
+                "mut "
+            } else {
+                ""
+            };
+            format!("&{}{}{}", lifetime, mutability, self::ty(&value["type"]))
+        }
+        "raw_pointer" => {
+            let mutability = if value["is_mutable"] == true {
+                "mut"
+            } else {
+                "const"
+            };
+            format!("*{} {}", mutability, self::ty(&value["type"]))
+        }
+        "slice" => format!("[{}]", self::ty(value)),
+        "array" => format!(
+            "[{}; {}]",
+            self::ty(&value["type"]),
+            value["len"].as_str().unwrap()
+        ),
+        "tuple" => format!(
+            "({})",
+            join(value.as_array().unwrap().iter().map(self::ty), ", ")
+        ),
+        "impl_trait" => format!("impl {}", bounds(value)),
+        "dyn_trait" => {
+            let traits = value["traits"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|t| path(&t["trait"]));
+            let mut rendered = format!("dyn {}", join(traits, " + "));
+            if let Some(lifetime) = value["lifetime"].as_str() {
+                rendered = format!("{} + {}", rendered, lifetime);
+            }
+            rendered
+        }
+        "qualified_path" => {
+            let self_type = self::ty(&value["self_type"]);
+            match value.get("trait").filter(|t| !t.is_null()) {
+                Some(trait_) => format!(
+                    "<{} as {}>::{}",
+                    self_type,
+                    path(trait_),
+                    value["name"].as_str().unwrap()
+                ),
+                None => format!("{}::{}", self_type, value["name"].as_str().unwrap()),
+            }
+        }
+        "function_pointer" => format!("fn{}", signature(&value["sig"])),
+        "infer" => String::from("_"),
+        _ => value.to_string(),
+    }
+}
+
+fn generics(generics: &Value) -> String {
+    let params: Vec<String> = generics["params"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|param| {
+            let name = param["name"].as_str().unwrap();
+            let kind = &param["kind"];
+            if let Some(ty) = kind.get("type") {
+                if ty["is_synthetic"] == true {
+                    return None;
+                }
+                if ty["bounds"].as_array().unwrap().is_empty() {
+                    return Some(name.into());
+                }
+                return Some(format!("{}: {}", name, bounds(&ty["bounds"])));
+            }
+            if let Some(constant) = kind.get("const") {
+                return Some(format!("const {}: {}", name, self::ty(&constant["type"])));
+            }
+            Some(name.into())
+        })
+        .collect();
+    let mut rendered = String::new();
+    if !params.is_empty() {
+        rendered = format!("<{}>", params.join(", "));
+    }
+
+    let predicates: Vec<String> = generics["where_predicates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|predicate| {
+            let predicate = predicate.get("bound_predicate")?;
+            Some(format!(
+                "{}: {}",
+                ty(&predicate["type"]),
+                bounds(&predicate["bounds"])
+            ))
+        })
+        .collect();
+    if !predicates.is_empty() {
+        rendered = format!("{} where {}", rendered, predicates.join(", "));
+    }
+    rendered
+}
+
+fn signature(sig: &Value) -> String {
+    let inputs = sig["inputs"].as_array().unwrap().iter().map(|input| {
+        let name = input[0].as_str().unwrap();
+        let ty = ty(&input[1]);
+        match (name, ty.as_str()) {
+            ("self", "Self") => String::from("self"),
+            ("self", "&Self") => String::from("&self"),
+            ("self", "&mut Self") => String::from("&mut self"),
+            _ => format!("{}: {}", name, ty),
+        }
+    });
+    let inputs = join(inputs, ", ");
+    match sig.get("output").filter(|o| !o.is_null()) {
+        Some(output) => format!("({}) -> {}", inputs, ty(output)),
+        None => format!("({})", inputs),
+    }
+}
+
+fn function(name: &str, function: &Value) -> String {
+    let generics = generics(&function["generics"]);
+    let (generics, predicates) = match generics.split_once(" where ") {
+        Some((generics, predicates)) => (generics.to_owned(), format!(" where {}", predicates)),
+        None => (generics, String::new()),
+    };
+    let unsafety = if function["header"]["is_unsafe"] == true {
+        "unsafe "
+    } else {
+        ""
+    };
+    format!(
+        "{}fn {}{}{}{}",
+        unsafety,
+        name,
+        generics,
+        signature(&function["sig"]),
+        predicates
+    )
+}
+
+impl<'a> Listing<'a> {
+    fn item(&self, id: &Value) -> Option<&'a Value> {
+        self.index.get(id.to_string())
+    }
+
+    fn module(&mut self, prefix: &str, module: &Value) {
+        for id in module["items"].as_array().unwrap() {
+            if let Some(item) = self.item(id) {
+                self.add(prefix, item, None, false);
+            }
+        }
+    }
+
+    // Re-exports make items of private modules public under the name of the re-export
+    fn add(&mut self, prefix: &str, item: &'a Value, rename: Option<&str>, reexported: bool) {
+        if !reexported && item["visibility"] != "public" {
+            return;
+        }
+        let (kind, inner) = item["inner"].as_object().unwrap().iter().next().unwrap();
+        let name = rename.or_else(|| item["name"].as_str()).unwrap_or_default();
+        let path = format!("{}::{}", prefix, name);
+        if !self.visited.insert((path.clone(), item["id"].to_string())) {
+            return;
+        }
+
+        match kind.as_str() {
+            "use" => match self.item(&inner["id"]) {
+                Some(target) if inner["is_glob"] == true => {
+                    if let Some(module) = target["inner"].get("module") {
+                        self.module(prefix, module);
+                    }
+                }
+                Some(target) => self.add(prefix, target, inner["name"].as_str(), true),
+                None => self.lines.push(format!(
+                    "pub use {} = {}",
+                    path,
+                    inner["source"].as_str().unwrap()
+                )),
+            },
+            "module" => {
+                self.lines.push(format!("pub mod {}", path));
+                self.module(&path, inner);
+            }
+            "function" => self.lines.push(format!("pub {}", function(&path, inner))),
+            "constant" => self
+                .lines
+                .push(format!("pub const {}: {}", path, ty(&inner["type"]))),
+            "static" => self
+                .lines
+                .push(format!("pub static {}: {}", path, ty(&inner["type"]))),
+            "type_alias" => self.lines.push(format!(
+                "pub type {}{} = {}",
+                path,
+                generics(&inner["generics"]),
+                ty(&inner["type"])
+            )),
+            "macro" => self.lines.push(format!("pub macro {}!", path)),
+            "struct" => {
+                self.lines.push(format!(
+                    "pub struct {}{}",
+                    path,
+                    generics(&inner["generics"])
+                ));
+                let fields = match inner["kind"].as_object().and_then(|k| k.iter().next()) {
+                    Some((kind, fields)) if kind == "plain" => fields["fields"].clone(),
+                    Some((kind, fields)) if kind == "tuple" => fields.clone(),
+                    _ => Value::Null,
+                };
+                for (i, id) in fields.as_array().into_iter().flatten().enumerate() {
+                    let Some(field) = self.item(id) else {
+                        continue;
+                    };
+                    if field["visibility"] != "public" {
+                        continue;
+                    }
+                    let name = field["name"]
+                        .as_str()
+                        .map_or_else(|| i.to_string(), String::from);
+                    self.lines.push(format!(
+                        "pub {}::{}: {}",
+                        path,
+                        name,
+                        ty(&field["inner"]["struct_field"])
+                    ));
+                }
+                self.impls(&path, &inner["impls"]);
+            }
+            "enum" => {
+                self.lines
+                    .push(format!("pub enum {}{}", path, generics(&inner["generics"])));
+                for id in inner["variants"].as_array().unwrap() {
+                    let Some(variant) = self.item(id) else {
+                        continue;
+                    };
+                    let fields = match variant["inner"]["variant"]["kind"]
+                        .as_object()
+                        .and_then(|k| k.iter().next())
+                    {
+                        Some((kind, fields)) if kind == "tuple" => {
+                            let fields = fields.as_array().unwrap().iter().map(|id| {
+                                self.item(id).map_or(String::from("_"), |field| {
+                                    ty(&field["inner"]["struct_field"])
+                                })
+                            });
+                            format!("({})", join(fields, ", "))
+                        }
+                        Some((kind, fields)) if kind == "struct" => {
+                            let fields = fields["fields"].as_array().unwrap().iter().map(|id| {
+                                self.item(id).map_or(String::from("_"), |field| {
+                                    format!(
+                                        "{}: {}",
+                                        field["name"].as_str().unwrap(),
+                                        ty(&field["inner"]["struct_field"])
+                                    )
+                                })
+                            });
+                            format!(" {{ {} }}", join(fields, ", "))
+                        }
+                        _ => String::new(),
+                    };
+                    self.lines.push(format!(
+                        "pub {}::{}{}",
+                        path,
+                        variant["name"].as_str().unwrap(),
+                        fields
+                    ));
+                }
+                self.impls(&path, &inner["impls"]);
+            }
+            "trait" => {
+                self.lines.push(format!(
+                    "pub trait {}{}",
+                    path,
+                    generics(&inner["generics"])
+                ));
+                for id in inner["items"].as_array().unwrap() {
+                    let Some(trait_item) = self.item(id) else {
+                        continue;
+                    };
+                    self.assoc_item(&path, trait_item);
+                }
+            }
+            _ => self.lines.push(format!("pub {} {}", kind, path)),
+        }
+    }
+
+    fn assoc_item(&mut self, path: &str, item: &Value) {
+        let name = item["name"].as_str().unwrap_or_default();
+        let (kind, inner) = item["inner"].as_object().unwrap().iter().next().unwrap();
+        let item_path = format!("{}::{}", path, name);
+        match kind.as_str() {
+            "function" => self
+                .lines
+                .push(format!("pub {}", function(&item_path, inner))),
+            "assoc_const" => {
+                self.lines
+                    .push(format!("pub const {}: {}", item_path, ty(&inner["type"])))
+            }
+            "assoc_type" => self.lines.push(format!("pub type {}", item_path)),
+            _ => self.lines.push(format!("pub {} {}", kind, item_path)),
+        }
+    }
+
+    fn impls(&mut self, path: &str, impls: &Value) {
+        for id in impls.as_array().unwrap() {
+            let Some(item) = self.item(id) else {
+                continue;
+            };
+            let inner = &item["inner"]["impl"];
+            // Auto traits and blanket impls come with every type
+            if inner["is_synthetic"] == true || !inner["blanket_impl"].is_null() {
+                continue;
+            }
+            match inner.get("trait").filter(|t| !t.is_null()) {
+                Some(trait_) => {
+                    let negative = if inner["is_negative"] == true {
+                        "!"
+                    } else {
+                        ""
+                    };
+                    self.lines.push(format!(
+                        "impl{} {}{} for {}",
+                        generics(&inner["generics"]),
+                        negative,
+                        self::path(trait_),
+                        ty(&inner["for"])
+                    ));
+                }
+                None => {
+                    for id in inner["items"].as_array().unwrap() {
+                        let Some(method) = self.item(id) else {
+                            continue;
+                        };
+                        if method["visibility"] == "public" {
+                            self.assoc_item(path, method);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn public_api_snapshot() {
+    let mut json = rustdoc_json();
+    let paths = json["paths"].take();
+    qualify_paths(&mut json["index"], &paths);
+    let root = &json["index"][json["root"].to_string()];
+    let mut listing = Listing {
+        index: &json["index"],
+        lines: Vec::new(),
+        visited: HashSet::new(),
+    };
+    listing.module(root["name"].as_str().unwrap(), &root["inner"]["module"]);
+    listing.lines.sort();
+    listing.lines.dedup();
+    let mut listing = listing.lines.join("\n");
+    listing.push('\n');
+
+    let snapshot_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/public-api.txt");
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        fs::write(&snapshot_path, &listing).unwrap();
+        return;
+    }
+    let snapshot = fs::read_to_string(&snapshot_path).unwrap_or_default();
+    if snapshot != listing {
+        let old: HashSet<&str> = snapshot.lines().collect();
+        let new: HashSet<&str> = listing.lines().collect();
+        let mut diff: Vec<String> = old
+            .difference(&new)
+            .map(|line| format!("- {}", line))
+            .chain(new.difference(&old).map(|line| format!("+ {}", line)))
+            .collect();
+        diff.sort_by(|a, b| a[2..].cmp(&b[2..]));
+        panic!(
+            "the public API changed, rerun with UPDATE_PUBLIC_API=1 if that is intended:\n{}",
+            diff.join("\n")
+        );
+    }
+}
@@ -3,10 +3,23 @@ extern crate napi_derive;
 extern crate rkyv;
 extern crate speedy_parcel_sourcemap;
 
-use napi::{bindgen_prelude::*, Env, JsString};
-use speedy_parcel_sourcemap::{Mapping, OriginalLocation, SourceMap};
+use napi::{
+    bindgen_prelude::*,
+    sys,
+    threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunctionCallMode},
+    Env, JsFunction, JsString, JsUnknown, Ref,
+};
 use rkyv::AlignedVec;
-use serde_json::{from_str, to_string};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_slice, from_str, to_string, Value};
+use speedy_parcel_sourcemap::{
+    catch_panic, CapacityHints, ErrorFormat, Features, FlatArraysOptions, FlatMappings,
+    FromOptions, IterateOptions, LineOffsetOptions, LookupBias, Mapping, MappingCursor,
+    MemoryBudget, MutationEvent, MutationObserver, OffsetOptions, OriginalLocation,
+    ProgressOptions, ProgressReporter, SourceMap, SourceMapError, SourceMapErrorType,
+};
+use std::borrow::Cow;
+use std::thread::{self, ThreadId};
 
 #[cfg(target_os = "macos")]
 #[global_allocator]
@@ -35,107 +48,352 @@ pub struct VlqMapping {
     pub sources: Array,
     pub sources_content: Array,
     pub names: Array,
+    pub ignore_list: Option<Vec<u32>>,
+    pub debug_id: Option<String>,
+    pub source_root: Option<String>,
 }
 
-#[napi]
-impl JsSourceMap {
-    #[napi(constructor)]
-    pub fn new(project_root: String, second_argument: Option<Buffer>) -> Result<Self> {
-        match second_argument {
-            Some(js_buffer) => Ok(Self(SourceMap::from_buffer(
-                project_root.as_str(),
-                js_buffer.as_ref(),
-            )?)),
-            None => Ok(Self(SourceMap::new(project_root.as_str()))),
+#[napi(object)]
+pub struct FlatMappingArrays {
+    pub generated_lines: Uint32Array,
+    pub generated_columns: Uint32Array,
+    pub sources: Int32Array,
+    pub original_lines: Int32Array,
+    pub original_columns: Int32Array,
+    pub names: Int32Array,
+}
+
+impl From<FlatMappings> for FlatMappingArrays {
+    fn from(flat_mappings: FlatMappings) -> Self {
+        FlatMappingArrays {
+            generated_lines: Uint32Array::new(flat_mappings.generated_lines),
+            generated_columns: Uint32Array::new(flat_mappings.generated_columns),
+            sources: Int32Array::new(flat_mappings.sources),
+            original_lines: Int32Array::new(flat_mappings.original_lines),
+            original_columns: Int32Array::new(flat_mappings.original_columns),
+            names: Int32Array::new(flat_mappings.names),
         }
     }
+}
 
-    #[napi]
-    pub fn add_source(&mut self, source: String) -> u32 {
-        self.0.add_source(source.as_str())
+// A MutationEvent as setMutationObserver passes it to JS, `type` tells which fields are set.
+// Lines are 1-based like everywhere else in the JS api.
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct MutationEventObject {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<u32>,
+}
+
+impl From<&MutationEvent> for MutationEventObject {
+    fn from(event: &MutationEvent) -> Self {
+        match *event {
+            MutationEvent::SourceAdded(index) => MutationEventObject {
+                kind: "sourceAdded",
+                index: Some(index),
+                ..Default::default()
+            },
+            MutationEvent::NameAdded(index) => MutationEventObject {
+                kind: "nameAdded",
+                index: Some(index),
+                ..Default::default()
+            },
+            MutationEvent::MappingAdded {
+                generated_line,
+                generated_column,
+                original,
+            } => MutationEventObject {
+                kind: "mappingAdded",
+                line: Some(generated_line + 1),
+                column: Some(generated_column),
+                original_line: original.map(|o| o.original_line + 1),
+                original_column: original.map(|o| o.original_column),
+                source: original.map(|o| o.source),
+                name: original.and_then(|o| o.name),
+                ..Default::default()
+            },
+            MutationEvent::LineReplaced(line) => MutationEventObject {
+                kind: "lineReplaced",
+                line: Some(line + 1),
+                ..Default::default()
+            },
+            MutationEvent::LineCleared(line) => MutationEventObject {
+                kind: "lineCleared",
+                line: Some(line + 1),
+                ..Default::default()
+            },
+            MutationEvent::LinesTruncated { line_count } => MutationEventObject {
+                kind: "linesTruncated",
+                line_count: Some(line_count),
+                ..Default::default()
+            },
+            MutationEvent::LinesShifted {
+                generated_line,
+                offset,
+            } => MutationEventObject {
+                kind: "linesShifted",
+                line: Some(generated_line + 1),
+                offset: Some(offset),
+                ..Default::default()
+            },
+            MutationEvent::ColumnsShifted {
+                generated_line,
+                generated_column,
+                offset,
+            } => MutationEventObject {
+                kind: "columnsShifted",
+                line: Some(generated_line + 1),
+                column: Some(generated_column),
+                offset: Some(offset),
+                ..Default::default()
+            },
+            MutationEvent::Reset => MutationEventObject {
+                kind: "reset",
+                ..Default::default()
+            },
+        }
     }
+}
 
-    #[napi]
-    pub fn get_source(&self, source_index: u32) -> String {
-        match self.0.get_source(source_index) {
-            Ok(source) => source,
-            Err(_err) => "",
+#[napi(object)]
+pub struct BuildFeatures {
+    pub diagnostics: bool,
+    pub native: bool,
+    pub wasm: bool,
+    pub simd: bool,
+    pub compressed_sources_content: bool,
+    pub parallel: bool,
+    pub mmap: bool,
+    pub serde: bool,
+    pub lsp_types: bool,
+}
+
+impl From<Features> for BuildFeatures {
+    fn from(features: Features) -> Self {
+        BuildFeatures {
+            diagnostics: features.diagnostics,
+            native: features.native,
+            wasm: features.wasm,
+            simd: features.simd,
+            compressed_sources_content: features.compressed_sources_content,
+            parallel: features.parallel,
+            mmap: features.mmap,
+            serde: features.serde,
+            lsp_types: features.lsp_types,
         }
-        .to_owned()
     }
+}
 
-    #[napi]
-    pub fn _get_sources(&self) -> &Vec<String> {
-        self.0.get_sources()
+#[napi(object)]
+pub struct MappingBatchCursor {
+    pub line: u32,
+    pub index: u32,
+}
+
+#[napi(object)]
+pub struct MappingBatch {
+    pub mappings: FlatMappingArrays,
+    // Where the next batch starts
+    pub cursor: MappingBatchCursor,
+    pub done: bool,
+}
+
+// Borrows strings straight from the JSON input unless they contain escapes
+#[derive(Deserialize)]
+struct JsonStrings<'a>(#[serde(borrow)] Vec<Cow<'a, str>>);
+
+impl<'a> JsonStrings<'a> {
+    fn as_strs(&self) -> Vec<&str> {
+        self.0.iter().map(|s| s.as_ref()).collect()
+    }
+}
+
+// Options objects are validated by the core, a missing object is the same as an empty one
+fn read_options<T: FromOptions>(env: &Env, options: Option<JsUnknown>) -> Result<T> {
+    let value: Value = match options {
+        Some(options) => env.from_js_value(options)?,
+        None => Value::Null,
+    };
+    Ok(T::from_options(&value)?)
+}
+
+// A JS function the core can call while a method of the map is running, e.g. from the parse
+// loop. That only happens inside a synchronous call on the JS thread it was created on, calls
+// from any other thread are dropped.
+struct SyncCallback {
+    env: sys::napi_env,
+    callback: Ref<()>,
+    thread: ThreadId,
+}
+
+// Only used on the thread it was created on, see call
+unsafe impl Send for SyncCallback {}
+unsafe impl Sync for SyncCallback {}
+
+impl SyncCallback {
+    fn new(env: &Env, callback: JsFunction) -> Result<Self> {
+        Ok(Self {
+            env: env.raw(),
+            callback: env.create_reference(callback)?,
+            thread: thread::current().id(),
+        })
+    }
+
+    fn call<T: Serialize>(&self, value: &T) {
+        if thread::current().id() != self.thread {
+            return;
+        }
+        let env = unsafe { Env::from_raw(self.env) };
+        let call = || -> Result<()> {
+            let callback: JsFunction = env.get_reference_value_unchecked(&self.callback)?;
+            callback.call(None, &[env.to_js_value(value)?])?;
+            Ok(())
+        };
+        // An exception thrown by the callback stays pending and is thrown once the method
+        // returns
+        let _ = call();
+    }
+}
+
+impl Drop for SyncCallback {
+    fn drop(&mut self) {
+        let _ = self.callback.unref(unsafe { Env::from_raw(self.env) });
+    }
+}
+
+// The return type of an infallible method, which is () for methods without one
+type ReturnType<T = ()> = T;
+
+macro_rules! napi_methods {
+    (
+        infallible {
+            $($js:tt fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? |$map:ident| $body:block)*
+        }
+        fallible {
+            $($fallible_js:tt fn $fallible_name:ident($($fallible_arg:ident: $fallible_ty:ty),*) -> $fallible_ret:ty |$fallible_map:ident| $fallible_body:block)*
+        }
+    ) => {
+        #[napi]
+        impl JsSourceMap {
+            $(
+                #[napi(js_name = $js)]
+                pub fn $name(&mut self, $($arg: $ty),*) -> Result<ReturnType<$($ret)?>> {
+                    let $map = &mut self.0;
+                    Ok(catch_panic(move || Ok($body))?)
+                }
+            )*
+
+            $(
+                #[napi(js_name = $fallible_js)]
+                pub fn $fallible_name(&mut self, $($fallible_arg: $fallible_ty),*) -> Result<$fallible_ret> {
+                    fn call(
+                        $fallible_map: &mut SourceMap,
+                        $($fallible_arg: $fallible_ty),*
+                    ) -> std::result::Result<$fallible_ret, SourceMapError> $fallible_body
+
+                    Ok(catch_panic(|| call(&mut self.0, $($fallible_arg),*))?)
+                }
+            )*
+        }
+    };
+}
+
+speedy_parcel_sourcemap::shared_binding_methods!(napi_methods);
+
+#[napi]
+impl JsSourceMap {
+    #[napi(constructor)]
+    pub fn new(
+        env: Env,
+        project_root: String,
+        second_argument: Option<Buffer>,
+        options: Option<JsUnknown>,
+    ) -> Result<Self> {
+        let hints: CapacityHints = read_options(&env, options)?;
+        let mut map = match second_argument {
+            Some(js_buffer) => {
+                catch_panic(|| SourceMap::from_buffer(project_root.as_str(), js_buffer.as_ref()))?
+            }
+            None => SourceMap::new(project_root.as_str()),
+        };
+        map.reserve(&hints);
+        Ok(Self(map))
     }
 
     #[napi]
-    pub fn get_sources(&self) -> Result<String> {
-        Ok(to_string(self.0.get_sources())?)
+    pub fn features() -> BuildFeatures {
+        SourceMap::features().into()
     }
 
+    // Process wide, applies to the errors of every map
     #[napi]
-    pub fn get_sources_content(&self) -> &Vec<String> {
-        self.0.get_sources_content()
+    pub fn set_error_format(format: String) -> Result<()> {
+        let format = ErrorFormat::from_name(&format).ok_or_else(|| {
+            SourceMapError::new_with_reason(
+                SourceMapErrorType::InvalidOptions,
+                "format must be one of text, json",
+            )
+        })?;
+        speedy_parcel_sourcemap::set_error_format(format);
+        Ok(())
     }
 
+    // Process wide, maps created afterwards count against it. null removes it.
     #[napi]
-    pub fn get_source_index(&self, source: String) -> Result<i32> {
-        Ok(self
-            .0
-            .get_source_index(source.as_str())?
-            .map(|i| i as i32)
-            .unwrap_or(-1))
+    pub fn set_memory_budget(limit: Option<f64>) {
+        MemoryBudget::set_global(limit.map(|limit| MemoryBudget::new(limit as usize)));
     }
 
     #[napi]
-    pub fn set_source_content_by_source(
-        &mut self,
-        source: String,
-        source_content: String,
-    ) -> Result<()> {
-        let source_index: usize = self.0.add_source(source.as_str()) as usize;
-        self.0
-            .set_source_content(source_index, source_content.as_str())?;
-        Ok(())
+    pub fn get_memory_usage() -> f64 {
+        MemoryBudget::global().map_or(0, |budget| budget.used()) as f64
     }
 
     #[napi]
-    pub fn get_source_content_by_source(&self, source: String) -> Result<String> {
-        let source_index = self.0.get_source_index(source.as_str())?;
-        Ok(match source_index {
-            Some(i) => self.0.get_source_content(i)?,
-            None => "",
-        }
-        .to_owned())
+    pub fn _get_sources(&self) -> &Vec<String> {
+        self.0.get_sources()
     }
 
     #[napi]
-    pub fn add_name(&mut self, name: String) -> u32 {
-        self.0.add_name(name.as_str())
+    pub fn get_sources(&self) -> Result<String> {
+        Ok(to_string(self.0.get_sources())?)
     }
 
     #[napi]
-    pub fn get_name(&self, name_index: u32) -> String {
-        match self.0.get_name(name_index) {
-            Ok(name) => name,
-            Err(_err) => "",
-        }
-        .to_owned()
+    pub fn get_resolved_sources(&self) -> Result<Vec<String>> {
+        let sources = catch_panic(|| Ok(self.0.get_resolved_sources()))?;
+        Ok(sources
+            .iter()
+            .map(|source| source.to_string_lossy().into_owned())
+            .collect())
     }
 
     #[napi]
-    pub fn get_names(&self) -> Result<String> {
-        Ok(to_string(&self.0.get_names())?)
+    pub fn get_sources_content(&self) -> Result<&Vec<String>> {
+        Ok(self.0.try_get_sources_content()?)
     }
 
     #[napi]
-    pub fn get_name_index(&self, name: String) -> Result<i32> {
-        Ok(self
-            .0
-            .get_name_index(name.as_str())
-            .map(|i| i as i32)
-            .unwrap_or(-1))
+    pub fn get_names(&self) -> Result<String> {
+        Ok(to_string(&self.0.get_names())?)
     }
 
     #[napi(
@@ -160,9 +418,8 @@ impl JsSourceMap {
     }
 
     #[napi]
-    pub fn get_mappings(&self) -> Vec<MappingObject> {
-        self.0
-            .get_mappings()
+    pub fn get_mappings(&self) -> Result<Vec<MappingObject>> {
+        Ok(catch_panic(|| Ok(self.0.get_mappings()))?
             .iter()
             .map(|mapping| MappingObject {
                 generated: Position {
@@ -176,13 +433,132 @@ impl JsSourceMap {
                 source: mapping.original.map(|o| o.source),
                 name: mapping.original.and_then(|o| o.name),
             })
-            .collect()
+            .collect())
+    }
+
+    #[napi]
+    pub fn to_flat_arrays(&self) -> Result<FlatMappingArrays> {
+        Ok(catch_panic(|| self.0.to_flat_arrays())?.into())
+    }
+
+    // The callback is called from inside the parse and serialize loops, while the method that
+    // parses or serializes is still running
+    #[napi]
+    pub fn set_progress_callback(
+        &mut self,
+        env: Env,
+        callback: Option<JsFunction>,
+        options: Option<JsUnknown>,
+    ) -> Result<()> {
+        let callback = match callback {
+            Some(callback) => callback,
+            None => {
+                catch_panic(|| {
+                    self.0.set_progress_reporter(None);
+                    Ok(())
+                })?;
+                return Ok(());
+            }
+        };
+
+        let options: ProgressOptions = read_options(&env, options)?;
+        let callback = SyncCallback::new(&env, callback)?;
+        let reporter = ProgressReporter::new(options.every as usize, move |progress| {
+            callback.call(&progress);
+        });
+        catch_panic(|| {
+            self.0.set_progress_reporter(Some(reporter));
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    // Delivered like the progress callback: through a threadsafe function, once the JS thread
+    // is free again, in the order the mutations happened
+    #[napi]
+    pub fn set_mutation_observer(&mut self, env: Env, callback: Option<JsFunction>) -> Result<()> {
+        let observer = match callback {
+            Some(callback) => {
+                let tsfn = env.create_threadsafe_function(
+                    &callback,
+                    0,
+                    |ctx: ThreadSafeCallContext<MutationEventObject>| {
+                        Ok(vec![ctx.env.to_js_value(&ctx.value)?])
+                    },
+                )?;
+                Some(MutationObserver::new(move |event| {
+                    tsfn.call(Ok(event.into()), ThreadsafeFunctionCallMode::NonBlocking);
+                }))
+            }
+            None => None,
+        };
+        catch_panic(|| {
+            self.0.set_mutation_observer(observer);
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    // Used by iterate() on the JS side, which turns the batches into an async iterator
+    #[napi]
+    pub fn read_mapping_batch(
+        &mut self,
+        env: Env,
+        cursor: MappingBatchCursor,
+        options: Option<JsUnknown>,
+    ) -> Result<MappingBatch> {
+        let options: IterateOptions = read_options(&env, options)?;
+        let mut cursor = MappingCursor {
+            line: cursor.line,
+            index: cursor.index,
+        };
+        let mut mappings = FlatMappings::with_capacity(options.batch_size as usize);
+        let has_more = catch_panic(|| {
+            self.0
+                .read_mappings(&mut cursor, options.batch_size as usize, &mut mappings)
+        })?;
+        Ok(MappingBatch {
+            done: !has_more,
+            mappings: mappings.into(),
+            cursor: MappingBatchCursor {
+                line: cursor.line,
+                index: cursor.index,
+            },
+        })
+    }
+
+    #[napi]
+    pub fn add_flat_arrays(
+        &mut self,
+        arrays: FlatMappingArrays,
+        env: Env,
+        options: Option<JsUnknown>,
+    ) -> Result<()> {
+        let options: FlatArraysOptions = read_options(&env, options)?;
+        let flat_mappings = FlatMappings {
+            generated_lines: arrays.generated_lines.to_vec(),
+            generated_columns: arrays.generated_columns.to_vec(),
+            sources: arrays.sources.to_vec(),
+            original_lines: arrays.original_lines.to_vec(),
+            original_columns: arrays.original_columns.to_vec(),
+            names: arrays.names.to_vec(),
+        };
+        catch_panic(|| {
+            self.0.add_flat_arrays(
+                &flat_mappings,
+                options.line_offset,
+                options.column_offset,
+                options.source_index_offset,
+                options.name_index_offset,
+            )
+        })?;
+        Ok(())
     }
 
     #[napi]
     pub fn to_buffer(&self) -> Result<Buffer> {
         let mut buffer_data = AlignedVec::new();
-        self.0.to_buffer(&mut buffer_data)?;
+        catch_panic(|| self.0.to_buffer(&mut buffer_data))?;
         Ok(buffer_data.into_vec().into())
     }
 
@@ -190,10 +566,14 @@ impl JsSourceMap {
     pub fn add_source_map(
         &mut self,
         previous_map_instance: &mut JsSourceMap,
-        line_offset: i64,
+        env: Env,
+        options: Option<JsUnknown>,
     ) -> Result<()> {
-        self.0
-            .add_sourcemap(&mut previous_map_instance.0, line_offset)?;
+        let options: LineOffsetOptions = read_options(&env, options)?;
+        catch_panic(|| {
+            self.0
+                .add_sourcemap(&mut previous_map_instance.0, options.line_offset)
+        })?;
         Ok(())
     }
 
@@ -204,118 +584,135 @@ impl JsSourceMap {
         js_sources_arr_input: String,
         js_sources_content_arr_input: String,
         js_names_arr_input: String,
-        line_offset: i64,
-        column_offset: i64,
+        env: Env,
+        options: Option<JsUnknown>,
+    ) -> Result<()> {
+        let options: OffsetOptions = read_options(&env, options)?;
+        let sources: JsonStrings = from_str(js_sources_arr_input.as_str())?;
+        let sources_content: JsonStrings = from_str(js_sources_content_arr_input.as_str())?;
+        let names: JsonStrings = from_str(js_names_arr_input.as_str())?;
+
+        catch_panic(|| {
+            self.0.add_vlq_map(
+                vlq_mappings.as_bytes(),
+                sources.as_strs(),
+                sources_content.as_strs(),
+                names.as_strs(),
+                options.line_offset,
+                options.column_offset,
+            )
+        })?;
+        Ok(())
+    }
+
+    // Same as addVLQMap but reads everything from utf-8 buffers, which avoids copying
+    // the (potentially huge) mappings and sourcesContent into owned strings first
+    #[napi(js_name = "addVLQMapBuffer")]
+    pub fn add_vlq_map_buffer(
+        &mut self,
+        vlq_mappings: Buffer,
+        js_sources_arr_input: Buffer,
+        js_sources_content_arr_input: Buffer,
+        js_names_arr_input: Buffer,
+        env: Env,
+        options: Option<JsUnknown>,
     ) -> Result<()> {
-        let sources: Vec<String> = from_str(js_sources_arr_input.as_str())?;
-        let sources_content: Vec<String> = from_str(js_sources_content_arr_input.as_str())?;
-        let names: Vec<String> = from_str(js_names_arr_input.as_str())?;
-
-        self.0.add_vlq_map(
-            vlq_mappings.as_bytes(),
-            sources.iter().map(|s| s.as_str()).collect(),
-            sources_content.iter().map(|s| s.as_str()).collect(),
-            names.iter().map(|s| s.as_str()).collect(),
-            line_offset,
-            column_offset,
-        )?;
+        let options: OffsetOptions = read_options(&env, options)?;
+        let sources: JsonStrings = from_slice(js_sources_arr_input.as_ref())?;
+        let sources_content: JsonStrings = from_slice(js_sources_content_arr_input.as_ref())?;
+        let names: JsonStrings = from_slice(js_names_arr_input.as_ref())?;
+
+        catch_panic(|| {
+            self.0.add_vlq_map(
+                vlq_mappings.as_ref(),
+                sources.as_strs(),
+                sources_content.as_strs(),
+                names.as_strs(),
+                options.line_offset,
+                options.column_offset,
+            )
+        })?;
         Ok(())
     }
 
     #[napi(js_name = "toVLQ")]
     pub fn to_vlq(&mut self, env: Env) -> Result<VlqMapping> {
         let mut vlq_output: Vec<u8> = vec![];
-        self.0.write_vlq(&mut vlq_output)?;
+        catch_panic(|| self.0.write_vlq(&mut vlq_output))?;
         let vlq_string = env.create_string_latin1(vlq_output.as_slice())?;
         Ok(VlqMapping {
             sources: Array::from_ref_vec_string(&env, self.0.get_sources())?,
             mappings: vlq_string,
-            sources_content: Array::from_ref_vec_string(&env, self.get_sources_content())?,
+            sources_content: Array::from_ref_vec_string(&env, self.get_sources_content()?)?,
             names: Array::from_ref_vec_string(&env, self.0.get_names())?,
+            ignore_list: Some(self.0.get_ignore_list().to_vec()).filter(|l| !l.is_empty()),
+            debug_id: self.0.get_debug_id().map(String::from),
+            source_root: self.0.get_source_root().map(String::from),
         })
     }
 
     #[napi]
-    pub fn add_indexed_mappings(&mut self, mappings_arr: Int32Array) {
+    pub fn add_indexed_mappings(&mut self, mappings_arr: Int32Array) -> Result<()> {
         let mappings_count = mappings_arr.len();
-
-        let mut generated_line: u32 = 0; // 0
-        let mut generated_column: u32 = 0; // 1
-        let mut original_line: i32 = 0; // 2
-        let mut original_column: i32 = 0; // 3
-        let mut original_source: i32 = 0; // 4
-        for (i, value) in mappings_arr.iter().enumerate().take(mappings_count) {
-            let value = *value;
-            match i % 6 {
-                0 => {
-                    generated_line = value as u32;
-                }
-                1 => {
-                    generated_column = value as u32;
-                }
-                2 => {
-                    original_line = value;
-                }
-                3 => {
-                    original_column = value;
+        catch_panic(|| {
+            self.0.reserve_mapping_memory(mappings_count / 6)?;
+
+            let mut generated_line: u32 = 0; // 0
+            let mut generated_column: u32 = 0; // 1
+            let mut original_line: i32 = 0; // 2
+            let mut original_column: i32 = 0; // 3
+            let mut original_source: i32 = 0; // 4
+            for (i, value) in mappings_arr.iter().enumerate().take(mappings_count) {
+                let value = *value;
+                match i % 6 {
+                    0 => {
+                        generated_line = value as u32;
+                    }
+                    1 => {
+                        generated_column = value as u32;
+                    }
+                    2 => {
+                        original_line = value;
+                    }
+                    3 => {
+                        original_column = value;
+                    }
+                    4 => {
+                        original_source = value;
+                    }
+                    5 => {
+                        self.0.add_mapping(
+                            generated_line,
+                            generated_column,
+                            if original_line > -1 && original_column > -1 && original_source > -1 {
+                                Some(OriginalLocation {
+                                    original_line: original_line as u32,
+                                    original_column: original_column as u32,
+                                    source: original_source as u32,
+                                    name: if value > -1 { Some(value as u32) } else { None },
+                                })
+                            } else {
+                                None
+                            },
+                        );
+                    }
+                    _ => unreachable!(),
                 }
-                4 => {
-                    original_source = value;
-                }
-                5 => {
-                    self.0.add_mapping(
-                        generated_line,
-                        generated_column,
-                        if original_line > -1 && original_column > -1 && original_source > -1 {
-                            Some(OriginalLocation {
-                                original_line: original_line as u32,
-                                original_column: original_column as u32,
-                                source: original_source as u32,
-                                name: if value > -1 { Some(value as u32) } else { None },
-                            })
-                        } else {
-                            None
-                        },
-                    );
-                }
-                _ => unreachable!(),
             }
-        }
-    }
-
-    #[napi]
-    pub fn offset_lines(&mut self, generated_line: u32, generated_line_offset: i64) -> Result<()> {
-        self.0.offset_lines(generated_line, generated_line_offset)?;
+            Ok(())
+        })?;
         Ok(())
     }
 
     #[napi]
-    pub fn offset_columns(
-        &mut self,
-        generated_line: u32,
-        generated_column: u32,
-        generated_column_offset: i64,
-    ) -> Result<()> {
-        self.0
-            .offset_columns(generated_line, generated_column, generated_column_offset)?;
-        Ok(())
-    }
-
-    #[napi]
-    pub fn add_empty_map(
-        &mut self,
-        source: String,
-        source_content: String,
-        line_offset: i64,
-    ) -> Result<()> {
-        self.0
-            .add_empty_map(source.as_str(), source_content.as_str(), line_offset)?;
+    pub fn extends(&mut self, previous_map_instance: &mut JsSourceMap) -> Result<()> {
+        catch_panic(|| self.0.extends(&mut previous_map_instance.0))?;
         Ok(())
     }
 
     #[napi]
-    pub fn extends(&mut self, previous_map_instance: &mut JsSourceMap) -> Result<()> {
-        self.0.extends(&mut previous_map_instance.0)?;
+    pub fn apply(&mut self, original_map_instance: &JsSourceMap) -> Result<()> {
+        catch_panic(|| self.0.apply(&original_map_instance.0))?;
         Ok(())
     }
 
@@ -324,15 +721,14 @@ impl JsSourceMap {
         &mut self,
         generated_line: u32,
         generated_column: u32,
+        bias: Option<String>,
     ) -> Result<Option<MappingObject>> {
-        Ok(self
-            .0
-            .find_closest_mapping(generated_line, generated_column)
-            .map(|mapping| self.mapping_to_js_object(mapping)))
-    }
-
-    #[napi]
-    pub fn get_project_root(&self) -> &str {
-        self.0.project_root.as_str()
+        let bias = LookupBias::from_optional_name(bias.as_deref())?;
+        let mapping = catch_panic(|| {
+            Ok(self
+                .0
+                .find_closest_mapping_with_bias(generated_line, generated_column, bias))
+        })?;
+        Ok(mapping.map(|mapping| self.mapping_to_js_object(mapping)))
     }
 }
@@ -2,11 +2,16 @@
 
 extern crate speedy_parcel_sourcemap;
 
-use js_sys::Uint8Array;
-use speedy_parcel_sourcemap::{Mapping, OriginalLocation, SourceMap as NativeSourceMap};
+use js_sys::{Int32Array, Object, Reflect, Uint32Array, Uint8Array};
 use rkyv::AlignedVec;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::convert::TryFrom;
+use serde_json::Value;
+use speedy_parcel_sourcemap::{
+    catch_panic, CapacityHints, ErrorFormat, FlatArraysOptions, FlatMappings, FromOptions,
+    LineOffsetOptions, LookupBias, Mapping, MemoryBudget, OffsetOptions, OriginalLocation,
+    SourceMap as NativeSourceMap, SourceMapError, SourceMapErrorType,
+};
 use wasm_bindgen::prelude::*;
 
 #[derive(Serialize)]
@@ -16,6 +21,12 @@ struct VLQResult {
     sources: Vec<String>,
     sourcesContent: Vec<String>,
     names: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ignoreList: Vec<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debugId: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sourceRoot: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -53,32 +64,115 @@ impl From<&Mapping> for MappingResult {
     }
 }
 
+// Conversions through serde, invalid input from JS throws instead of panicking
+fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, JsValue> {
+    value
+        .into_serde()
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+fn to_js<T: Serialize + ?Sized>(value: &T) -> Result<JsValue, JsValue> {
+    JsValue::from_serde(value).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+// Options objects are validated by the core, undefined is the same as an empty object
+fn read_options<T: FromOptions>(options: &JsValue) -> Result<T, JsValue> {
+    let value: Value = if options.is_undefined() || options.is_null() {
+        Value::Null
+    } else {
+        from_js(options)?
+    };
+    Ok(T::from_options(&value)?)
+}
+
 #[wasm_bindgen]
 pub struct SourceMap {
     map: NativeSourceMap,
 }
 
+// The return type of an infallible method, which is () for methods without one
+type ReturnType<T = ()> = T;
+
+macro_rules! wasm_methods {
+    (
+        infallible {
+            $($js:tt fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? |$map:ident| $body:block)*
+        }
+        fallible {
+            $($fallible_js:tt fn $fallible_name:ident($($fallible_arg:ident: $fallible_ty:ty),*) -> $fallible_ret:ty |$fallible_map:ident| $fallible_body:block)*
+        }
+    ) => {
+        #[wasm_bindgen]
+        impl SourceMap {
+            $(
+                #[wasm_bindgen(js_name = $js)]
+                pub fn $name(&mut self, $($arg: $ty),*) -> Result<ReturnType<$($ret)?>, JsValue> {
+                    let $map = &mut self.map;
+                    Ok(catch_panic(move || Ok($body))?)
+                }
+            )*
+
+            $(
+                #[wasm_bindgen(js_name = $fallible_js)]
+                pub fn $fallible_name(&mut self, $($fallible_arg: $fallible_ty),*) -> Result<$fallible_ret, JsValue> {
+                    fn call(
+                        $fallible_map: &mut NativeSourceMap,
+                        $($fallible_arg: $fallible_ty),*
+                    ) -> Result<$fallible_ret, SourceMapError> $fallible_body
+
+                    Ok(catch_panic(|| call(&mut self.map, $($fallible_arg),*))?)
+                }
+            )*
+        }
+    };
+}
+
+speedy_parcel_sourcemap::shared_binding_methods!(wasm_methods);
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 impl SourceMap {
     #[wasm_bindgen(constructor)]
-    pub fn new(project_root: String, buffer: JsValue) -> Result<SourceMap, JsValue> {
-        if !buffer.is_undefined() {
-            return Ok(SourceMap {
-                map: NativeSourceMap::from_buffer(
-                    &project_root,
-                    &Uint8Array::from(buffer).to_vec(),
-                )?,
-            });
-        }
+    pub fn new(
+        project_root: String,
+        buffer: JsValue,
+        options: JsValue,
+    ) -> Result<SourceMap, JsValue> {
+        let hints: CapacityHints = read_options(&options)?;
+        let mut map = if buffer.is_undefined() || buffer.is_null() {
+            NativeSourceMap::new(&project_root)
+        } else {
+            catch_panic(|| {
+                NativeSourceMap::from_buffer(&project_root, &Uint8Array::from(buffer).to_vec())
+            })?
+        };
+        map.reserve(&hints);
+        Ok(SourceMap { map })
+    }
+
+    pub fn features() -> Result<JsValue, JsValue> {
+        to_js(&NativeSourceMap::features())
+    }
 
-        Ok(SourceMap {
-            map: NativeSourceMap::new(&project_root),
-        })
+    // Process wide, applies to the errors of every map
+    pub fn setErrorFormat(format: String) -> Result<(), JsValue> {
+        let format = ErrorFormat::from_name(&format).ok_or_else(|| {
+            SourceMapError::new_with_reason(
+                SourceMapErrorType::InvalidOptions,
+                "format must be one of text, json",
+            )
+        })?;
+        speedy_parcel_sourcemap::set_error_format(format);
+        Ok(())
     }
 
-    pub fn getProjectRoot(&self) -> String {
-        self.map.project_root.clone()
+    // Process wide, maps created afterwards count against it. null removes it.
+    pub fn setMemoryBudget(limit: Option<f64>) {
+        MemoryBudget::set_global(limit.map(|limit| MemoryBudget::new(limit as usize)));
+    }
+
+    pub fn getMemoryUsage() -> f64 {
+        MemoryBudget::global().map_or(0, |budget| budget.used()) as f64
     }
 
     pub fn addVLQMap(
@@ -87,35 +181,40 @@ impl SourceMap {
         sources: JsValue,
         sources_content: JsValue,
         names: JsValue,
-        line_offset: i32,
-        column_offset: i32,
+        options: JsValue,
     ) -> Result<JsValue, JsValue> {
-        let sources_string: Vec<String> = sources.into_serde().unwrap();
-        let sources_content_string: Vec<String> = sources_content.into_serde().unwrap();
-        let names_string: Vec<String> = names.into_serde().unwrap();
-        self.map.add_vlq_map(
-            vlq_mappings.as_bytes(),
-            sources_string.iter().map(|s| s.as_str()).collect(),
-            sources_content_string.iter().map(|s| s.as_str()).collect(),
-            names_string.iter().map(|s| s.as_str()).collect(),
-            line_offset.into(),
-            column_offset.into(),
-        )?;
+        let options: OffsetOptions = read_options(&options)?;
+        let sources_string: Vec<String> = from_js(&sources)?;
+        let sources_content_string: Vec<String> = from_js(&sources_content)?;
+        let names_string: Vec<String> = from_js(&names)?;
+        catch_panic(|| {
+            self.map.add_vlq_map(
+                vlq_mappings.as_bytes(),
+                sources_string.iter().map(|s| s.as_str()).collect(),
+                sources_content_string.iter().map(|s| s.as_str()).collect(),
+                names_string.iter().map(|s| s.as_str()).collect(),
+                options.line_offset,
+                options.column_offset,
+            )
+        })?;
 
         Ok(JsValue::UNDEFINED)
     }
 
     pub fn toVLQ(&mut self) -> Result<JsValue, JsValue> {
         let mut vlq_output: Vec<u8> = vec![];
-        self.map.write_vlq(&mut vlq_output)?;
+        catch_panic(|| self.map.write_vlq(&mut vlq_output))?;
 
         let result = VLQResult {
-            mappings: String::from_utf8(vlq_output).unwrap(),
+            mappings: String::from_utf8(vlq_output).map_err(SourceMapError::from)?,
             sources: self.map.get_sources().clone(),
-            sourcesContent: self.map.get_sources_content().clone(),
+            sourcesContent: self.map.try_get_sources_content()?.clone(),
             names: self.map.get_names().clone(),
+            ignoreList: self.map.get_ignore_list().to_vec(),
+            debugId: self.map.get_debug_id().map(String::from),
+            sourceRoot: self.map.get_source_root().map(String::from),
         };
-        Ok(JsValue::from_serde(&result).unwrap())
+        to_js(&result)
     }
 
     pub fn getMappings(&self) -> Result<JsValue, JsValue> {
@@ -123,7 +222,7 @@ impl SourceMap {
         for mapping in self.map.get_mappings().iter() {
             mappings.push(MappingResult {
                 generated: PositionResult {
-                    line: (mapping.generated_line + 1) as u32,
+                    line: mapping.generated_line + 1,
                     column: mapping.generated_column,
                 },
                 original: mapping.original.map(|p| PositionResult {
@@ -134,54 +233,103 @@ impl SourceMap {
                 source: mapping.original.map(|p| p.source),
             });
         }
-        Ok(JsValue::from_serde(&mappings).unwrap())
-    }
-
-    pub fn getSources(&self) -> Result<JsValue, JsValue> {
-        Ok(JsValue::from_serde(&self.map.get_sources()).unwrap())
+        to_js(&mappings)
     }
 
-    pub fn getSourcesContent(&self) -> Result<JsValue, JsValue> {
-        Ok(JsValue::from_serde(&self.map.get_sources_content()).unwrap())
-    }
-
-    pub fn getNames(&self) -> Result<JsValue, JsValue> {
-        Ok(JsValue::from_serde(&self.map.get_names()).unwrap())
+    pub fn toFlatArrays(&self) -> Result<JsValue, JsValue> {
+        let flat_mappings = catch_panic(|| self.map.to_flat_arrays())?;
+        let result = Object::new();
+        Reflect::set(
+            &result,
+            &"generatedLines".into(),
+            &Uint32Array::from(flat_mappings.generated_lines.as_slice()),
+        )?;
+        Reflect::set(
+            &result,
+            &"generatedColumns".into(),
+            &Uint32Array::from(flat_mappings.generated_columns.as_slice()),
+        )?;
+        Reflect::set(
+            &result,
+            &"sources".into(),
+            &Int32Array::from(flat_mappings.sources.as_slice()),
+        )?;
+        Reflect::set(
+            &result,
+            &"originalLines".into(),
+            &Int32Array::from(flat_mappings.original_lines.as_slice()),
+        )?;
+        Reflect::set(
+            &result,
+            &"originalColumns".into(),
+            &Int32Array::from(flat_mappings.original_columns.as_slice()),
+        )?;
+        Reflect::set(
+            &result,
+            &"names".into(),
+            &Int32Array::from(flat_mappings.names.as_slice()),
+        )?;
+        Ok(result.into())
     }
 
-    pub fn addName(&mut self, name: &str) -> u32 {
-        self.map.add_name(name)
-    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn addFlatArrays(
+        &mut self,
+        generated_lines: &[u32],
+        generated_columns: &[u32],
+        sources: &[i32],
+        original_lines: &[i32],
+        original_columns: &[i32],
+        names: &[i32],
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let options: FlatArraysOptions = read_options(&options)?;
+        let flat_mappings = FlatMappings {
+            generated_lines: generated_lines.to_vec(),
+            generated_columns: generated_columns.to_vec(),
+            sources: sources.to_vec(),
+            original_lines: original_lines.to_vec(),
+            original_columns: original_columns.to_vec(),
+            names: names.to_vec(),
+        };
+        catch_panic(|| {
+            self.map.add_flat_arrays(
+                &flat_mappings,
+                options.line_offset,
+                options.column_offset,
+                options.source_index_offset,
+                options.name_index_offset,
+            )
+        })?;
 
-    pub fn addSource(&mut self, source: &str) -> u32 {
-        self.map.add_source(source)
+        Ok(JsValue::UNDEFINED)
     }
 
-    pub fn getName(&self, index: u32) -> String {
-        self.map.get_name(index).unwrap_or("").to_string()
+    pub fn getSources(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.map.get_sources())
     }
 
-    pub fn getSource(&self, index: u32) -> String {
-        self.map.get_source(index).unwrap_or("").to_string()
+    pub fn getResolvedSources(&self) -> Result<JsValue, JsValue> {
+        let resolved_sources: Vec<String> = self
+            .map
+            .get_resolved_sources()
+            .iter()
+            .map(|source| source.to_string_lossy().into_owned())
+            .collect();
+        to_js(&resolved_sources)
     }
 
-    pub fn getNameIndex(&self, name: &str) -> i32 {
-        self.map
-            .get_name_index(name)
-            .map(|v| i32::try_from(v).unwrap())
-            .unwrap_or(-1)
+    pub fn getSourcesContent(&self) -> Result<JsValue, JsValue> {
+        to_js(self.map.try_get_sources_content()?)
     }
 
-    pub fn getSourceIndex(&self, source: &str) -> Result<JsValue, JsValue> {
-        let mapped_val: i32 = match self.map.get_source_index(source)? {
-            Some(found_source_index) => i32::try_from(found_source_index).unwrap_or(-1),
-            None => -1,
-        };
-        Ok(JsValue::from(mapped_val))
+    pub fn getNames(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.map.get_names())
     }
 
-    pub fn addIndexedMappings(&mut self, mappings_arr: &[i32]) {
+    pub fn addIndexedMappings(&mut self, mappings_arr: &[i32]) -> Result<JsValue, JsValue> {
         let mappings_count = mappings_arr.len();
+        catch_panic(|| self.map.reserve_mapping_memory(mappings_count / 6))?;
         let mut generated_line: u32 = 0; // 0
         let mut generated_column: u32 = 0; // 1
         let mut original_line: i32 = 0; // 2
@@ -225,98 +373,56 @@ impl SourceMap {
                 _ => unreachable!(),
             }
         }
+        Ok(JsValue::UNDEFINED)
     }
 
     pub fn toBuffer(&self) -> Result<JsValue, JsValue> {
         let mut buffer_data = AlignedVec::new();
-        self.map.to_buffer(&mut buffer_data)?;
+        catch_panic(|| self.map.to_buffer(&mut buffer_data))?;
         Ok(Uint8Array::from(buffer_data.as_slice()).into())
     }
 
     pub fn addSourceMap(
         &mut self,
         previous_map_instance: &mut SourceMap,
-        line_offset: i32,
-    ) -> Result<JsValue, JsValue> {
-        self.map
-            .add_sourcemap(&mut previous_map_instance.map, line_offset.into())?;
-
-        Ok(JsValue::UNDEFINED)
-    }
-
-    pub fn setSourceContentBySource(
-        &mut self,
-        source: &str,
-        source_content: &str,
+        options: JsValue,
     ) -> Result<JsValue, JsValue> {
-        let source_index = self.addSource(source) as usize;
-        self.map.set_source_content(source_index, source_content)?;
-
-        Ok(JsValue::UNDEFINED)
-    }
-
-    pub fn getSourceContentBySource(&self, source: &str) -> Result<JsValue, JsValue> {
-        let source_index = self.map.get_source_index(source)?;
-        match source_index {
-            Some(i) => {
-                let source_content = self.map.get_source_content(i)?;
-                Ok(JsValue::from_str(source_content))
-            }
-            None => Ok(JsValue::from_str("")),
-        }
-    }
-
-    pub fn addEmptyMap(
-        &mut self,
-        source: &str,
-        source_content: &str,
-        line_offset: i32,
-    ) -> Result<JsValue, JsValue> {
-        self.map
-            .add_empty_map(source, source_content, line_offset.into())?;
+        let options: LineOffsetOptions = read_options(&options)?;
+        catch_panic(|| {
+            self.map
+                .add_sourcemap(&mut previous_map_instance.map, options.line_offset)
+        })?;
 
         Ok(JsValue::UNDEFINED)
     }
 
     pub fn extends(&mut self, previous_map_instance: &mut SourceMap) -> Result<JsValue, JsValue> {
-        self.map.extends(&mut previous_map_instance.map)?;
+        catch_panic(|| self.map.extends(&mut previous_map_instance.map))?;
 
         Ok(JsValue::UNDEFINED)
     }
 
-    pub fn findClosestMapping(&mut self, generated_line: u32, generated_column: u32) -> JsValue {
-        match self
-            .map
-            .find_closest_mapping(generated_line, generated_column)
-        {
-            Some(mapping) => JsValue::from_serde(&MappingResult::from(&mapping)).unwrap(),
-            None => JsValue::NULL,
-        }
-    }
-
-    pub fn offsetLines(
-        &mut self,
-        generated_line: u32,
-        generated_line_offset: i32,
-    ) -> Result<JsValue, JsValue> {
-        self.map
-            .offset_lines(generated_line, generated_line_offset.into())?;
+    pub fn apply(&mut self, original_map_instance: &SourceMap) -> Result<JsValue, JsValue> {
+        catch_panic(|| self.map.apply(&original_map_instance.map))?;
 
         Ok(JsValue::UNDEFINED)
     }
 
-    pub fn offsetColumns(
+    pub fn findClosestMapping(
         &mut self,
         generated_line: u32,
         generated_column: u32,
-        generated_column_offset: i32,
+        bias: Option<String>,
     ) -> Result<JsValue, JsValue> {
-        self.map.offset_columns(
-            generated_line,
-            generated_column,
-            generated_column_offset.into(),
-        )?;
-
-        Ok(JsValue::UNDEFINED)
+        let bias = LookupBias::from_optional_name(bias.as_deref())?;
+        Ok(
+            match self
+                .map
+                .find_closest_mapping_with_bias(generated_line, generated_column, bias)
+            {
+                Some(mapping) => to_js(&MappingResult::from(&mapping))?,
+                None => JsValue::NULL,
+            },
+        )
     }
 }
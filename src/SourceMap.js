@@ -1,5 +1,16 @@
 // @flow
-import type { ParsedMap, VLQMap, SourceMapStringifyOptions, IndexedMapping, GenerateEmptyMapOptions } from './types';
+import type {
+  ParsedMap,
+  VLQMap,
+  SourceMapStringifyOptions,
+  IndexedMapping,
+  GenerateEmptyMapOptions,
+  SourceMapFeatures,
+  ErrorFormat,
+  CapacityOptions,
+  OverlayFrame,
+  AddSourceMode,
+} from './types';
 
 import path from 'path';
 import { generateInlineMap, partialVlqMapToSourceMap } from './utils';
@@ -20,8 +31,10 @@ export default class SourceMap {
    * Construct a SourceMap instance
    *
    * @param projectRoot root directory of the project, this is to ensure all source paths are relative to this path
+   * @param buffer a buffer written by toBuffer to restore the map from
+   * @param options sizes to reserve up front, when the caller knows roughly how big the map gets
    */
-  constructor(projectRoot: string = '/', buffer?: Buffer) {}
+  constructor(projectRoot: string = '/', buffer?: Buffer, options?: CapacityOptions) {}
 
   // Use this to invalidate saved buffers, we don't check versioning at all in Rust
   get libraryVersion(): string {
@@ -44,6 +57,37 @@ export default class SourceMap {
     throw new Error('SourceMap.generateEmptyMap() must be implemented when extending SourceMap');
   }
 
+  /**
+   * Returns which optional capabilities the loaded binary was built with
+   */
+  static features(): SourceMapFeatures {
+    throw new Error('SourceMap.features() must be implemented when extending SourceMap');
+  }
+
+  /**
+   * Switches how errors thrown by the bindings are formatted, 'json' gives a JSON string
+   * with the code, message, reason and position so tooling can check for specific failures.
+   * Applies to every SourceMap instance.
+   */
+  static setErrorFormat(format: ErrorFormat): void {
+    throw new Error('SourceMap.setErrorFormat() must be implemented when extending SourceMap');
+  }
+
+  /**
+   * Limits the bytes held by all SourceMap instances created after this call, operations
+   * that would grow them past it throw a MemoryBudgetExceeded error. null removes the limit.
+   */
+  static setMemoryBudget(bytes: number | null): void {
+    throw new Error('SourceMap.setMemoryBudget() must be implemented when extending SourceMap');
+  }
+
+  /**
+   * Bytes held by the instances that count against the memory budget
+   */
+  static getMemoryUsage(): number {
+    throw new Error('SourceMap.getMemoryUsage() must be implemented when extending SourceMap');
+  }
+
   /**
    * Generates an empty map from the provided fileName and sourceContent
    *
@@ -231,6 +275,92 @@ export default class SourceMap {
     return this.sourceMapInstance.getSources();
   }
 
+  /**
+   * Get a list of all sources as absolute paths, resolved against the project root
+   */
+  getResolvedSources(): Array<string> {
+    return this.sourceMapInstance.getResolvedSources();
+  }
+
+  /**
+   * Returns whether a source was listed in a parsed map ('declared'), added directly ('added')
+   * or made up while repairing a broken map ('synthesized')
+   *
+   * @param index the index of the source
+   */
+  getSourceProvenance(index: number): 'added' | 'declared' | 'synthesized' {
+    return this.sourceMapInstance.getSourceProvenance(index);
+  }
+
+  /**
+   * Returns the sourceRoot of the map, which gets prepended to every source, or null if it has none
+   */
+  getSourceRoot(): string | null {
+    return this.sourceMapInstance.getSourceRoot() || null;
+  }
+
+  /**
+   * Sets the sourceRoot of the map, null removes it
+   *
+   * @param sourceRoot the path or url that sources are relative to
+   */
+  setSourceRoot(sourceRoot: string | null): SourceMap {
+    this.sourceMapInstance.setSourceRoot(sourceRoot || '');
+    return this;
+  }
+
+  /**
+   * Returns the debugId of the map, a UUID shared with the bundle that symbol upload tools use
+   * to match the two, or null if it has none
+   */
+  getDebugId(): string | null {
+    return this.sourceMapInstance.getDebugId() || null;
+  }
+
+  /**
+   * Sets the debugId of the map, null removes it
+   *
+   * @param debugId the UUID of the bundle
+   */
+  setDebugId(debugId: string | null): SourceMap {
+    this.sourceMapInstance.setDebugId(debugId || '');
+    return this;
+  }
+
+  /**
+   * Adds a source to ignoreList, so debuggers hide it from stack traces (e.g. framework or
+   * vendored code)
+   *
+   * @param index the index of the source
+   */
+  addIgnoredSource(index: number): SourceMap {
+    this.sourceMapInstance.addIgnoredSource(index);
+    return this;
+  }
+
+  /**
+   * Orders the sources by path and the names alphabetically and updates the mappings to match,
+   * so the output is the same no matter in which order they were added, e.g. for reproducible
+   * builds
+   */
+  sortSources(): SourceMap {
+    this.sourceMapInstance.sortSources();
+    return this;
+  }
+
+  /**
+   * How sources that are already in the map get treated when they're added again:
+   * - dedupeByPath (default): the existing source is reused and its content replaced
+   * - alwaysAppend: every add creates a new source
+   * - dedupeByPathAndContent: the existing source is only reused when the content matches too
+   *
+   * Except for alwaysAppend, adding a source looks through all sources of the map.
+   */
+  setAddSourceMode(mode: AddSourceMode): SourceMap {
+    this.sourceMapInstance.setAddSourceMode(mode);
+    return this;
+  }
+
   /**
    * Set the sourceContent for a certain file
    * this is optional and is only recommended for files that we cannot read in at the end when we serialise the sourcemap
@@ -341,6 +471,17 @@ export default class SourceMap {
     throw new Error('Should be implemented by extending');
   }
 
+  /**
+   * Composes this map with the map of its input, like extends, but a position between two
+   * mappings of the provided map keeps its distance to the mapping before it, so columns
+   * stay accurate through a chain of transforms (e.g. minifier -> babel -> typescript)
+   *
+   * @param buffer exported SourceMap as a buffer
+   */
+  apply(buffer: Buffer | SourceMap): SourceMap {
+    throw new Error('Should be implemented by extending');
+  }
+
   /**
    * Returns an object with mappings, sources and names
    * This should only be used for tests, debugging and visualising sourcemaps
@@ -361,9 +502,14 @@ export default class SourceMap {
    *
    * @param line the line in the generated code (starts at 1)
    * @param column the column in the generated code (starts at 0)
-   */
-  findClosestMapping(line: number, column: number): ?IndexedMapping<string> {
-    let mapping = this.sourceMapInstance.findClosestMapping(line - 1, column);
+   * @param bias which mapping a column without a mapping resolves to, the closest one before it (default) or after it on the same line
+   */
+  findClosestMapping(
+    line: number,
+    column: number,
+    bias?: 'greatestLowerBound' | 'leastUpperBound'
+  ): ?IndexedMapping<string> {
+    let mapping = this.sourceMapInstance.findClosestMapping(line - 1, column, bias);
     if (mapping) {
       let v = this.indexedMappingToStringMapping(mapping);
       return v;
@@ -372,6 +518,28 @@ export default class SourceMap {
     }
   }
 
+  /**
+   * Rewrites the frames of a stack trace (V8, Firefox, Safari or Hermes format) to the original
+   * files, lines and columns, using the original function names where the map has them.
+   * The trace should only contain frames of the file this map belongs to.
+   *
+   * @param stack the stack of an error thrown by the generated code
+   */
+  remapStackTrace(stack: string): string {
+    return this.sourceMapInstance.remapStackTrace(stack);
+  }
+
+  /**
+   * Resolves the frames of an error for the dev server overlay, including a code frame of the
+   * original source (or of the generated code for frames that aren't mapped)
+   *
+   * @param errorStack the stack of an error thrown by the generated code
+   * @param code the generated code
+   */
+  toOverlayFrames(errorStack: string, code: string): Array<OverlayFrame> {
+    return JSON.parse(this.sourceMapInstance.toOverlayFrames(errorStack, code));
+  }
+
   /**
    * Offset mapping lines from a certain position
    *
@@ -409,6 +577,31 @@ export default class SourceMap {
     this.sourceMapInstance.offsetColumns(line - 1, column, columnOffset);
   }
 
+  /**
+   * Returns the map as an inline data url (\`data:application/json;charset=utf-8;base64,...\`),
+   * to append to the generated code as a sourceMappingURL comment
+   */
+  toDataURL(): string {
+    return this.sourceMapInstance.toDataURL();
+  }
+
+  /**
+   * Estimates the length in bytes of the map produced by stringify (without file and sourceRoot)
+   * from the number and length of its strings and mappings, without encoding anything.
+   * Useful to choose between an inline and an external map upfront.
+   */
+  estimateJSONSize(): number {
+    return this.sourceMapInstance.estimateJsonSize();
+  }
+
+  /**
+   * Returns an upper bound of the length in bytes of the buffer produced by toBuffer,
+   * without building it
+   */
+  estimateBufferSize(): number {
+    return this.sourceMapInstance.estimateBufferSize();
+  }
+
   /**
    * Returns a buffer that represents this sourcemap, used for caching
    */
@@ -1,15 +1,31 @@
 // @flow
-import type { ParsedMap, VLQMap, SourceMapStringifyOptions, IndexedMapping, GenerateEmptyMapOptions } from './types';
+import type {
+  ParsedMap,
+  VLQMap,
+  VLQMapBuffers,
+  SourceMapStringifyOptions,
+  IndexedMapping,
+  GenerateEmptyMapOptions,
+  FlatMappingArrays,
+  SourceMapFeatures,
+  ErrorFormat,
+  CapacityOptions,
+  Progress,
+  MutationEvent,
+} from './types';
 import path from 'path';
 import SourceMap from './SourceMap';
+import { MISSING_FEATURES } from './utils';
 
 const bindings = require('../parcel_sourcemap_node/index');
 
+const EMPTY_ARRAY_BUFFER = Buffer.from('[]');
+
 export default class NodeSourceMap extends SourceMap {
-  constructor(projectRoot: string = '/', buffer?: Buffer) {
+  constructor(projectRoot: string = '/', buffer?: Buffer, options?: CapacityOptions) {
     super(projectRoot);
     this.projectRoot = projectRoot;
-    this.sourceMapInstance = new bindings.SourceMap(projectRoot, buffer);
+    this.sourceMapInstance = new bindings.SourceMap(projectRoot, buffer, options);
   }
 
   addVLQMap(map: VLQMap, lineOffset: number = 0, columnOffset: number = 0): SourceMap {
@@ -24,18 +40,28 @@ export default class NodeSourceMap extends SourceMap {
       JSON.stringify(sources),
       JSON.stringify(sourcesContent.map((content) => (content ? content : ''))),
       JSON.stringify(names),
-      lineOffset,
-      columnOffset
+      { lineOffset, columnOffset }
     );
     return this;
   }
 
+  // Same as addVLQMap, the buffers are read as they are instead of being copied into strings.
+  // A missing sourcesContent or names counts as an empty array.
+  addVLQMapBuffer(map: VLQMapBuffers, lineOffset: number = 0, columnOffset: number = 0): SourceMap {
+    let { mappings, sources, sourcesContent = EMPTY_ARRAY_BUFFER, names = EMPTY_ARRAY_BUFFER } = map;
+    this.sourceMapInstance.addVLQMapBuffer(mappings, sources, sourcesContent, names, {
+      lineOffset,
+      columnOffset,
+    });
+    return this;
+  }
+
   addSourceMap(sourcemap: SourceMap, lineOffset: number = 0): SourceMap {
     if (!(sourcemap.sourceMapInstance instanceof bindings.SourceMap)) {
       throw new Error('The sourcemap provided to addSourceMap is not a valid sourcemap instance');
     }
 
-    this.sourceMapInstance.addSourceMap(sourcemap.sourceMapInstance, lineOffset);
+    this.sourceMapInstance.addSourceMap(sourcemap.sourceMapInstance, { lineOffset });
     return this;
   }
 
@@ -51,6 +77,47 @@ export default class NodeSourceMap extends SourceMap {
     return this;
   }
 
+  apply(input: Buffer | SourceMap): SourceMap {
+    // $FlowFixMe
+    let inputSourceMap: SourceMap = Buffer.isBuffer(input) ? new NodeSourceMap(this.projectRoot, input) : input;
+    this.sourceMapInstance.apply(inputSourceMap.sourceMapInstance);
+    return this;
+  }
+
+  // Yields the mappings in batches of flat typed arrays, giving the event loop a chance to
+  // run in between batches so huge maps don't have to be materialized all at once
+  async *iterate({ batchSize = 10000 }: { batchSize?: number } = {}): AsyncGenerator<FlatMappingArrays, void, void> {
+    let cursor = { line: 0, index: 0 };
+    while (true) {
+      let batch = this.sourceMapInstance.readMappingBatch(cursor, { batchSize });
+      if (batch.mappings.generatedLines.length > 0) {
+        yield batch.mappings;
+      }
+      if (batch.done) {
+        return;
+      }
+      cursor = batch.cursor;
+      await new Promise((resolve) => setImmediate(resolve));
+    }
+  }
+
+  // Called every `every` mappings while parsing or serializing, and once when done.
+  // The calls happen synchronously, while the method that parses or serializes is running.
+  setProgressCallback(callback: ?(progress: Progress) => mixed, { every = 10000 }: { every?: number } = {}): SourceMap {
+    this.sourceMapInstance.setProgressCallback(callback || undefined, {
+      every,
+    });
+    return this;
+  }
+
+  // Called for every change to the map, so derived indexes can be updated incrementally.
+  // The calls are delivered once the thread is free again, in order.
+  // Keeps the process alive until cleared with null or delete()
+  setMutationObserver(callback: ?(event: MutationEvent) => mixed): SourceMap {
+    this.sourceMapInstance.setMutationObserver(callback ? (err, event) => callback(event) : undefined);
+    return this;
+  }
+
   getNames(): Array<string> {
     return JSON.parse(this.sourceMapInstance.getNames());
   }
@@ -59,7 +126,10 @@ export default class NodeSourceMap extends SourceMap {
     return JSON.parse(this.sourceMapInstance.getSources());
   }
 
-  delete() {}
+  delete() {
+    this.sourceMapInstance.setProgressCallback(undefined);
+    this.sourceMapInstance.setMutationObserver(undefined);
+  }
 
   static generateEmptyMap({
     projectRoot,
@@ -71,6 +141,25 @@ export default class NodeSourceMap extends SourceMap {
     map.addEmptyMap(sourceName, sourceContent, lineOffset);
     return map;
   }
+
+  static features(): SourceMapFeatures {
+    // Older binaries don't have features() or only some of the flags, treat everything
+    // optional they don't report as missing
+    let features = typeof bindings.SourceMap.features === 'function' ? bindings.SourceMap.features() : {};
+    return { ...MISSING_FEATURES, native: true, wasm: false, ...features };
+  }
+
+  static setErrorFormat(format: ErrorFormat): void {
+    bindings.SourceMap.setErrorFormat(format);
+  }
+
+  static setMemoryBudget(bytes: number | null): void {
+    bindings.SourceMap.setMemoryBudget(bytes);
+  }
+
+  static getMemoryUsage(): number {
+    return bindings.SourceMap.getMemoryUsage();
+  }
 }
 
 export const init: Promise<void> = Promise.resolve();
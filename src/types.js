@@ -27,6 +27,18 @@ export type VLQMap = {
   +version?: number,
   +file?: string,
   +sourceRoot?: string,
+  +ignoreList?: $ReadOnlyArray<number>,
+  +debugId?: string,
+  ...
+};
+
+// A VLQMap that is already utf-8 encoded, e.g. sliced out of a file that was read from disk.
+// sources, sourcesContent and names are JSON arrays.
+export type VLQMapBuffers = {
+  +mappings: Buffer,
+  +sources: Buffer,
+  +sourcesContent?: Buffer,
+  +names?: Buffer,
   ...
 };
 
@@ -50,3 +62,80 @@ export type GenerateEmptyMapOptions = {
   lineOffset?: number,
   ...
 };
+
+export type FlatMappingArrays = {
+  generatedLines: Uint32Array,
+  generatedColumns: Uint32Array,
+  sources: Int32Array,
+  originalLines: Int32Array,
+  originalColumns: Int32Array,
+  names: Int32Array,
+  ...
+};
+
+export type SourceMapFeatures = {
+  diagnostics: boolean,
+  native: boolean,
+  wasm: boolean,
+  simd: boolean,
+  compressedSourcesContent: boolean,
+  parallel: boolean,
+  mmap: boolean,
+  serde: boolean,
+  lspTypes: boolean,
+  ...
+};
+
+export type ErrorFormat = 'text' | 'json';
+
+// Reserved up front, for callers that know roughly how big the map is going to get
+export type CapacityOptions = {
+  reserveSources?: number,
+  reserveNames?: number,
+  reserveLines?: number,
+  reserveMappings?: number,
+  ...
+};
+
+export type AddSourceMode = 'dedupeByPath' | 'alwaysAppend' | 'dedupeByPathAndContent';
+
+export type OverlayFrame = {
+  file: string,
+  line: number,
+  column: number,
+  functionName: ?string,
+  original: boolean,
+  codeFrame: ?string,
+  ...
+};
+
+// Lines start at 1, `type` tells which of the other fields are set
+export type MutationEvent = {
+  type:
+    | 'sourceAdded'
+    | 'nameAdded'
+    | 'mappingAdded'
+    | 'lineReplaced'
+    | 'lineCleared'
+    | 'linesTruncated'
+    | 'linesShifted'
+    | 'columnsShifted'
+    | 'reset',
+  index?: number,
+  line?: number,
+  column?: number,
+  offset?: number,
+  lineCount?: number,
+  originalLine?: number,
+  originalColumn?: number,
+  source?: number,
+  name?: number,
+  ...
+};
+
+export type Progress = {
+  stage: 'parse' | 'serialize',
+  processed: number,
+  total: ?number,
+  ...
+};
@@ -1,13 +1,27 @@
 // @flow
-import type { VLQMap, SourceMapStringifyOptions } from './types';
+import type { VLQMap, SourceMapStringifyOptions, SourceMapFeatures } from './types';
 import path from 'path';
 
+// What features() reports for flags a binary doesn't know about yet
+export const MISSING_FEATURES: SourceMapFeatures = {
+  diagnostics: false,
+  native: false,
+  wasm: false,
+  simd: false,
+  compressedSourcesContent: false,
+  parallel: false,
+  mmap: false,
+  serde: false,
+  lspTypes: false,
+};
+
 export function generateInlineMap(map: string): string {
   return `data:application/json;charset=utf-8;base64,${Buffer.from(map).toString('base64')}`;
 }
 
 export async function partialVlqMapToSourceMap(map: VLQMap, opts: SourceMapStringifyOptions): Promise<VLQMap | string> {
-  let { fs, file, sourceRoot, inlineSources, rootDir, format = 'string' } = opts;
+  // The sourceRoot of the map is kept unless the options replace it
+  let { fs, file, sourceRoot = map.sourceRoot, inlineSources, rootDir, format = 'string' } = opts;
 
   let resultMap = {
     ...map,
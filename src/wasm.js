@@ -1,16 +1,26 @@
 // @flow
-import type { ParsedMap, VLQMap, SourceMapStringifyOptions, IndexedMapping, GenerateEmptyMapOptions } from './types';
+import type {
+  ParsedMap,
+  VLQMap,
+  SourceMapStringifyOptions,
+  IndexedMapping,
+  GenerateEmptyMapOptions,
+  SourceMapFeatures,
+  ErrorFormat,
+  CapacityOptions,
+} from './types';
 import path from 'path';
 import SourceMap from './SourceMap';
+import { MISSING_FEATURES } from './utils';
 
 import * as bindings from './wasm-bindings';
 
 export const init: Promise<void> = typeof bindings.init === 'function' ? bindings.init() : Promise.resolve();
 
 export default class WasmSourceMap extends SourceMap {
-  constructor(projectRoot: string = '/', buffer?: Buffer) {
-    super(projectRoot, buffer);
-    this.sourceMapInstance = new bindings.SourceMap(projectRoot, buffer);
+  constructor(projectRoot: string = '/', buffer?: Buffer, options?: CapacityOptions) {
+    super(projectRoot, buffer, options);
+    this.sourceMapInstance = new bindings.SourceMap(projectRoot, buffer, options);
     this.projectRoot = this.sourceMapInstance.getProjectRoot();
   }
 
@@ -26,8 +36,7 @@ export default class WasmSourceMap extends SourceMap {
       sources,
       sourcesContent.map((content) => (content ? content : '')),
       names,
-      lineOffset,
-      columnOffset
+      { lineOffset, columnOffset }
     );
     return this;
   }
@@ -37,7 +46,7 @@ export default class WasmSourceMap extends SourceMap {
       throw new Error('The sourcemap provided to addSourceMap is not a valid sourcemap instance');
     }
 
-    this.sourceMapInstance.addSourceMap(sourcemap.sourceMapInstance, lineOffset);
+    this.sourceMapInstance.addSourceMap(sourcemap.sourceMapInstance, { lineOffset });
     return this;
   }
 
@@ -53,6 +62,13 @@ export default class WasmSourceMap extends SourceMap {
     return this;
   }
 
+  apply(input: Buffer | SourceMap): SourceMap {
+    // $FlowFixMe
+    let inputSourceMap: SourceMap = input instanceof Uint8Array ? new WasmSourceMap(this.projectRoot, input) : input;
+    this.sourceMapInstance.apply(inputSourceMap.sourceMapInstance);
+    return this;
+  }
+
   delete() {
     this.sourceMapInstance.free();
   }
@@ -67,4 +83,23 @@ export default class WasmSourceMap extends SourceMap {
     map.addEmptyMap(sourceName, sourceContent, lineOffset);
     return map;
   }
+
+  static features(): SourceMapFeatures {
+    // Older binaries don't have features() or only some of the flags, treat everything
+    // optional they don't report as missing
+    let features = typeof bindings.SourceMap.features === 'function' ? bindings.SourceMap.features() : {};
+    return { ...MISSING_FEATURES, native: false, wasm: true, ...features };
+  }
+
+  static setErrorFormat(format: ErrorFormat): void {
+    bindings.SourceMap.setErrorFormat(format);
+  }
+
+  static setMemoryBudget(bytes: number | null): void {
+    bindings.SourceMap.setMemoryBudget(bytes);
+  }
+
+  static getMemoryUsage(): number {
+    return bindings.SourceMap.getMemoryUsage();
+  }
 }
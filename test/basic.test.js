@@ -425,4 +425,122 @@ describe('SourceMap - Basics', () => {
     assert.deepEqual(stringifiedMap.sourcesContent, ['second']);
     assert.deepEqual(stringifiedMap.names, ['a']);
   });
+
+  it('Should keep the ignoreList when stringifying', async () => {
+    let map = new SourceMap('/test-root');
+    map.addVLQMap({
+      mappings: SIMPLE_SOURCE_MAP.mappings,
+      sources: ['index.js', 'node_modules/react/index.js'],
+      names: [],
+    });
+    assert.equal(map.toVLQ().ignoreList, undefined);
+
+    map.addIgnoredSource(1);
+    assert.deepEqual(map.toVLQ().ignoreList, [1]);
+    let stringifiedMap = JSON.parse(await map.stringify({}));
+    assert.deepEqual(stringifiedMap.ignoreList, [1]);
+  });
+
+  it('Should keep the debugId when stringifying', async () => {
+    let map = new SourceMap('/test-root');
+    map.addVLQMap(SIMPLE_SOURCE_MAP);
+    assert.equal(map.toVLQ().debugId, undefined);
+
+    map.setDebugId('85314830-023f-4cf1-a267-535f4e37bb17');
+    assert.equal(map.toVLQ().debugId, '85314830-023f-4cf1-a267-535f4e37bb17');
+    let stringifiedMap = JSON.parse(await map.stringify({}));
+    assert.equal(stringifiedMap.debugId, '85314830-023f-4cf1-a267-535f4e37bb17');
+
+    map.setDebugId(null);
+    assert.equal(JSON.parse(await map.stringify({})).debugId, undefined);
+  });
+
+  it('Should keep the sourceRoot when stringifying unless it is overridden', async () => {
+    let map = new SourceMap('/test-root');
+    map.addVLQMap(SIMPLE_SOURCE_MAP);
+    map.setSourceRoot('webpack://app/');
+    assert.equal(map.toVLQ().sourceRoot, 'webpack://app/');
+    assert.equal(JSON.parse(await map.stringify({})).sourceRoot, 'webpack://app/');
+    assert.equal(JSON.parse(await map.stringify({ sourceRoot: '/' })).sourceRoot, '/');
+  });
+
+  it('Should report mutations to the observer', async function () {
+    if (process.env.BACKEND === 'wasm') {
+      this.skip();
+    }
+
+    let events = [];
+    let map = new SourceMap('/test-root');
+    map.setMutationObserver((event) => events.push(event));
+    map.addIndexedMapping({
+      generated: { line: 2, column: 4 },
+      original: { line: 1, column: 0 },
+      source: 'index.js',
+    });
+    map.offsetLines(2, 1);
+    await new Promise((resolve) => setImmediate(resolve));
+    map.delete();
+
+    assert.deepEqual(events, [
+      { type: 'sourceAdded', index: 0 },
+      { type: 'mappingAdded', line: 2, column: 4, originalLine: 1, originalColumn: 0, source: 0 },
+      { type: 'linesShifted', line: 2, offset: 1 },
+    ]);
+  });
+
+  it('Should add a VLQ map from buffers', function () {
+    if (process.env.BACKEND === 'wasm') {
+      this.skip();
+    }
+
+    let map = new SourceMap('/test-root');
+    map.addVLQMapBuffer({
+      mappings: Buffer.from(SIMPLE_SOURCE_MAP.mappings),
+      sources: Buffer.from(JSON.stringify(SIMPLE_SOURCE_MAP.sources)),
+    });
+    let expected = new SourceMap('/test-root');
+    expected.addVLQMap(SIMPLE_SOURCE_MAP);
+    assert.deepEqual(map.getMap(), expected.getMap());
+    map.delete();
+    expected.delete();
+  });
+
+  it('Should report progress while the map is being parsed', function () {
+    if (process.env.BACKEND === 'wasm') {
+      this.skip();
+    }
+
+    let reports = [];
+    let map = new SourceMap('/test-root');
+    map.setProgressCallback((progress) => reports.push(progress), { every: 2 });
+    map.addVLQMap(SIMPLE_SOURCE_MAP);
+    // Delivered before addVLQMap returned
+    assert.deepEqual(reports[reports.length - 1], { stage: 'parse', processed: 11, total: 11 });
+    map.delete();
+  });
+
+  it('Should report every optional feature as a boolean', () => {
+    let features = SourceMap.features();
+    for (let feature of [
+      'diagnostics',
+      'native',
+      'wasm',
+      'simd',
+      'compressedSourcesContent',
+      'parallel',
+      'mmap',
+      'serde',
+      'lspTypes',
+    ]) {
+      assert.equal(typeof features[feature], 'boolean', feature);
+    }
+    assert.notEqual(features.native, features.wasm);
+  });
+
+  it('Should accept capacity options in the constructor', () => {
+    let map = new SourceMap('/', undefined, { reserveSources: 2, reserveMappings: 100 });
+    map.addVLQMap(SIMPLE_SOURCE_MAP);
+    assert.deepEqual(map.getMap().sources, ['helloworld.coffee']);
+    assert.throws(() => new SourceMap('/', undefined, { reserveSource: 2 }), /unknown option reserveSource/);
+  });
 });
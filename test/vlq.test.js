@@ -20,4 +20,22 @@ describe('SourceMap - VLQ', () => {
     let stringifiedMap = map.toVLQ();
     assert.equal(stringifiedMap.mappings, SIMPLE_SOURCE_MAP.mappings);
   });
+
+  it('Should throw JSON errors once the error format is json', async () => {
+    SourceMap.setErrorFormat('json');
+    try {
+      let map = new SourceMap('/test-root');
+      assert.throws(
+        () => map.addVLQMap({ mappings: 'AAAA;AA!A', sources: ['a.js'], names: [] }),
+        (err) => {
+          let parsed = JSON.parse(err.message);
+          assert.equal(parsed.code, 'VlqInvalidBase64');
+          assert.equal(parsed.position.generatedLine, 1);
+          return true;
+        }
+      );
+    } finally {
+      SourceMap.setErrorFormat('text');
+    }
+  });
 });